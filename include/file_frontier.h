@@ -0,0 +1,47 @@
+/* C interface to file-frontier, built with `--features ffi`. See src/ffi.rs. */
+#ifndef FILE_FRONTIER_H
+#define FILE_FRONTIER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FfTree FfTree;
+typedef struct FfWatch FfWatch;
+
+typedef struct FfStats {
+    uint64_t files;
+    uint64_t dirs;
+    uint64_t bytes;
+} FfStats;
+
+#define FF_EVENT_CREATED 0
+#define FF_EVENT_MODIFIED 1
+#define FF_EVENT_REMOVED 2
+#define FF_EVENT_RENAMED 3
+/* Changes below `path` may have been missed; the tree was rescanned there. */
+#define FF_EVENT_DESYNCED 4
+
+/* Return non-zero to stop the search. */
+typedef int (*FfSearchCallback)(const char *path, uint64_t size, int is_dir, void *user);
+/* Called from a background thread; `to` is NULL except for renames. */
+typedef void (*FfEventCallback)(int kind, const char *path, const char *to, void *user);
+
+const char *ff_last_error(void);
+
+FfTree *ff_tree_scan(const char *root);
+void ff_tree_free(FfTree *tree);
+int ff_tree_refresh(const FfTree *tree);
+int ff_tree_stats(const FfTree *tree, FfStats *stats);
+int ff_tree_search(const FfTree *tree, const char *pattern, FfSearchCallback callback, void *user);
+
+FfWatch *ff_watch_start(const FfTree *tree, FfEventCallback callback, void *user);
+void ff_watch_stop(FfWatch *watch);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FILE_FRONTIER_H */
@@ -0,0 +1,5 @@
+"""Create, navigate and search directory trees, backed by the file-frontier crate."""
+
+from ._native import Diff, Node, Tree, Watcher
+
+__all__ = ["Diff", "Node", "Tree", "Watcher"]
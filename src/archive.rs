@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// How file contents are compressed by `Tree::to_zip`.
+#[cfg(feature = "zip")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipCompression {
+    /// Store files without compression.
+    Stored,
+    /// Deflate files at the given level, from 0 (fastest) to 9 (smallest).
+    Deflated(u8),
+}
+
+#[cfg(feature = "zip")]
+impl Default for ZipCompression {
+    fn default() -> Self {
+        ZipCompression::Deflated(6)
+    }
+}
+
+impl Tree {
+    /// Stream the nodes accepted by `filter` into a tar archive written to `writer`,
+    /// named by their path relative to the root. File contents, permissions, ownership
+    /// and timestamps are taken from disk. Returns the writer once the archive is finished.
+    #[cfg(feature = "tar")]
+    pub fn to_tar<W, F>(&self, writer: W, filter: F) -> io::Result<W>
+    where
+        W: Write,
+        F: Fn(&Node) -> bool,
+    {
+        let mut builder = tar::Builder::new(writer);
+        for (rel, node) in self.archive_entries(&filter) {
+            // Links the scan recorded as such, including dangling ones, stay links.
+            builder.follow_symlinks(self.options.follow_symlinks && !node.is_symlink());
+            builder.append_path_with_name(self.physical_path(&node.path), rel)?;
+        }
+        builder.into_inner()
+    }
+
+    /// Write the nodes accepted by `selection` into a zip archive, named by their path
+    /// relative to the root. Permissions and modification times are preserved, and
+    /// unfollowed symbolic links are stored as links. Returns the writer once the archive
+    /// is finished.
+    #[cfg(feature = "zip")]
+    pub fn to_zip<W, F>(
+        &self,
+        writer: W,
+        selection: F,
+        compression: ZipCompression,
+    ) -> io::Result<W>
+    where
+        W: Write + io::Seek,
+        F: Fn(&Node) -> bool,
+    {
+        use zip::write::SimpleFileOptions;
+        use zip::CompressionMethod;
+
+        use crate::node::NodeType;
+
+        let base = match compression {
+            ZipCompression::Stored => {
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored)
+            }
+            ZipCompression::Deflated(level) => SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .compression_level(Some(i64::from(level.min(9)))),
+        };
+
+        let mut zip = zip::ZipWriter::new(writer);
+        for (rel, node) in self.archive_entries(&selection) {
+            let path = self.physical_path(&node.path);
+            let metadata = match node.is_symlink() {
+                true => std::fs::symlink_metadata(&path)?,
+                false => std::fs::metadata(&path)?,
+            };
+            let mut options = base
+                .unix_permissions(unix_mode(&metadata))
+                .large_file(node.size >= u64::from(u32::MAX));
+            if let Some(time) = node.metadata.modified.and_then(zip_time) {
+                options = options.last_modified_time(time);
+            }
+
+            if node.is_dir() {
+                zip.add_directory_from_path(rel, options)?;
+            } else if let NodeType::Symlink { target } = &node.node_type {
+                zip.add_symlink_from_path(rel, target, options)?;
+            } else {
+                zip.start_file_from_path(rel, options)?;
+                io::copy(&mut std::fs::File::open(&path)?, &mut zip)?;
+            }
+        }
+        Ok(zip.finish()?)
+    }
+
+    /// Collects the nodes accepted by `filter` with their root-relative names,
+    /// sorted so that directories precede their contents.
+    fn archive_entries<'a>(&'a self, filter: &impl Fn(&Node) -> bool) -> Vec<(&'a Path, &'a Node)> {
+        let mut entries: Vec<_> = self
+            .iter()
+            .filter(|node| filter(node))
+            .filter_map(|node| {
+                let rel = node.path.strip_prefix(&self.head.path).ok()?;
+                (!rel.as_os_str().is_empty()).then_some((rel, node))
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+}
+
+/// The Unix permission bits to record for an entry.
+#[cfg(all(feature = "zip", unix))]
+fn unix_mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+/// On platforms without Unix permissions (e.g. WASI), an equivalent of the read-only flag.
+#[cfg(all(feature = "zip", not(unix)))]
+fn unix_mode(metadata: &std::fs::Metadata) -> u32 {
+    match (metadata.is_dir(), metadata.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
+
+/// Converts a timestamp into the (UTC) calendar fields a zip entry stores.
+/// Returns `None` for times outside the range zip can represent (1980-2107).
+#[cfg(feature = "zip")]
+fn zip_time(time: std::time::SystemTime) -> Option<zip::DateTime> {
+    let ((year, month, day), rem) = crate::clock::civil_date(time)?;
+    zip::DateTime::from_date_and_time(
+        u16::try_from(year).ok()?,
+        month as u8,
+        day as u8,
+        (rem / 3_600) as u8,
+        (rem % 3_600 / 60) as u8,
+        (rem % 60) as u8,
+    )
+    .ok()
+}
@@ -0,0 +1,278 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::node::{Node, NodeType};
+use crate::tree::Tree;
+
+/// How a file system updates access times, as read from its mount options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AtimePolicy {
+    /// Every read updates the access time (`strictatime`).
+    Strict,
+    /// Reads update the access time only if it is older than the modification time or
+    /// more than a day old (`relatime`, the Linux default), so it may lag up to a day.
+    Relatime,
+    /// Reads never update the access time, because the file system is mounted
+    /// `noatime` or read-only.
+    Noatime,
+    /// The mount holding the entries could not be found.
+    Unknown,
+}
+
+/// How far a "probably unused" verdict can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// Access times are unreliable here: not updated, of unknown policy, or lagging by
+    /// more than the window asked about.
+    Low,
+    /// Access times lag by up to a day (`relatime`); the entry went unread for at least
+    /// the window less a day.
+    Medium,
+    /// Every read is recorded (`strictatime`).
+    High,
+}
+
+/// A mount the tree extends into, with how it records access times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountAtime {
+    /// Where the file system is mounted.
+    pub mount_point: PathBuf,
+    /// How it updates access times.
+    pub policy: AtimePolicy,
+}
+
+/// A file, or a directory all of whose files qualify, not read within the window.
+#[derive(Debug, Clone, Copy)]
+pub struct UnusedEntry<'a> {
+    /// The entry.
+    pub node: &'a Node,
+    /// When it was last read; for directories, the latest of their files.
+    pub accessed: SystemTime,
+    /// How reliable the access times behind this are; for directories, the lowest of
+    /// their files'.
+    pub confidence: Confidence,
+}
+
+/// What `Tree::probably_unused` found.
+#[derive(Debug, Clone)]
+pub struct UnusedReport<'a> {
+    /// Entries not read within the window, least recently read first. A directory whose
+    /// files all qualify is reported in place of them.
+    pub entries: Vec<UnusedEntry<'a>>,
+    /// The mounts the reported entries are on, by mount point.
+    pub mounts: Vec<MountAtime>,
+}
+
+impl UnusedReport<'_> {
+    /// A message for each mount whose access times cannot be relied on.
+    pub fn warnings(&self) -> Vec<String> {
+        self.mounts
+            .iter()
+            .filter_map(|mount| {
+                let reason = match mount.policy {
+                    AtimePolicy::Strict | AtimePolicy::Relatime => return None,
+                    AtimePolicy::Noatime => "is mounted noatime or read-only",
+                    AtimePolicy::Unknown => "is on a mount that could not be identified",
+                };
+                Some(format!(
+                    "{} {}; its access times do not show which files are in use",
+                    mount.mount_point.display(),
+                    reason
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Whether the entries below a node were all read before the window.
+enum Usage {
+    /// All were, the latest at the time given, with the lowest confidence among them.
+    Unused(SystemTime, Confidence),
+    /// At least one was read within the window, or is of no known access time.
+    Used,
+    /// There are no files to go by.
+    Empty,
+}
+
+/// What the walk looking for unused entries needs at hand.
+struct Search<'a> {
+    cutoff: SystemTime,
+    window: Duration,
+    /// Maps the tree's paths to those in the mount table.
+    root: &'a Path,
+    host: PathBuf,
+    mounts: Vec<MountAtime>,
+}
+
+impl Tree {
+    /// Files and directories not read for `unused_for`, judged by their access times and
+    /// each given a confidence from how the mount holding it records them; see
+    /// `Confidence`. Mount options are read from `/proc/self/mounts`, and
+    /// `UnusedReport::warnings` names the mounts where access times say nothing.
+    ///
+    /// Directories are judged by the files below them alone, since listing a directory,
+    /// as scanning it does, updates its own access time. Files of unknown access time
+    /// count as in use, and empty directories are not reported. Reading the files'
+    /// contents, e.g. for checksums, updates their access times too.
+    pub fn probably_unused(&self, unused_for: Duration) -> io::Result<UnusedReport<'_>> {
+        let now = self.clock.now();
+        let search = Search {
+            cutoff: now
+                .checked_sub(unused_for)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+            window: unused_for,
+            root: &self.head.path,
+            host: fs::canonicalize(self.host_root())?,
+            mounts: read_mounts()?,
+        };
+        let mut entries = Vec::new();
+        if let Usage::Unused(accessed, confidence) = search.visit(&self.head, &mut entries) {
+            entries.push(UnusedEntry {
+                node: &self.head,
+                accessed,
+                confidence,
+            });
+        }
+        entries.sort_by(|a, b| (a.accessed, &a.node.path).cmp(&(b.accessed, &b.node.path)));
+
+        let mut mounts: Vec<MountAtime> = entries
+            .iter()
+            .map(|entry| search.mount_of(&entry.node.path))
+            .collect();
+        mounts.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        mounts.dedup();
+        Ok(UnusedReport { entries, mounts })
+    }
+}
+
+impl Search<'_> {
+    /// How `node` was used, recording in `entries` the unused children of a node that
+    /// was not unused as a whole.
+    fn visit<'n>(&self, node: &'n Node, entries: &mut Vec<UnusedEntry<'n>>) -> Usage {
+        match node.node_type {
+            NodeType::Symlink { .. } => return Usage::Empty,
+            NodeType::File => {
+                return match node.metadata.accessed {
+                    Some(accessed) if accessed < self.cutoff => {
+                        Usage::Unused(accessed, self.confidence(&node.path))
+                    }
+                    _ => Usage::Used,
+                }
+            }
+            NodeType::Directory => {}
+        }
+        // A directory not loaded yet may hold anything.
+        let Some(children) = &node.children else {
+            return Usage::Used;
+        };
+
+        let mut usage = Usage::Empty;
+        let mut unused = Vec::new();
+        for child in children {
+            match self.visit(child, entries) {
+                Usage::Unused(accessed, confidence) => {
+                    unused.push(UnusedEntry {
+                        node: child,
+                        accessed,
+                        confidence,
+                    });
+                    usage = match usage {
+                        Usage::Empty => Usage::Unused(accessed, confidence),
+                        Usage::Unused(latest, lowest) => {
+                            Usage::Unused(latest.max(accessed), lowest.min(confidence))
+                        }
+                        Usage::Used => Usage::Used,
+                    };
+                }
+                Usage::Used => usage = Usage::Used,
+                Usage::Empty => {}
+            }
+        }
+        if let Usage::Used = usage {
+            entries.extend(unused);
+        }
+        usage
+    }
+
+    /// How far the access time of the entry at `path` can be trusted.
+    fn confidence(&self, path: &Path) -> Confidence {
+        match self.mount_of(path).policy {
+            AtimePolicy::Strict => Confidence::High,
+            AtimePolicy::Relatime if self.window > Duration::from_secs(24 * 60 * 60) => {
+                Confidence::Medium
+            }
+            _ => Confidence::Low,
+        }
+    }
+
+    /// The mount holding the entry at the tree's `path`: the one with the longest mount
+    /// point above it on disk.
+    fn mount_of(&self, path: &Path) -> MountAtime {
+        let physical = match path.strip_prefix(self.root) {
+            Ok(rel) => self.host.join(rel),
+            Err(_) => path.to_path_buf(),
+        };
+        self.mounts
+            .iter()
+            .filter(|mount| physical.starts_with(&mount.mount_point))
+            .max_by_key(|mount| mount.mount_point.components().count())
+            .cloned()
+            .unwrap_or(MountAtime {
+                mount_point: physical,
+                policy: AtimePolicy::Unknown,
+            })
+    }
+}
+
+/// The mounts in `/proc/self/mounts`, later mounts over the same point first so that
+/// they win ties.
+fn read_mounts() -> io::Result<Vec<MountAtime>> {
+    let table = fs::read_to_string("/proc/self/mounts")?;
+    let mut mounts: Vec<MountAtime> = table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = unescape(fields.nth(1)?);
+            let options: Vec<&str> = fields.nth(1)?.split(',').collect();
+            let policy = if options.contains(&"ro") || options.contains(&"noatime") {
+                AtimePolicy::Noatime
+            } else if options.contains(&"relatime") {
+                AtimePolicy::Relatime
+            } else {
+                // The kernel lists neither option for `strictatime` mounts.
+                AtimePolicy::Strict
+            };
+            Some(MountAtime {
+                mount_point: PathBuf::from(mount_point),
+                policy,
+            })
+        })
+        .collect();
+    mounts.reverse();
+    Ok(mounts)
+}
+
+/// A field of the mount table with its octal escapes (`\040` for a space) decoded.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).filter(|_| bytes[i] == b'\\');
+        match escape
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok())
+        {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
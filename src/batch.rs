@@ -0,0 +1,133 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, SystemClock};
+
+use crate::diff::TreeDiff;
+use crate::event::{FsEvent, RescanPolicy};
+use crate::tree::Tree;
+
+/// Collects events and applies them to a tree at most once per interval, summarizing
+/// each delivery as a delta. This matches how UI frameworks want model updates: a few
+/// coalesced notifications instead of one per filesystem event. Events in one of the
+/// policy's priority lanes are delivered right away.
+#[derive(Debug, Clone)]
+pub struct DeltaBatcher {
+    interval: Duration,
+    policy: RescanPolicy,
+    pending: Vec<FsEvent>,
+    urgent: bool,
+    last_delivery: Option<Instant>,
+    clock: Arc<dyn Clock>,
+}
+
+impl DeltaBatcher {
+    /// Deliver at most one delta every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            policy: RescanPolicy::default(),
+            pending: Vec::new(),
+            urgent: false,
+            last_delivery: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use `policy` when applying the collected events.
+    pub fn with_policy(mut self, policy: RescanPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Measure the interval with `clock` instead of the system's monotonic time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Queue an event for the next delivery.
+    pub fn push(&mut self, event: FsEvent) {
+        self.urgent |= self.policy.priority.is_urgent(&event);
+        self.pending.push(event);
+    }
+
+    /// Returns `true` if events are waiting to be delivered.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// When the next delta may be delivered: `None` if nothing is pending, and possibly
+    /// in the past if one is already due.
+    pub fn ready_at(&self) -> Option<Instant> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(match self.last_delivery {
+            Some(last) if !self.urgent => last + self.interval,
+            _ => self.clock.instant(),
+        })
+    }
+
+    /// How long until the next delta may be delivered, by the batcher's clock: `None` if
+    /// nothing is pending, zero if one is already due.
+    pub fn due_in(&self) -> Option<Duration> {
+        let at = self.ready_at()?;
+        Some(at.saturating_duration_since(self.clock.instant()))
+    }
+
+    /// Apply the pending events and return the delta if a delivery is due, or `None` if
+    /// nothing is pending or the interval has not passed since the last delivery.
+    pub fn poll(&mut self, tree: &mut Tree) -> io::Result<Option<TreeDiff>> {
+        match self.ready_at() {
+            Some(at) if at <= self.clock.instant() => self.flush(tree).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Apply the pending events now, regardless of the interval.
+    ///
+    /// The delta lists the changed paths themselves, relative to the tree's root, by
+    /// whether they appeared, disappeared or were present before and after. Entries
+    /// below a created or removed directory are not listed separately.
+    pub fn flush(&mut self, tree: &mut Tree) -> io::Result<TreeDiff> {
+        let events = std::mem::take(&mut self.pending);
+        self.urgent = false;
+        self.last_delivery = Some(self.clock.instant());
+
+        let root = tree.head.path.clone();
+        // `apply_events` translates chroot events itself; only the delta needs them here.
+        let logical = match tree.host_root {
+            Some(_) => tree.logical_events(&events),
+            None => events.clone(),
+        };
+        let paths: BTreeSet<PathBuf> = logical
+            .iter()
+            .flat_map(FsEvent::paths)
+            .filter(|path| path.starts_with(&root))
+            .map(|path| path.to_path_buf())
+            .collect();
+        let existed: Vec<bool> = paths
+            .iter()
+            .map(|path| tree.get_node(path).is_some())
+            .collect();
+
+        tree.apply_events(&events, &self.policy)?;
+
+        let mut delta = TreeDiff::default();
+        for (path, existed) in paths.iter().zip(existed) {
+            let exists = tree.get_node(path).is_some();
+            let rel = path.strip_prefix(&root).unwrap_or(path).to_path_buf();
+            match (existed, exists) {
+                (false, true) => delta.added.push(rel),
+                (true, false) => delta.removed.push(rel),
+                (true, true) => delta.modified.push(rel),
+                (false, false) => {}
+            }
+        }
+        Ok(delta)
+    }
+}
@@ -0,0 +1,121 @@
+//! Throughput measurements over synthetic trees for evaluating scan performance.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::event::{FsEvent, RescanPolicy};
+use crate::source::{EventSource, SimulatedWatcher};
+use crate::testing::{fake_tree_at, GeneratedTree, TreeSpec};
+use crate::tree::Tree;
+
+/// Timings from a benchmark run.
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// What was generated for the run.
+    pub generated: GeneratedTree,
+    /// Number of nodes in the scanned tree, including the root.
+    pub nodes: u64,
+    /// Time taken to build the tree from disk.
+    pub scan: Duration,
+    /// Time taken to refresh the already built tree.
+    pub refresh: Duration,
+    /// Number of change events in the burst applied after the refresh, one per file.
+    pub events: u64,
+    /// Time from handing the burst to an event source until the tree had taken it in.
+    pub watch: Duration,
+    /// Time `Tree::apply_events` took over the burst, the part of `watch` spent
+    /// updating the tree.
+    pub apply: Duration,
+}
+
+impl BenchReport {
+    /// Nodes scanned per second during the initial scan.
+    pub fn scan_rate(&self) -> f64 {
+        rate(self.nodes, self.scan)
+    }
+
+    /// Nodes scanned per second during the refresh.
+    pub fn refresh_rate(&self) -> f64 {
+        rate(self.nodes, self.refresh)
+    }
+
+    /// Change events taken in per second, from the source to the updated tree.
+    pub fn event_rate(&self) -> f64 {
+        rate(self.events, self.watch)
+    }
+}
+
+/// Generate a synthetic tree below `root` (which should not exist yet or be empty),
+/// then time a full scan and a refresh of it, and the watch path: every file is
+/// appended to, and the matching `Modified` events go through an injected event source
+/// and are applied in one batch with the default `RescanPolicy`.
+pub fn run(root: &Path, spec: &TreeSpec) -> io::Result<BenchReport> {
+    let generated = fake_tree_at(root, spec)?;
+
+    let started = Instant::now();
+    let mut tree = Tree::new(root)?;
+    let scan = started.elapsed();
+
+    let started = Instant::now();
+    tree.refresh()?;
+    let refresh = started.elapsed();
+    let nodes = tree.iter().count() as u64;
+
+    let files: Vec<PathBuf> = tree
+        .iter()
+        .filter(|node| node.is_file())
+        .map(|node| node.path.clone())
+        .collect();
+    for path in &files {
+        OpenOptions::new()
+            .append(true)
+            .open(path)?
+            .write_all(b"+")?;
+    }
+    let source = SimulatedWatcher::new();
+    let started = Instant::now();
+    source.inject_all(files.iter().cloned().map(FsEvent::Modified));
+    let batch = source.recv_batch(Duration::ZERO);
+    let applying = Instant::now();
+    tree.apply_events(&batch, &RescanPolicy::default())?;
+    let apply = applying.elapsed();
+    let watch = started.elapsed();
+
+    Ok(BenchReport {
+        generated,
+        nodes,
+        scan,
+        refresh,
+        events: batch.len() as u64,
+        watch,
+        apply,
+    })
+}
+
+fn rate(count: u64, elapsed: Duration) -> f64 {
+    count as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::testing::{empty_dir, TreeSpec};
+
+    #[test]
+    fn run_applies_a_burst_per_file() {
+        let dir = empty_dir().unwrap();
+        let root = dir.root.join("bench");
+        let spec = TreeSpec {
+            breadth: 2,
+            depth: 2,
+            files_per_dir: 3,
+            ..TreeSpec::default()
+        };
+        let report = run(&root, &spec).unwrap();
+        assert_eq!(report.events, report.generated.files);
+        assert!(report.apply <= report.watch);
+        assert!(report.event_rate() > 0.0);
+    }
+}
@@ -0,0 +1,73 @@
+//! Keeps one or more trees scanned and watched, serving queries over a Unix socket.
+//!
+//! Usage: `frontierd <socket> <root>...` or `frontierd <socket> --config <file>`, where
+//! the file is a `ScanConfig` in TOML or JSON. A configuration file is checked for
+//! changes every few seconds and reloaded into the running daemon.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use file_frontier::daemon::Daemon;
+use file_frontier::{RescanPolicy, ScanConfig};
+
+const USAGE: &str = "usage: frontierd <socket> <root>... | frontierd <socket> --config <file>";
+
+/// How often the configuration file is checked for changes.
+const CONFIG_CHECK: Duration = Duration::from_secs(5);
+
+fn main() -> ExitCode {
+    let mut args = std::env::args_os().skip(1).map(PathBuf::from);
+    let (Some(socket), rest) = (args.next(), args.collect::<Vec<_>>()) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    let daemon = match rest.as_slice() {
+        [] => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+        [flag, file] if flag.as_os_str() == "--config" => ScanConfig::load(file)
+            .and_then(|config| Daemon::from_config(&config))
+            .map(|daemon| {
+                let daemon = Arc::new(daemon);
+                reload_on_change(Arc::clone(&daemon), file.clone());
+                daemon
+            }),
+        roots => Daemon::new(roots, RescanPolicy::default()).map(Arc::new),
+    };
+
+    match daemon.and_then(|daemon| daemon.serve(&socket)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("frontierd: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reloads the configuration at `file` into `daemon` whenever it is modified. A file
+/// that fails to load or apply is reported, and the daemon carries on.
+fn reload_on_change(daemon: Arc<Daemon>, file: PathBuf) {
+    thread::spawn(move || {
+        let mut seen = modified(&file);
+        loop {
+            thread::sleep(CONFIG_CHECK);
+            let current = modified(&file);
+            if current == seen {
+                continue;
+            }
+            seen = current;
+            match ScanConfig::load(&file).and_then(|config| daemon.reload(&config)) {
+                Ok(()) => eprintln!("frontierd: reloaded {}", file.display()),
+                Err(e) => eprintln!("frontierd: reloading {}: {}", file.display(), e),
+            }
+        }
+    });
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|metadata| metadata.modified()).ok()
+}
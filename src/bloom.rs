@@ -0,0 +1,163 @@
+use std::io::{self, Read, Write};
+use std::path::{Component, Path};
+
+use crate::codec::{fnv1a, invalid, write_varint, Input};
+use crate::tree::Tree;
+
+/// Identifies an encoded path filter, version 1.
+const MAGIC: &[u8; 4] = b"FFB1";
+
+/// A Bloom filter of the paths in a tree, relative to its root, to hand to a peer so it
+/// can tell cheaply which of its own paths the tree lacks. Built by `Tree::path_filter`.
+///
+/// `contains` never misses a path that was added, but claims a path that was not with
+/// the probability the filter was sized for; only the "absent" answers are certain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathFilter {
+    bits: Vec<u64>,
+    /// Number of usable bits, at least 64.
+    len: u64,
+    hashes: u32,
+    /// Number of paths added.
+    paths: u64,
+}
+
+impl PathFilter {
+    /// Create an empty filter sized for `paths` entries and the false-positive rate
+    /// `rate`, which is clamped to a sensible range.
+    pub fn new(paths: u64, rate: f64) -> Self {
+        let rate = rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let len = ((paths.max(1) as f64) * -rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let len = len.max(64);
+        let hashes = ((len as f64 / paths.max(1) as f64) * ln2).round() as u32;
+        Self {
+            bits: vec![0; len.div_ceil(64) as usize],
+            len,
+            hashes: hashes.clamp(1, 30),
+            paths: 0,
+        }
+    }
+
+    /// Add the relative path `rel`.
+    pub fn insert(&mut self, rel: &Path) {
+        for bit in self.bit_indexes(rel) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.paths += 1;
+    }
+
+    /// Returns `false` if `rel` was certainly not added, `true` if it probably was.
+    pub fn contains(&self, rel: &Path) -> bool {
+        self.bit_indexes(rel)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Number of paths added.
+    pub fn paths(&self) -> u64 {
+        self.paths
+    }
+
+    /// The probability that `contains` claims a path that was not added, estimated from
+    /// the share of bits set.
+    pub fn false_positive_rate(&self) -> f64 {
+        let set: u64 = self
+            .bits
+            .iter()
+            .map(|word| u64::from(word.count_ones()))
+            .sum();
+        (set as f64 / self.len as f64).powi(self.hashes as i32)
+    }
+
+    /// Encode the filter: a magic number, the sizes as variable-length integers, then
+    /// the bits as little-endian 64-bit words.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut out = MAGIC.to_vec();
+        write_varint(&mut out, self.len);
+        write_varint(&mut out, u64::from(self.hashes));
+        write_varint(&mut out, self.paths);
+        for word in &self.bits {
+            out.extend(word.to_le_bytes());
+        }
+        writer.write_all(&out)
+    }
+
+    /// Decode a filter written by `write_to`. Malformed input is reported as `InvalidData`.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut input = Input { bytes: &bytes };
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a path filter"));
+        }
+
+        let len = input.varint()?;
+        let hashes = u32::try_from(input.varint()?).map_err(|_| invalid("too many hashes"))?;
+        let paths = input.varint()?;
+        if len == 0 || hashes == 0 || input.bytes.len() as u64 != len.div_ceil(64) * 8 {
+            return Err(invalid("invalid path filter size"));
+        }
+        let bits = (0..len.div_ceil(64))
+            .map(|_| input.u64_le())
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            bits,
+            len,
+            hashes,
+            paths,
+        })
+    }
+
+    /// The bits for `rel`, by double hashing its normalized form.
+    fn bit_indexes(&self, rel: &Path) -> impl Iterator<Item = u64> {
+        let first = fnv1a(&key(rel));
+        let second = mix(first) | 1;
+        let len = self.len;
+        (0..u64::from(self.hashes)).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % len)
+    }
+}
+
+impl Tree {
+    /// A Bloom filter of every path below the root, relative to it, with a 1%
+    /// false-positive rate. See `path_filter_with_rate`.
+    pub fn path_filter(&self) -> PathFilter {
+        self.path_filter_with_rate(0.01)
+    }
+
+    /// A Bloom filter of every path below the root, relative to it, sized so that a path
+    /// not in the tree is claimed with probability `rate`. Paths are compared component by
+    /// component, so filters are interchangeable between platforms.
+    pub fn path_filter_with_rate(&self, rate: f64) -> PathFilter {
+        let paths: Vec<&Path> = self
+            .iter()
+            .filter_map(|node| node.path.strip_prefix(&self.head.path).ok())
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .collect();
+        let mut filter = PathFilter::new(paths.len() as u64, rate);
+        for rel in paths {
+            filter.insert(rel);
+        }
+        filter
+    }
+}
+
+/// The bytes hashed for `rel`: its normal components joined by `/`.
+fn key(rel: &Path) -> Vec<u8> {
+    let mut key = Vec::new();
+    for component in rel.components() {
+        if let Component::Normal(name) = component {
+            if !key.is_empty() {
+                key.push(b'/');
+            }
+            key.extend_from_slice(name.to_string_lossy().as_bytes());
+        }
+    }
+    key
+}
+
+/// splitmix64's finalizer, deriving a second hash from the first.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::node::Node;
+use crate::snapshot::Snapshot;
+use crate::tree::Tree;
+
+/// A named pointer to a path in a tree.
+///
+/// Bookmarks are kept by path rather than by node, so they survive refreshes and
+/// remain valid (if unresolvable) while their target is missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bookmark {
+    /// Path relative to the tree's root.
+    pub path: PathBuf,
+    /// Optional free-form description.
+    pub note: Option<String>,
+    /// When the bookmark was created.
+    pub created: SystemTime,
+}
+
+/// The bookmarks of a tree, by name.
+pub(crate) type Bookmarks = BTreeMap<String, Bookmark>;
+
+impl Tree {
+    /// Bookmark `path` under `name`, replacing any bookmark of the same name. The path
+    /// must be inside the tree, but need not exist yet. Returns the bookmark so that a
+    /// note can be attached.
+    pub fn bookmark(&mut self, name: impl Into<String>, path: &Path) -> io::Result<&mut Bookmark> {
+        let rel = path.strip_prefix(&self.head.path).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside the tree", path.display()),
+            )
+        })?;
+        let bookmark = Bookmark {
+            path: rel.to_path_buf(),
+            note: None,
+            created: self.clock.now(),
+        };
+        let name = name.into();
+        self.bookmarks.insert(name.clone(), bookmark);
+        Ok(self.bookmarks.get_mut(&name).expect("just inserted"))
+    }
+
+    /// Remove a bookmark, returning it if it existed.
+    pub fn remove_bookmark(&mut self, name: &str) -> Option<Bookmark> {
+        self.bookmarks.remove(name)
+    }
+
+    /// Every bookmark, ordered by name.
+    pub fn bookmarks(&self) -> impl Iterator<Item = (&str, &Bookmark)> {
+        self.bookmarks
+            .iter()
+            .map(|(name, bookmark)| (name.as_str(), bookmark))
+    }
+
+    /// The absolute path a bookmark points to.
+    pub fn bookmark_path(&self, name: &str) -> Option<PathBuf> {
+        let bookmark = self.bookmarks.get(name)?;
+        Some(self.head.path.join(&bookmark.path))
+    }
+
+    /// The node a bookmark points to, if it is currently in the tree.
+    pub fn resolve_bookmark(&self, name: &str) -> Option<&Node> {
+        self.get_node(&self.bookmark_path(name)?)
+    }
+
+    /// Replace the tree's bookmarks with those saved in `snapshot`.
+    pub fn restore_bookmarks(&mut self, snapshot: &Snapshot) {
+        self.bookmarks = snapshot.bookmarks.clone();
+    }
+}
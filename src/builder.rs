@@ -0,0 +1,611 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::checksum::{checksum_file, Checksum, HashAlgorithm};
+use crate::enrich::scan_structure;
+use crate::mime::sniff_file;
+use crate::node::{enter_dir, stat_entry, ExtendedMetadata, Node, NodeType};
+use crate::options::{ErrorPolicy, Excludes, ScanOptions, SizeMetric, SortOrder};
+use crate::platform::{device, is_hidden, DirId};
+use crate::priority::{ScanBudget, ScanPriority, Spent};
+use crate::progress::{ProgressReporter, Reporting, Tally};
+use crate::resources::{Resources, Workers};
+use crate::tree::Tree;
+
+/// Configures how a `Tree` is scanned, so that unwanted entries are never read instead
+/// of being filtered out of a complete tree afterwards.
+///
+/// The settings are kept as the tree's `ScanOptions`, and refreshes rescan with them.
+#[derive(Debug, Clone)]
+pub struct TreeBuilder {
+    root: PathBuf,
+    options: ScanOptions,
+    resources: Resources,
+    priority: ScanPriority,
+    budget: ScanBudget,
+    structure_only: bool,
+    progress: Option<Reporting>,
+}
+
+impl TreeBuilder {
+    /// Start configuring a scan of `root` with the default options, which scan
+    /// everything as `Tree::new` does.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            options: ScanOptions::default(),
+            resources: Resources::default(),
+            priority: ScanPriority::default(),
+            budget: ScanBudget::default(),
+            structure_only: false,
+            progress: None,
+        }
+    }
+
+    /// Scan with `options` in place of those set so far, e.g. from a `ScanConfig`.
+    pub fn with_options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Include entries at most `depth` levels below the root; 1 only lists the root's
+    /// own entries.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    /// Follow symbolic links (the default), or record them as `NodeType::Symlink` nodes
+    /// without reading their targets. Dangling links are recorded either way.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.options.follow_symlinks = follow;
+        self
+    }
+
+    /// Include hidden entries (the default), or leave them out. Entries are hidden if
+    /// their name starts with a dot, or on Windows if they have the hidden attribute.
+    pub fn include_hidden(mut self, include: bool) -> Self {
+        self.options.include_hidden = include;
+        self
+    }
+
+    /// Leave out the entries whose path relative to the root matches `pattern`, as
+    /// `PathPatterns` matches them, and everything below them, e.g. `**/node_modules`
+    /// or `var/cache`. Refreshes and applied events leave them out too. Not honoured for
+    /// lazy trees.
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.options.excludes.push(pattern.to_string());
+        self
+    }
+
+    /// Only list the root now, and deeper directories when first reached; see
+    /// `Tree::new_lazy`. Of the other options, lazy trees honour `include_hidden`, and
+    /// follow symbolic links.
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.options.lazy = lazy;
+        self
+    }
+
+    /// Build the tree from directory listings alone, without reading each entry's
+    /// metadata, which is much faster on large trees; fill in the sizes and metadata
+    /// afterwards with `Tree::enrich`. Until then only the root has metadata, and every
+    /// size is 0. Symbolic links are recorded rather than followed, the scan does not
+    /// stop at other file systems, and size-based sort orders have nothing to go by.
+    /// Ignored for lazy trees.
+    pub fn structure_only(mut self, structure_only: bool) -> Self {
+        self.structure_only = structure_only;
+        self
+    }
+
+    /// Stay on the root's file system, not descending into mount points.
+    pub fn same_file_system(mut self, same: bool) -> Self {
+        self.options.same_file_system = same;
+        self
+    }
+
+    /// Read every entry's extended attributes into `ExtendedMetadata::xattrs`, at the
+    /// cost of a few more system calls per entry. File systems without extended
+    /// attributes yield empty maps.
+    #[cfg(feature = "xattr")]
+    pub fn xattrs(mut self, read: bool) -> Self {
+        self.options.xattrs = read;
+        self
+    }
+
+    /// Compute every file's checksum with `algorithm` into `ExtendedMetadata::checksum`,
+    /// reading the files in full as they are scanned and again when refreshed, on the
+    /// scan threads. Files are read in pieces, or mapped as `Resources::mmap_threshold`
+    /// allows. A file that cannot be read is handled as the error policy says. Not
+    /// computed for lazy and structure-only trees.
+    pub fn checksums(mut self, algorithm: HashAlgorithm) -> Self {
+        self.options.checksums = Some(algorithm);
+        self
+    }
+
+    /// Detect every file's MIME type from its first bytes into
+    /// `ExtendedMetadata::mime_type`, for `Node::mime_type` to prefer over the type its
+    /// extension suggests. Reads up to 512 bytes of each file as it is scanned and again
+    /// when refreshed; a file that cannot be read is handled as the error policy says.
+    /// Not detected for lazy and structure-only trees.
+    pub fn mime_types(mut self, detect: bool) -> Self {
+        self.options.mime_types = detect;
+        self
+    }
+
+    /// Count a file with several hard links in the tree once in directory sizes, for
+    /// the first link in tree order, as `du` does, instead of once per link. The links'
+    /// own sizes stay those of the file. Refreshes sum the sizes again, which walks the
+    /// whole tree.
+    pub fn count_hardlinks_once(mut self, once: bool) -> Self {
+        self.options.hardlinks_once = once;
+        self
+    }
+
+    /// Record `metric` as each entry's size and add it up into directory sizes: the
+    /// apparent length by default, or the space allocated on disk, with each
+    /// directory's own blocks added as `du` counts them.
+    /// Both are available on every node through `Node::apparent_size` and
+    /// `Node::disk_usage`. Refreshes sum the sizes again, which walks the whole tree.
+    pub fn size_metric(mut self, metric: SizeMetric) -> Self {
+        self.options.size_metric = metric;
+        self
+    }
+
+    /// Keep each directory's children in `order`.
+    pub fn sort(mut self, order: SortOrder) -> Self {
+        self.options.sort = order;
+        self
+    }
+
+    /// Decide what happens with entries that cannot be read.
+    pub fn errors(mut self, policy: ErrorPolicy) -> Self {
+        self.options.errors = policy;
+        self
+    }
+
+    /// Limit how many threads the scan and the tree's later operations use; see
+    /// `Resources`.
+    pub fn resources(mut self, resources: Resources) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    /// Descend into each directory's entries in `priority` order; see `ScanPriority`.
+    pub fn priority(mut self, priority: ScanPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Stop the scan early when `budget` runs out; see `ScanBudget`.
+    pub fn budget(mut self, budget: ScanBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Report how far the scan has got to `reporter`, at most once per `interval` while
+    /// directories are entered, and once more when done. Not reported for lazy and
+    /// structure-only trees, which are quick to build.
+    pub fn progress(mut self, reporter: Arc<dyn ProgressReporter>, interval: Duration) -> Self {
+        self.progress = Some(Reporting { reporter, interval });
+        self
+    }
+
+    /// The root to be scanned.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The options configured so far.
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Scan the root and build the tree.
+    pub fn build(self) -> io::Result<Tree> {
+        self.build_into(None)
+    }
+
+    /// Like `build`, also returning the entries left out under `ErrorPolicy::Collect`
+    /// with the error each ran into, in no particular order. An entry whose directory
+    /// could not be listed is reported for the directory alone. Lazy trees report none.
+    pub fn build_collecting(self) -> io::Result<(Tree, Vec<(PathBuf, io::Error)>)> {
+        let errors = Mutex::new(Vec::new());
+        let tree = self.build_into(Some(&errors))?;
+        let errors = errors
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok((tree, errors))
+    }
+
+    /// Builds the tree, recording the errors of entries left out in `errors`.
+    fn build_into(self, errors: Option<&Mutex<Vec<(PathBuf, io::Error)>>>) -> io::Result<Tree> {
+        if self.options.lazy {
+            let mut tree = Tree::build_lazy(&self.root, self.options.include_hidden)?;
+            tree.options = self.options;
+            tree.resources = self.resources;
+            return Ok(tree);
+        }
+        if self.structure_only {
+            let options = ScanOptions {
+                follow_symlinks: false,
+                same_file_system: false,
+                ..self.options
+            };
+            let mut tree = Tree::from_head(scan_structure(&self.root, &options, errors)?);
+            tree.options = options;
+            tree.resources = self.resources;
+            tree.details_pending = true;
+            return Ok(tree);
+        }
+        let spent = Spent::new(&self.budget);
+        let tally = self.progress.as_ref().map(Tally::new);
+        let head = Scanner::new(&self.options, &self.root)?
+            .resources(&self.resources)
+            .priority(&self.priority, &self.root)
+            .budget(&spent)
+            .collect_errors(errors)
+            .progress(tally.as_ref())
+            .scan(self.root.clone(), 0)?;
+        if let Some(tally) = &tally {
+            tally.finish(&self.root);
+        }
+        let mut tree = Tree::from_head(head);
+        tree.options = self.options;
+        tree.resources = self.resources;
+        tree.recount_sizes();
+        Ok(tree)
+    }
+}
+
+/// Scans entries as a tree's `ScanOptions` ask for.
+pub(crate) struct Scanner<'a> {
+    options: &'a ScanOptions,
+    /// The entries left out.
+    excludes: Excludes,
+    /// The root's device, when staying on its file system.
+    device: Option<u64>,
+    /// The threads to scan on, and how to read files for checksums.
+    resources: Resources,
+    /// The order to scan entries in, with the root that paths are relative to.
+    priority: Option<(&'a ScanPriority, &'a Path)>,
+    /// When to stop descending.
+    spent: Option<&'a Spent<'a>>,
+    /// Where to record the entries left out under `ErrorPolicy::Collect`.
+    errors: Option<&'a Mutex<Vec<(PathBuf, io::Error)>>>,
+    /// What was scanned so far, to report.
+    tally: Option<&'a Tally<'a>>,
+}
+
+impl<'a> Scanner<'a> {
+    /// Prepare to scan below the tree rooted (on disk) at `root`.
+    pub(crate) fn new(options: &'a ScanOptions, root: &Path) -> io::Result<Self> {
+        let device = match options.same_file_system {
+            true => device(&fs::metadata(root)?),
+            false => None,
+        };
+        Ok(Self {
+            options,
+            excludes: Excludes::new(options, root),
+            device,
+            resources: Resources::default(),
+            priority: None,
+            spent: None,
+            errors: None,
+            tally: None,
+        })
+    }
+
+    /// Scan with the threads `resources` allow for scanning.
+    pub(crate) fn resources(mut self, resources: &Resources) -> Self {
+        self.resources = *resources;
+        self
+    }
+
+    /// Scan entries in `priority` order, for a tree rooted at `root`.
+    pub(crate) fn priority(mut self, priority: &'a ScanPriority, root: &'a Path) -> Self {
+        self.priority = Some((priority, root));
+        self
+    }
+
+    /// Stop descending once `spent` is exhausted.
+    pub(crate) fn budget(mut self, spent: &'a Spent<'a>) -> Self {
+        self.spent = Some(spent);
+        self
+    }
+
+    /// Record the entries left out under `ErrorPolicy::Collect` in `errors`.
+    pub(crate) fn collect_errors(
+        mut self,
+        errors: Option<&'a Mutex<Vec<(PathBuf, io::Error)>>>,
+    ) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Count what is scanned in `tally`, and report it.
+    pub(crate) fn progress(mut self, tally: Option<&'a Tally<'a>>) -> Self {
+        self.tally = tally;
+        self
+    }
+
+    /// Scans the entry at `path`, `depth` levels below the root.
+    pub(crate) fn scan(&self, path: PathBuf, depth: usize) -> io::Result<Node> {
+        let workers = Workers::new(self.resources.scan_threads, self.resources.numa)?;
+        self.scan_below(path, depth, &mut Vec::new(), Some(&workers))
+    }
+
+    /// Like `scan`, for an entry reached through the directories `ancestors`. The
+    /// children of a directory at the top are shared out between `workers`; further
+    /// down, they are scanned on the thread pool already running.
+    fn scan_below(
+        &self,
+        path: PathBuf,
+        depth: usize,
+        ancestors: &mut Vec<DirId>,
+        workers: Option<&Workers>,
+    ) -> io::Result<Node> {
+        let (metadata, node_type) = stat_entry(&path, self.options.follow_symlinks)?;
+        if let Some(spent) = self.spent {
+            spent.entry();
+        }
+        let extended = ExtendedMetadata {
+            xattrs: self.xattrs(&path, &node_type)?,
+            checksum: self.checksum(&path, &node_type)?,
+            mime_type: self.mime_type(&path, &node_type)?,
+            ..ExtendedMetadata::from_metadata(&metadata)
+        };
+        if let Some(tally) = self.tally {
+            tally.entry(match node_type {
+                NodeType::Directory => 0,
+                _ => metadata.len(),
+            });
+        }
+        if node_type != NodeType::Directory {
+            return Ok(Node::from_parts(path, node_type, extended, metadata.len()));
+        }
+
+        let mut node = Node::from_parts(path, NodeType::Directory, extended, 0);
+        let too_deep = self.options.max_depth.is_some_and(|max| depth >= max);
+        let elsewhere = self.device.is_some() && device(&metadata) != self.device;
+        let exhausted = self.spent.is_some_and(|spent| spent.exhausted());
+        if too_deep || elsewhere || exhausted {
+            node.children = None;
+            return Ok(node);
+        }
+        let Some(id) = enter_dir(&mut node, &metadata, ancestors)? else {
+            // Now standing for the link leading back, not the directory.
+            node.metadata.xattrs = self.xattrs(&node.path, &node.node_type)?;
+            return Ok(node);
+        };
+
+        if let Some(tally) = self.tally {
+            tally.directory(&node.path);
+        }
+        ancestors.push(id);
+        let children = self.scan_children(&node.path, depth, ancestors, workers);
+        ancestors.pop();
+        let children = children?;
+        node.size = children.iter().map(|child| child.size).sum();
+        node.children = Some(children);
+        Ok(node)
+    }
+
+    /// Scans the entries of the directory at `dir`, `depth` levels below the root.
+    fn scan_children(
+        &self,
+        dir: &Path,
+        depth: usize,
+        ancestors: &mut Vec<DirId>,
+        workers: Option<&Workers>,
+    ) -> io::Result<Vec<Node>> {
+        let workers = workers.filter(|workers| workers.is_parallel());
+        if workers.is_some() || cfg!(feature = "rayon") && self.resources.scan_threads > 1 {
+            return self.scan_children_parallel(dir, depth, ancestors, workers);
+        }
+        let mut entries: Vec<_> = fs::read_dir(dir)?.collect();
+        self.order(dir, &mut entries);
+        let mut children = Vec::new();
+        for entry in entries {
+            let path = entry_path(&entry, dir);
+            let child = entry.and_then(|entry| self.scan_entry(entry, depth, ancestors));
+            self.keep(path, child, &mut children)?;
+        }
+        self.sort(&mut children);
+        Ok(children)
+    }
+
+    /// Like `scan_children`, scanning the entries concurrently on `workers`, or on the
+    /// current thread pool.
+    fn scan_children_parallel(
+        &self,
+        dir: &Path,
+        depth: usize,
+        ancestors: &[DirId],
+        workers: Option<&Workers>,
+    ) -> io::Result<Vec<Node>> {
+        let mut entries: Vec<_> = fs::read_dir(dir)?.collect();
+        self.order(dir, &mut entries);
+        let scan = |entry: io::Result<fs::DirEntry>| {
+            let path = entry_path(&entry, dir);
+            let child =
+                entry.and_then(|entry| self.scan_entry(entry, depth, &mut ancestors.to_vec()));
+            (path, child)
+        };
+        let scanned: Vec<_> = match workers {
+            Some(workers) => workers.map(entries, |entry| Ok(scan(entry)))?,
+            #[cfg(feature = "rayon")]
+            None => {
+                use rayon::prelude::*;
+                entries.into_par_iter().map(scan).collect()
+            }
+            #[cfg(not(feature = "rayon"))]
+            None => entries.into_iter().map(scan).collect(),
+        };
+        let mut children = Vec::new();
+        for (path, child) in scanned {
+            self.keep(path, child, &mut children)?;
+        }
+        self.sort(&mut children);
+        Ok(children)
+    }
+
+    /// Scans a directory entry, `depth` levels below the root, unless it is left out.
+    fn scan_entry(
+        &self,
+        entry: fs::DirEntry,
+        depth: usize,
+        ancestors: &mut Vec<DirId>,
+    ) -> io::Result<Option<Node>> {
+        let hidden = is_hidden(&entry) && !self.options.include_hidden;
+        match hidden || self.excluded(&entry.path()) {
+            true => Ok(None),
+            false => self
+                .scan_below(entry.path(), depth + 1, ancestors, None)
+                .map(Some),
+        }
+    }
+
+    /// Returns `true` if the entry at `path` is left out by the excludes.
+    pub(crate) fn excluded(&self, path: &Path) -> bool {
+        self.excludes.covers(path)
+    }
+
+    /// Puts the `entries` of the directory at `dir` in the order to scan them in.
+    fn order(&self, dir: &Path, entries: &mut [io::Result<fs::DirEntry>]) {
+        if let Some((priority, root)) = self.priority {
+            priority.order(dir.strip_prefix(root).unwrap_or(dir), entries);
+        }
+    }
+
+    /// Adds the child scanned at `path` to `children`, or handles its error as the
+    /// options ask.
+    fn keep(
+        &self,
+        path: PathBuf,
+        child: io::Result<Option<Node>>,
+        children: &mut Vec<Node>,
+    ) -> io::Result<()> {
+        match (child, self.options.errors) {
+            (Ok(Some(child)), _) => children.push(child),
+            (Ok(None), _) => {}
+            (Err(e), ErrorPolicy::Abort) => return Err(e),
+            (Err(e), ErrorPolicy::Collect) => collect(self.errors, path, e),
+            (Err(_), ErrorPolicy::Skip) => {}
+        }
+        Ok(())
+    }
+
+    /// The extended attributes of the entry at `path`, if they are to be read.
+    #[cfg(feature = "xattr")]
+    fn xattrs(
+        &self,
+        path: &Path,
+        node_type: &NodeType,
+    ) -> io::Result<Option<BTreeMap<String, Vec<u8>>>> {
+        match self.options.xattrs {
+            true => read_xattrs(path, node_type).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    #[cfg(not(feature = "xattr"))]
+    fn xattrs(
+        &self,
+        _path: &Path,
+        _node_type: &NodeType,
+    ) -> io::Result<Option<BTreeMap<String, Vec<u8>>>> {
+        Ok(None)
+    }
+
+    /// The checksum of the entry at `path`, if it is a file and checksums are computed.
+    fn checksum(&self, path: &Path, node_type: &NodeType) -> io::Result<Option<Checksum>> {
+        match (self.options.checksums, node_type) {
+            (Some(algorithm), NodeType::File) => {
+                checksum_file(path, algorithm, &self.resources).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The MIME type of the entry at `path` from its contents, if it is a file and types
+    /// are detected.
+    fn mime_type(&self, path: &Path, node_type: &NodeType) -> io::Result<Option<String>> {
+        match (self.options.mime_types, node_type) {
+            (true, NodeType::File) => sniff_file(path).map(|mime| Some(mime.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Puts `children` in the configured order.
+    pub(crate) fn sort(&self, children: &mut [Node]) {
+        self.options.sort.apply(children);
+    }
+}
+
+/// The path of a listed entry, or of the directory `dir` being listed if listing failed.
+fn entry_path(entry: &io::Result<fs::DirEntry>, dir: &Path) -> PathBuf {
+    match entry {
+        Ok(entry) => entry.path(),
+        Err(_) => dir.to_path_buf(),
+    }
+}
+
+/// Records that the entry at `path` was left out with `error`, if errors are collected.
+pub(crate) fn collect(
+    errors: Option<&Mutex<Vec<(PathBuf, io::Error)>>>,
+    path: PathBuf,
+    error: io::Error,
+) {
+    if let Some(errors) = errors {
+        let mut errors = errors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        errors.push((path, error));
+    }
+}
+
+impl SortOrder {
+    /// Puts `children` in this order.
+    pub(crate) fn apply(self, children: &mut [Node]) {
+        match self {
+            SortOrder::Unsorted => {}
+            SortOrder::Name => children.sort_by(|a, b| a.path.cmp(&b.path)),
+            SortOrder::LargestFirst => {
+                children.sort_by(|a, b| (Reverse(a.size), &a.path).cmp(&(Reverse(b.size), &b.path)))
+            }
+        }
+    }
+}
+
+/// The extended attributes of the entry at `path`: of the link itself for an unfollowed
+/// symlink, of what it points to otherwise.
+#[cfg(feature = "xattr")]
+fn read_xattrs(path: &Path, node_type: &NodeType) -> io::Result<BTreeMap<String, Vec<u8>>> {
+    let follow = !matches!(node_type, NodeType::Symlink { .. });
+    let names = match follow {
+        true => xattr::list_deref(path),
+        false => xattr::list(path),
+    };
+    let names = match names {
+        Ok(names) => names,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut xattrs = BTreeMap::new();
+    for name in names {
+        let value = match follow {
+            true => xattr::get_deref(path, &name)?,
+            false => xattr::get(path, &name)?,
+        };
+        // Attributes removed since they were listed are left out.
+        if let Some(value) = value {
+            xattrs.insert(name.to_string_lossy().into_owned(), value);
+        }
+    }
+    Ok(xattrs)
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use crate::snapshot::{Snapshot, SnapshotEntry};
+
+/// A series of snapshots of the same tree, queried by path: the index behind a backup
+/// browser.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    /// Ordered by the time they were taken.
+    snapshots: Vec<Snapshot>,
+}
+
+/// The state of one path in one snapshot of a `Catalog`.
+#[derive(Debug, Clone, Copy)]
+pub struct PathState<'a> {
+    /// The snapshot this state was recorded in.
+    pub snapshot: &'a Snapshot,
+    /// The path's entry, or `None` if it did not exist then.
+    pub entry: Option<&'a SnapshotEntry>,
+}
+
+impl Catalog {
+    /// An empty catalog.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a snapshot, keeping the series ordered by the time snapshots were taken.
+    pub fn add(&mut self, snapshot: Snapshot) {
+        let index = self
+            .snapshots
+            .partition_point(|existing| existing.taken <= snapshot.taken);
+        self.snapshots.insert(index, snapshot);
+    }
+
+    /// The snapshots, oldest first.
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    /// The number of snapshots.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns `true` if the catalog holds no snapshots.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// The state of `rel_path` (relative to the snapshots' root) in every snapshot,
+    /// oldest first, including those where it was absent.
+    pub fn history(&self, rel_path: &Path) -> Vec<PathState<'_>> {
+        self.snapshots
+            .iter()
+            .map(|snapshot| PathState {
+                snapshot,
+                entry: snapshot.get(rel_path),
+            })
+            .collect()
+    }
+
+    /// Like `history`, but only the snapshots in which the path appeared, disappeared
+    /// or changed size or modification time compared to the snapshot before.
+    pub fn versions(&self, rel_path: &Path) -> Vec<PathState<'_>> {
+        let mut versions: Vec<PathState<'_>> = Vec::new();
+        for state in self.history(rel_path) {
+            let changed = match versions.last() {
+                None => state.entry.is_some(),
+                Some(previous) => !same_version(previous.entry, state.entry),
+            };
+            if changed {
+                versions.push(state);
+            }
+        }
+        versions
+    }
+
+    /// The first snapshot in which `rel_path` existed.
+    pub fn first_appeared(&self, rel_path: &Path) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.get(rel_path).is_some())
+    }
+
+    /// The first snapshot missing `rel_path` after the last one containing it, or
+    /// `None` if it still exists in the newest snapshot or never existed.
+    pub fn disappeared(&self, rel_path: &Path) -> Option<&Snapshot> {
+        let last_present = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.get(rel_path).is_some())?;
+        self.snapshots.get(last_present + 1)
+    }
+}
+
+fn same_version(a: Option<&SnapshotEntry>, b: Option<&SnapshotEntry>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            a.node_type == b.node_type
+                && a.size == b.size
+                && a.metadata.modified == b.metadata.modified
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::content::read_contents;
+use crate::node::Node;
+use crate::resources::Resources;
+
+/// How file contents are hashed, by `Node::hash` and during scans set up with
+/// `TreeBuilder::checksums`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HashAlgorithm {
+    /// CRC-32 as used by zip and gzip: fast, and enough to notice corruption, but too
+    /// short to tell files apart among many.
+    Crc32,
+    /// SHA-256, for telling files apart by content. Needs the `sha256` feature (also
+    /// enabled by `store`); hashing fails with `Unsupported` without it.
+    Sha256,
+}
+
+/// A digest of a file's contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checksum {
+    /// The algorithm the digest was computed with.
+    pub algorithm: HashAlgorithm,
+    /// The digest, most significant byte first: 4 bytes for CRC-32, 32 for SHA-256.
+    pub digest: Vec<u8>,
+}
+
+impl Checksum {
+    /// The digest in lowercase hexadecimal, as `sha256sum` and the like print it.
+    pub fn to_hex(&self) -> String {
+        self.digest
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl Node {
+    /// The checksum of this file's contents with `algorithm`: the one recorded during the
+    /// scan if it was computed with `algorithm`, read from the file at the node's path
+    /// otherwise. Files are read in pieces, however large.
+    ///
+    /// A recorded checksum is as of the last scan or refresh of the node. Only files can
+    /// be hashed; directories and unfollowed symbolic links fail with `InvalidInput`.
+    pub fn hash(&self, algorithm: HashAlgorithm) -> io::Result<Checksum> {
+        if !self.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file", self.path.display()),
+            ));
+        }
+        match &self.metadata.checksum {
+            Some(checksum) if checksum.algorithm == algorithm => Ok(checksum.clone()),
+            _ => checksum_file(&self.path, algorithm, &Resources::default()),
+        }
+    }
+}
+
+/// Hashes the contents of the file at `path` with `algorithm`, reading it as
+/// `resources` asks for.
+pub(crate) fn checksum_file(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    resources: &Resources,
+) -> io::Result<Checksum> {
+    let digest = match algorithm {
+        HashAlgorithm::Crc32 => {
+            let mut crc = !0u32;
+            read_contents(path, resources, |bytes| {
+                crc = crc32_extend(crc, bytes);
+                Ok(())
+            })?;
+            (!crc).to_be_bytes().to_vec()
+        }
+        #[cfg(feature = "sha256")]
+        HashAlgorithm::Sha256 => {
+            use sha2::{Digest, Sha256};
+            let mut hasher = Sha256::new();
+            read_contents(path, resources, |bytes| {
+                hasher.update(bytes);
+                Ok(())
+            })?;
+            hasher.finalize().to_vec()
+        }
+        #[cfg(not(feature = "sha256"))]
+        HashAlgorithm::Sha256 => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "SHA-256 checksums need the `sha256` feature",
+            ))
+        }
+    };
+    Ok(Checksum { algorithm, digest })
+}
+
+/// The CRC-32 lookup table for the reflected IEEE polynomial.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb8_8320,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continues the (pre-inverted) CRC-32 `crc` with `bytes`.
+fn crc32_extend(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
@@ -0,0 +1,120 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::builder::TreeBuilder;
+use crate::event::FsEvent;
+use crate::node::Node;
+use crate::tree::Tree;
+
+impl Tree {
+    /// Scan `dir` as if it were `/`, e.g. an unpacked container root filesystem. Every
+    /// stored and reported path is inside the image (`/etc/passwd` rather than
+    /// `/mnt/rootfs/etc/passwd`); no actual chroot takes place. Refreshes, content
+    /// comparisons and archives read through to `dir`, and events for paths below `dir`
+    /// are translated when applied. Symbolic links are recorded with their targets and
+    /// never followed, since an absolute target means a path inside the image but would
+    /// resolve on the host.
+    pub fn new_chroot(dir: &Path) -> io::Result<Self> {
+        let mut tree = TreeBuilder::new(dir).follow_symlinks(false).build()?;
+        tree.present_at(Path::new("/"));
+        Ok(tree)
+    }
+
+    /// Store and report the paths of a freshly built tree under `prefix` instead of
+    /// where its root is on disk, which becomes its host root.
+    pub(crate) fn present_at(&mut self, prefix: &Path) {
+        let host = self.host_root().to_path_buf();
+        rebase(&mut self.head, &host, prefix);
+        self.host_root = Some(host);
+    }
+
+    /// The directory on disk the tree's root stands for. The same as `head.path`
+    /// unless the tree was built with `new_chroot` or mounted in a `Forest`.
+    pub fn host_root(&self) -> &Path {
+        self.host_root.as_deref().unwrap_or(&self.head.path)
+    }
+
+    /// Where a path of the tree lives on disk.
+    pub fn physical_path(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.head.path) {
+            Ok(rel) if self.host_root.is_some() => self.host_root().join(rel),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// The tree's path for a location on disk, or `None` if it is outside the host root.
+    pub fn logical_path(&self, physical: &Path) -> Option<PathBuf> {
+        let rel = physical.strip_prefix(self.host_root()).ok()?;
+        Some(self.head.path.join(rel))
+    }
+
+    /// Translates events reported for locations on disk into the tree's paths, dropping
+    /// those outside the host root.
+    pub(crate) fn logical_events(&self, events: &[FsEvent]) -> Vec<FsEvent> {
+        events
+            .iter()
+            .filter_map(|event| {
+                Some(match event {
+                    FsEvent::Created(path) => FsEvent::Created(self.logical_path(path)?),
+                    FsEvent::Modified(path) => FsEvent::Modified(self.logical_path(path)?),
+                    FsEvent::Removed(path) => FsEvent::Removed(self.logical_path(path)?),
+                    FsEvent::Renamed { from, to } => FsEvent::Renamed {
+                        from: self.logical_path(from)?,
+                        to: self.logical_path(to)?,
+                    },
+                    FsEvent::Desynced(path) => FsEvent::Desynced(self.logical_path(path)?),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Rewrites the paths of `node` and everything below it from under `from` to under `to`.
+pub(crate) fn rebase(node: &mut Node, from: &Path, to: &Path) {
+    if let Ok(rel) = node.path.strip_prefix(from) {
+        // Joining an empty path would add a trailing separator to the root's.
+        node.path = match rel.as_os_str().is_empty() {
+            true => to.to_path_buf(),
+            false => to.join(rel),
+        };
+    }
+    for child in node.children.iter_mut().flatten() {
+        rebase(child, from, to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::node::NodeType;
+    use crate::testing::empty_dir;
+    use crate::tree::Tree;
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_links_are_not_followed_onto_the_host() {
+        let dir = empty_dir().unwrap();
+        let host = dir.root.join("host");
+        fs::create_dir(&host).unwrap();
+        fs::write(host.join("shadow"), "host secret").unwrap();
+        let rootfs = dir.root.join("rootfs");
+        fs::create_dir_all(rootfs.join("etc")).unwrap();
+        fs::write(rootfs.join("etc/passwd"), "root").unwrap();
+        std::os::unix::fs::symlink(&host, rootfs.join("etc/link")).unwrap();
+
+        let tree = Tree::new_chroot(&rootfs).unwrap();
+        let link = tree.get_node(Path::new("/etc/link")).unwrap();
+        assert_eq!(
+            link.node_type,
+            NodeType::Symlink {
+                target: host.clone()
+            }
+        );
+        assert!(link.children.is_none());
+        assert!(tree.get_node(Path::new("/etc/link/shadow")).is_none());
+        assert_eq!(tree.get_node(Path::new("/etc/passwd")).unwrap().size, 4);
+        assert_eq!(tree.head.size, 4 + link.size);
+    }
+}
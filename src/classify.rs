@@ -0,0 +1,138 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::diff::{same_contents, Entries, Entry};
+use crate::node::NodeType;
+use crate::platform::{read_permissions, recorded_permissions, Permissions};
+use crate::tree::Tree;
+
+/// The differences between two trees, sorted into categories that sync tools handle
+/// differently. Paths are relative to the roots. Every path appears in one list only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassifiedDiff {
+    /// Entries present only in the newer tree.
+    pub added: Vec<PathBuf>,
+    /// Entries present only in the older tree.
+    pub removed: Vec<PathBuf>,
+    /// Entries whose path only changed in letter case, as `(old, new)`, e.g. after a
+    /// rename on a case-insensitive file system. Their contents are not compared.
+    pub case_renamed: Vec<(PathBuf, PathBuf)>,
+    /// Entries with unchanged contents whose permissions or owner changed. Their
+    /// timestamps may have changed as well.
+    pub permissions_changed: Vec<PathBuf>,
+    /// Files with unchanged contents and permissions whose modification time changed,
+    /// e.g. after a `touch` or a copy that did not preserve times. Creation times are
+    /// not compared, since they differ between any two copies.
+    pub timestamps_only: Vec<PathBuf>,
+    /// Entries whose contents or type changed.
+    pub modified: Vec<PathBuf>,
+}
+
+impl ClassifiedDiff {
+    /// Returns `true` if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.case_renamed.is_empty()
+            && self.permissions_changed.is_empty()
+            && self.timestamps_only.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+impl Tree {
+    /// Compare this tree against `other`, treating `self` as the older side, and classify
+    /// each difference.
+    ///
+    /// Files of equal size are taken to have the same contents if their modification time
+    /// is unchanged, and are read to find out otherwise. Permissions and owners are those
+    /// recorded by the scan; where none were (e.g. off Unix, or for trees read from
+    /// manifests) they are read from disk, so the entries must still exist there.
+    pub fn diff_classified(&self, other: &Tree) -> io::Result<ClassifiedDiff> {
+        let old = self.entries();
+        let new = other.entries();
+        let mut result = ClassifiedDiff::default();
+
+        for (rel, old_entry) in &old {
+            let Some(new_entry) = new.get(rel) else {
+                result.removed.push(rel.clone());
+                continue;
+            };
+            if let Some(list) = classify(old_entry, new_entry, &mut result)? {
+                list.push(rel.clone());
+            }
+        }
+        result.added = new
+            .keys()
+            .filter(|rel| !old.contains_key(*rel))
+            .cloned()
+            .collect();
+        pair_case_renames(&old, &new, &mut result);
+        Ok(result)
+    }
+}
+
+/// The list the change between two entries at the same path belongs in, if any.
+fn classify<'r>(
+    old: &Entry,
+    new: &Entry,
+    result: &'r mut ClassifiedDiff,
+) -> io::Result<Option<&'r mut Vec<PathBuf>>> {
+    if old.node_type != new.node_type {
+        return Ok(Some(&mut result.modified));
+    }
+    let file = *old.node_type == NodeType::File;
+    if file && old.size != new.size {
+        return Ok(Some(&mut result.modified));
+    }
+    // A directory's times follow its contents, which are compared entry by entry.
+    let times_changed = file && old.metadata.modified != new.metadata.modified;
+    if times_changed && !same_contents(&old.location, &new.location)? {
+        return Ok(Some(&mut result.modified));
+    }
+    if permissions(old)? != permissions(new)? {
+        return Ok(Some(&mut result.permissions_changed));
+    }
+    Ok(times_changed.then_some(&mut result.timestamps_only))
+}
+
+/// Moves added and removed entries whose paths only differ in case to `case_renamed`.
+fn pair_case_renames(old: &Entries, new: &Entries, result: &mut ClassifiedDiff) {
+    let folded = |path: &Path| path.to_string_lossy().to_lowercase();
+    let mut removed: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    for rel in &result.removed {
+        removed.entry(folded(rel)).or_default().push(rel);
+    }
+
+    let mut renamed = Vec::new();
+    for rel in &result.added {
+        let Some(candidates) = removed.get_mut(&folded(rel)) else {
+            continue;
+        };
+        let same_type = |from: &&PathBuf| old[*from].node_type == new[rel].node_type;
+        if let Some(index) = candidates.iter().position(same_type) {
+            renamed.push((candidates.swap_remove(index).clone(), rel.clone()));
+        }
+    }
+    if renamed.is_empty() {
+        return;
+    }
+
+    let from: HashSet<&PathBuf> = renamed.iter().map(|(from, _)| from).collect();
+    let to: HashSet<&PathBuf> = renamed.iter().map(|(_, to)| to).collect();
+    result.removed.retain(|rel| !from.contains(rel));
+    result.added.retain(|rel| !to.contains(rel));
+    renamed.sort();
+    result.case_renamed = renamed;
+}
+
+/// The permissions of `entry` as recorded at scan time, or as read from disk if they
+/// were not.
+fn permissions(entry: &Entry) -> io::Result<Permissions> {
+    match recorded_permissions(entry.metadata) {
+        Some(recorded) => Ok(recorded),
+        None => Ok(read_permissions(&fs::metadata(&entry.location)?)),
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Where time-based features (delta batching, tombstone retention, age groups, snapshot
+/// and bookmark timestamps) read the current time from, so tests can move it forward
+/// deterministically instead of sleeping.
+///
+/// Blocking waits on real event sources, such as `EventSource::recv_batch`, always use
+/// the system's time.
+pub trait Clock: Debug + Send + Sync {
+    /// The current wall-clock time.
+    fn now(&self) -> SystemTime;
+    /// The current monotonic time.
+    fn instant(&self) -> Instant;
+}
+
+/// The operating system's clocks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to. Share it through an `Arc` and advance it from
+/// the test while the code under test reads it.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: SystemTime,
+    origin: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Create a clock reading `start` until advanced.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            origin: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        *self.lock() += by;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.elapsed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    /// A clock starting at the current system time.
+    fn default() -> Self {
+        Self::new(SystemTime::now())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.start + *self.lock()
+    }
+
+    fn instant(&self) -> Instant {
+        self.origin + *self.lock()
+    }
+}
+
+/// The UTC calendar date of `time` as (year, month, day), and the seconds into that day.
+/// Times before the epoch give `None`.
+pub(crate) fn civil_date(time: SystemTime) -> Option<((i64, u32, u32), u64)> {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+    let days = (secs / 86_400) as i64;
+
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    Some(((year, month as u32, day as u32), secs % 86_400))
+}
+
+/// `time` in UTC, as `2023-07-04T12:00:00Z`, or `20230704T120000Z` if `compact`.
+pub(crate) fn utc_timestamp(time: SystemTime, compact: bool) -> String {
+    let Some(((year, month, day), secs)) = civil_date(time) else {
+        return "unknown".to_string();
+    };
+    let (hour, minute, second) = (secs / 3_600, secs % 3_600 / 60, secs % 60);
+    match compact {
+        true => format!("{year:04}{month:02}{day:02}T{hour:02}{minute:02}{second:02}Z"),
+        false => format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z"),
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::bookmark::{Bookmark, Bookmarks};
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::node::{ExtendedMetadata, NodeType};
+use crate::options::{ErrorPolicy, ScanOptions, SizeMetric, SortOrder};
+use crate::snapshot::SnapshotEntry;
+
+/// The 64-bit FNV-1a hash of no bytes, to continue with `fnv1a_extend`.
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// A 64-bit FNV-1a hash of `bytes`.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_extend(FNV_OFFSET, bytes)
+}
+
+/// Continues the FNV-1a hash `hash` with `bytes`.
+pub(crate) fn fnv1a_extend(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Writes 0 for none, or the value plus one.
+pub(crate) fn write_option_varint(out: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) if value < u64::MAX => write_varint(out, value + 1),
+        // Cannot be told apart from smaller values plus one; recorded as unknown.
+        _ => write_varint(out, 0),
+    }
+}
+
+pub(crate) fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+pub(crate) fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_bytes(out, value.as_bytes());
+}
+
+pub(crate) fn write_option_str(out: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_str(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn write_path(out: &mut Vec<u8>, path: &std::path::Path) {
+    use std::os::unix::ffi::OsStrExt;
+    write_bytes(out, path.as_os_str().as_bytes());
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_path(out: &mut Vec<u8>, path: &std::path::Path) {
+    write_str(out, &path.to_string_lossy());
+}
+
+/// Writes a tag (0 for none, 1 after the epoch, 2 before it), then seconds and nanoseconds.
+pub(crate) fn write_time(out: &mut Vec<u8>, time: Option<SystemTime>) {
+    let Some(time) = time else {
+        out.push(0);
+        return;
+    };
+    let (tag, offset) = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => (1, after),
+        Err(before) => (2, before.duration()),
+    };
+    out.push(tag);
+    write_varint(out, offset.as_secs());
+    write_varint(out, u64::from(offset.subsec_nanos()));
+}
+
+pub(crate) fn write_options(out: &mut Vec<u8>, options: &ScanOptions) {
+    out.push(options.follow_symlinks as u8);
+    write_varint(out, options.max_depth.map_or(0, |depth| depth as u64 + 1));
+    out.push(options.lazy as u8);
+    out.push(options.include_hidden as u8);
+    out.push(options.same_file_system as u8);
+    out.push(match options.sort {
+        SortOrder::Unsorted => 0,
+        SortOrder::Name => 1,
+        SortOrder::LargestFirst => 2,
+    });
+    out.push(match options.errors {
+        ErrorPolicy::Abort => 0,
+        ErrorPolicy::Skip => 1,
+        ErrorPolicy::Collect => 2,
+    });
+    out.push(options.xattrs as u8);
+    out.push(options.hardlinks_once as u8);
+    out.push(match options.size_metric {
+        SizeMetric::Apparent => 0,
+        SizeMetric::DiskUsage => 1,
+    });
+    out.push(match options.checksums {
+        None => 0,
+        Some(algorithm) => algorithm_tag(algorithm),
+    });
+    write_varint(out, options.excludes.len() as u64);
+    for pattern in &options.excludes {
+        write_str(out, pattern);
+    }
+    out.push(options.mime_types as u8);
+}
+
+fn algorithm_tag(algorithm: HashAlgorithm) -> u8 {
+    match algorithm {
+        HashAlgorithm::Crc32 => 1,
+        HashAlgorithm::Sha256 => 2,
+    }
+}
+
+pub(crate) fn write_entry(out: &mut Vec<u8>, entry: &SnapshotEntry) {
+    write_path(out, &entry.path);
+    write_node_type(out, &entry.node_type);
+    write_varint(out, entry.size);
+    write_metadata(out, &entry.metadata);
+}
+
+pub(crate) fn write_node_type(out: &mut Vec<u8>, node_type: &NodeType) {
+    match node_type {
+        NodeType::File => out.push(0),
+        NodeType::Directory => out.push(1),
+        NodeType::Symlink { target } => {
+            out.push(2);
+            write_path(out, target);
+        }
+    }
+}
+
+pub(crate) fn write_metadata(out: &mut Vec<u8>, metadata: &ExtendedMetadata) {
+    write_time(out, metadata.modified);
+    write_time(out, metadata.accessed);
+    write_time(out, metadata.created);
+    write_option_varint(out, metadata.mode.map(u64::from));
+    write_option_varint(out, metadata.uid.map(u64::from));
+    write_option_varint(out, metadata.gid.map(u64::from));
+    write_option_varint(out, metadata.inode);
+    write_option_varint(out, metadata.device);
+    write_option_varint(out, metadata.nlink);
+    write_option_varint(out, metadata.len);
+    write_option_varint(out, metadata.blocks);
+    match &metadata.xattrs {
+        Some(xattrs) => {
+            write_varint(out, xattrs.len() as u64 + 1);
+            for (name, value) in xattrs {
+                write_str(out, name);
+                write_bytes(out, value);
+            }
+        }
+        None => write_varint(out, 0),
+    }
+    match &metadata.checksum {
+        Some(checksum) => {
+            out.push(algorithm_tag(checksum.algorithm));
+            write_bytes(out, &checksum.digest);
+        }
+        None => out.push(0),
+    }
+    match &metadata.mime_type {
+        Some(mime_type) => {
+            out.push(1);
+            write_str(out, mime_type);
+        }
+        None => out.push(0),
+    }
+}
+
+pub(crate) fn write_bookmarks(out: &mut Vec<u8>, bookmarks: &Bookmarks) {
+    write_varint(out, bookmarks.len() as u64);
+    for (name, bookmark) in bookmarks {
+        write_str(out, name);
+        write_path(out, &bookmark.path);
+        write_option_str(out, bookmark.note.as_deref());
+        write_time(out, Some(bookmark.created));
+    }
+}
+
+/// The unread rest of an encoded value.
+pub(crate) struct Input<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated input"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn flag(&mut self) -> io::Result<bool> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("invalid flag")),
+        }
+    }
+
+    pub(crate) fn u64_le(&mut self) -> io::Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().expect("eight bytes")))
+    }
+
+    pub(crate) fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("integer too long"))
+    }
+
+    pub(crate) fn option_varint(&mut self) -> io::Result<Option<u64>> {
+        Ok(self.varint()?.checked_sub(1))
+    }
+
+    pub(crate) fn option_u32(&mut self) -> io::Result<Option<u32>> {
+        match self.option_varint()? {
+            Some(value) => u32::try_from(value)
+                .map(Some)
+                .map_err(|_| invalid("integer too large")),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.varint()?).map_err(|_| invalid("length too large"))
+    }
+
+    pub(crate) fn str(&mut self) -> io::Result<String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("invalid UTF-8"))
+    }
+
+    pub(crate) fn option_str(&mut self) -> io::Result<Option<String>> {
+        match self.flag()? {
+            true => self.str().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn path(&mut self) -> io::Result<PathBuf> {
+        use std::os::unix::ffi::OsStrExt;
+        let len = self.len()?;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(self.take(len)?)))
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn path(&mut self) -> io::Result<PathBuf> {
+        self.str().map(PathBuf::from)
+    }
+
+    pub(crate) fn time(&mut self) -> io::Result<Option<SystemTime>> {
+        let tag = self.byte()?;
+        if tag == 0 {
+            return Ok(None);
+        }
+        let secs = self.varint()?;
+        let nanos = u32::try_from(self.varint()?)
+            .ok()
+            .filter(|nanos| *nanos < 1_000_000_000)
+            .ok_or_else(|| invalid("invalid nanoseconds"))?;
+        let offset = Duration::new(secs, nanos);
+        let time = match tag {
+            1 => UNIX_EPOCH.checked_add(offset),
+            2 => UNIX_EPOCH.checked_sub(offset),
+            _ => return Err(invalid("invalid time")),
+        };
+        time.map(Some).ok_or_else(|| invalid("time out of range"))
+    }
+
+    pub(crate) fn options(&mut self) -> io::Result<ScanOptions> {
+        Ok(ScanOptions {
+            follow_symlinks: self.flag()?,
+            max_depth: match self.varint()? {
+                0 => None,
+                depth => Some((depth - 1) as usize),
+            },
+            lazy: self.flag()?,
+            include_hidden: self.flag()?,
+            same_file_system: self.flag()?,
+            sort: match self.byte()? {
+                0 => SortOrder::Unsorted,
+                1 => SortOrder::Name,
+                2 => SortOrder::LargestFirst,
+                _ => return Err(invalid("invalid sort order")),
+            },
+            errors: match self.byte()? {
+                0 => ErrorPolicy::Abort,
+                1 => ErrorPolicy::Skip,
+                2 => ErrorPolicy::Collect,
+                _ => return Err(invalid("invalid error policy")),
+            },
+            xattrs: self.flag()?,
+            hardlinks_once: self.flag()?,
+            size_metric: match self.byte()? {
+                0 => SizeMetric::Apparent,
+                1 => SizeMetric::DiskUsage,
+                _ => return Err(invalid("invalid size metric")),
+            },
+            checksums: self.algorithm()?,
+            excludes: (0..self.varint()?)
+                .map(|_| self.str())
+                .collect::<io::Result<_>>()?,
+            mime_types: self.flag()?,
+        })
+    }
+
+    fn algorithm(&mut self) -> io::Result<Option<HashAlgorithm>> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(HashAlgorithm::Crc32)),
+            2 => Ok(Some(HashAlgorithm::Sha256)),
+            _ => Err(invalid("invalid hash algorithm")),
+        }
+    }
+
+    fn checksum(&mut self) -> io::Result<Option<Checksum>> {
+        let Some(algorithm) = self.algorithm()? else {
+            return Ok(None);
+        };
+        let len = self.len()?;
+        Ok(Some(Checksum {
+            algorithm,
+            digest: self.take(len)?.to_vec(),
+        }))
+    }
+
+    fn xattrs(&mut self) -> io::Result<Option<BTreeMap<String, Vec<u8>>>> {
+        let Some(count) = self.option_varint()? else {
+            return Ok(None);
+        };
+        let mut xattrs = BTreeMap::new();
+        for _ in 0..count {
+            let name = self.str()?;
+            let len = self.len()?;
+            xattrs.insert(name, self.take(len)?.to_vec());
+        }
+        Ok(Some(xattrs))
+    }
+
+    pub(crate) fn entry(&mut self) -> io::Result<SnapshotEntry> {
+        Ok(SnapshotEntry {
+            path: self.path()?,
+            node_type: self.node_type()?,
+            size: self.varint()?,
+            metadata: self.metadata()?,
+        })
+    }
+
+    pub(crate) fn node_type(&mut self) -> io::Result<NodeType> {
+        match self.byte()? {
+            0 => Ok(NodeType::File),
+            1 => Ok(NodeType::Directory),
+            2 => Ok(NodeType::Symlink {
+                target: self.path()?,
+            }),
+            _ => Err(invalid("invalid entry type")),
+        }
+    }
+
+    pub(crate) fn metadata(&mut self) -> io::Result<ExtendedMetadata> {
+        Ok(ExtendedMetadata {
+            modified: self.time()?,
+            accessed: self.time()?,
+            created: self.time()?,
+            mode: self.option_u32()?,
+            uid: self.option_u32()?,
+            gid: self.option_u32()?,
+            inode: self.option_varint()?,
+            device: self.option_varint()?,
+            nlink: self.option_varint()?,
+            len: self.option_varint()?,
+            blocks: self.option_varint()?,
+            xattrs: self.xattrs()?,
+            checksum: self.checksum()?,
+            mime_type: match self.flag()? {
+                true => Some(self.str()?),
+                false => None,
+            },
+        })
+    }
+
+    pub(crate) fn bookmarks(&mut self) -> io::Result<Bookmarks> {
+        let mut bookmarks = Bookmarks::new();
+        for _ in 0..self.varint()? {
+            let name = self.str()?;
+            let bookmark = Bookmark {
+                path: self.path()?,
+                note: self.option_str()?,
+                created: self
+                    .time()?
+                    .ok_or_else(|| invalid("missing bookmark time"))?,
+            };
+            bookmarks.insert(name, bookmark);
+        }
+        Ok(bookmarks)
+    }
+}
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// Search results together with how much of the tree they cover, so that "no matches"
+/// can be told apart from "not scanned yet". Returned by `Tree::search_partial`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryResult<T> {
+    /// What matched among the entries known.
+    pub matches: Vec<T>,
+    /// Whether the whole tree was searched with every entry's details: nothing is
+    /// unexplored and no details are pending.
+    pub complete: bool,
+    /// Directories whose contents are not known, and may hold more matches; see
+    /// `Tree::unexplored`.
+    pub unexplored: Vec<PathBuf>,
+    /// Whether sizes and metadata are still to be read, for a tree built with
+    /// `TreeBuilder::structure_only` that was not enriched yet. Predicates on them saw
+    /// zero sizes and no metadata.
+    pub details_pending: bool,
+}
+
+impl Tree {
+    /// Search for nodes matching `predicate`, as `search` does, and report what the
+    /// search could not see.
+    pub fn search_partial<F>(&self, predicate: F) -> QueryResult<&Node>
+    where
+        F: Fn(&Node) -> bool,
+    {
+        let unexplored = self.unexplored();
+        QueryResult {
+            matches: self.search(predicate),
+            complete: unexplored.is_empty() && !self.details_pending,
+            unexplored,
+            details_pending: self.details_pending,
+        }
+    }
+
+    /// The directories whose contents are not known, in tree order:
+    /// those never listed in lazy trees, evicted ones, ones beyond the maximum depth or
+    /// on other file systems, and those a `ScanBudget` stopped short of. Empty for a
+    /// tree scanned in full.
+    pub fn unexplored(&self) -> Vec<PathBuf> {
+        self.iter()
+            .filter(|node| node.is_dir() && node.children.is_none())
+            .map(|node| node.path.clone())
+            .collect()
+    }
+
+    /// Returns `true` while the tree's sizes and metadata are still to be read; see
+    /// `TreeBuilder::structure_only` and `Tree::enrich`.
+    pub fn details_pending(&self) -> bool {
+        self.details_pending
+    }
+}
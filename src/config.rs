@@ -0,0 +1,760 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::builder::TreeBuilder;
+use crate::checksum::HashAlgorithm;
+use crate::event::{PriorityLanes, RescanPolicy};
+use crate::options::{ErrorPolicy, ScanOptions, SizeMetric, SortOrder};
+
+/// What to scan and watch, and how, loaded from a TOML or JSON file so that a deployment
+/// is configured without rebuilding the application embedding the crate. Feed it to
+/// `ScanConfig::builders` or `Daemon::from_config`.
+///
+/// Both formats take the same keys, all but `roots` optional:
+///
+/// ```toml
+/// roots = ["/srv/data", "/home"]
+/// excludes = ["**/node_modules", "**/.cache"]
+/// max_depth = 12
+/// follow_symlinks = false
+/// include_hidden = true
+/// same_file_system = true
+/// lazy = false
+/// sort = "name"                # unsorted, name or largest_first
+/// errors = "skip"              # abort, skip or collect
+/// size_metric = "disk_usage"   # apparent or disk_usage
+/// hardlinks_once = true
+/// analyzers = ["sha256", "mime_types"]
+///
+/// [rescan]
+/// subtree_threshold = 64
+/// priority = ["/home/*/Desktop"]
+/// ```
+///
+/// `analyzers` lists what is worked out for each entry beyond its metadata: a checksum
+/// with `crc32` or `sha256`, `xattrs` to read extended attributes, and `mime_types` to
+/// detect types from file contents.
+///
+/// A root can also be a table with its `path` and any of the scan options above, which
+/// then override the ones given for all roots, e.g.
+/// `{ path = "/var/log", max_depth = 2, analyzers = [] }`.
+///
+/// In JSON, `rescan` and such roots are nested objects. Unknown keys and keys given twice
+/// are refused, so that a misspelt setting does not go unnoticed. Only the parts of TOML
+/// these keys need are read: strings, integers, booleans, arrays and tables.
+#[derive(Debug, Clone, Default)]
+pub struct ScanConfig {
+    /// The directories to scan, each into a tree of its own.
+    pub roots: Vec<PathBuf>,
+    /// How each root is scanned, unless it has options of its own.
+    pub options: ScanOptions,
+    /// The options of the roots that set some of their own, which override `options`.
+    pub root_options: BTreeMap<PathBuf, ScanOptions>,
+    /// How watched trees apply the changes they are notified of.
+    pub rescan: RescanPolicy,
+}
+
+impl ScanConfig {
+    /// Read the configuration at `path`, as TOML or JSON by its `.toml` or `.json`
+    /// extension.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is neither .toml nor .json", path.display()),
+            )),
+        }
+    }
+
+    /// Parse a configuration in TOML.
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        Self::from_value(Parser::new(text).toml()?)
+    }
+
+    /// Parse a configuration in JSON.
+    pub fn from_json(text: &str) -> io::Result<Self> {
+        Self::from_value(Parser::new(text).json()?)
+    }
+
+    /// How `root` is scanned: with its own options if it has any, and with `options`
+    /// otherwise.
+    pub fn options_for(&self, root: &Path) -> &ScanOptions {
+        self.root_options.get(root).unwrap_or(&self.options)
+    }
+
+    /// A builder for each root, configured with its options.
+    pub fn builders(&self) -> Vec<TreeBuilder> {
+        self.roots
+            .iter()
+            .map(|root| TreeBuilder::new(root).with_options(self.options_for(root).clone()))
+            .collect()
+    }
+
+    fn from_value(value: Value) -> io::Result<Self> {
+        let mut config = ScanConfig::default();
+        let mut roots = None;
+        for (key, value) in value.into_table("the configuration")? {
+            match key.as_str() {
+                "roots" => roots = Some(value.into_array(&key)?),
+                "rescan" => {
+                    for (key, value) in value.into_table(&key)? {
+                        match key.as_str() {
+                            "subtree_threshold" => {
+                                config.rescan.subtree_threshold = value.into_int(&key)? as usize
+                            }
+                            "priority" => {
+                                let mut lanes = PriorityLanes::new();
+                                for pattern in value.into_strings(&key)? {
+                                    lanes.add(&pattern);
+                                }
+                                config.rescan.priority = lanes;
+                            }
+                            _ => return Err(invalid(format!("unknown setting rescan.{key}"))),
+                        }
+                    }
+                }
+                _ => set_option(&mut config.options, &key, value)?,
+            }
+        }
+        for root in roots.ok_or_else(|| invalid("missing roots".to_string()))? {
+            let table = match root {
+                Value::Str(path) => {
+                    config.roots.push(PathBuf::from(path));
+                    continue;
+                }
+                Value::Table(table) => table,
+                _ => return Err(mistyped("roots", "an array of paths and tables")),
+            };
+            let mut options = config.options.clone();
+            let mut path = None;
+            for (key, value) in table {
+                match key.as_str() {
+                    "path" => path = Some(PathBuf::from(value.into_str(&key)?)),
+                    _ => set_option(&mut options, &key, value)?,
+                }
+            }
+            let path = path.ok_or_else(|| invalid("a root without a path".to_string()))?;
+            config.root_options.insert(path.clone(), options);
+            config.roots.push(path);
+        }
+        Ok(config)
+    }
+}
+
+/// Sets the scan option `key` of `options` to `value`.
+fn set_option(options: &mut ScanOptions, key: &str, value: Value) -> io::Result<()> {
+    match key {
+        "excludes" => options.excludes = value.into_strings(key)?,
+        "max_depth" => options.max_depth = Some(value.into_int(key)? as usize),
+        "follow_symlinks" => options.follow_symlinks = value.into_bool(key)?,
+        "include_hidden" => options.include_hidden = value.into_bool(key)?,
+        "same_file_system" => options.same_file_system = value.into_bool(key)?,
+        "lazy" => options.lazy = value.into_bool(key)?,
+        "hardlinks_once" => options.hardlinks_once = value.into_bool(key)?,
+        "sort" => {
+            options.sort = match value.into_str(key)?.as_str() {
+                "unsorted" => SortOrder::Unsorted,
+                "name" => SortOrder::Name,
+                "largest_first" => SortOrder::LargestFirst,
+                other => return Err(unknown(key, other)),
+            }
+        }
+        "errors" => {
+            options.errors = match value.into_str(key)?.as_str() {
+                "abort" => ErrorPolicy::Abort,
+                "skip" => ErrorPolicy::Skip,
+                "collect" => ErrorPolicy::Collect,
+                other => return Err(unknown(key, other)),
+            }
+        }
+        "size_metric" => {
+            options.size_metric = match value.into_str(key)?.as_str() {
+                "apparent" => SizeMetric::Apparent,
+                "disk_usage" => SizeMetric::DiskUsage,
+                other => return Err(unknown(key, other)),
+            }
+        }
+        "analyzers" => {
+            options.checksums = None;
+            options.xattrs = false;
+            options.mime_types = false;
+            for analyzer in value.into_strings(key)? {
+                let checksums = match analyzer.as_str() {
+                    "xattrs" => {
+                        options.xattrs = true;
+                        continue;
+                    }
+                    "mime_types" => {
+                        options.mime_types = true;
+                        continue;
+                    }
+                    "crc32" => HashAlgorithm::Crc32,
+                    "sha256" => HashAlgorithm::Sha256,
+                    other => return Err(unknown("analyzer", other)),
+                };
+                if options.checksums.is_some_and(|other| other != checksums) {
+                    return Err(invalid("analyzers name two checksums".to_string()));
+                }
+                options.checksums = Some(checksums);
+            }
+        }
+        _ => return Err(invalid(format!("unknown setting {key}"))),
+    }
+    Ok(())
+}
+
+/// A parsed value, in either format.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+    /// JSON's `null`.
+    Null,
+}
+
+impl Value {
+    fn into_table(self, key: &str) -> io::Result<Vec<(String, Value)>> {
+        match self {
+            Value::Table(table) => Ok(table),
+            _ => Err(mistyped(key, "a table")),
+        }
+    }
+
+    fn into_str(self, key: &str) -> io::Result<String> {
+        match self {
+            Value::Str(value) => Ok(value),
+            _ => Err(mistyped(key, "a string")),
+        }
+    }
+
+    fn into_int(self, key: &str) -> io::Result<u64> {
+        match self {
+            Value::Int(value) if value >= 0 => Ok(value as u64),
+            _ => Err(mistyped(key, "a non-negative integer")),
+        }
+    }
+
+    fn into_bool(self, key: &str) -> io::Result<bool> {
+        match self {
+            Value::Bool(value) => Ok(value),
+            _ => Err(mistyped(key, "true or false")),
+        }
+    }
+
+    fn into_array(self, key: &str) -> io::Result<Vec<Value>> {
+        match self {
+            Value::Array(items) => Ok(items),
+            _ => Err(mistyped(key, "an array")),
+        }
+    }
+
+    fn into_strings(self, key: &str) -> io::Result<Vec<String>> {
+        match self {
+            Value::Array(items) => items.into_iter().map(|item| item.into_str(key)).collect(),
+            _ => Err(mistyped(key, "an array of strings")),
+        }
+    }
+}
+
+/// Reads values from the text of a configuration, keeping track of the line for errors.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        invalid(format!("line {}: {}", self.line, message))
+    }
+
+    fn expect(&mut self, expected: char) -> io::Result<()> {
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected `{expected}`"))),
+        }
+    }
+
+    /// Adds `key` to `table`, refusing to define it twice.
+    fn insert(
+        &self,
+        table: &mut Vec<(String, Value)>,
+        key: String,
+        value: Value,
+    ) -> io::Result<()> {
+        if table.iter().any(|(existing, _)| *existing == key) {
+            return Err(self.error(&format!("{key} is defined twice")));
+        }
+        table.push((key, value));
+        Ok(())
+    }
+
+    /// Skips spaces and tabs, and with `newlines` also line breaks and `#` comments.
+    fn skip_space(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => {}
+                '#' if newlines => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                    continue;
+                }
+                _ => return,
+            }
+            self.bump();
+        }
+    }
+
+    /// Parses a whole JSON document.
+    fn json(mut self) -> io::Result<Value> {
+        let value = self.json_value()?;
+        self.skip_json_space();
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(self.error("unexpected text after the value")),
+        }
+    }
+
+    fn skip_json_space(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn json_value(&mut self) -> io::Result<Value> {
+        self.skip_json_space();
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                let mut table = Vec::new();
+                self.skip_json_space();
+                if self.peek() == Some('}') {
+                    self.bump();
+                    return Ok(Value::Table(table));
+                }
+                loop {
+                    self.skip_json_space();
+                    let key = self.string()?;
+                    self.skip_json_space();
+                    self.expect(':')?;
+                    let value = self.json_value()?;
+                    self.insert(&mut table, key, value)?;
+                    self.skip_json_space();
+                    match self.bump() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Value::Table(table)),
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                self.skip_json_space();
+                if self.peek() == Some(']') {
+                    self.bump();
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.json_value()?);
+                    self.skip_json_space();
+                    match self.bump() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(items)),
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('"') => Ok(Value::Str(self.string()?)),
+            _ => match self.word().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "null" => Ok(Value::Null),
+                word => self.integer(word),
+            },
+        }
+    }
+
+    /// Parses a whole TOML document into a table, with one nested table per header.
+    fn toml(mut self) -> io::Result<Value> {
+        let mut root: Vec<(String, Value)> = Vec::new();
+        let mut current: Option<String> = None;
+        let mut tables: BTreeMap<String, Vec<(String, Value)>> = BTreeMap::new();
+        loop {
+            self.skip_space(true);
+            let Some(c) = self.peek() else {
+                break;
+            };
+            if c == '[' {
+                self.bump();
+                self.skip_space(false);
+                let name = self.key()?;
+                self.skip_space(false);
+                self.expect(']')?;
+                if tables.contains_key(&name) || root.iter().any(|(key, _)| *key == name) {
+                    return Err(self.error(&format!("{name} is defined twice")));
+                }
+                tables.insert(name.clone(), Vec::new());
+                current = Some(name);
+            } else {
+                let key = self.key()?;
+                self.skip_space(false);
+                self.expect('=')?;
+                self.skip_space(false);
+                let value = self.toml_value()?;
+                let table = match &current {
+                    Some(name) => tables.get_mut(name).expect("created with its header"),
+                    None => &mut root,
+                };
+                self.insert(table, key, value)?;
+            }
+            self.skip_space(false);
+            match self.peek() {
+                None | Some('\n') | Some('#') => {}
+                Some(_) => return Err(self.error("expected the end of the line")),
+            }
+        }
+        root.extend(
+            tables
+                .into_iter()
+                .map(|(name, table)| (name, Value::Table(table))),
+        );
+        Ok(Value::Table(root))
+    }
+
+    /// A bare or quoted key.
+    fn key(&mut self) -> io::Result<String> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string(),
+            _ => {
+                let key = self.word();
+                match key.is_empty() {
+                    true => Err(self.error("expected a key")),
+                    false => Ok(key),
+                }
+            }
+        }
+    }
+
+    fn toml_value(&mut self) -> io::Result<Value> {
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::Str(self.string()?)),
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_space(true);
+                    if self.peek() == Some(']') {
+                        self.bump();
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.toml_value()?);
+                    self.skip_space(true);
+                    match self.bump() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(items)),
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.bump();
+                let mut table = Vec::new();
+                self.skip_space(false);
+                if self.peek() == Some('}') {
+                    self.bump();
+                    return Ok(Value::Table(table));
+                }
+                loop {
+                    self.skip_space(false);
+                    let key = self.key()?;
+                    self.skip_space(false);
+                    self.expect('=')?;
+                    self.skip_space(false);
+                    let value = self.toml_value()?;
+                    self.insert(&mut table, key, value)?;
+                    self.skip_space(false);
+                    match self.bump() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Value::Table(table)),
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            _ => match self.word().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                word => self.integer(&word.replace('_', "")),
+            },
+        }
+    }
+
+    /// A run of characters that may make up a bare key, number or keyword.
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+')) {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        word
+    }
+
+    fn integer(&self, word: &str) -> io::Result<Value> {
+        word.parse()
+            .map(Value::Int)
+            .map_err(|_| self.error("expected a value"))
+    }
+
+    /// A string in double quotes with escapes, as both formats have them, or in TOML's
+    /// single quotes without.
+    fn string(&mut self) -> io::Result<String> {
+        let quote = self.bump().filter(|c| matches!(c, '"' | '\''));
+        let Some(quote) = quote else {
+            return Err(self.error("expected a string"));
+        };
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => return Ok(value),
+                Some('\\') if quote == '"' => value.push(self.escape()?),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> io::Result<char> {
+        match self.bump() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let unit = self.hex(4)?;
+                let code = match unit {
+                    // A UTF-16 surrogate pair, as JSON encoders write characters outside
+                    // the Basic Multilingual Plane.
+                    0xd800..=0xdbff => {
+                        if self.bump() != Some('\\') || self.bump() != Some('u') {
+                            return Err(self.error("unpaired surrogate in \\u escape"));
+                        }
+                        match self.hex(4)? {
+                            low @ 0xdc00..=0xdfff => {
+                                0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)
+                            }
+                            _ => return Err(self.error("unpaired surrogate in \\u escape")),
+                        }
+                    }
+                    code => code,
+                };
+                char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))
+            }
+            Some('U') => {
+                let code = self.hex(8)?;
+                char::from_u32(code).ok_or_else(|| self.error("invalid \\U escape"))
+            }
+            _ => Err(self.error("invalid escape")),
+        }
+    }
+
+    /// The value of the next `digits` hexadecimal digits.
+    fn hex(&mut self, digits: usize) -> io::Result<u32> {
+        let hex: String = (0..digits).filter_map(|_| self.bump()).collect();
+        match hex.len() == digits && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => u32::from_str_radix(&hex, 16).map_err(|_| self.error("invalid escape")),
+            false => Err(self.error("invalid escape")),
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn mistyped(key: &str, expected: &str) -> io::Error {
+    invalid(format!("{key} should be {expected}"))
+}
+
+fn unknown(key: &str, value: &str) -> io::Error {
+    invalid(format!("unknown {key} {value:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::ScanConfig;
+    use crate::checksum::HashAlgorithm;
+    use crate::options::{ErrorPolicy, SortOrder};
+
+    fn error(result: std::io::Result<ScanConfig>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn toml_with_every_kind_of_value() {
+        let config = ScanConfig::from_toml(
+            r#"
+            # Deployment settings.
+            roots = ["/srv/data", '/home']
+            excludes = [
+                "**/node_modules",  # trailing commas and comments are fine
+                "**/.cache",
+            ]
+            max_depth = 1_000
+            sort = "name"
+            errors = "skip"
+            analyzers = ["sha256", "xattrs", "mime_types"]
+
+            [rescan]
+            subtree_threshold = 64
+            priority = ["/home/*/Desktop"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.roots,
+            [PathBuf::from("/srv/data"), PathBuf::from("/home")]
+        );
+        assert_eq!(config.options.excludes, ["**/node_modules", "**/.cache"]);
+        assert_eq!(config.options.max_depth, Some(1000));
+        assert_eq!(config.options.sort, SortOrder::Name);
+        assert_eq!(config.options.errors, ErrorPolicy::Skip);
+        assert_eq!(config.options.checksums, Some(HashAlgorithm::Sha256));
+        assert!(config.options.xattrs && config.options.mime_types);
+        assert_eq!(config.rescan.subtree_threshold, 64);
+    }
+
+    #[test]
+    fn json_matches_toml() {
+        let json = ScanConfig::from_json(
+            r#"{"roots": ["/srv/data"], "max_depth": 3, "rescan": {"subtree_threshold": 8}}"#,
+        )
+        .unwrap();
+        let toml = ScanConfig::from_toml(
+            "roots = [\"/srv/data\"]\nmax_depth = 3\n[rescan]\nsubtree_threshold = 8\n",
+        )
+        .unwrap();
+        assert_eq!(json.roots, toml.roots);
+        assert_eq!(json.options, toml.options);
+        assert_eq!(json.rescan.subtree_threshold, toml.rescan.subtree_threshold);
+    }
+
+    #[test]
+    fn escapes() {
+        let config = ScanConfig::from_json(
+            r#"{"roots": ["/tab\there", "/quote\"d", "/caf\u00e9", "/\ud83d\udcc1 files"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.roots,
+            ["/tab\there", "/quote\"d", "/café", "/📁 files"].map(PathBuf::from)
+        );
+        let config = ScanConfig::from_toml(r#"roots = ["/\U0001F4C1", 'C:\raw']"#).unwrap();
+        assert_eq!(config.roots, ["/📁", r"C:\raw"].map(PathBuf::from));
+
+        assert!(error(ScanConfig::from_json(r#"{"roots": ["\ud83d"]}"#)).contains("surrogate"));
+        assert!(error(ScanConfig::from_json(r#"{"roots": ["\u12"]}"#)).contains("escape"));
+    }
+
+    #[test]
+    fn roots_override_options() {
+        let config = ScanConfig::from_toml(
+            r#"
+            roots = ["/home", { path = "/var/log", max_depth = 2, analyzers = [] }]
+            max_depth = 10
+            analyzers = ["crc32"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.roots, ["/home", "/var/log"].map(PathBuf::from));
+        let home = config.options_for(Path::new("/home"));
+        assert_eq!(
+            (home.max_depth, home.checksums),
+            (Some(10), Some(HashAlgorithm::Crc32))
+        );
+        let log = config.options_for(Path::new("/var/log"));
+        assert_eq!((log.max_depth, log.checksums), (Some(2), None));
+
+        let json = ScanConfig::from_json(
+            r#"{"roots": [{"path": "/var/log", "max_depth": 2, "analyzers": []}],
+                "max_depth": 10, "analyzers": ["crc32"]}"#,
+        )
+        .unwrap();
+        assert_eq!(json.root_options, config.root_options);
+        assert!(error(ScanConfig::from_toml("roots = [{ max_depth = 2 }]")).contains("path"));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let message = error(ScanConfig::from_toml(
+            "roots = [\"/a\"]\n\nmax_depth = = 3\n",
+        ));
+        assert!(message.starts_with("line 3:"), "{message}");
+        let message = error(ScanConfig::from_json(
+            "{\n  \"roots\": [\"/a\"\n  \"lazy\": true\n}",
+        ));
+        assert!(message.starts_with("line 3:"), "{message}");
+        let message = error(ScanConfig::from_toml(
+            "roots = [\"/a\"]\nname = \"unterminated\n",
+        ));
+        assert!(message.contains("unterminated"), "{message}");
+    }
+
+    #[test]
+    fn duplicate_and_unknown_keys_are_refused() {
+        for result in [
+            ScanConfig::from_json(r#"{"roots": ["/a"], "lazy": true, "lazy": false}"#),
+            ScanConfig::from_toml("roots = [\"/a\"]\nlazy = true\nlazy = false\n"),
+            ScanConfig::from_toml("roots = [\"/a\"]\n[rescan]\n[rescan]\n"),
+        ] {
+            assert!(error(result).contains("defined twice"));
+        }
+        let message = error(ScanConfig::from_toml("roots = [\"/a\"]\nmax_dpeth = 3\n"));
+        assert!(message.contains("max_dpeth"), "{message}");
+        let message = error(ScanConfig::from_toml(
+            "roots = [\"/a\"]\nanalyzers = [\"md5\"]\n",
+        ));
+        assert!(message.contains("md5"), "{message}");
+        assert!(error(ScanConfig::from_toml("lazy = true\n")).contains("missing roots"));
+    }
+}
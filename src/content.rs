@@ -0,0 +1,78 @@
+use std::fs::File;
+#[cfg(feature = "mmap")]
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::resources::Resources;
+
+/// Hands the contents of the file at `path` to `consume` in order, a piece at a time,
+/// as `resources` asks for.
+///
+/// With the `mmap` feature, regular files of at least `Resources::mmap_threshold` bytes
+/// are mapped into memory and handed over in one piece, saving the copy into a buffer.
+/// Smaller files, files that cannot be mapped, and all files without the feature are
+/// read in pieces of 256 KiB. With the `fadvise` feature the kernel is told the file is
+/// read sequentially, and `Resources::spare_page_cache` is honoured.
+///
+/// A mapped file whose size or modification time changed by the time it was consumed
+/// fails with `InvalidData`, since what was seen may mix old and new contents. A file
+/// truncated while it is mapped can still kill the process with `SIGBUS` on Unix when
+/// the missing part is touched; leave the threshold unset where files may shrink under
+/// a read, e.g. logs being rotated.
+pub(crate) fn read_contents(
+    path: &Path,
+    resources: &Resources,
+    mut consume: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let file = File::open(path)?;
+    #[cfg(all(feature = "fadvise", target_os = "linux"))]
+    let _hints = crate::fadvise::Streaming::read(&file, resources.spare_page_cache);
+    #[cfg(feature = "mmap")]
+    if let Some(threshold) = resources.mmap_threshold {
+        let before = file.metadata()?;
+        if before.is_file() && before.len() > 0 && before.len() >= threshold {
+            // SAFETY: the mapping is only read, and a change to the file while it is
+            // read is detected afterwards; see above for truncation.
+            if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                #[cfg(all(feature = "fadvise", target_os = "linux"))]
+                let _ = map.advise(memmap2::Advice::Sequential);
+                consume(&map)?;
+                return unchanged(&file, &before);
+            }
+        }
+    }
+    #[cfg(not(any(feature = "mmap", all(feature = "fadvise", target_os = "linux"))))]
+    let _ = resources;
+
+    read_all(&file, &mut consume)
+}
+
+/// Hands everything `reader` yields to `consume` in order, in pieces of up to 256 KiB.
+pub(crate) fn read_all(
+    mut reader: impl Read,
+    mut consume: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut buf = vec![0u8; 256 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(read) => consume(&buf[..read])?,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Fails if `file` no longer has the size and modification time in `before`.
+#[cfg(feature = "mmap")]
+fn unchanged(file: &File, before: &Metadata) -> io::Result<()> {
+    let after = file.metadata()?;
+    match after.len() == before.len() && after.modified().ok() == before.modified().ok() {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file changed while being read",
+        )),
+    }
+}
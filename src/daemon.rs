@@ -0,0 +1,789 @@
+//! A long-running daemon that keeps trees scanned and watched, answering queries from
+//! other processes over a Unix socket.
+//!
+//! The protocol is line based, with tab-separated fields. Each request is a single line:
+//!
+//! - `get <path>`: the node at `path`
+//! - `search <text>`: nodes whose file name contains `text`
+//! - `stats`: file, directory and byte totals across all roots
+//! - `subscribe [<seq>]`: stream every subsequent change, or every change after `seq`
+//!
+//! Nodes are answered as `node\t<kind>\t<size>\t<mtime>\t<path>` lines, followed by
+//! `end`. `kind` is `file`, `dir` or `link`; a `link` line has the link's target as a
+//! further `\t<target>` field. `mtime` is `<secs>.<nanos>` since the Unix epoch, with
+//! nine digits of nanoseconds, or `-` if unknown. Statistics are answered as
+//! `stats\t<files>\t<dirs>\t<bytes>` followed by `end`, where links count as files, and
+//! failures as `err\t<message>`.
+//!
+//! Subscriptions are acknowledged with `subscribed\t<seq>`, giving the sequence number of
+//! the latest change, and then stream `event\t<seq>\t<kind>\t<path>` lines until the
+//! connection closes. `kind` is `created`, `modified`, `removed`, `renamed` or
+//! `desynced`; `renamed` lines carry the old path and then the new one. A subscription
+//! resumed from a sequence number the journal no longer covers is refused with
+//! `err\tgap`. A subscriber that falls as many changes behind as the journal holds is
+//! disconnected; resuming from the last sequence number it read picks up from there.
+//!
+//! Paths, link targets and search text are escaped both ways: a backslash is sent as
+//! `\\`, a tab as `\t`, a newline as `\n` and a carriage return as `\r`.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::builder::TreeBuilder;
+use crate::config::ScanConfig;
+use crate::event::{FsEvent, PriorityLanes, RescanPolicy};
+use crate::journal::{ChangeJournal, JournalEntry};
+use crate::manifest::parse_epoch;
+use crate::node::{Node, NodeType};
+use crate::tree::Tree;
+use crate::source::{batch_after, EventSource};
+use crate::watcher::FsWatcher;
+
+/// How long the daemon's watchers wait after a change for the rest of a burst.
+const BATCH_WINDOW: Duration = Duration::from_millis(200);
+
+/// Longest a watcher thread waits for events before checking whether to stop.
+const STOP_CHECK: Duration = Duration::from_secs(1);
+
+/// How many changes the daemon remembers for subscribers resuming after a disconnect.
+const JOURNAL_CAPACITY: usize = 65_536;
+
+/// Keeps trees warm and watched, serving queries over a Unix socket.
+pub struct Daemon {
+    roots: Arc<Mutex<Vec<Watched>>>,
+    policy: Arc<Mutex<RescanPolicy>>,
+    changes: Arc<Mutex<Changes>>,
+}
+
+/// A root the daemon keeps scanned, and the switch that stops its watcher.
+struct Watched {
+    root: PathBuf,
+    tree: Arc<Mutex<Tree>>,
+    stop: Arc<AtomicBool>,
+    /// Why the tree last failed to take changes in, while it is out of date because of
+    /// it; see `Daemon::failures`.
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+/// Recent changes across all roots and the subscribers waiting for new ones. Kept under a
+/// single lock so that a resuming subscriber sees every change exactly once.
+struct Changes {
+    journal: ChangeJournal,
+    subscribers: Vec<SyncSender<JournalEntry>>,
+}
+
+impl Daemon {
+    /// Scan every root and start watching it for changes.
+    pub fn new(roots: &[PathBuf], policy: RescanPolicy) -> io::Result<Self> {
+        let builders = roots.iter().map(TreeBuilder::new).collect();
+        Self::watching(builders, policy)
+    }
+
+    /// Scan every root of `config` with its options and start watching it for changes,
+    /// applying them with its rescan policy.
+    pub fn from_config(config: &ScanConfig) -> io::Result<Self> {
+        Self::watching(config.builders(), config.rescan.clone())
+    }
+
+    /// Builds each tree with its builder and starts watching its root for changes.
+    fn watching(builders: Vec<TreeBuilder>, policy: RescanPolicy) -> io::Result<Self> {
+        let daemon = Self {
+            roots: Arc::new(Mutex::new(Vec::new())),
+            policy: Arc::new(Mutex::new(policy)),
+            changes: Arc::new(Mutex::new(Changes {
+                journal: ChangeJournal::new(JOURNAL_CAPACITY),
+                subscribers: Vec::new(),
+            })),
+        };
+        for builder in builders {
+            let watched = daemon.watch(builder)?;
+            lock(&daemon.roots)?.push(watched);
+        }
+        Ok(daemon)
+    }
+
+    /// Builds the tree of `builder` and starts a thread applying the changes below its
+    /// root, until stopped.
+    fn watch(&self, builder: TreeBuilder) -> io::Result<Watched> {
+        let root = builder.root().to_path_buf();
+        let watcher = FsWatcher::new(&root)?;
+        let tree = Arc::new(Mutex::new(builder.build()?));
+        let stop = Arc::new(AtomicBool::new(false));
+        let failure = Arc::new(Mutex::new(None));
+
+        let watched = Watched {
+            root: root.clone(),
+            tree: Arc::clone(&tree),
+            stop: Arc::clone(&stop),
+            failure: Arc::clone(&failure),
+        };
+        let policy = Arc::clone(&self.policy);
+        let changes = Arc::clone(&self.changes);
+        thread::spawn(move || loop {
+            let Ok(lanes) = policy.lock().map(|policy| policy.priority.clone()) else {
+                return;
+            };
+            let Some(batch) = next_batch(&watcher, &lanes, &stop) else {
+                return;
+            };
+            // Read again, so that a reload while waiting applies to this batch.
+            let Ok(policy) = policy.lock().map(|policy| policy.clone()) else {
+                return;
+            };
+            let mut batch = batch;
+            if let Ok(mut tree) = tree.lock() {
+                // A tree that failed to take a batch in rescans as a whole instead,
+                // which subscribers are told to do too.
+                let applied = match resync(&mut tree, &failure) {
+                    true => tree.apply_events(&batch, &policy).map(drop),
+                    false => Err(io::Error::other("still out of date")),
+                };
+                if let Err(error) = applied {
+                    if let Ok(mut failure) = failure.lock() {
+                        failure.get_or_insert_with(|| error.to_string());
+                    }
+                    batch.push(FsEvent::Desynced(root.clone()));
+                }
+            }
+            if let Ok(mut changes) = changes.lock() {
+                let Changes {
+                    journal,
+                    subscribers,
+                } = &mut *changes;
+                for event in batch {
+                    let entry = journal.record(event);
+                    // A client too far behind is dropped rather than queued for without
+                    // bound; it can resume from the journal.
+                    subscribers.retain(|subscriber| subscriber.try_send(entry.clone()).is_ok());
+                }
+            }
+        });
+        Ok(watched)
+    }
+
+    /// Bring the running daemon in line with `config`, without a restart: roots no
+    /// longer listed stop being watched and are dropped, new ones are scanned and
+    /// watched, and the trees kept switch to their new options with `Tree::reconfigure`,
+    /// which rescans newly included areas and prunes newly excluded ones. Changes are
+    /// applied with the new rescan policy from the next batch on.
+    ///
+    /// The first root that fails to scan or reconfigure ends the reload with its error,
+    /// leaving the roots reconciled so far as they are.
+    pub fn reload(&self, config: &ScanConfig) -> io::Result<()> {
+        *lock(&self.policy)? = config.rescan.clone();
+        let kept: Vec<(PathBuf, Arc<Mutex<Tree>>)> = {
+            let mut roots = lock(&self.roots)?;
+            roots.retain(|watched| {
+                let keep = config.roots.contains(&watched.root);
+                if !keep {
+                    watched.stop.store(true, Ordering::Relaxed);
+                }
+                keep
+            });
+            roots
+                .iter()
+                .map(|watched| (watched.root.clone(), Arc::clone(&watched.tree)))
+                .collect()
+        };
+        for (root, tree) in kept {
+            lock(&tree)?.reconfigure(config.options_for(&root).clone())?;
+        }
+        for builder in config.builders() {
+            let known = lock(&self.roots)?
+                .iter()
+                .any(|watched| watched.root == builder.root());
+            if !known {
+                // Scanned without holding the lock, so queries carry on meanwhile.
+                let watched = self.watch(builder)?;
+                lock(&self.roots)?.push(watched);
+            }
+        }
+        Ok(())
+    }
+
+    /// The roots whose trees failed to take the latest changes in, with the error.
+    /// Such a tree is rescanned as a whole by the next request or batch of changes, and
+    /// leaves the list once that succeeds.
+    pub fn failures(&self) -> io::Result<Vec<(PathBuf, String)>> {
+        let roots = lock(&self.roots)?;
+        let mut failures = Vec::new();
+        for watched in roots.iter() {
+            if let Some(failure) = lock(&watched.failure)?.clone() {
+                failures.push((watched.root.clone(), failure));
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Listen on `socket` and serve clients until an error occurs.
+    /// Each client is handled on its own thread. A socket left at that path, e.g. by a
+    /// daemon that died, is replaced; anything else there fails with `AlreadyExists`.
+    pub fn serve(&self, socket: &Path) -> io::Result<()> {
+        remove_stale_socket(socket)?;
+        let listener = UnixListener::bind(socket)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let roots = Arc::clone(&self.roots);
+            let changes = Arc::clone(&self.changes);
+            thread::spawn(move || {
+                let _ = handle_client(stream, &roots, &changes);
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Removes the socket at `path`, if there is one, so that it can be bound again.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Waits for the next batch of events from `watcher`, as `recv_batch_prioritized`
+/// does, checking `stop` meanwhile. `None` once `stop` is set.
+fn next_batch(
+    watcher: &FsWatcher,
+    lanes: &PriorityLanes,
+    stop: &AtomicBool,
+) -> Option<Vec<FsEvent>> {
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        if let Some(first) = watcher.recv_timeout(STOP_CHECK) {
+            return Some(batch_after(watcher, first, BATCH_WINDOW, lanes));
+        }
+    }
+}
+
+/// The trees of the daemon's roots as they are now, first rescanning those that failed
+/// to take changes in.
+fn trees(roots: &Mutex<Vec<Watched>>) -> io::Result<Vec<Arc<Mutex<Tree>>>> {
+    let trees = lock(roots)?
+        .iter()
+        .map(|watched| (Arc::clone(&watched.tree), Arc::clone(&watched.failure)))
+        .collect::<Vec<_>>();
+    for (tree, failure) in &trees {
+        if lock(failure)?.is_some() {
+            resync(&mut *lock(tree)?, failure);
+        }
+    }
+    Ok(trees.into_iter().map(|(tree, _)| tree).collect())
+}
+
+/// Rescans `tree` as a whole if `failure` says it is out of date, clearing it on success
+/// and recording the new error otherwise. Returns `true` if the tree is up to date
+/// with the events seen so far.
+fn resync(tree: &mut Tree, failure: &Mutex<Option<String>>) -> bool {
+    let Ok(mut failure) = failure.lock() else {
+        return false;
+    };
+    if failure.is_none() {
+        return true;
+    }
+    match tree.refresh() {
+        Ok(()) => {
+            *failure = None;
+            true
+        }
+        Err(error) => {
+            *failure = Some(error.to_string());
+            false
+        }
+    }
+}
+
+fn handle_client(
+    stream: UnixStream,
+    roots: &Mutex<Vec<Watched>>,
+    changes: &Mutex<Changes>,
+) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    for line in reader.lines() {
+        let line = line?;
+        let (command, argument) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        match command {
+            "get" => {
+                let path = PathBuf::from(unescape(argument));
+                let mut found = false;
+                for tree in &trees(roots)? {
+                    let tree = lock(tree)?;
+                    if let Some(node) = tree.get_node(&path) {
+                        writeln!(writer, "{}", node_line(node))?;
+                        found = true;
+                        break;
+                    }
+                }
+                if found {
+                    writeln!(writer, "end")?;
+                } else {
+                    writeln!(writer, "err\tnot found")?;
+                }
+            }
+            "search" => {
+                let text = unescape(argument);
+                for tree in &trees(roots)? {
+                    let tree = lock(tree)?;
+                    let matches = tree.search(|node| {
+                        node.path
+                            .file_name()
+                            .is_some_and(|name| name.to_string_lossy().contains(&text))
+                    });
+                    for node in matches {
+                        writeln!(writer, "{}", node_line(node))?;
+                    }
+                }
+                writeln!(writer, "end")?;
+            }
+            "stats" => {
+                let mut stats = RemoteStats::default();
+                for tree in &trees(roots)? {
+                    let tree = lock(tree)?.stats();
+                    stats.files += tree.files + tree.symlinks;
+                    stats.dirs += tree.dirs;
+                    stats.bytes += tree.size;
+                }
+                writeln!(
+                    writer,
+                    "stats\t{}\t{}\t{}\nend",
+                    stats.files, stats.dirs, stats.bytes
+                )?;
+            }
+            "subscribe" => {
+                let after = match argument {
+                    "" => None,
+                    seq => match seq.parse::<u64>() {
+                        Ok(seq) => Some(seq),
+                        Err(_) => {
+                            writeln!(writer, "err\tmalformed sequence number")?;
+                            continue;
+                        }
+                    },
+                };
+                let (sender, receiver) = mpsc::sync_channel(JOURNAL_CAPACITY);
+                let (last, backlog) = {
+                    let mut changes = changes
+                        .lock()
+                        .map_err(|_| io::Error::other("subscriber list poisoned"))?;
+                    let last = changes.journal.last_seq();
+                    let backlog: Vec<JournalEntry> = match after {
+                        None => Vec::new(),
+                        Some(seq) => match changes.journal.since(seq) {
+                            Some(entries) => entries.cloned().collect(),
+                            None => {
+                                writeln!(writer, "err\tgap")?;
+                                continue;
+                            }
+                        },
+                    };
+                    changes.subscribers.push(sender);
+                    (last, backlog)
+                };
+                writeln!(writer, "subscribed\t{}", last)?;
+                for entry in backlog.into_iter().chain(receiver) {
+                    writeln!(writer, "{}", event_line(&entry))?;
+                }
+                return Ok(());
+            }
+            _ => writeln!(writer, "err\tunknown command")?,
+        }
+    }
+    Ok(())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> io::Result<std::sync::MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| io::Error::other("daemon state poisoned by a panicked watcher"))
+}
+
+/// A node as reported by the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteNode {
+    /// Filesystem path of the node.
+    pub path: PathBuf,
+    /// Whether the node is a file or a directory.
+    pub node_type: NodeType,
+    /// Self size if file, cumulative size if directory.
+    pub size: u64,
+    /// Last modification time, if known.
+    pub modified: Option<SystemTime>,
+}
+
+/// Totals across every tree served by the daemon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RemoteStats {
+    /// Number of files.
+    pub files: u64,
+    /// Number of directories, including the roots.
+    pub dirs: u64,
+    /// Total size of all roots.
+    pub bytes: u64,
+}
+
+/// A connection to a running daemon.
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connect to the daemon listening on `socket`.
+    pub fn connect(socket: &Path) -> io::Result<Self> {
+        let writer = UnixStream::connect(socket)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self { reader, writer })
+    }
+
+    /// Look up the node at `path`.
+    pub fn get_node(&mut self, path: &Path) -> io::Result<Option<RemoteNode>> {
+        writeln!(self.writer, "get {}", escape(&path.to_string_lossy()))?;
+        match self.read_response() {
+            Ok(lines) => lines.iter().map(|line| parse_node(line)).next().transpose(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Find nodes whose file name contains `text`.
+    pub fn search(&mut self, text: &str) -> io::Result<Vec<RemoteNode>> {
+        writeln!(self.writer, "search {}", escape(text))?;
+        self.read_response()?
+            .iter()
+            .map(|line| parse_node(line))
+            .collect()
+    }
+
+    /// Totals across every tree served by the daemon.
+    pub fn stats(&mut self) -> io::Result<RemoteStats> {
+        writeln!(self.writer, "stats")?;
+        let lines = self.read_response()?;
+        let fields: Vec<&str> = lines
+            .first()
+            .ok_or_else(|| invalid("empty stats response"))?
+            .split('\t')
+            .collect();
+        let number = |index: usize| -> io::Result<u64> {
+            fields
+                .get(index)
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| invalid("malformed stats response"))
+        };
+        Ok(RemoteStats {
+            files: number(1)?,
+            dirs: number(2)?,
+            bytes: number(3)?,
+        })
+    }
+
+    /// Turn the connection into a stream of changes made from now on. Also returns the
+    /// sequence number of the latest change before the subscription, to resume from if no
+    /// change arrives before a disconnect.
+    pub fn subscribe(self) -> io::Result<(u64, impl Iterator<Item = io::Result<JournalEntry>>)> {
+        self.stream_changes("subscribe".to_string())
+    }
+
+    /// Turn the connection into a stream of every change after `seq`, as last seen on an
+    /// earlier subscription, followed by changes made from now on. Fails with
+    /// `ErrorKind::InvalidInput` if the daemon no longer remembers all of those changes
+    /// (or has restarted since); the caller then has to start over from a fresh scan.
+    pub fn resume(
+        self,
+        seq: u64,
+    ) -> io::Result<(u64, impl Iterator<Item = io::Result<JournalEntry>>)> {
+        self.stream_changes(format!("subscribe {}", seq))
+    }
+
+    fn stream_changes(
+        mut self,
+        request: String,
+    ) -> io::Result<(u64, impl Iterator<Item = io::Result<JournalEntry>>)> {
+        writeln!(self.writer, "{}", request)?;
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let line = line.trim_end_matches('\n');
+        if line == "err\tgap" {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the daemon's journal no longer covers that sequence number",
+            ));
+        }
+        let last = line
+            .strip_prefix("subscribed\t")
+            .and_then(|seq| seq.parse().ok())
+            .ok_or_else(|| invalid("malformed subscription response"))?;
+        Ok((last, self.reader.lines().map(|line| parse_event(&line?))))
+    }
+
+    /// Reads lines up to `end`, turning an `err` line into an error.
+    fn read_response(&mut self) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "daemon closed the connection",
+                ));
+            }
+            let line = line.trim_end_matches('\n');
+            if line == "end" {
+                return Ok(lines);
+            }
+            if let Some(message) = line.strip_prefix("err\t") {
+                let kind = if message == "not found" {
+                    io::ErrorKind::NotFound
+                } else {
+                    io::ErrorKind::Other
+                };
+                return Err(io::Error::new(kind, message.to_string()));
+            }
+            lines.push(line.to_string());
+        }
+    }
+}
+
+fn node_line(node: &Node) -> String {
+    let (kind, target) = match &node.node_type {
+        NodeType::File => ("file", None),
+        NodeType::Directory => ("dir", None),
+        NodeType::Symlink { target } => ("link", Some(target)),
+    };
+    let modified = node
+        .metadata
+        .modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| format!("{}.{:09}", since.as_secs(), since.subsec_nanos()))
+        .unwrap_or_else(|| "-".to_string());
+    let mut line = format!(
+        "node\t{}\t{}\t{}\t{}",
+        kind,
+        node.size,
+        modified,
+        escape(&node.path.to_string_lossy())
+    );
+    if let Some(target) = target {
+        line.push('\t');
+        line.push_str(&escape(&target.to_string_lossy()));
+    }
+    line
+}
+
+fn parse_node(line: &str) -> io::Result<RemoteNode> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let (kind, size, modified, path, target) = match fields[..] {
+        [_, kind, size, modified, path] => (kind, size, modified, path, None),
+        [_, kind, size, modified, path, target] => (kind, size, modified, path, Some(target)),
+        _ => return Err(invalid("malformed node line")),
+    };
+    let node_type = match (kind, target) {
+        ("dir", _) => NodeType::Directory,
+        ("link", Some(target)) => NodeType::Symlink {
+            target: PathBuf::from(unescape(target)),
+        },
+        ("link", None) => return Err(invalid("link without a target")),
+        _ => NodeType::File,
+    };
+    Ok(RemoteNode {
+        path: PathBuf::from(unescape(path)),
+        node_type,
+        size: size.parse().map_err(|_| invalid("malformed node size"))?,
+        modified: parse_epoch(modified),
+    })
+}
+
+fn event_line(entry: &JournalEntry) -> String {
+    let path = |path: &Path| escape(&path.to_string_lossy());
+    let seq = entry.seq;
+    match &entry.event {
+        FsEvent::Created(p) => format!("event\t{}\tcreated\t{}", seq, path(p)),
+        FsEvent::Modified(p) => format!("event\t{}\tmodified\t{}", seq, path(p)),
+        FsEvent::Removed(p) => format!("event\t{}\tremoved\t{}", seq, path(p)),
+        FsEvent::Renamed { from, to } => {
+            format!("event\t{}\trenamed\t{}\t{}", seq, path(from), path(to))
+        }
+        FsEvent::Desynced(p) => format!("event\t{}\tdesynced\t{}", seq, path(p)),
+    }
+}
+
+fn parse_event(line: &str) -> io::Result<JournalEntry> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let path = |index: usize| -> io::Result<PathBuf> {
+        fields
+            .get(index)
+            .map(|field| PathBuf::from(unescape(field)))
+            .ok_or_else(|| invalid("malformed event line"))
+    };
+    let seq = fields
+        .get(1)
+        .and_then(|seq| seq.parse().ok())
+        .ok_or_else(|| invalid("malformed event line"))?;
+    let event = match fields.get(2).copied() {
+        Some("created") => FsEvent::Created(path(3)?),
+        Some("modified") => FsEvent::Modified(path(3)?),
+        Some("removed") => FsEvent::Removed(path(3)?),
+        Some("renamed") => FsEvent::Renamed {
+            from: path(3)?,
+            to: path(4)?,
+        },
+        Some("desynced") => FsEvent::Desynced(path(3)?),
+        _ => return Err(invalid("malformed event line")),
+    };
+    Ok(JournalEntry { seq, event })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{self, BufReader};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::{
+        escape, handle_client, remove_stale_socket, trees, unescape, Changes, Client, Watched,
+        JOURNAL_CAPACITY,
+    };
+    use crate::builder::TreeBuilder;
+    use crate::journal::ChangeJournal;
+    use crate::node::NodeType;
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn only_sockets_are_replaced() {
+        let dir = empty_dir().unwrap();
+        let file = dir.root.join("not-a-socket");
+        fs::write(&file, "keep me").unwrap();
+        let error = remove_stale_socket(&file).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+
+        let socket = dir.root.join("daemon.sock");
+        drop(UnixListener::bind(&socket).unwrap());
+        remove_stale_socket(&socket).unwrap();
+        assert!(!socket.exists());
+        remove_stale_socket(&socket).unwrap();
+    }
+
+    #[test]
+    fn escaping_round_trips() {
+        for text in ["plain", "tab\there", "new\nline", "back\\slash\\t", "cr\r", "\\"] {
+            let escaped = escape(text);
+            assert!(!escaped.contains(['\t', '\n', '\r']), "{escaped:?}");
+            assert_eq!(unescape(&escaped), text);
+        }
+    }
+
+    #[test]
+    fn client_and_server_agree_on_the_wire_format() {
+        let dir = empty_dir().unwrap();
+        fs::write(dir.root.join("odd\tname\nwith breaks"), "12345").unwrap();
+        fs::write(dir.root.join("plain.txt"), "1").unwrap();
+        std::os::unix::fs::symlink("plain.txt", dir.root.join("link")).unwrap();
+        let tree = TreeBuilder::new(&dir.root)
+            .follow_symlinks(false)
+            .build()
+            .unwrap();
+
+        let roots = Mutex::new(vec![Watched {
+            root: dir.root.clone(),
+            tree: Arc::new(Mutex::new(tree)),
+            stop: Arc::new(AtomicBool::new(false)),
+            failure: Arc::new(Mutex::new(None)),
+        }]);
+        let changes = Mutex::new(Changes {
+            journal: ChangeJournal::new(JOURNAL_CAPACITY),
+            subscribers: Vec::new(),
+        });
+        let (server, client) = UnixStream::pair().unwrap();
+        thread::scope(|scope| {
+            scope.spawn(|| handle_client(server, &roots, &changes));
+            let mut client = Client {
+                reader: BufReader::new(client.try_clone().unwrap()),
+                writer: client,
+            };
+
+            let found = client.search("name\nwith").unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].path, dir.root.join("odd\tname\nwith breaks"));
+            assert_eq!(found[0].size, 5);
+            assert!(client.search("nothing\tlike this").unwrap().is_empty());
+
+            let link = client.get_node(&dir.root.join("link")).unwrap().unwrap();
+            assert_eq!(
+                link.node_type,
+                NodeType::Symlink {
+                    target: "plain.txt".into()
+                }
+            );
+            let stats = client.stats().unwrap();
+            assert_eq!((stats.files, stats.dirs), (3, 1));
+            drop(client);
+        });
+    }
+
+    #[test]
+    fn failed_trees_are_rescanned_by_the_next_request() {
+        let dir = empty_dir().unwrap();
+        let tree = TreeBuilder::new(&dir.root).build().unwrap();
+        let failure = Arc::new(Mutex::new(Some("an event was lost".to_string())));
+        let roots = Mutex::new(vec![Watched {
+            root: dir.root.clone(),
+            tree: Arc::new(Mutex::new(tree)),
+            stop: Arc::new(AtomicBool::new(false)),
+            failure: Arc::clone(&failure),
+        }]);
+        fs::write(dir.root.join("missed.txt"), "123").unwrap();
+
+        let trees = trees(&roots).unwrap();
+        assert_eq!(*failure.lock().unwrap(), None);
+        let tree = trees[0].lock().unwrap();
+        assert!(tree.get_node(&dir.root.join("missed.txt")).is_some());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::codec::{fnv1a_extend, FNV_OFFSET};
+use crate::content::read_contents;
+use crate::resources::{Resources, Workers};
+use crate::tree::Tree;
+
+/// Chunk sizes for content-defined chunking, in bytes. Chunk boundaries are placed where
+/// a rolling hash of the content matches, so an insertion early in a file only changes
+/// the chunks around it instead of shifting every later one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkingOptions {
+    /// No chunk is cut shorter than this, except at the end of a file.
+    pub min_size: usize,
+    /// The size chunks average out at; rounded up to a power of two.
+    pub avg_size: usize,
+    /// Chunks are cut at this size at the latest.
+    pub max_size: usize,
+}
+
+impl Default for ChunkingOptions {
+    fn default() -> Self {
+        Self {
+            min_size: 2 * 1024,
+            avg_size: 8 * 1024,
+            max_size: 64 * 1024,
+        }
+    }
+}
+
+/// How much of a tree's file contents is made of chunks occurring more than once, as
+/// found by `Tree::chunk_dedup_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Files read.
+    pub files: u64,
+    /// Chunks found across all files.
+    pub chunks: u64,
+    /// Distinct chunks among them.
+    pub unique_chunks: u64,
+    /// Combined size of all files.
+    pub total_bytes: u64,
+    /// Combined size of the distinct chunks: what a chunk-deduplicating store would hold.
+    pub unique_bytes: u64,
+}
+
+impl DedupStats {
+    /// Bytes chunk-level deduplication would save.
+    pub fn savings(&self) -> u64 {
+        self.total_bytes - self.unique_bytes
+    }
+
+    /// Total size over deduplicated size; 1.0 when nothing is shared or the tree is empty.
+    pub fn ratio(&self) -> f64 {
+        match self.unique_bytes {
+            0 => 1.0,
+            unique => self.total_bytes as f64 / unique as f64,
+        }
+    }
+}
+
+impl Tree {
+    /// Estimate how much chunk-level deduplication would save, by splitting every file
+    /// into content-defined chunks and counting those seen before, in the same or another
+    /// file. Unlike whole-file duplicate detection this finds the shared blocks of VM
+    /// images, databases and other large files that differ only in places.
+    ///
+    /// Every file is read in full, on up to `Resources::hash_threads` threads at once.
+    /// Chunks are told apart by a 64-bit hash and their length, so the result is an
+    /// estimate.
+    pub fn chunk_dedup_stats(&self, options: &ChunkingOptions) -> io::Result<DedupStats> {
+        let gear = gear_table();
+        // Cut where the top bits of the rolling hash, which depend on the most bytes, are 0.
+        let bits = options.avg_size.max(2).next_power_of_two().trailing_zeros();
+        let cut = Cut {
+            options,
+            gear: &gear,
+            shift: 64 - bits,
+            resources: self.resources,
+        };
+        let files: Vec<PathBuf> = self
+            .iter()
+            .filter(|node| node.is_file())
+            .map(|node| self.physical_path(&node.path))
+            .collect();
+        let workers = Workers::new(self.resources.hash_threads, self.resources.numa)?;
+        let mut seen = HashSet::new();
+        let mut stats = DedupStats::default();
+
+        // Files are chunked concurrently a batch at a time, and counted in order.
+        for batch in files.chunks(64) {
+            for chunks in workers.map(batch.to_vec(), |path| cut.chunks(&path))? {
+                stats.files += 1;
+                for (hash, len) in chunks {
+                    stats.chunks += 1;
+                    stats.total_bytes += len as u64;
+                    if seen.insert((hash, len)) {
+                        stats.unique_chunks += 1;
+                        stats.unique_bytes += len as u64;
+                    }
+                }
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Where to cut files into chunks.
+struct Cut<'a> {
+    options: &'a ChunkingOptions,
+    gear: &'a [u64; 256],
+    /// Chunks end where the rolling hash shifted right by this is 0.
+    shift: u32,
+    /// How files are read.
+    resources: Resources,
+}
+
+impl Cut<'_> {
+    /// The hash and length of every chunk of the file at `path`, in order.
+    fn chunks(&self, path: &Path) -> io::Result<Vec<(u64, usize)>> {
+        let mut chunks = Vec::new();
+        let mut chunk = Chunk::default();
+        read_contents(path, &self.resources, |bytes| {
+            let mut start = 0;
+            for (offset, &byte) in bytes.iter().enumerate() {
+                chunk.len += 1;
+                chunk.rolling = (chunk.rolling << 1).wrapping_add(self.gear[byte as usize]);
+                let boundary =
+                    chunk.len >= self.options.min_size && chunk.rolling >> self.shift == 0;
+                if boundary || chunk.len >= self.options.max_size {
+                    chunk.hash = fnv1a_extend(chunk.hash, &bytes[start..=offset]);
+                    chunks.push((chunk.hash, chunk.len));
+                    chunk = Chunk::default();
+                    start = offset + 1;
+                }
+            }
+            chunk.hash = fnv1a_extend(chunk.hash, &bytes[start..]);
+            Ok(())
+        })?;
+        if chunk.len > 0 {
+            chunks.push((chunk.hash, chunk.len));
+        }
+        Ok(chunks)
+    }
+}
+
+/// The chunk being read.
+struct Chunk {
+    len: usize,
+    /// Hash of the chunk's bytes so far.
+    hash: u64,
+    /// Gear hash of the last 64 bytes, deciding where chunks end.
+    rolling: u64,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            hash: FNV_OFFSET,
+            rolling: 0,
+        }
+    }
+}
+
+/// Pseudo-random values for every byte, fixed so that chunk boundaries are reproducible.
+fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    for value in &mut table {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        *value = z ^ (z >> 31);
+    }
+    table
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::bookmark::Bookmark;
+use crate::codec::{
+    fnv1a, invalid, write_bookmarks, write_entry, write_option_str, write_options, write_path,
+    write_str, write_time, write_varint, Input,
+};
+use crate::options::ScanOptions;
+use crate::snapshot::{Snapshot, SnapshotEntry};
+use crate::tree::descendants;
+
+const MAGIC: &[u8; 4] = b"FFD1";
+
+/// The changes turning one snapshot into a newer one, for shipping snapshots of
+/// mostly-unchanged trees between hosts: only entries that changed travel, together with
+/// the newer snapshot's label, tags, options, time, root and bookmarks.
+///
+/// A delta names the snapshot it applies to by its `Snapshot::digest`, and the result by
+/// another, so applying it to the wrong base (or getting a corrupted result) fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDelta {
+    /// Digest of the snapshot the delta applies to.
+    pub base: u64,
+    /// Digest of the snapshot the delta produces.
+    pub target: u64,
+    /// Entries that appeared or changed, sorted by relative path.
+    pub changed: Vec<SnapshotEntry>,
+    /// Entries that vanished, sorted. Descendants of a removed directory are implied.
+    pub removed: Vec<PathBuf>,
+    /// The newer snapshot's label.
+    pub label: Option<String>,
+    /// The newer snapshot's tags.
+    pub tags: Vec<String>,
+    /// The newer snapshot's scan settings.
+    pub options: ScanOptions,
+    /// When the newer snapshot was taken.
+    pub taken: SystemTime,
+    /// The newer snapshot's root.
+    pub root: PathBuf,
+    /// The newer snapshot's bookmarks.
+    pub bookmarks: BTreeMap<String, Bookmark>,
+}
+
+impl Snapshot {
+    /// A 64-bit FNV-1a digest of the snapshot's entries (paths, types, sizes and times),
+    /// identifying it as the base of a `SnapshotDelta`. Label, tags, root and bookmarks are
+    /// not part of it. The digest is stable across platforms and releases, but is not a
+    /// cryptographic hash.
+    pub fn digest(&self) -> u64 {
+        let mut bytes = Vec::new();
+        for entry in &self.entries {
+            write_entry(&mut bytes, entry);
+        }
+        fnv1a(&bytes)
+    }
+
+    /// The changes from `base` to this snapshot.
+    pub fn delta_from(&self, base: &Snapshot) -> SnapshotDelta {
+        let mut changed = Vec::new();
+        let mut removed: Vec<PathBuf> = Vec::new();
+        let (mut old, mut new) = (
+            base.entries.iter().peekable(),
+            self.entries.iter().peekable(),
+        );
+        loop {
+            match (old.peek(), new.peek()) {
+                (Some(o), Some(n)) if o.path == n.path => {
+                    if o != n {
+                        changed.push((*n).clone());
+                    }
+                    old.next();
+                    new.next();
+                }
+                (Some(o), Some(n)) if o.path > n.path => {
+                    changed.push((*n).clone());
+                    new.next();
+                }
+                (Some(o), _) => {
+                    if !removed
+                        .last()
+                        .is_some_and(|outer| o.path.starts_with(outer))
+                    {
+                        removed.push(o.path.clone());
+                    }
+                    old.next();
+                }
+                (None, Some(n)) => {
+                    changed.push((*n).clone());
+                    new.next();
+                }
+                (None, None) => break,
+            }
+        }
+
+        SnapshotDelta {
+            base: base.digest(),
+            target: self.digest(),
+            changed,
+            removed,
+            label: self.label.clone(),
+            tags: self.tags.clone(),
+            options: self.options.clone(),
+            taken: self.taken,
+            root: self.root.clone(),
+            bookmarks: self.bookmarks.clone(),
+        }
+    }
+
+    /// Rebuild the newer snapshot from this one and `delta`. Fails with `InvalidInput` if
+    /// the delta was made against a different base, and with `InvalidData` if the result
+    /// does not match the delta's target digest.
+    pub fn apply_delta(&self, delta: &SnapshotDelta) -> io::Result<Snapshot> {
+        if self.digest() != delta.base {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the delta was made against a different base snapshot",
+            ));
+        }
+
+        let mut entries: BTreeMap<PathBuf, SnapshotEntry> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.clone()))
+            .collect();
+        for path in &delta.removed {
+            let below: Vec<PathBuf> = descendants(&entries, path).cloned().collect();
+            for rel in below {
+                entries.remove(&rel);
+            }
+        }
+        for entry in &delta.changed {
+            entries.insert(entry.path.clone(), entry.clone());
+        }
+
+        let snapshot = Snapshot {
+            label: delta.label.clone(),
+            tags: delta.tags.clone(),
+            options: delta.options.clone(),
+            taken: delta.taken,
+            root: delta.root.clone(),
+            entries: entries.into_values().collect(),
+            bookmarks: delta.bookmarks.clone(),
+        };
+        if snapshot.digest() != delta.target {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "applying the delta did not produce the expected snapshot",
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+impl SnapshotDelta {
+    /// Returns `true` if no entry changed.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Encode the delta in a compact binary form: a magic number followed by
+    /// variable-length integers and length-prefixed strings.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut out = MAGIC.to_vec();
+        out.extend(self.base.to_le_bytes());
+        out.extend(self.target.to_le_bytes());
+        write_option_str(&mut out, self.label.as_deref());
+        write_varint(&mut out, self.tags.len() as u64);
+        for tag in &self.tags {
+            write_str(&mut out, tag);
+        }
+        write_options(&mut out, &self.options);
+        write_time(&mut out, Some(self.taken));
+        write_path(&mut out, &self.root);
+        write_bookmarks(&mut out, &self.bookmarks);
+        write_varint(&mut out, self.removed.len() as u64);
+        for path in &self.removed {
+            write_path(&mut out, path);
+        }
+        write_varint(&mut out, self.changed.len() as u64);
+        for entry in &self.changed {
+            write_entry(&mut out, entry);
+        }
+        writer.write_all(&out)
+    }
+
+    /// Decode a delta written by `write_to`. Malformed input is reported as `InvalidData`.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut input = Input { bytes: &bytes };
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a snapshot delta"));
+        }
+
+        let base = input.u64_le()?;
+        let target = input.u64_le()?;
+        let label = input.option_str()?;
+        let tags = (0..input.varint()?)
+            .map(|_| input.str())
+            .collect::<io::Result<_>>()?;
+        let options = input.options()?;
+        let taken = input
+            .time()?
+            .ok_or_else(|| invalid("missing snapshot time"))?;
+        let root = input.path()?;
+        let bookmarks = input.bookmarks()?;
+        let removed = (0..input.varint()?)
+            .map(|_| input.path())
+            .collect::<io::Result<_>>()?;
+        let changed = (0..input.varint()?)
+            .map(|_| input.entry())
+            .collect::<io::Result<_>>()?;
+        if !input.bytes.is_empty() {
+            return Err(invalid("trailing bytes after the delta"));
+        }
+
+        Ok(Self {
+            base,
+            target,
+            changed,
+            removed,
+            label,
+            tags,
+            options,
+            taken,
+            root,
+            bookmarks,
+        })
+    }
+}
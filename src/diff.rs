@@ -0,0 +1,571 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::checksum::checksum_file;
+use crate::node::{ExtendedMetadata, Node, NodeType};
+use crate::reconcile::scan_compared;
+use crate::resources::Resources;
+use crate::tree::Tree;
+
+/// Decides which attributes are compared when looking for modified entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComparePolicy {
+    /// Compare file contents only. Timestamps are ignored, so files that were
+    /// merely touched are not reported. Contents are compared by the checksums
+    /// recorded during the scans where there are some (see `TreeBuilder::checksums`),
+    /// and by reading both files otherwise. Two scans or snapshots of the same
+    /// directory can only be told apart by checksums, recorded on at least one side;
+    /// without any, comparing them fails with `InvalidInput`.
+    ContentOnly,
+    /// Compare size and the modification/creation times without reading contents.
+    MetadataOnly,
+    /// Compare size and modification time only (the classic quick check).
+    #[default]
+    SizeMtime,
+    /// Compare metadata and file contents, the latter as `ContentOnly` does.
+    Full,
+}
+
+impl ComparePolicy {
+    fn compares_content(self) -> bool {
+        matches!(self, ComparePolicy::ContentOnly | ComparePolicy::Full)
+    }
+
+    fn compares_mtime(self) -> bool {
+        !matches!(self, ComparePolicy::ContentOnly)
+    }
+
+    fn compares_created(self) -> bool {
+        matches!(self, ComparePolicy::MetadataOnly | ComparePolicy::Full)
+    }
+}
+
+/// The differences between two trees.
+/// Paths are relative to the root of each tree, so trees rooted at different
+/// locations can be compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+    /// Entries present only in the newer tree.
+    pub added: Vec<PathBuf>,
+    /// Entries present only in the older tree.
+    pub removed: Vec<PathBuf>,
+    /// Entries present in both trees that differ under the chosen policy.
+    pub modified: Vec<PathBuf>,
+}
+
+impl TreeDiff {
+    /// Returns `true` if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// What changed about an entry present in both trees, as found by `Tree::modifications`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modification {
+    /// Path relative to the roots.
+    pub path: PathBuf,
+    /// Size in the older tree.
+    pub old_size: u64,
+    /// Size in the newer tree.
+    pub new_size: u64,
+    /// Modification time in the older tree, if known.
+    pub old_modified: Option<SystemTime>,
+    /// Modification time in the newer tree, if known.
+    pub new_modified: Option<SystemTime>,
+    /// Whether the entry became a different kind of entry, e.g. a file replaced by a
+    /// directory.
+    pub type_changed: bool,
+}
+
+impl Modification {
+    /// How many bytes the entry grew by, negative if it shrank.
+    pub fn size_delta(&self) -> i64 {
+        self.new_size.wrapping_sub(self.old_size) as i64
+    }
+}
+
+/// One difference found by a streaming diff such as `Tree::diff_each`. Paths are
+/// relative to the roots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffChange {
+    /// The entry is only in the newer tree.
+    Added(PathBuf),
+    /// The entry is only in the older tree.
+    Removed(PathBuf),
+    /// The entry is in both trees but differs under the chosen policy.
+    Modified(PathBuf),
+}
+
+impl DiffChange {
+    /// The path of the entry that changed.
+    pub fn path(&self) -> &Path {
+        match self {
+            DiffChange::Added(path) | DiffChange::Removed(path) | DiffChange::Modified(path) => {
+                path
+            }
+        }
+    }
+}
+
+/// Compare two trees, reporting entries added, removed and modified going from `old` to `new`.
+pub fn diff(old: &Tree, new: &Tree, policy: ComparePolicy) -> io::Result<TreeDiff> {
+    diff_entries(&old.entries(), &new.entries(), policy)
+}
+
+/// A comparable view of a single entry, independent of whether it came from a live
+/// tree or a snapshot.
+pub(crate) struct Entry<'a> {
+    pub(crate) node_type: &'a NodeType,
+    pub(crate) size: u64,
+    pub(crate) metadata: &'a ExtendedMetadata,
+    /// Where the entry's contents can currently be read from.
+    pub(crate) location: PathBuf,
+}
+
+/// Entries keyed by their path relative to the root they were collected from.
+pub(crate) type Entries<'a> = BTreeMap<PathBuf, Entry<'a>>;
+
+/// Compare two sets of entries keyed by relative path.
+pub(crate) fn diff_entries(
+    old: &Entries,
+    new: &Entries,
+    policy: ComparePolicy,
+) -> io::Result<TreeDiff> {
+    let mut result = TreeDiff::default();
+
+    for (rel, old_entry) in old {
+        match new.get(rel) {
+            None => result.removed.push(rel.clone()),
+            Some(new_entry) => {
+                if !same_entry(old_entry, new_entry, policy)? {
+                    result.modified.push(rel.clone());
+                }
+            }
+        }
+    }
+    for rel in new.keys() {
+        if !old.contains_key(rel) {
+            result.added.push(rel.clone());
+        }
+    }
+
+    Ok(result)
+}
+
+/// The entries of `old` that are modified in `new`, with what changed about them.
+pub(crate) fn modifications(
+    old: &Entries,
+    new: &Entries,
+    policy: ComparePolicy,
+) -> io::Result<Vec<Modification>> {
+    let mut result = Vec::new();
+    for (rel, old_entry) in old {
+        let Some(new_entry) = new.get(rel) else {
+            continue;
+        };
+        if !same_entry(old_entry, new_entry, policy)? {
+            result.push(Modification {
+                path: rel.clone(),
+                old_size: old_entry.size,
+                new_size: new_entry.size,
+                old_modified: old_entry.metadata.modified,
+                new_modified: new_entry.metadata.modified,
+                type_changed: old_entry.node_type != new_entry.node_type,
+            });
+        }
+    }
+    Ok(result)
+}
+
+impl Tree {
+    /// Every node below the root, with contents located on disk.
+    pub(crate) fn entries(&self) -> Entries<'_> {
+        let mut entries = node_entries(&self.head);
+        if self.host_root.is_some() {
+            for entry in entries.values_mut() {
+                entry.location = self.physical_path(&entry.location);
+            }
+        }
+        entries
+    }
+
+    /// Compare this tree against `other`, treating `self` as the older side.
+    pub fn diff(&self, other: &Tree, policy: ComparePolicy) -> io::Result<TreeDiff> {
+        diff(self, other, policy)
+    }
+
+    /// The entries `diff` would report as modified, sorted by path, each with its size and
+    /// modification time in both trees, e.g. for a report of what grew.
+    pub fn modifications(
+        &self,
+        other: &Tree,
+        policy: ComparePolicy,
+    ) -> io::Result<Vec<Modification>> {
+        modifications(&self.entries(), &other.entries(), policy)
+    }
+
+    /// Compare this tree against `other` like `diff`, but hand each difference to `emit`
+    /// as it is found instead of collecting them, walking both trees side by side so
+    /// that memory use does not grow with their size. Differences come in no particular
+    /// order; every entry below an added or removed directory is reported too.
+    pub fn diff_each<F>(&self, other: &Tree, policy: ComparePolicy, mut emit: F) -> io::Result<()>
+    where
+        F: FnMut(DiffChange),
+    {
+        let sides = Sides::new(self, other, policy);
+        let mut stack = vec![(&self.head, &other.head)];
+        while let Some((old, new)) = stack.pop() {
+            for pair in pair_children(old, new) {
+                match pair {
+                    (Some(old), Some(new)) => {
+                        if !sides.same(old, new)? {
+                            emit(DiffChange::Modified(sides.old_rel(old)));
+                        }
+                        stack.push((old, new));
+                    }
+                    (Some(old), None) => walk_subtree(old, &mut |node| {
+                        emit(DiffChange::Removed(sides.old_rel(node)))
+                    }),
+                    (None, Some(new)) => walk_subtree(new, &mut |node| {
+                        emit(DiffChange::Added(sides.new_rel(node)))
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify that the directory at `target` matches this tree, scanning it with the
+    /// tree's options, so that what they leave out is not expected there either.
+    /// Entries missing from `target` are reported as removed, unexpected ones as added.
+    pub fn verify(&self, target: &Path, policy: ComparePolicy) -> io::Result<TreeDiff> {
+        let actual = scan_compared(target, self.options.clone(), self.resources)?;
+        diff(self, &actual, policy)
+    }
+}
+
+/// Collects every node below `root`, keyed by its path relative to `root`.
+pub(crate) fn node_entries(root: &Node) -> Entries<'_> {
+    let mut entries = BTreeMap::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if let Ok(rel) = node.path.strip_prefix(&root.path) {
+            if !rel.as_os_str().is_empty() {
+                entries.insert(
+                    rel.to_path_buf(),
+                    Entry {
+                        node_type: &node.node_type,
+                        size: node.size,
+                        metadata: &node.metadata,
+                        location: node.path.clone(),
+                    },
+                );
+            }
+        }
+        if let Some(children) = &node.children {
+            stack.extend(children);
+        }
+    }
+    entries
+}
+
+/// The two trees being compared by a streaming diff. Only their roots are kept, so
+/// that it can be shared between threads.
+pub(crate) struct Sides<'a> {
+    old: Side<'a>,
+    new: Side<'a>,
+    policy: ComparePolicy,
+}
+
+/// Where one tree of a streaming diff is rooted, in the tree and on disk.
+struct Side<'a> {
+    root: &'a Path,
+    host: Option<&'a Path>,
+}
+
+impl<'a> Side<'a> {
+    fn of(tree: &'a Tree) -> Self {
+        Self {
+            root: &tree.head.path,
+            host: tree.host_root.as_deref(),
+        }
+    }
+
+    fn rel(&self, node: &Node) -> PathBuf {
+        node.path
+            .strip_prefix(self.root)
+            .unwrap_or(&node.path)
+            .to_path_buf()
+    }
+
+    /// A comparable view of `node`, located on disk.
+    fn view<'n>(&self, node: &'n Node) -> Entry<'n> {
+        let location = match self.host {
+            Some(host) => host.join(self.rel(node)),
+            None => node.path.clone(),
+        };
+        Entry {
+            node_type: &node.node_type,
+            size: node.size,
+            metadata: &node.metadata,
+            location,
+        }
+    }
+}
+
+impl<'a> Sides<'a> {
+    pub(crate) fn new(old: &'a Tree, new: &'a Tree, policy: ComparePolicy) -> Self {
+        Self {
+            old: Side::of(old),
+            new: Side::of(new),
+            policy,
+        }
+    }
+
+    /// Returns `true` if the matching entries `old` and `new` are considered equal.
+    pub(crate) fn same(&self, old: &Node, new: &Node) -> io::Result<bool> {
+        same_entry(&self.old.view(old), &self.new.view(new), self.policy)
+    }
+
+    pub(crate) fn old_rel(&self, node: &Node) -> PathBuf {
+        self.old.rel(node)
+    }
+
+    pub(crate) fn new_rel(&self, node: &Node) -> PathBuf {
+        self.new.rel(node)
+    }
+}
+
+/// The children of two directories at the same place in their trees, paired by name.
+pub(crate) fn pair_children<'a>(
+    old: &'a Node,
+    new: &'a Node,
+) -> Vec<(Option<&'a Node>, Option<&'a Node>)> {
+    let mut by_name: BTreeMap<&std::ffi::OsStr, (Option<&Node>, Option<&Node>)> = BTreeMap::new();
+    for child in old.children.iter().flatten() {
+        if let Some(name) = child.path.file_name() {
+            by_name.entry(name).or_default().0 = Some(child);
+        }
+    }
+    for child in new.children.iter().flatten() {
+        if let Some(name) = child.path.file_name() {
+            by_name.entry(name).or_default().1 = Some(child);
+        }
+    }
+    by_name.into_values().collect()
+}
+
+/// Calls `visit` on `node` and everything below it.
+pub(crate) fn walk_subtree<'a>(node: &'a Node, visit: &mut impl FnMut(&'a Node)) {
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        visit(node);
+        stack.extend(node.children.iter().flatten());
+    }
+}
+
+/// Returns `true` if the two entries are considered equal under `policy`.
+fn same_entry(a: &Entry, b: &Entry, policy: ComparePolicy) -> io::Result<bool> {
+    if a.node_type != b.node_type {
+        return Ok(false);
+    }
+    // A directory's size and timestamps follow its contents, which are compared entry by entry.
+    if *a.node_type == NodeType::Directory {
+        return Ok(true);
+    }
+    if a.size != b.size {
+        return Ok(false);
+    }
+    if policy.compares_mtime() && a.metadata.modified != b.metadata.modified {
+        return Ok(false);
+    }
+    if policy.compares_created() && a.metadata.created != b.metadata.created {
+        return Ok(false);
+    }
+    // Links are compared by target, which the node types already hold; opening them
+    // would read whatever they point at, or fail for dangling ones.
+    if policy.compares_content() && !matches!(a.node_type, NodeType::Symlink { .. }) {
+        return same_content(a, b);
+    }
+    Ok(true)
+}
+
+/// Returns `true` if the files `a` and `b` have the same contents: their recorded
+/// checksums if both have one, a recorded checksum against the other file as it is on
+/// disk if only one does, and both files as they are on disk otherwise.
+fn same_content(a: &Entry, b: &Entry) -> io::Result<bool> {
+    let resources = Resources::default();
+    match (&a.metadata.checksum, &b.metadata.checksum) {
+        (Some(old), Some(new)) if old.algorithm == new.algorithm => Ok(old == new),
+        (Some(old), _) => Ok(*old == checksum_file(&b.location, old.algorithm, &resources)?),
+        (None, Some(new)) => Ok(checksum_file(&a.location, new.algorithm, &resources)? == *new),
+        // Both sides would read the one file as it is now, which always matches itself.
+        (None, None) if a.location == b.location => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{}: comparing the contents of two scans of the same file needs checksums",
+                a.location.display()
+            ),
+        )),
+        (None, None) => same_contents(&a.location, &b.location),
+    }
+}
+
+/// Streams both files and compares them chunk by chunk.
+pub(crate) fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let mut file_a = File::open(a)?;
+    let mut file_b = File::open(b)?;
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    loop {
+        let read_a = read_full(&mut file_a, &mut buf_a)?;
+        let read_b = read_full(&mut file_b, &mut buf_b)?;
+        if read_a != read_b || buf_a[..read_a] != buf_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Reads until `buf` is full or the reader is exhausted, returning the number of bytes read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use super::ComparePolicy;
+    use crate::builder::TreeBuilder;
+    use crate::checksum::HashAlgorithm;
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn content_of_rescans_is_compared_by_checksum() {
+        let dir = empty_dir().unwrap();
+        let path = dir.root.join("greeting");
+        fs::write(&path, "hello").unwrap();
+        let scan = || {
+            TreeBuilder::new(&dir.root)
+                .checksums(HashAlgorithm::Crc32)
+                .build()
+                .unwrap()
+        };
+        let old = scan();
+        let snapshot = old.snapshot();
+        let unhashed = TreeBuilder::new(&dir.root).build().unwrap();
+        fs::write(&path, "HELLO").unwrap();
+        let new = scan();
+
+        let changed = ["greeting"].map(PathBuf::from);
+        for policy in [ComparePolicy::ContentOnly, ComparePolicy::Full] {
+            assert_eq!(old.diff(&new, policy).unwrap().modified, changed);
+            assert_eq!(new.diff_since(&snapshot, policy).unwrap().modified, changed);
+        }
+        // A checksum on one side is compared with the file as it is now.
+        let diff = old.diff(&unhashed, ComparePolicy::ContentOnly).unwrap();
+        assert_eq!(diff.modified, changed);
+        let rescanned = TreeBuilder::new(&dir.root).build().unwrap();
+        let error = unhashed
+            .diff(&rescanned, ComparePolicy::ContentOnly)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn policies_tell_touched_files_from_edited_ones() {
+        let dir = empty_dir().unwrap();
+        for side in ["built", "rebuilt"] {
+            fs::create_dir(dir.root.join(side)).unwrap();
+            fs::write(dir.root.join(side).join("main.o"), "object").unwrap();
+        }
+        let built = TreeBuilder::new(dir.root.join("built")).build().unwrap();
+        let rebuilt = dir.root.join("rebuilt");
+
+        let file = fs::File::options()
+            .write(true)
+            .open(rebuilt.join("main.o"))
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let diff = built.verify(&rebuilt, ComparePolicy::ContentOnly).unwrap();
+        assert!(diff.is_empty(), "{diff:?}");
+        let diff = built.verify(&rebuilt, ComparePolicy::SizeMtime).unwrap();
+        assert_eq!(diff.modified, ["main.o"].map(PathBuf::from));
+
+        fs::write(rebuilt.join("main.o"), "linked").unwrap();
+        let diff = built.verify(&rebuilt, ComparePolicy::ContentOnly).unwrap();
+        assert_eq!(diff.modified, ["main.o"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn verify_scans_the_target_with_the_tree_options() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir_all(dir.root.join("a/b/c")).unwrap();
+        fs::create_dir_all(dir.root.join("target")).unwrap();
+        fs::write(dir.root.join("a/b/c/deep.txt"), "deep").unwrap();
+        fs::write(dir.root.join(".hidden"), "hidden").unwrap();
+        fs::write(dir.root.join("target/out.o"), "obj").unwrap();
+        fs::write(dir.root.join("kept.txt"), "kept").unwrap();
+
+        let tree = TreeBuilder::new(&dir.root)
+            .include_hidden(false)
+            .max_depth(2)
+            .exclude("target")
+            .build()
+            .unwrap();
+        let diff = tree.verify(&dir.root, ComparePolicy::SizeMtime).unwrap();
+        assert!(diff.is_empty(), "{diff:?}");
+
+        fs::write(dir.root.join("kept.txt"), "changed").unwrap();
+        let diff = tree.verify(&dir.root, ComparePolicy::SizeMtime).unwrap();
+        assert_eq!(diff.modified, ["kept.txt"].map(std::path::PathBuf::from));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn content_comparison_skips_symlinks() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir_all(dir.root.join("old")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.root.join("old/dangling")).unwrap();
+        std::os::unix::fs::symlink("kept.txt", dir.root.join("old/live")).unwrap();
+        fs::write(dir.root.join("old/kept.txt"), "kept").unwrap();
+
+        let old = TreeBuilder::new(dir.root.join("old"))
+            .follow_symlinks(false)
+            .checksums(HashAlgorithm::Crc32)
+            .build()
+            .unwrap();
+        for policy in [ComparePolicy::ContentOnly, ComparePolicy::Full] {
+            let diff = old.verify(&dir.root.join("old"), policy).unwrap();
+            assert!(diff.is_empty(), "{diff:?}");
+        }
+
+        fs::remove_file(dir.root.join("old/live")).unwrap();
+        std::os::unix::fs::symlink("elsewhere", dir.root.join("old/live")).unwrap();
+        let diff = old
+            .verify(&dir.root.join("old"), ComparePolicy::ContentOnly)
+            .unwrap();
+        assert_eq!(diff.modified, ["live"].map(PathBuf::from));
+    }
+}
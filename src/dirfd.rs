@@ -0,0 +1,192 @@
+use std::ffi::{CStr, CString, OsStr};
+use std::fs::File;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::node::{ExtendedMetadata, Node, NodeType};
+use crate::tree::Tree;
+
+impl Tree {
+    /// Open `root` and scan it with `scan_dir`. Only `root` itself is resolved by path.
+    pub fn new_at(root: &Path) -> io::Result<Self> {
+        let dir = File::open(root)?;
+        Self::scan_dir(dir.into(), root)
+    }
+
+    /// Scan the already opened directory `dir`, reporting it as `root`.
+    ///
+    /// Every entry below is reached relative to its parent's descriptor (`openat` with
+    /// `O_NOFOLLOW`, `fstatat` without following links), so no path is resolved through
+    /// a component that could be swapped for a symlink mid-scan. Symlinks are recorded
+    /// as `NodeType::Symlink` nodes of their own size and never followed. Later refreshes
+    /// resolve paths as usual; scan again for another race-free view.
+    pub fn scan_dir(dir: OwnedFd, root: &Path) -> io::Result<Self> {
+        let stat = fstat(&dir)?;
+        if stat.st_mode & libc::S_IFMT != libc::S_IFDIR {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        let head = scan(dir, root.to_path_buf(), &stat)?;
+        let mut tree = Tree::from_head(head);
+        tree.options.follow_symlinks = false;
+        Ok(tree)
+    }
+}
+
+/// Builds the node for the directory open as `dir`, recursing through its children.
+fn scan(dir: OwnedFd, path: PathBuf, stat: &libc::stat) -> io::Result<Node> {
+    let mut node = Node::from_parts(path, NodeType::Directory, metadata(stat), 0);
+    let mut children = Vec::new();
+    for name in list(&dir)? {
+        let child_path = node.path.join(OsStr::from_bytes(name.to_bytes()));
+        let child_stat = fstatat(&dir, &name)?;
+        let child = if child_stat.st_mode & libc::S_IFMT == libc::S_IFDIR {
+            let child_dir = openat(&dir, &name)?;
+            // Use what was actually opened, in case the entry changed since the stat.
+            let opened = fstat(&child_dir)?;
+            scan(child_dir, child_path, &opened)?
+        } else {
+            let node_type = match child_stat.st_mode & libc::S_IFMT {
+                libc::S_IFLNK => NodeType::Symlink {
+                    target: readlinkat(&dir, &name)?,
+                },
+                _ => NodeType::File,
+            };
+            Node::from_parts(
+                child_path,
+                node_type,
+                metadata(&child_stat),
+                child_stat.st_size as u64,
+            )
+        };
+        children.push(child);
+    }
+    node.size = children.iter().map(|child| child.size).sum();
+    node.children = Some(children);
+    Ok(node)
+}
+
+/// The names in a directory, excluding `.` and `..`.
+fn list(dir: &OwnedFd) -> io::Result<Vec<CString>> {
+    // `fdopendir` takes ownership of its descriptor, so give it a duplicate.
+    let fd = unsafe { libc::dup(dir.as_raw_fd()) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stream = unsafe { libc::fdopendir(fd) };
+    if stream.is_null() {
+        let error = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(error);
+    }
+    // The duplicate shares its offset with `dir`, which may have been read before.
+    unsafe { libc::rewinddir(stream) };
+
+    let mut names = Vec::new();
+    loop {
+        let entry = unsafe { libc::readdir(stream) };
+        if entry.is_null() {
+            break;
+        }
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+        if name.to_bytes() != b"." && name.to_bytes() != b".." {
+            names.push(name.to_owned());
+        }
+    }
+    unsafe { libc::closedir(stream) };
+    Ok(names)
+}
+
+fn openat(dir: &OwnedFd, name: &CStr) -> io::Result<OwnedFd> {
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn readlinkat(dir: &OwnedFd, name: &CStr) -> io::Result<PathBuf> {
+    let mut buf = vec![0u8; 256];
+    loop {
+        let len = unsafe {
+            libc::readlinkat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // A full buffer may have truncated the target.
+        if (len as usize) < buf.len() {
+            buf.truncate(len as usize);
+            return Ok(PathBuf::from(OsStr::from_bytes(&buf)));
+        }
+        buf.resize(buf.len() * 2, 0);
+    }
+}
+
+fn fstatat(dir: &OwnedFd, name: &CStr) -> io::Result<libc::stat> {
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    let result = unsafe {
+        libc::fstatat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            stat.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { stat.assume_init() })
+}
+
+fn fstat(fd: &OwnedFd) -> io::Result<libc::stat> {
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    if unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { stat.assume_init() })
+}
+
+/// Extended metadata from a raw stat. Creation times are not part of `stat`.
+// The widths of `time_t` and the nanosecond fields vary across platforms.
+#[allow(clippy::unnecessary_cast)]
+fn metadata(stat: &libc::stat) -> ExtendedMetadata {
+    ExtendedMetadata {
+        modified: system_time(stat.st_mtime as i64, stat.st_mtime_nsec as i64),
+        accessed: system_time(stat.st_atime as i64, stat.st_atime_nsec as i64),
+        created: None,
+        mode: Some(stat.st_mode as u32 & 0o7777),
+        uid: Some(stat.st_uid as u32),
+        gid: Some(stat.st_gid as u32),
+        inode: Some(stat.st_ino as u64),
+        device: Some(stat.st_dev as u64),
+        nlink: Some(stat.st_nlink as u64),
+        len: Some(stat.st_size as u64),
+        blocks: Some(stat.st_blocks as u64),
+        xattrs: None,
+        checksum: None,
+        mime_type: None,
+    }
+}
+
+fn system_time(secs: i64, nanos: i64) -> Option<SystemTime> {
+    let nanos = u32::try_from(nanos).ok()?;
+    match u64::try_from(secs) {
+        Ok(secs) => UNIX_EPOCH.checked_add(Duration::new(secs, nanos)),
+        Err(_) => UNIX_EPOCH
+            .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+            .checked_add(Duration::from_nanos(u64::from(nanos))),
+    }
+}
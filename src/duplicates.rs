@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::codec::fnv1a;
+use crate::resources::{Resources, Workers};
+use crate::tree::Tree;
+
+/// How many leading bytes of each candidate are hashed before whole files are.
+const PARTIAL_BYTES: u64 = 4096;
+
+/// Files with identical contents, found by `Tree::find_duplicates`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// The size of each file.
+    pub size: u64,
+    /// The files' paths in the tree, sorted.
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// The bytes that keeping a single copy would free: the size of all copies but one.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// A file that may have duplicates.
+struct Candidate {
+    path: PathBuf,
+    physical: PathBuf,
+    size: u64,
+    /// Its SHA-256 checksum, if recorded during the scan.
+    recorded: Option<Checksum>,
+}
+
+impl Tree {
+    /// Find the groups of files with identical contents, those that waste the most
+    /// space first.
+    ///
+    /// Files are grouped by their recorded sizes first, so that only files sharing a
+    /// size are read at all; then by a hash of their first 4 KiB; and last by their
+    /// whole contents, hashed with SHA-256, or with the `sha256` feature off compared
+    /// byte for byte. SHA-256 checksums recorded with `TreeBuilder::checksums` save
+    /// reading whole files again. Reading runs on up to
+    /// `Resources::hash_threads` threads.
+    ///
+    /// Empty files are left out, as are files that cannot be read. Hard links to the
+    /// same file count as duplicates of each other.
+    pub fn find_duplicates(&self) -> Vec<DuplicateGroup> {
+        let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        for node in self.iter().filter(|node| node.is_file() && node.size > 0) {
+            let recorded = node
+                .metadata
+                .checksum
+                .clone()
+                .filter(|checksum| checksum.algorithm == HashAlgorithm::Sha256);
+            by_size.entry(node.size).or_default().push(Candidate {
+                path: node.path.clone(),
+                physical: self.physical_path(&node.path),
+                size: node.size,
+                recorded,
+            });
+        }
+        let candidates: Vec<Candidate> = by_size
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .flatten()
+            .collect();
+        let Ok(workers) = Workers::new(self.resources.hash_threads, self.resources.numa) else {
+            return Vec::new();
+        };
+
+        let partial = workers
+            .map(candidates, |candidate| {
+                let key = partial_hash(&candidate.physical).ok();
+                Ok((candidate, key))
+            })
+            .unwrap_or_default();
+        let mut by_partial: HashMap<(u64, u64), Vec<Candidate>> = HashMap::new();
+        for (candidate, key) in partial {
+            if let Some(key) = key {
+                by_partial
+                    .entry((candidate.size, key))
+                    .or_default()
+                    .push(candidate);
+            }
+        }
+        let candidates: Vec<Vec<Candidate>> = by_partial
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+
+        let mut groups: Vec<DuplicateGroup> = candidates
+            .into_iter()
+            .flat_map(|group| identical(group, &workers, &self.resources))
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(size, mut paths)| {
+                paths.sort();
+                DuplicateGroup { size, paths }
+            })
+            .collect();
+        groups.sort_by(|a, b| {
+            b.wasted()
+                .cmp(&a.wasted())
+                .then_with(|| a.paths.cmp(&b.paths))
+        });
+        groups
+    }
+}
+
+/// Splits `group`, files of the same size and partial hash, into the sets with the same
+/// contents, as (size, paths).
+#[cfg(feature = "sha256")]
+fn identical(
+    group: Vec<Candidate>,
+    workers: &Workers,
+    resources: &Resources,
+) -> Vec<(u64, Vec<PathBuf>)> {
+    use crate::checksum::checksum_file;
+
+    let hashed = workers
+        .map(group, |candidate| {
+            let checksum = match candidate.recorded {
+                Some(checksum) => Ok(checksum),
+                None => checksum_file(&candidate.physical, HashAlgorithm::Sha256, resources),
+            };
+            Ok((candidate.path, candidate.size, checksum.ok()))
+        })
+        .unwrap_or_default();
+    let mut by_digest: HashMap<(u64, Vec<u8>), Vec<PathBuf>> = HashMap::new();
+    for (path, size, checksum) in hashed {
+        if let Some(checksum) = checksum {
+            by_digest
+                .entry((size, checksum.digest))
+                .or_default()
+                .push(path);
+        }
+    }
+    by_digest
+        .into_iter()
+        .map(|((size, _), paths)| (size, paths))
+        .collect()
+}
+
+#[cfg(not(feature = "sha256"))]
+fn identical(
+    group: Vec<Candidate>,
+    _workers: &Workers,
+    _resources: &Resources,
+) -> Vec<(u64, Vec<PathBuf>)> {
+    // Each file joins the first set whose first file it matches.
+    let mut sets: Vec<(Candidate, Vec<PathBuf>)> = Vec::new();
+    for candidate in group {
+        let set = sets
+            .iter_mut()
+            .find(|(first, _)| match (&first.recorded, &candidate.recorded) {
+                (Some(a), Some(b)) => a == b,
+                _ => crate::diff::same_contents(&first.physical, &candidate.physical)
+                    .unwrap_or(false),
+            });
+        match set {
+            Some((_, paths)) => paths.push(candidate.path),
+            None if File::open(&candidate.physical).is_ok() => {
+                let paths = vec![candidate.path.clone()];
+                sets.push((candidate, paths));
+            }
+            None => {}
+        }
+    }
+    sets.into_iter()
+        .map(|(first, paths)| (first.size, paths))
+        .collect()
+}
+
+/// The FNV-1a hash of the first 4 KiB of the file at `path`.
+fn partial_hash(path: &Path) -> io::Result<u64> {
+    let mut start = Vec::with_capacity(PARTIAL_BYTES as usize);
+    File::open(path)?
+        .take(PARTIAL_BYTES)
+        .read_to_end(&mut start)?;
+    Ok(fnv1a(&start))
+}
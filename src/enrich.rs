@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::builder::collect;
+use crate::node::{stat_entry, ExtendedMetadata, Node, NodeType};
+use crate::options::{ErrorPolicy, Excludes, ScanOptions};
+use crate::platform::is_hidden;
+use crate::tree::Tree;
+
+/// Entries sent at most per batch, and how long the first entry of a batch may wait.
+const BATCH_ENTRIES: usize = 1024;
+const BATCH_DELAY: Duration = Duration::from_millis(50);
+
+/// The details read for one entry.
+struct Details {
+    path: PathBuf,
+    metadata: ExtendedMetadata,
+    size: u64,
+}
+
+/// Sizes and metadata being read in the background for a tree, started with
+/// `Tree::enrich`, so that a tree built with `TreeBuilder::structure_only` can be shown
+/// at once and filled in as the details arrive.
+///
+/// Details are read in tree order and handed over in batches; `apply` puts what has
+/// arrived into the tree. Entries that vanish or cannot be read keep what they had.
+/// Dropping the handle stops the reading.
+pub struct Enrichment {
+    batches: Receiver<Vec<Details>>,
+    done: bool,
+}
+
+impl Enrichment {
+    /// Put the details that have arrived into `tree` without waiting, returning the
+    /// paths of the entries updated.
+    pub fn apply(&mut self, tree: &mut Tree) -> Vec<PathBuf> {
+        let mut updated = Vec::new();
+        loop {
+            match self.batches.try_recv() {
+                Ok(batch) => updated.extend(apply_batch(tree, batch)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.complete(tree);
+                    break;
+                }
+            }
+        }
+        updated
+    }
+
+    /// Like `apply`, waiting up to `timeout` for details if none have arrived.
+    pub fn apply_timeout(&mut self, tree: &mut Tree, timeout: Duration) -> Vec<PathBuf> {
+        match self.batches.recv_timeout(timeout) {
+            Ok(batch) => {
+                let mut updated = apply_batch(tree, batch);
+                updated.extend(self.apply(tree));
+                updated
+            }
+            Err(RecvTimeoutError::Timeout) => Vec::new(),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.complete(tree);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Wait for the remaining details and put them into `tree`.
+    pub fn finish(mut self, tree: &mut Tree) {
+        while let Ok(batch) = self.batches.recv() {
+            apply_batch(tree, batch);
+        }
+        self.complete(tree);
+    }
+
+    /// Returns `true` once every detail was read and put into the tree.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Brings the tree's indexes and live queries, which go by the details, up to date
+    /// once everything was applied.
+    fn complete(&mut self, tree: &mut Tree) {
+        if !self.done {
+            self.done = true;
+            tree.details_pending = false;
+            let root = tree.head.path.clone();
+            tree.rescanned(&root, None);
+        }
+    }
+}
+
+impl Tree {
+    /// Read every entry's size and metadata on a background thread, to be put into the
+    /// tree with the returned `Enrichment`. Meant for trees built with
+    /// `TreeBuilder::structure_only`; for others it reads again what they hold.
+    ///
+    /// File and link sizes are replaced and directory sizes adjusted to match as the
+    /// details come in. Indexes such as `track_recent`, and live queries, catch up once
+    /// everything was read.
+    pub fn enrich(&self) -> Enrichment {
+        let follow = self.options.follow_symlinks;
+        let entries: Vec<(PathBuf, PathBuf)> = self
+            .iter()
+            .map(|node| (node.path.clone(), self.physical_path(&node.path)))
+            .collect();
+        let (sender, batches) = mpsc::channel();
+        thread::spawn(move || {
+            let mut batch = Vec::new();
+            let mut started = Instant::now();
+            for (path, physical) in entries {
+                let Ok((metadata, _)) = stat_entry(&physical, follow) else {
+                    continue;
+                };
+                if batch.is_empty() {
+                    started = Instant::now();
+                }
+                batch.push(Details {
+                    path,
+                    metadata: ExtendedMetadata::from_metadata(&metadata),
+                    size: metadata.len(),
+                });
+                let due = batch.len() >= BATCH_ENTRIES || started.elapsed() >= BATCH_DELAY;
+                if due && sender.send(std::mem::take(&mut batch)).is_err() {
+                    return;
+                }
+            }
+            if !batch.is_empty() {
+                let _ = sender.send(batch);
+            }
+        });
+        Enrichment {
+            batches,
+            done: false,
+        }
+    }
+}
+
+/// Puts `batch` into `tree`, a directory at a time, returning the paths updated.
+fn apply_batch(tree: &mut Tree, batch: Vec<Details>) -> Vec<PathBuf> {
+    let mut updated = Vec::new();
+    let mut by_parent: BTreeMap<PathBuf, Vec<Details>> = BTreeMap::new();
+    for details in batch {
+        if details.path == tree.head.path {
+            tree.head.metadata = details.metadata;
+            updated.push(details.path);
+            continue;
+        }
+        if let Some(parent) = details.path.parent() {
+            by_parent
+                .entry(parent.to_path_buf())
+                .or_default()
+                .push(details);
+        }
+    }
+
+    for (parent, batch) in by_parent {
+        let Some(children) = tree
+            .get_node_mut(&parent)
+            .and_then(|node| node.children.as_mut())
+        else {
+            continue;
+        };
+        let index: HashMap<OsString, usize> = children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, child)| Some((child.path.file_name()?.to_owned(), i)))
+            .collect();
+        let mut delta: i64 = 0;
+        for details in batch {
+            let Some(&i) = details.path.file_name().and_then(|name| index.get(name)) else {
+                continue;
+            };
+            let child = &mut children[i];
+            child.metadata = details.metadata;
+            if !child.is_dir() {
+                delta += details.size as i64 - child.size as i64;
+                child.size = details.size;
+            }
+            updated.push(details.path);
+        }
+        if delta != 0 {
+            for ancestor in parent.ancestors() {
+                if !ancestor.starts_with(&tree.head.path) {
+                    break;
+                }
+                if let Some(node) = tree.get_node_mut(ancestor) {
+                    node.size = node.size.saturating_add_signed(delta);
+                }
+            }
+        }
+    }
+    updated
+}
+
+/// Scans `path` from directory listings alone, for `TreeBuilder::structure_only`. The
+/// root is read in full; below it only names and types are known, and symbolic links
+/// are recorded rather than followed.
+pub(crate) fn scan_structure(
+    root: &Path,
+    options: &ScanOptions,
+    errors: Option<&Mutex<Vec<(PathBuf, io::Error)>>>,
+) -> io::Result<Node> {
+    let (metadata, node_type) = stat_entry(root, true)?;
+    let extended = ExtendedMetadata::from_metadata(&metadata);
+    let mut node = Node::from_parts(root.to_path_buf(), node_type, extended, metadata.len());
+    if node.is_dir() {
+        node.size = 0;
+        let excludes = Excludes::new(options, root);
+        list_structure(&mut node, 0, options, &excludes, errors)?;
+    }
+    Ok(node)
+}
+
+/// Lists the directory `node`, `depth` levels below the root, and everything below it.
+/// Entries left out under `ErrorPolicy::Collect` are recorded in `errors`.
+fn list_structure(
+    node: &mut Node,
+    depth: usize,
+    options: &ScanOptions,
+    excludes: &Excludes,
+    errors: Option<&Mutex<Vec<(PathBuf, io::Error)>>>,
+) -> io::Result<()> {
+    if options.max_depth.is_some_and(|max| depth >= max) {
+        node.children = None;
+        return Ok(());
+    }
+    let mut children = Vec::new();
+    for entry in fs::read_dir(&node.path)? {
+        let path = entry
+            .as_ref()
+            .map_or_else(|_| node.path.clone(), |entry| entry.path());
+        let child = entry.and_then(|entry| {
+            if !options.include_hidden && is_hidden(&entry) || excludes.covers(&entry.path()) {
+                return Ok(None);
+            }
+            let file_type = entry.file_type()?;
+            let node_type = match file_type {
+                _ if file_type.is_symlink() => NodeType::Symlink {
+                    target: fs::read_link(entry.path())?,
+                },
+                _ if file_type.is_dir() => NodeType::Directory,
+                _ => NodeType::File,
+            };
+            let mut child =
+                Node::from_parts(entry.path(), node_type, ExtendedMetadata::default(), 0);
+            if child.is_dir() {
+                list_structure(&mut child, depth + 1, options, excludes, errors)?;
+            }
+            Ok(Some(child))
+        });
+        match (child, options.errors) {
+            (Ok(Some(child)), _) => children.push(child),
+            (Ok(None), _) => {}
+            (Err(e), ErrorPolicy::Abort) => return Err(e),
+            (Err(e), ErrorPolicy::Collect) => collect(errors, path, e),
+            (Err(_), ErrorPolicy::Skip) => {}
+        }
+    }
+    options.sort.apply(&mut children);
+    node.children = Some(children);
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::selection::glob_match;
+use crate::tree::Tree;
+
+/// A change reported for a path on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsEvent {
+    /// A file or directory was created.
+    Created(PathBuf),
+    /// A file or directory's contents or metadata changed.
+    Modified(PathBuf),
+    /// A file or directory was removed.
+    Removed(PathBuf),
+    /// A file or directory was renamed.
+    Renamed { from: PathBuf, to: PathBuf },
+    /// Changes below the path may have gone unreported, e.g. because the backend's queue
+    /// overflowed or its watch had to be re-established. Applying it rescans the path.
+    Desynced(PathBuf),
+}
+
+impl FsEvent {
+    /// The paths affected by the event.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            FsEvent::Created(path)
+            | FsEvent::Modified(path)
+            | FsEvent::Removed(path)
+            | FsEvent::Desynced(path) => vec![path],
+            FsEvent::Renamed { from, to } => vec![from, to],
+        }
+    }
+}
+
+/// Decides how a batch of events is applied to a tree.
+#[derive(Debug, Clone)]
+pub struct RescanPolicy {
+    /// Once at least this many distinct changed paths fall under one directory, that
+    /// directory is rescanned as a whole instead of handling each path separately.
+    /// When the changes are spread so widely that only the root qualifies, the whole
+    /// tree is refreshed.
+    pub subtree_threshold: usize,
+    /// Paths whose changes are handled ahead of everything else.
+    pub priority: PriorityLanes,
+}
+
+impl Default for RescanPolicy {
+    fn default() -> Self {
+        Self {
+            subtree_threshold: 64,
+            priority: PriorityLanes::default(),
+        }
+    }
+}
+
+/// Path patterns marking changes as urgent, so that e.g. the directories a user is looking
+/// at stay current while a build floods events elsewhere.
+///
+/// Patterns are matched against paths as events report them, using `*` for any run of
+/// characters (including `/`), `?` for a single one and `[...]` for one of a class. A
+/// pattern matching a directory also covers everything below it.
+#[derive(Debug, Clone, Default)]
+pub struct PriorityLanes {
+    patterns: Vec<Vec<char>>,
+}
+
+impl PriorityLanes {
+    /// Create lanes with no urgent paths.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pattern for urgent paths.
+    pub fn with_pattern(mut self, pattern: &str) -> Self {
+        self.add(pattern);
+        self
+    }
+
+    /// Add a pattern for urgent paths.
+    pub fn add(&mut self, pattern: &str) {
+        self.patterns.push(pattern.chars().collect());
+    }
+
+    /// Returns `true` if no pattern was added.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns `true` if `path` or one of its ancestors matches a pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        path.ancestors().any(|ancestor| {
+            let name: Vec<char> = ancestor.to_string_lossy().chars().collect();
+            self.patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, &name, false))
+        })
+    }
+
+    /// Returns `true` if any path affected by `event` is urgent.
+    pub fn is_urgent(&self, event: &FsEvent) -> bool {
+        event.paths().into_iter().any(|path| self.matches(path))
+    }
+}
+
+/// The approach taken to apply a batch of events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateStrategy {
+    /// Every changed path was rescanned on its own.
+    PerEvent,
+    /// At least one busy directory was rescanned as a whole.
+    SubtreeRescan,
+    /// The whole tree was refreshed.
+    FullRefresh,
+}
+
+/// What `Tree::apply_events` did, for observability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateReport {
+    /// The overall strategy used.
+    pub strategy: UpdateStrategy,
+    /// Directories that were rescanned as a whole.
+    pub subtrees: Vec<PathBuf>,
+    /// Paths that were rescanned individually.
+    pub paths: Vec<PathBuf>,
+    /// Paths in a priority lane, rescanned individually before everything else.
+    pub prioritized: Vec<PathBuf>,
+}
+
+impl Tree {
+    /// Apply a batch of coalesced events to the tree.
+    ///
+    /// Changes in a priority lane are rescanned first, one by one. Then directories
+    /// collecting many changes are rescanned as a whole (see `RescanPolicy`), and the
+    /// remaining paths are rescanned one by one. Events outside the tree are ignored.
+    /// For trees built with `new_chroot` or mounted in a `Forest`, events carry paths on
+    /// disk.
+    pub fn apply_events(
+        &mut self,
+        events: &[FsEvent],
+        policy: &RescanPolicy,
+    ) -> io::Result<UpdateReport> {
+        let (urgent, bulk): (Vec<FsEvent>, Vec<FsEvent>) = events
+            .iter()
+            .cloned()
+            .partition(|event| policy.priority.is_urgent(event));
+        let (urgent, bulk) = if self.host_root.is_some() {
+            (self.logical_events(&urgent), self.logical_events(&bulk))
+        } else {
+            (urgent, bulk)
+        };
+
+        let mut seen = HashSet::new();
+        let mut prioritized = Vec::new();
+        for path in urgent.iter().flat_map(FsEvent::paths) {
+            if path.starts_with(&self.head.path) && seen.insert(path) {
+                prioritized.push(path.to_path_buf());
+            }
+        }
+        for path in &prioritized {
+            self.refresh_nearest(path)?;
+        }
+
+        let mut remaining = Vec::new();
+        for path in bulk.iter().flat_map(FsEvent::paths) {
+            if path.starts_with(&self.head.path) && seen.insert(path) {
+                remaining.push(path.to_path_buf());
+            }
+        }
+
+        let subtrees = busy_directories(&mut remaining, &self.head.path, policy);
+        if subtrees.contains(&self.head.path) {
+            self.refresh()?;
+            return Ok(UpdateReport {
+                strategy: UpdateStrategy::FullRefresh,
+                subtrees,
+                paths: remaining,
+                prioritized,
+            });
+        }
+
+        for dir in &subtrees {
+            self.refresh_nearest(dir)?;
+        }
+        for path in &remaining {
+            self.refresh_nearest(path)?;
+        }
+
+        let strategy = if subtrees.is_empty() {
+            UpdateStrategy::PerEvent
+        } else {
+            UpdateStrategy::SubtreeRescan
+        };
+        Ok(UpdateReport {
+            strategy,
+            subtrees,
+            paths: remaining,
+            prioritized,
+        })
+    }
+
+    /// Rescans `path`, falling back to the closest ancestor already in the tree when
+    /// its parent is not known yet (e.g. a newly created nested directory).
+    pub(crate) fn refresh_nearest(&mut self, path: &Path) -> io::Result<()> {
+        let mut target = path;
+        loop {
+            match self.refresh_path(target) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => match target.parent() {
+                    Some(parent) if parent.starts_with(&self.head.path) => target = parent,
+                    _ => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Repeatedly picks the deepest directory under `root` holding at least the threshold
+/// of changed paths, removing the paths it covers from `remaining`.
+fn busy_directories(
+    remaining: &mut Vec<PathBuf>,
+    root: &Path,
+    policy: &RescanPolicy,
+) -> Vec<PathBuf> {
+    let threshold = policy.subtree_threshold.max(1);
+    let mut chosen = Vec::new();
+    loop {
+        let mut counts: HashMap<&Path, usize> = HashMap::new();
+        for path in remaining.iter() {
+            for ancestor in path.ancestors().skip(1) {
+                if !ancestor.starts_with(root) {
+                    break;
+                }
+                *counts.entry(ancestor).or_default() += 1;
+            }
+        }
+
+        let busiest = counts
+            .into_iter()
+            .filter(|(_, count)| *count >= threshold)
+            .map(|(dir, _)| dir)
+            .max_by(|a, b| {
+                let depth = |dir: &Path| dir.components().count();
+                depth(a).cmp(&depth(b)).then_with(|| b.cmp(a))
+            })
+            .map(Path::to_path_buf);
+        match busiest {
+            Some(dir) => {
+                remaining.retain(|path| !path.starts_with(&dir));
+                chosen.push(dir);
+            }
+            None => return chosen,
+        }
+    }
+}
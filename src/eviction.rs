@@ -0,0 +1,233 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// Limits how much of a tree stays resident in memory.
+#[derive(Debug, Clone)]
+pub struct EvictionPolicy {
+    /// Number of nodes to keep resident. Once exceeded, the least recently accessed
+    /// subtrees are collapsed into size-only stubs until the tree fits again.
+    pub max_resident_nodes: usize,
+}
+
+/// What decides whether a subtree stays resident: logical access times per directory,
+/// maintained by `Tree::access`, and the set of pinned paths.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Residency {
+    clock: u64,
+    last_access: HashMap<PathBuf, u64>,
+    pinned: BTreeSet<PathBuf>,
+}
+
+impl Residency {
+    /// Records an access to `path`, which also counts as an access to every ancestor.
+    fn touch(&mut self, path: &Path, root: &Path) {
+        self.clock += 1;
+        for ancestor in path.ancestors() {
+            if !ancestor.starts_with(root) {
+                break;
+            }
+            self.last_access.insert(ancestor.to_path_buf(), self.clock);
+        }
+    }
+
+    fn last_access(&self, path: &Path) -> u64 {
+        self.last_access.get(path).copied().unwrap_or(0)
+    }
+
+    /// Estimated bytes held by the access log and the pin set.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        // A hash map entry also carries roughly one control byte.
+        let access = self.last_access.capacity() * (size_of::<PathBuf>() + size_of::<u64>() + 1);
+        let pins = self.pinned.len() * size_of::<PathBuf>();
+        let paths: usize = self
+            .last_access
+            .keys()
+            .chain(&self.pinned)
+            .map(PathBuf::capacity)
+            .sum();
+        access + pins + paths
+    }
+
+    /// Returns `true` if `path` is pinned or lies below a pinned path.
+    pub(crate) fn is_pinned(&self, path: &Path) -> bool {
+        path.ancestors()
+            .any(|ancestor| self.pinned.contains(ancestor))
+    }
+
+    /// Returns `true` if collapsing `path` would drop a pinned subtree.
+    fn protects(&self, path: &Path) -> bool {
+        self.is_pinned(path) || self.pinned.iter().any(|pinned| pinned.starts_with(path))
+    }
+}
+
+impl Tree {
+    /// Look up the node at `path`, recording the access and transparently rescanning
+    /// any evicted subtree on the way to it. In lazy trees, directories on the way are
+    /// listed as with `load`.
+    pub fn access(&mut self, path: &Path) -> io::Result<&Node> {
+        self.residency.touch(path, &self.head.path);
+        self.rehydrate(path)?;
+        if self.options.lazy {
+            return self.load(path);
+        }
+        self.get_node(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in the tree", path.display()),
+            )
+        })
+    }
+
+    /// Keep the subtree at `path` fully resident: it is rescanned now if it was evicted,
+    /// and is never collapsed by `evict` (nor are its ancestors) until unpinned.
+    pub fn pin(&mut self, path: &Path) -> io::Result<()> {
+        self.access(path)?;
+        self.residency.pinned.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Remove a pin added with `pin`, returning `true` if the path was pinned.
+    pub fn unpin(&mut self, path: &Path) -> bool {
+        self.residency.pinned.remove(path)
+    }
+
+    /// Returns `true` if `path` is pinned or lies below a pinned path.
+    pub fn is_pinned(&self, path: &Path) -> bool {
+        self.residency.is_pinned(path)
+    }
+
+    /// Rescans the outermost evicted stub on the way to `path`, if any.
+    fn rehydrate(&mut self, path: &Path) -> io::Result<()> {
+        let stub = path
+            .ancestors()
+            .filter(|ancestor| ancestor.starts_with(&self.head.path))
+            .filter(|ancestor| self.get_node(ancestor).is_some_and(|n| n.evicted.is_some()))
+            .last()
+            .map(Path::to_path_buf);
+        match stub {
+            Some(stub) => self.refresh_path(&stub),
+            None => Ok(()),
+        }
+    }
+
+    /// Collapse the least recently accessed subtrees into size-only stubs until at most
+    /// `policy.max_resident_nodes` nodes remain resident. The root is never evicted.
+    /// Pinned subtrees and their ancestors are kept. Returns the number of subtrees evicted.
+    /// A change applied below a stub, with `apply_events` or `refresh_path`, rescans the
+    /// whole stub and makes it resident again.
+    pub fn evict(&mut self, policy: &EvictionPolicy) -> usize {
+        let mut resident = self.iter().count();
+        if resident <= policy.max_resident_nodes {
+            return 0;
+        }
+
+        let mut candidates: Vec<(u64, usize, PathBuf)> = self
+            .iter()
+            .filter(|node| node.is_dir() && node.children.is_some() && node.path != self.head.path)
+            .filter(|node| !self.residency.protects(&node.path))
+            .map(|node| {
+                let depth = node.path.components().count();
+                (
+                    self.residency.last_access(&node.path),
+                    depth,
+                    node.path.clone(),
+                )
+            })
+            .collect();
+        // Coldest first; among equally cold subtrees, prefer the larger (shallower) one.
+        candidates.sort();
+
+        let mut evicted = 0;
+        let mut collapsed: Vec<PathBuf> = Vec::new();
+        for (_, _, path) in candidates {
+            if resident <= policy.max_resident_nodes {
+                break;
+            }
+            if collapsed.iter().any(|done| path.starts_with(done)) {
+                continue;
+            }
+            if let Some(node) = self.get_node_mut(&path) {
+                resident -= collapse(node);
+                collapsed.push(path);
+                evicted += 1;
+            }
+        }
+        evicted
+    }
+}
+
+/// Replaces a directory's children with a stub, returning how many resident nodes
+/// were dropped.
+fn collapse(node: &mut Node) -> usize {
+    let (resident, total) = count_below(node);
+    node.children = None;
+    node.evicted = Some(total);
+    resident
+}
+
+/// Counts the resident nodes below `node`, and all entries including those already
+/// collapsed into nested stubs.
+fn count_below(node: &Node) -> (usize, u64) {
+    let mut resident = 0;
+    let mut total = 0;
+    for child in node.children.iter().flatten() {
+        let (child_resident, child_total) = count_below(child);
+        resident += 1 + child_resident;
+        total += 1 + child.evicted.unwrap_or(0) + child_total;
+    }
+    (resident, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::EvictionPolicy;
+    use crate::builder::TreeBuilder;
+    use crate::event::{FsEvent, RescanPolicy};
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn access_rescans_evicted_subtrees() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir(dir.root.join("a")).unwrap();
+        fs::write(dir.root.join("a/one"), "123").unwrap();
+        let mut tree = TreeBuilder::new(&dir.root).build().unwrap();
+
+        let policy = EvictionPolicy {
+            max_resident_nodes: 1,
+        };
+        assert_eq!(tree.evict(&policy), 1);
+        let stub = tree.get_node(&dir.root.join("a")).unwrap();
+        assert_eq!((stub.evicted, stub.size), (Some(1), 3));
+        assert!(tree.get_node(&dir.root.join("a/one")).is_none());
+
+        assert_eq!(tree.access(&dir.root.join("a/one")).unwrap().size, 3);
+        assert_eq!(tree.get_node(&dir.root.join("a")).unwrap().evicted, None);
+    }
+
+    #[test]
+    fn changes_below_a_stub_rescan_it() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir(dir.root.join("a")).unwrap();
+        fs::write(dir.root.join("a/one"), "123").unwrap();
+        let mut tree = TreeBuilder::new(&dir.root).build().unwrap();
+        tree.evict(&EvictionPolicy {
+            max_resident_nodes: 1,
+        });
+
+        fs::write(dir.root.join("a/two"), "1234567890").unwrap();
+        let created = FsEvent::Created(dir.root.join("a/two"));
+        tree.apply_events(&[created], &RescanPolicy::default())
+            .unwrap();
+        let a = tree.get_node(&dir.root.join("a")).unwrap();
+        assert_eq!((a.evicted, a.size), (None, 13));
+        assert_eq!(tree.head.size, 13);
+        assert!(tree.validate().is_ok());
+    }
+}
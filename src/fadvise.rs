@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::ptr;
+
+/// How much of a file `cached` asks `mincore` about at once, so that the residency
+/// vector stays small however large the file is.
+const WINDOW: usize = 64 << 20;
+
+/// Page-cache hints for a file read or written once, from start to end. Given when
+/// created and, to spare the page cache, when dropped. All hints are best effort.
+pub(crate) struct Streaming<'a> {
+    file: &'a File,
+    /// Drop the file's pages from the page cache when done.
+    drop_pages: bool,
+    /// Write dirty pages back first, which are not dropped otherwise.
+    written: bool,
+}
+
+impl<'a> Streaming<'a> {
+    /// For a file about to be read: readahead is raised for sequential access. With
+    /// `spare`, the pages read are dropped again when done, unless some of the file was
+    /// cached before, in which case someone uses it and it is left alone.
+    pub(crate) fn read(file: &'a File, spare: bool) -> Self {
+        advise(file, libc::POSIX_FADV_SEQUENTIAL);
+        Self {
+            file,
+            drop_pages: spare && !cached(file),
+            written: false,
+        }
+    }
+
+    /// For a new file about to be written: with `spare`, its pages are written back and
+    /// dropped when done.
+    #[cfg(feature = "store")]
+    pub(crate) fn write(file: &'a File, spare: bool) -> Self {
+        Self {
+            file,
+            drop_pages: spare,
+            written: true,
+        }
+    }
+}
+
+impl Drop for Streaming<'_> {
+    fn drop(&mut self) {
+        if !self.drop_pages {
+            return;
+        }
+        if self.written {
+            let _ = self.file.sync_data();
+        }
+        advise(self.file, libc::POSIX_FADV_DONTNEED);
+    }
+}
+
+/// Applies `advice` to the whole of `file`.
+fn advise(file: &File, advice: libc::c_int) {
+    // SAFETY: the descriptor is open for as long as `file` is borrowed.
+    unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) };
+}
+
+/// Whether any page of `file` is in the page cache, by mapping it and asking `mincore`
+/// a window at a time, stopping at the first resident page. When that fails the file is
+/// taken to be cached, so that it is not dropped.
+fn cached(file: &File) -> bool {
+    let Ok(len) = file.metadata().map(|metadata| metadata.len()) else {
+        return true;
+    };
+    let Ok(len) = usize::try_from(len) else {
+        return true;
+    };
+    if len == 0 {
+        return false;
+    }
+    // SAFETY: sysconf has no preconditions.
+    let page = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    };
+    let mut resident = vec![0u8; WINDOW / page];
+    // SAFETY: the mapping is never touched, only queried and unmapped again. Each query
+    // covers at most `WINDOW` bytes from a page-aligned offset, for which `resident`
+    // has a byte per page.
+    unsafe {
+        let addr = libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        );
+        if addr == libc::MAP_FAILED {
+            return true;
+        }
+        let mut found = false;
+        let mut offset = 0;
+        while offset < len && !found {
+            let chunk = WINDOW.min(len - offset);
+            let start = addr.cast::<u8>().add(offset).cast();
+            found = libc::mincore(start, chunk, resident.as_mut_ptr()) != 0
+                || resident[..chunk.div_ceil(page)]
+                    .iter()
+                    .any(|page| page & 1 != 0);
+            offset += chunk;
+        }
+        libc::munmap(addr, len);
+        found
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use crate::event::FsEvent;
+
+/// What a subscriber's queue does with a new event when it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Discard the new event, keeping the backlog intact.
+    DropNewest,
+    /// Discard the oldest queued event to make room, favouring fresh state.
+    DropOldest,
+}
+
+/// Delivers every published event to any number of subscribers, each with its own
+/// bounded queue, so a slow consumer never holds up the publisher or the others.
+/// Clones publish to the same subscribers.
+#[derive(Clone, Default)]
+pub struct Fanout {
+    hub: Arc<Hub>,
+}
+
+#[derive(Default)]
+struct Hub {
+    queues: Mutex<Vec<Weak<Queue>>>,
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<FsEvent>,
+    missed: u64,
+    closed: bool,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        // A panicking consumer cannot leave the queue half updated.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Fanout {
+    /// Create a fan-out with no subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a subscriber that buffers up to `capacity` events, handling any beyond that
+    /// according to `overflow`. It receives events published from now on.
+    pub fn subscribe(&self, capacity: usize, overflow: Overflow) -> Subscriber {
+        let queue = Arc::new(Queue {
+            state: Mutex::default(),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+            overflow,
+        });
+        self.hub.lock().push(Arc::downgrade(&queue));
+        Subscriber { queue }
+    }
+
+    /// Deliver `event` to every subscriber, forgetting those that were dropped.
+    pub fn publish(&self, event: &FsEvent) {
+        self.hub.lock().retain(|queue| {
+            let Some(queue) = queue.upgrade() else {
+                return false;
+            };
+            let mut state = queue.lock();
+            if state.events.len() >= queue.capacity {
+                state.missed += 1;
+                match queue.overflow {
+                    Overflow::DropNewest => return true,
+                    Overflow::DropOldest => {
+                        state.events.pop_front();
+                    }
+                }
+            }
+            state.events.push_back(event.clone());
+            queue.ready.notify_one();
+            true
+        });
+    }
+
+    /// Number of subscribers still listening.
+    pub fn subscriber_count(&self) -> usize {
+        let mut queues = self.hub.lock();
+        queues.retain(|queue| queue.strong_count() > 0);
+        queues.len()
+    }
+}
+
+impl Hub {
+    fn lock(&self) -> MutexGuard<'_, Vec<Weak<Queue>>> {
+        self.queues
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for Hub {
+    /// Once nothing can publish any more, wake every subscriber so it sees the end.
+    fn drop(&mut self) {
+        for queue in self.lock().iter().filter_map(Weak::upgrade) {
+            queue.lock().closed = true;
+            queue.ready.notify_all();
+        }
+    }
+}
+
+/// One consumer's view of a `Fanout`. Dropping it unsubscribes.
+pub struct Subscriber {
+    queue: Arc<Queue>,
+}
+
+impl Subscriber {
+    /// Block until the next event arrives, or return `None` once the fan-out is gone and
+    /// the queue is drained.
+    pub fn recv(&self) -> Option<FsEvent> {
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .queue
+                .ready
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Return the next event if one is already queued.
+    pub fn try_recv(&self) -> Option<FsEvent> {
+        self.queue.lock().events.pop_front()
+    }
+
+    /// Wait up to `timeout` for the next event.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<FsEvent> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if state.closed || left.is_zero() {
+                return None;
+            }
+            state = self
+                .queue
+                .ready
+                .wait_timeout(state, left)
+                .map(|(state, _)| state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+    }
+
+    /// Number of events queued and not yet received.
+    pub fn pending(&self) -> usize {
+        self.queue.lock().events.len()
+    }
+
+    /// Number of events this subscriber lost because its queue was full. A consumer
+    /// that has missed events should resynchronise, e.g. by refreshing its tree.
+    pub fn missed(&self) -> u64 {
+        self.queue.lock().missed
+    }
+}
+
+impl Iterator for Subscriber {
+    type Item = FsEvent;
+
+    fn next(&mut self) -> Option<FsEvent> {
+        self.recv()
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::group::GroupBy;
+use crate::node::Node;
+use crate::platform::create_symlink;
+use crate::tree::Tree;
+
+/// A directory of symbolic links to a tree's files, arranged into one subdirectory per
+/// group, e.g. all photos of 2023 by month: a view of the files on disk that leaves them
+/// where they are. Planned by `Tree::plan_symlink_farm`; nothing is created until
+/// `execute`, so the plan can be looked over or edited first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymlinkFarm {
+    /// The directory the group directories are created in.
+    pub root: PathBuf,
+    /// The links to create, as (link, target) pairs: the link's path below `root`, and
+    /// the absolute path of the file it points to.
+    pub links: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SymlinkFarm {
+    /// Create the planned directories and links.
+    ///
+    /// Nothing is created if any link's path is taken already. If creating a link or
+    /// directory fails, the links and directories created before it are removed again
+    /// and the error is returned, so the farm is made in full or not at all.
+    pub fn execute(&self) -> io::Result<()> {
+        if let Some((link, _)) = self
+            .links
+            .iter()
+            .find(|(link, _)| link.symlink_metadata().is_ok())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", link.display()),
+            ));
+        }
+        let mut dirs = Vec::new();
+        let mut links = Vec::new();
+        let created = self.create(&mut dirs, &mut links);
+        if created.is_err() {
+            for link in links.iter().rev() {
+                let _ = fs::remove_file(link);
+            }
+            for dir in dirs.iter().rev() {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+        created
+    }
+
+    /// Creates the farm, recording in `dirs` and `links` what it created.
+    fn create(&self, dirs: &mut Vec<PathBuf>, links: &mut Vec<PathBuf>) -> io::Result<()> {
+        for (link, target) in &self.links {
+            if let Some(parent) = link.parent() {
+                create_dirs(parent, dirs)?;
+            }
+            create_symlink(target, link)?;
+            links.push(link.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Tree {
+    /// Plan a symlink farm in `root`: a link to every resident file for which `filter`
+    /// returns `true`, in a directory per group as `Tree::group_view` arranges them.
+    ///
+    /// Links are named after their files; a name used more than once in a group gets a
+    /// number, as in `photo (2).jpg`. Targets are the files' paths on disk, made
+    /// absolute, so the links resolve wherever `root` is.
+    pub fn plan_symlink_farm<F>(&self, root: &Path, by: GroupBy, filter: F) -> SymlinkFarm
+    where
+        F: Fn(&Node) -> bool,
+    {
+        let mut links = Vec::new();
+        for group in self.group_view(by).groups {
+            let dir = root.join(&group.name);
+            let mut used = HashSet::new();
+            for node in group.files.into_iter().filter(|node| filter(node)) {
+                let Some(name) = node.path.file_name() else {
+                    continue;
+                };
+                let name = name.to_string_lossy();
+                let mut link_name = name.to_string();
+                let mut copy = 1;
+                while !used.insert(link_name.clone()) {
+                    copy += 1;
+                    link_name = numbered(&name, copy);
+                }
+                let physical = self.physical_path(&node.path);
+                let target = std::path::absolute(&physical).unwrap_or(physical);
+                links.push((dir.join(link_name), target));
+            }
+        }
+        SymlinkFarm {
+            root: root.to_path_buf(),
+            links,
+        }
+    }
+}
+
+/// `name` with `copy` inserted before its extension, as in `photo (2).jpg`.
+fn numbered(name: &str, copy: usize) -> String {
+    match name.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => format!("{} ({copy}){}", &name[..dot], &name[dot..]),
+        None => format!("{name} ({copy})"),
+    }
+}
+
+/// Creates `dir` and any missing parents, appending the ones created to `created`,
+/// outermost first.
+fn create_dirs(dir: &Path, created: &mut Vec<PathBuf>) -> io::Result<()> {
+    if dir.as_os_str().is_empty() || dir.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent() {
+        create_dirs(parent, created)?;
+    }
+    fs::create_dir(dir)?;
+    created.push(dir.to_path_buf());
+    Ok(())
+}
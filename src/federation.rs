@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::diff::node_entries;
+use crate::node::{Node, NodeType};
+use crate::snapshot::{Snapshot, SnapshotEntry};
+use crate::tree::{insert_entry, Tree};
+
+/// Trees from many hosts, each tagged with a host id, queried as one. A central service
+/// can load what each host serialized (a snapshot, manifest or mtree spec), keep it up to
+/// date from the host's change stream with `upsert` and `remove`, and answer where across
+/// the fleet a path exists and how big it is.
+///
+/// Host trees live in memory only and are never rescanned from disk here.
+#[derive(Default)]
+pub struct Federation {
+    hosts: BTreeMap<String, Tree>,
+}
+
+/// Where a path was found in a federation.
+#[derive(Debug, Clone, Copy)]
+pub struct Location<'a> {
+    /// The host holding the entry.
+    pub host: &'a str,
+    /// The entry in that host's tree.
+    pub node: &'a Node,
+}
+
+impl Federation {
+    /// Create a federation without hosts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the tree for `host`, returning the one it replaces.
+    pub fn add_tree(&mut self, host: impl Into<String>, tree: Tree) -> Option<Tree> {
+        self.hosts.insert(host.into(), tree)
+    }
+
+    /// Add `host` as recorded in `snapshot`, rooted where the snapshot was taken.
+    pub fn add_snapshot(&mut self, host: impl Into<String>, snapshot: &Snapshot) -> Option<Tree> {
+        let tree = Tree::from_entries(snapshot.root.clone(), snapshot.entries.iter().cloned());
+        self.add_tree(host, tree)
+    }
+
+    /// Drop `host` from the federation.
+    pub fn remove_host(&mut self, host: &str) -> Option<Tree> {
+        self.hosts.remove(host)
+    }
+
+    /// The host ids, in order.
+    pub fn hosts(&self) -> impl Iterator<Item = &str> {
+        self.hosts.keys().map(String::as_str)
+    }
+
+    /// The tree of `host`.
+    pub fn tree(&self, host: &str) -> Option<&Tree> {
+        self.hosts.get(host)
+    }
+
+    /// Record that the entry at `entry.path`, relative to the host's root, now looks like
+    /// `entry`, creating missing parent directories. The sizes of files are taken from the
+    /// entry and directory sizes follow from them, as with a freshly loaded snapshot.
+    pub fn upsert(&mut self, host: &str, entry: SnapshotEntry) -> io::Result<()> {
+        let tree = self.host_mut(host)?;
+        let path = tree.head.path.join(&entry.path);
+        if entry.path.as_os_str().is_empty() {
+            tree.head.metadata = entry.metadata;
+            return Ok(());
+        }
+
+        let old = tree
+            .get_node(&path)
+            .map(|node| (node.node_type.clone(), node.size));
+        if old
+            .as_ref()
+            .is_some_and(|(node_type, _)| *node_type != entry.node_type)
+        {
+            remove_below(tree, &path);
+        }
+        let old_size = match &old {
+            Some((node_type, size)) if *node_type == entry.node_type => *size,
+            _ => 0,
+        };
+        let new_size = match entry.node_type {
+            NodeType::Directory => old_size,
+            _ => entry.size,
+        };
+
+        insert_entry(
+            &mut tree.head,
+            SnapshotEntry {
+                size: new_size,
+                ..entry
+            },
+        );
+        adjust_ancestors(tree, &path, old_size, new_size);
+        Ok(())
+    }
+
+    /// Record that the entry at `rel` on `host` is gone, returning `false` if it was not
+    /// known.
+    pub fn remove(&mut self, host: &str, rel: &Path) -> io::Result<bool> {
+        let tree = self.host_mut(host)?;
+        let path = tree.head.path.join(rel);
+        if path == tree.head.path {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the root of a host cannot be removed",
+            ));
+        }
+        Ok(remove_below(tree, &path))
+    }
+
+    /// Every host holding `path`. Relative paths are looked up below each host's root,
+    /// absolute ones as they are.
+    pub fn locate(&self, path: &Path) -> Vec<Location<'_>> {
+        self.hosts
+            .iter()
+            .filter_map(|(host, tree)| {
+                let node = tree.get_node(&tree.head.path.join(path))?;
+                Some(Location { host, node })
+            })
+            .collect()
+    }
+
+    /// Every entry on every host matching `predicate`.
+    pub fn search<F>(&self, predicate: F) -> Vec<Location<'_>>
+    where
+        F: Fn(&Node) -> bool,
+    {
+        self.hosts
+            .iter()
+            .flat_map(|(host, tree)| {
+                tree.iter()
+                    .filter(|node| predicate(node))
+                    .map(move |node| Location { host, node })
+            })
+            .collect()
+    }
+
+    /// Combined size of every host's tree.
+    pub fn total_size(&self) -> u64 {
+        self.hosts.values().map(|tree| tree.head.size).sum()
+    }
+
+    /// One tree holding every host below a directory named after its id, under a virtual
+    /// root `/`. The result is a copy; later updates to the federation do not affect it.
+    pub fn merged(&self) -> Tree {
+        let mut entries = Vec::new();
+        for (host, tree) in &self.hosts {
+            let prefix = PathBuf::from(host);
+            entries.push(SnapshotEntry {
+                path: prefix.clone(),
+                node_type: NodeType::Directory,
+                size: 0,
+                metadata: tree.head.metadata.clone(),
+            });
+            for (rel, entry) in node_entries(&tree.head) {
+                entries.push(SnapshotEntry {
+                    path: prefix.join(rel),
+                    node_type: entry.node_type.clone(),
+                    size: entry.size,
+                    metadata: entry.metadata.clone(),
+                });
+            }
+        }
+        Tree::from_entries(PathBuf::from("/"), entries)
+    }
+
+    fn host_mut(&mut self, host: &str) -> io::Result<&mut Tree> {
+        self.hosts.get_mut(host).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("unknown host {}", host))
+        })
+    }
+}
+
+/// Removes the node at `path` and everything below it, adjusting ancestor sizes.
+fn remove_below(tree: &mut Tree, path: &Path) -> bool {
+    let Some(parent) = path.parent().and_then(|parent| tree.get_node_mut(parent)) else {
+        return false;
+    };
+    let Some(children) = parent.children.as_mut() else {
+        return false;
+    };
+    let Some(index) = children.iter().position(|child| child.path == path) else {
+        return false;
+    };
+    let removed = children.remove(index);
+    adjust_ancestors(tree, path, removed.size, 0);
+    true
+}
+
+/// Replaces `old` by `new` in the size of every ancestor of `path`.
+fn adjust_ancestors(tree: &mut Tree, path: &Path, old: u64, new: u64) {
+    let root = tree.head.path.clone();
+    for ancestor in path.ancestors().skip(1) {
+        if !ancestor.starts_with(&root) {
+            break;
+        }
+        if let Some(node) = tree.get_node_mut(ancestor) {
+            node.size = node.size.saturating_sub(old) + new;
+        }
+    }
+}
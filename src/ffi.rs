@@ -0,0 +1,309 @@
+//! A C-compatible API for embedding the crate in non-Rust applications.
+//!
+//! Trees are passed around as opaque `FfTree` handles created by `ff_tree_scan` and
+//! released with `ff_tree_free`. Functions returning `int` return a non-negative value
+//! on success and `-1` on failure, after which `ff_last_error` describes the problem.
+//! Paths are exchanged as NUL-terminated UTF-8 strings; strings handed to callbacks are
+//! only valid for the duration of the call. The matching declarations are in
+//! `include/file_frontier.h`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::event::{FsEvent, RescanPolicy};
+use crate::selection::glob_match;
+use crate::tree::Tree;
+use crate::source::EventSource;
+use crate::watcher::FsWatcher;
+
+/// An opaque handle to a scanned tree.
+pub struct FfTree {
+    tree: Arc<Mutex<Tree>>,
+}
+
+/// An opaque handle to a running watch started by `ff_watch_start`.
+pub struct FfWatch {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Totals across a tree, filled in by `ff_tree_stats`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FfStats {
+    pub files: u64,
+    pub dirs: u64,
+    pub bytes: u64,
+}
+
+/// Called once per search result with the node's path, its size, whether it is a
+/// directory, and the caller's `user` pointer. Returning non-zero stops the search.
+pub type FfSearchCallback =
+    extern "C" fn(path: *const c_char, size: u64, is_dir: c_int, user: *mut c_void) -> c_int;
+
+/// Called from a background thread for every change applied by a watch. `kind` is one of
+/// the `FF_EVENT_*` constants; `to` is NULL except for renames.
+pub type FfEventCallback =
+    extern "C" fn(kind: c_int, path: *const c_char, to: *const c_char, user: *mut c_void);
+
+pub const FF_EVENT_CREATED: c_int = 0;
+pub const FF_EVENT_MODIFIED: c_int = 1;
+pub const FF_EVENT_REMOVED: c_int = 2;
+pub const FF_EVENT_RENAMED: c_int = 3;
+/// Changes below `path` may have been missed; the tree was rescanned there.
+pub const FF_EVENT_DESYNCED: c_int = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The message of the last failure on the calling thread, or NULL if there was none.
+/// The string stays valid until the next call into the library on the same thread.
+#[no_mangle]
+pub extern "C" fn ff_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Scan the directory at `root`, returning NULL on failure.
+///
+/// # Safety
+///
+/// `root` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ff_tree_scan(root: *const c_char) -> *mut FfTree {
+    let result = path_arg(root).and_then(|root| Tree::new(&root).map_err(|e| e.to_string()));
+    match result {
+        Ok(tree) => Box::into_raw(Box::new(FfTree {
+            tree: Arc::new(Mutex::new(tree)),
+        })),
+        Err(message) => {
+            set_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Release a tree. Any watch on it must have been stopped first.
+///
+/// # Safety
+///
+/// `tree` must be NULL or a handle returned by `ff_tree_scan` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ff_tree_free(tree: *mut FfTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Rescan the whole tree from disk.
+///
+/// # Safety
+///
+/// `tree` must be a live handle returned by `ff_tree_scan`.
+#[no_mangle]
+pub unsafe extern "C" fn ff_tree_refresh(tree: *const FfTree) -> c_int {
+    let Some(tree) = tree.as_ref() else {
+        return fail("tree is NULL");
+    };
+    match lock(&tree.tree).refresh() {
+        Ok(()) => 0,
+        Err(e) => fail(e),
+    }
+}
+
+/// Fill in `stats` with the tree's totals.
+///
+/// # Safety
+///
+/// `tree` must be a live handle and `stats` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn ff_tree_stats(tree: *const FfTree, stats: *mut FfStats) -> c_int {
+    let (Some(tree), Some(stats)) = (tree.as_ref(), stats.as_mut()) else {
+        return fail("tree or stats is NULL");
+    };
+    let tree = lock(&tree.tree);
+    let mut totals = FfStats {
+        bytes: tree.head.size,
+        ..FfStats::default()
+    };
+    for node in tree.iter() {
+        if node.is_dir() {
+            totals.dirs += 1;
+        } else {
+            totals.files += 1;
+        }
+    }
+    *stats = totals;
+    0
+}
+
+/// Call `callback` for every node whose file name matches `pattern`, where `*` matches
+/// any run of characters, `?` any single one and `[...]` one of a class. Returns the
+/// number of results reported.
+///
+/// # Safety
+///
+/// `tree` must be a live handle and `pattern` a NUL-terminated string. `user` is passed
+/// through to `callback` untouched.
+#[no_mangle]
+pub unsafe extern "C" fn ff_tree_search(
+    tree: *const FfTree,
+    pattern: *const c_char,
+    callback: FfSearchCallback,
+    user: *mut c_void,
+) -> c_int {
+    let Some(tree) = tree.as_ref() else {
+        return fail("tree is NULL");
+    };
+    let pattern: Vec<char> = match str_arg(pattern) {
+        Ok(pattern) => pattern.chars().collect(),
+        Err(message) => return fail(message),
+    };
+
+    let tree = lock(&tree.tree);
+    let mut reported: c_int = 0;
+    for node in tree.iter() {
+        let Some(name) = node.path.file_name() else {
+            continue;
+        };
+        let name: Vec<char> = name.to_string_lossy().chars().collect();
+        if !glob_match(&pattern, &name, false) {
+            continue;
+        }
+        let path = c_path(&node.path);
+        reported = reported.saturating_add(1);
+        if callback(path.as_ptr(), node.size, c_int::from(node.is_dir()), user) != 0 {
+            break;
+        }
+    }
+    reported
+}
+
+/// Start watching the tree's root, applying changes to the tree as they happen and
+/// reporting each one to `callback` from a background thread. Returns NULL on failure.
+///
+/// # Safety
+///
+/// `tree` must be a live handle that outlives the watch. `callback` must be safe to call
+/// from another thread with `user` until `ff_watch_stop` returns.
+#[no_mangle]
+pub unsafe extern "C" fn ff_watch_start(
+    tree: *const FfTree,
+    callback: FfEventCallback,
+    user: *mut c_void,
+) -> *mut FfWatch {
+    let Some(tree) = tree.as_ref() else {
+        set_error("tree is NULL");
+        return ptr::null_mut();
+    };
+    let root = lock(&tree.tree).head.path.clone();
+    let watcher = match FsWatcher::new(&root) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            set_error(e);
+            return ptr::null_mut();
+        }
+    };
+
+    let shared = Arc::clone(&tree.tree);
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    // Raw pointers are not `Send`; the caller vouched for `user` being usable anywhere.
+    let user = user as usize;
+    let thread = thread::spawn(move || {
+        while !stopped.load(Ordering::Relaxed) {
+            let Some(first) = watcher.recv_timeout(POLL_INTERVAL) else {
+                continue;
+            };
+            let mut batch = vec![first];
+            while let Some(event) = watcher.try_recv() {
+                batch.push(event);
+            }
+            // A failed update is repaired by the next event touching the same area.
+            let _ = lock(&shared).apply_events(&batch, &RescanPolicy::default());
+            for event in &batch {
+                report(event, callback, user as *mut c_void);
+            }
+        }
+    });
+
+    Box::into_raw(Box::new(FfWatch {
+        stop,
+        thread: Some(thread),
+    }))
+}
+
+/// Stop a watch, waiting for any callback in progress to return, and release it.
+///
+/// # Safety
+///
+/// `watch` must be NULL or a handle returned by `ff_watch_start` that was not stopped yet.
+#[no_mangle]
+pub unsafe extern "C" fn ff_watch_stop(watch: *mut FfWatch) {
+    if watch.is_null() {
+        return;
+    }
+    let mut watch = Box::from_raw(watch);
+    watch.stop.store(true, Ordering::Relaxed);
+    if let Some(thread) = watch.thread.take() {
+        let _ = thread.join();
+    }
+}
+
+/// How often a watch thread checks whether it was asked to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn report(event: &FsEvent, callback: FfEventCallback, user: *mut c_void) {
+    let (kind, path, to) = match event {
+        FsEvent::Created(path) => (FF_EVENT_CREATED, path, None),
+        FsEvent::Modified(path) => (FF_EVENT_MODIFIED, path, None),
+        FsEvent::Removed(path) => (FF_EVENT_REMOVED, path, None),
+        FsEvent::Renamed { from, to } => (FF_EVENT_RENAMED, from, Some(c_path(to))),
+        FsEvent::Desynced(path) => (FF_EVENT_DESYNCED, path, None),
+    };
+    let path = c_path(path);
+    let to = to.as_ref().map_or(ptr::null(), |to| to.as_ptr());
+    callback(kind, path.as_ptr(), to, user);
+}
+
+fn lock(tree: &Mutex<Tree>) -> MutexGuard<'_, Tree> {
+    tree.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+unsafe fn str_arg<'a>(value: *const c_char) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err("string argument is NULL".to_string());
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| "string argument is not UTF-8".to_string())
+}
+
+unsafe fn path_arg(value: *const c_char) -> Result<PathBuf, String> {
+    str_arg(value).map(PathBuf::from)
+}
+
+/// A path as a C string. Interior NULs cannot occur in paths on supported platforms.
+fn c_path(path: &Path) -> CString {
+    CString::new(path.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn set_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn fail(message: impl ToString) -> c_int {
+    set_error(message);
+    -1
+}
@@ -0,0 +1,92 @@
+use crate::codec::{fnv1a, write_path, write_time, write_varint};
+use crate::node::{Node, NodeType};
+
+/// Which metadata goes into a fingerprint besides the names and types of the entries,
+/// which always do. Leave out fields that change without a change the consumer cares
+/// about, e.g. access times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintFields {
+    /// Sizes of files and symlinks.
+    pub size: bool,
+    /// Modification times.
+    pub modified: bool,
+    /// Access times.
+    pub accessed: bool,
+    /// Creation times.
+    pub created: bool,
+    /// Symlink targets.
+    pub link_targets: bool,
+}
+
+impl Default for FingerprintFields {
+    /// Sizes, modification times and link targets: what changes when contents do.
+    fn default() -> Self {
+        Self {
+            size: true,
+            modified: true,
+            accessed: false,
+            created: false,
+            link_targets: true,
+        }
+    }
+}
+
+impl Node {
+    /// A digest of this node and everything below it, with the default fields.
+    /// See `fingerprint_with`.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint_with(&FingerprintFields::default())
+    }
+
+    /// A digest of this node and everything below it: the names and types of all
+    /// entries and the metadata selected by `fields`. It stays the same as long as none
+    /// of these change, so a cached answer for a directory stays valid while its
+    /// fingerprint does.
+    ///
+    /// The digest does not depend on where the node is or on the order children were
+    /// scanned in, and is stable across runs and platforms for the same names. A directory
+    /// whose children are not known (evicted, unlisted or beyond the scan depth) only
+    /// contributes its own metadata. File contents are not read; a change that keeps the
+    /// size and modification time is not seen.
+    pub fn fingerprint_with(&self, fields: &FingerprintFields) -> u64 {
+        let mut out = Vec::new();
+        match &self.node_type {
+            NodeType::File => out.push(0),
+            NodeType::Directory => out.push(1),
+            NodeType::Symlink { target } => {
+                out.push(2);
+                if fields.link_targets {
+                    write_path(&mut out, target);
+                }
+            }
+        }
+        if fields.size && !self.is_dir() {
+            write_varint(&mut out, self.size);
+        }
+        if fields.modified {
+            write_time(&mut out, self.metadata.modified);
+        }
+        if fields.accessed {
+            write_time(&mut out, self.metadata.accessed);
+        }
+        if fields.created {
+            write_time(&mut out, self.metadata.created);
+        }
+
+        match &self.children {
+            Some(children) => {
+                out.push(1);
+                let mut children: Vec<&Node> = children.iter().collect();
+                children.sort_by(|a, b| a.path.file_name().cmp(&b.path.file_name()));
+                write_varint(&mut out, children.len() as u64);
+                for child in children {
+                    let name = child.path.file_name().unwrap_or_default();
+                    write_path(&mut out, name.as_ref());
+                    out.extend_from_slice(&child.fingerprint_with(fields).to_le_bytes());
+                }
+            }
+            None => out.push(0),
+        }
+        fnv1a(&out)
+    }
+}
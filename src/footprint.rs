@@ -0,0 +1,76 @@
+use std::fmt;
+use std::mem::size_of;
+
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// Estimated heap and inline bytes held by a tree, broken down by component.
+///
+/// The figures count what the tree's own structures hold (allocated capacity, not just
+/// length) and ignore allocator overhead, so they are a lower bound suitable for
+/// comparing configurations rather than an exact measurement.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFootprint {
+    /// Number of resident nodes counted.
+    pub node_count: usize,
+    /// The node structs themselves, including spare capacity in child lists.
+    pub nodes: usize,
+    /// Path buffers owned by the nodes.
+    pub paths: usize,
+    /// Bookkeeping used for eviction: access times and pinned paths.
+    pub residency: usize,
+    /// Optional indexes, such as the ones kept by `Tree::track_recent` and
+    /// `Tree::track_inodes`, the tombstones and the size history.
+    pub indexes: usize,
+}
+
+impl MemoryFootprint {
+    /// Sum of all components, in bytes.
+    pub fn total(&self) -> usize {
+        self.nodes + self.paths + self.residency + self.indexes
+    }
+}
+
+impl fmt::Display for MemoryFootprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Nodes:     {} bytes ({} nodes)",
+            self.nodes, self.node_count
+        )?;
+        writeln!(f, "Paths:     {} bytes", self.paths)?;
+        writeln!(f, "Residency: {} bytes", self.residency)?;
+        writeln!(f, "Indexes:   {} bytes", self.indexes)?;
+        write!(f, "Total:     {} bytes", self.total())
+    }
+}
+
+impl Tree {
+    /// Estimate how much memory the tree uses, so eviction and scan options can be
+    /// tuned with real numbers. This walks every resident node.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint {
+            nodes: size_of::<Tree>(),
+            residency: self.residency.heap_bytes(),
+            indexes: self.recent.as_ref().map_or(0, |index| index.heap_bytes())
+                + self.tombstones.as_ref().map_or(0, |tombstones| tombstones.heap_bytes())
+                + self.inodes.as_ref().map_or(0, |index| index.heap_bytes())
+                + self.history.as_ref().map_or(0, |history| history.heap_bytes()),
+            ..MemoryFootprint::default()
+        };
+        for node in self.iter() {
+            footprint.node_count += 1;
+            footprint.paths += node.path.capacity();
+            footprint.nodes += child_list_bytes(node);
+        }
+        footprint
+    }
+}
+
+/// Bytes allocated for a node's child list. The head is counted inline in `Tree`, and
+/// every other node is counted here as a slot in its parent's list.
+fn child_list_bytes(node: &Node) -> usize {
+    node.children
+        .as_ref()
+        .map_or(0, |children| children.capacity() * size_of::<Node>())
+}
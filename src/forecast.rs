@@ -0,0 +1,191 @@
+#[cfg(all(feature = "statvfs", unix))]
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::history::SizeSample;
+use crate::tree::Tree;
+
+/// Two-sided 97.5% quantiles of Student's t distribution for 1 to 30 degrees of
+/// freedom, for 95% confidence bounds; beyond that the normal quantile is close enough.
+const T_975: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// How sizes are extrapolated by `forecast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitModel {
+    /// A straight line through the sizes: growth by the same number of bytes each day.
+    #[default]
+    Linear,
+    /// A straight line through the logarithms of the sizes: growth by the same fraction
+    /// each day. Empty samples are left out.
+    Exponential,
+}
+
+/// When a directory or volume is expected to reach a limit, by a fit through its
+/// recorded sizes, from `forecast`, `Tree::forecast` or `Tree::forecast_volume`.
+///
+/// The bounds come from the 95% confidence interval of the fitted growth rate: the
+/// faster rate gives the earliest day, the slower one the latest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Forecast {
+    /// The fit the forecast follows.
+    pub model: FitModel,
+    /// The latest recorded size.
+    pub current: u64,
+    /// When the latest size was recorded; the days count from then.
+    pub measured: SystemTime,
+    /// The size that counts as exhausted, e.g. a quota or the volume's capacity.
+    pub limit: u64,
+    /// How many bytes a day the size grows by at the latest sample, by the fit.
+    pub per_day: f64,
+    /// Days until the limit is reached by the fit: 0 if it was reached already, `None`
+    /// if the size is not growing.
+    pub days: Option<f64>,
+    /// The earliest the limit may be reached, in days; `None` if even the fastest rate
+    /// within the bounds is not growth.
+    pub earliest: Option<f64>,
+    /// The latest the limit may be reached, in days; `None` if growth may be stopping
+    /// altogether.
+    pub latest: Option<f64>,
+}
+
+impl Forecast {
+    /// When the limit is expected to be reached, by the fit; `None` if it is not.
+    pub fn exhausted_at(&self) -> Option<SystemTime> {
+        let days = self.days?;
+        self.measured
+            .checked_add(Duration::try_from_secs_f64(days * 86_400.0).ok()?)
+    }
+}
+
+/// Forecast when the size sampled by `samples`, oldest first as `Tree::size_history`
+/// returns them, reaches `limit` bytes, fitting `model` by least squares over time.
+/// `None` with fewer than three usable samples, or if they were all recorded at once.
+pub fn forecast(samples: &[SizeSample], limit: u64, model: FitModel) -> Option<Forecast> {
+    let last = samples.last()?;
+    let first = samples.first()?.time;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .filter(|sample| model == FitModel::Linear || sample.size > 0)
+        .map(|sample| {
+            let days = sample
+                .time
+                .duration_since(first)
+                .unwrap_or_default()
+                .as_secs_f64()
+                / 86_400.0;
+            let size = match model {
+                FitModel::Linear => sample.size as f64,
+                FitModel::Exponential => (sample.size as f64).ln(),
+            };
+            (days, size)
+        })
+        .collect();
+    let (rate, margin) = fit(&points)?;
+
+    let current = last.size;
+    let days_at = |rate: f64| -> Option<f64> {
+        if current >= limit {
+            return Some(0.0);
+        }
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(match model {
+            FitModel::Linear => (limit - current) as f64 / rate,
+            FitModel::Exponential if current == 0 => return None,
+            FitModel::Exponential => (limit as f64 / current as f64).ln() / rate,
+        })
+    };
+    Some(Forecast {
+        model,
+        current,
+        measured: last.time,
+        limit,
+        per_day: match model {
+            FitModel::Linear => rate,
+            FitModel::Exponential => rate * current as f64,
+        },
+        days: days_at(rate),
+        earliest: days_at(rate + margin),
+        latest: days_at(rate - margin),
+    })
+}
+
+/// The least-squares slope through `points` and the half-width of its 95% confidence
+/// interval. `None` with fewer than three points or no spread in time.
+fn fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let n = points.len();
+    if n < 3 {
+        return None;
+    }
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n as f64;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n as f64;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let residuals: f64 = points
+        .iter()
+        .map(|(x, y)| (y - intercept - slope * x).powi(2))
+        .sum();
+    let freedom = n - 2;
+    let error = (residuals / freedom as f64 / sxx).sqrt();
+    let t = T_975.get(freedom - 1).copied().unwrap_or(1.96);
+    Some((slope, t * error))
+}
+
+impl Tree {
+    /// Forecast when the directory at `path` reaches `limit` bytes, such as its quota,
+    /// from its recorded size history; see `track_size_history` and `forecast`.
+    pub fn forecast(&self, path: &Path, limit: u64, model: FitModel) -> Option<Forecast> {
+        forecast(self.size_history(path), limit, model)
+    }
+
+    /// Forecast when the volume the tree is on fills up at the rate the tree grows, from
+    /// the root's recorded size history and the space available on the volume now: the
+    /// limit is the latest recorded size plus the available space. This fits best when
+    /// the tree is all that grows on the volume.
+    #[cfg(all(feature = "statvfs", unix))]
+    pub fn forecast_volume(&self, model: FitModel) -> io::Result<Option<Forecast>> {
+        let history = self.size_history(&self.head.path);
+        let Some(last) = history.last() else {
+            return Ok(None);
+        };
+        let available = available_space(self.host_root())?;
+        Ok(forecast(
+            history,
+            last.size.saturating_add(available),
+            model,
+        ))
+    }
+}
+
+/// The bytes available to unprivileged users on the file system holding `path`.
+#[cfg(all(feature = "statvfs", unix))]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: statvfs is plain old data, for which all zeroes is a valid value.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid statvfs to fill in.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The field widths vary across platforms.
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
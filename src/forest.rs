@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::builder::TreeBuilder;
+use crate::diff::node_entries;
+use crate::event::{FsEvent, RescanPolicy, UpdateReport};
+use crate::node::{Node, NodeType};
+use crate::snapshot::SnapshotEntry;
+use crate::tree::Tree;
+
+/// Trees of several roots, each presented under a virtual prefix of its own, such as
+/// `/projects` for `/home/me/code` and `/media` for `/mnt/nas`. Lookups, searches and
+/// exports use these logical paths, so they stay the same wherever the roots happen to
+/// be mounted; moving a root only changes the directory it is scanned from.
+///
+/// Every tree works as one built with `Tree::new_chroot` does: refreshes read through
+/// to the root on disk, and events carry paths on disk and are translated when applied.
+#[derive(Default)]
+pub struct Forest {
+    roots: BTreeMap<PathBuf, Tree>,
+}
+
+impl Forest {
+    /// Create a forest without roots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan the root of `builder` and present it under `prefix`, returning the tree
+    /// previously there. Fails with `InvalidInput` if `prefix` is not absolute or is
+    /// above or below the prefix of another root.
+    pub fn mount(
+        &mut self,
+        prefix: impl Into<PathBuf>,
+        builder: TreeBuilder,
+    ) -> io::Result<Option<Tree>> {
+        let prefix = normalize(prefix.into())?;
+        if let Some(other) = self.roots.keys().find(|other| {
+            **other != prefix && (other.starts_with(&prefix) || prefix.starts_with(other))
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} overlaps {}", prefix.display(), other.display()),
+            ));
+        }
+        let mut tree = builder.build()?;
+        tree.present_at(&prefix);
+        Ok(self.roots.insert(prefix, tree))
+    }
+
+    /// Drop the root presented under `prefix`.
+    pub fn unmount(&mut self, prefix: &Path) -> Option<Tree> {
+        self.roots.remove(prefix)
+    }
+
+    /// The prefixes of the roots, in order.
+    pub fn prefixes(&self) -> impl Iterator<Item = &Path> {
+        self.roots.keys().map(PathBuf::as_path)
+    }
+
+    /// The tree presented under `prefix`.
+    pub fn tree(&self, prefix: &Path) -> Option<&Tree> {
+        self.roots.get(prefix)
+    }
+
+    /// The tree presented under `prefix`, e.g. to enable indexes on it.
+    pub fn tree_mut(&mut self, prefix: &Path) -> Option<&mut Tree> {
+        self.roots.get_mut(prefix)
+    }
+
+    /// The tree holding the logical `path`.
+    pub fn tree_for(&self, path: &Path) -> Option<&Tree> {
+        self.roots
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix))
+            .map(|(_, tree)| tree)
+    }
+
+    /// The entry at the logical `path`.
+    pub fn get_node(&self, path: &Path) -> Option<&Node> {
+        self.tree_for(path)?.get_node(path)
+    }
+
+    /// Where the logical `path` lives on disk, or `None` if no root holds it.
+    pub fn physical_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.tree_for(path)?.physical_path(path))
+    }
+
+    /// The logical path of a location on disk, or `None` if it is outside every root.
+    /// Where roots are nested on disk, the innermost one holding it wins.
+    pub fn logical_path(&self, physical: &Path) -> Option<PathBuf> {
+        self.roots
+            .values()
+            .filter(|tree| physical.starts_with(tree.host_root()))
+            .max_by_key(|tree| tree.host_root().components().count())?
+            .logical_path(physical)
+    }
+
+    /// Every entry of every root, root by root in prefix order.
+    pub fn iter(&self) -> impl Iterator<Item = &Node> {
+        self.roots.values().flat_map(Tree::iter)
+    }
+
+    /// Every entry of every root matching `predicate`.
+    pub fn search<F>(&self, predicate: F) -> Vec<&Node>
+    where
+        F: Fn(&Node) -> bool,
+    {
+        self.iter().filter(|node| predicate(node)).collect()
+    }
+
+    /// Combined size of every root.
+    pub fn total_size(&self) -> u64 {
+        self.roots.values().map(|tree| tree.head.size).sum()
+    }
+
+    /// Rescan every root from disk.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        for tree in self.roots.values_mut() {
+            tree.refresh()?;
+        }
+        Ok(())
+    }
+
+    /// Apply a batch of events carrying paths on disk to the roots they fall in, as
+    /// `Tree::apply_events` does, returning the report of each root that had any, keyed
+    /// by its prefix.
+    pub fn apply_events(
+        &mut self,
+        events: &[FsEvent],
+        policy: &RescanPolicy,
+    ) -> io::Result<Vec<(PathBuf, UpdateReport)>> {
+        let mut reports = Vec::new();
+        for (prefix, tree) in &mut self.roots {
+            if tree.logical_events(events).is_empty() {
+                continue;
+            }
+            reports.push((prefix.clone(), tree.apply_events(events, policy)?));
+        }
+        Ok(reports)
+    }
+
+    /// One tree holding every root at its prefix under a virtual root `/`, with the
+    /// directories between them made up, e.g. to take a snapshot or write a manifest of
+    /// the logical namespace. The result is a copy; later changes to the forest do not
+    /// affect it.
+    pub fn merged(&self) -> Tree {
+        let mut entries = Vec::new();
+        for (prefix, tree) in &self.roots {
+            let prefix = prefix.strip_prefix("/").unwrap_or(prefix).to_path_buf();
+            entries.push(SnapshotEntry {
+                path: prefix.clone(),
+                node_type: NodeType::Directory,
+                size: 0,
+                metadata: tree.head.metadata.clone(),
+            });
+            for (rel, entry) in node_entries(&tree.head) {
+                entries.push(SnapshotEntry {
+                    path: prefix.join(rel),
+                    node_type: entry.node_type.clone(),
+                    size: entry.size,
+                    metadata: entry.metadata.clone(),
+                });
+            }
+        }
+        Tree::from_entries(PathBuf::from("/"), entries)
+    }
+}
+
+/// `prefix` without `.` components or a trailing separator, refusing relative prefixes,
+/// `..` and the root itself, which would leave no room for other roots.
+fn normalize(prefix: PathBuf) -> io::Result<PathBuf> {
+    let invalid = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("prefix {} {}", prefix.display(), reason),
+        )
+    };
+    if !prefix.is_absolute() {
+        return Err(invalid("is not absolute"));
+    }
+    if prefix.components().any(|part| part == Component::ParentDir) {
+        return Err(invalid("contains .."));
+    }
+    let normalized: PathBuf = prefix.components().collect();
+    if normalized.parent().is_none() {
+        return Err(invalid("is the root"));
+    }
+    Ok(normalized)
+}
@@ -0,0 +1,95 @@
+//! Sizes for people to read, such as `1.4 GiB` or `3.2 MB`.
+//!
+//! Sizes below one unit are shown in bytes (`512 B`). Above, the largest unit that fits
+//! is used, with one decimal below 10 and none from there on, as `ls -h` and `du -h`
+//! show them: `1.4 GiB`, `12 MiB`, `640 KiB`.
+
+use std::fmt;
+
+/// Which units sizes are given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Units {
+    /// Powers of 1024: KiB, MiB, GiB and so on, as file managers on Linux and most
+    /// tools show sizes.
+    #[default]
+    Binary,
+    /// Powers of 1000: kB, MB, GB and so on, as drives are sold and macOS shows sizes.
+    Decimal,
+}
+
+impl Units {
+    fn base(self) -> f64 {
+        match self {
+            Units::Binary => 1024.0,
+            Units::Decimal => 1000.0,
+        }
+    }
+
+    fn symbols(self) -> [&'static str; 6] {
+        match self {
+            Units::Binary => ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+            Units::Decimal => ["kB", "MB", "GB", "TB", "PB", "EB"],
+        }
+    }
+}
+
+/// A size in bytes that displays in `units`, for use in `format!` without an
+/// intermediate string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumanSize {
+    /// The size.
+    pub bytes: u64,
+    /// The units to show it in.
+    pub units: Units,
+}
+
+impl HumanSize {
+    /// Display `bytes` in binary units.
+    pub fn new(bytes: u64) -> Self {
+        Self {
+            bytes,
+            units: Units::Binary,
+        }
+    }
+
+    /// Display in `units` instead.
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+impl fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let base = self.units.base();
+        let mut value = self.bytes as f64;
+        if value < base {
+            return write!(f, "{} B", self.bytes);
+        }
+        let symbols = self.units.symbols();
+        let mut unit = 0;
+        value /= base;
+        // Moves up a unit also when rounding would show a full one, e.g. 1023.97 KiB.
+        while unit + 1 < symbols.len() && rounded(value) >= base {
+            value /= base;
+            unit += 1;
+        }
+        match rounded(value) < 10.0 {
+            true => write!(f, "{:.1} {}", value, symbols[unit]),
+            false => write!(f, "{:.0} {}", value, symbols[unit]),
+        }
+    }
+}
+
+/// `value` as it is shown: to one decimal below 10, to a whole number from there on.
+fn rounded(value: f64) -> f64 {
+    match (value * 10.0).round() / 10.0 {
+        tenths if tenths < 10.0 => tenths,
+        _ => value.round(),
+    }
+}
+
+/// `bytes` as a size for people to read, in `units`.
+pub fn size(bytes: u64, units: Units) -> String {
+    HumanSize::new(bytes).units(units).to_string()
+}
@@ -0,0 +1,187 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::content::read_contents;
+use crate::options::ErrorPolicy;
+use crate::patterns::PathPatterns;
+use crate::resources::{Resources, Workers};
+use crate::tree::Tree;
+
+/// How many leading bytes are looked at for a NUL to tell binary files from text.
+const BINARY_PROBE: usize = 8000;
+
+/// What `Tree::grep` searches and how.
+#[derive(Debug, Clone, Default)]
+pub struct GrepOptions {
+    /// Match ASCII letters regardless of case.
+    pub ignore_case: bool,
+    /// Only search files matching these patterns, relative to the root; see
+    /// `PathPatterns`. Without patterns every file is searched.
+    pub files: PathPatterns,
+    /// Skip files larger than this many bytes, by their recorded size.
+    pub max_file_size: Option<u64>,
+    /// Stop searching a file after this many matching lines.
+    pub max_matches_per_file: Option<usize>,
+    /// Search files that look binary (a NUL among their first 8000 bytes) as well.
+    /// They are skipped by default.
+    pub binary: bool,
+    /// What to do with a file that cannot be read: fail the search, or leave it out.
+    pub errors: ErrorPolicy,
+}
+
+/// A line containing the pattern, found by `Tree::grep`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    /// The file's path in the tree.
+    pub path: PathBuf,
+    /// The line's number, counting from 1.
+    pub line_number: u64,
+    /// The line without its terminator, with invalid UTF-8 replaced.
+    pub line: String,
+}
+
+impl Tree {
+    /// Search the contents of the tree's files for lines containing `pattern`, a literal
+    /// string, and return them in tree order, and by line within a file.
+    ///
+    /// Files are read as they are on disk now, on up to `Resources::hash_threads`
+    /// threads, and mapped into memory from `Resources::mmap_threshold` bytes. Which
+    /// files there are and how large they are is what the tree recorded; a file removed
+    /// since the last refresh is an error like any unreadable one, see
+    /// `GrepOptions::errors`. Lines may end in `\n` or `\r\n`.
+    pub fn grep(&self, pattern: &str, options: &GrepOptions) -> io::Result<Vec<GrepMatch>> {
+        let files: Vec<(PathBuf, PathBuf)> = self
+            .iter()
+            .filter(|node| node.is_file())
+            .filter(|node| options.max_file_size.is_none_or(|max| node.size <= max))
+            .filter(|node| {
+                let rel = node
+                    .path
+                    .strip_prefix(&self.head.path)
+                    .unwrap_or(&node.path);
+                options.files.is_empty() || options.files.matches(rel)
+            })
+            .map(|node| (node.path.clone(), self.physical_path(&node.path)))
+            .collect();
+        let workers = Workers::new(self.resources.hash_threads, self.resources.numa)?;
+        let resources = self.resources;
+        let search = |(path, physical): (PathBuf, PathBuf)| {
+            let found = grep_file(&physical, pattern.as_bytes(), options, &resources);
+            match (found, options.errors) {
+                (Ok(lines), _) => Ok((path, lines)),
+                (Err(e), ErrorPolicy::Abort) => Err(e),
+                (Err(_), _) => Ok((path, Vec::new())),
+            }
+        };
+
+        let mut matches = Vec::new();
+        for batch in files.chunks(64) {
+            for (path, lines) in workers.map(batch.to_vec(), search)? {
+                matches.extend(lines.into_iter().map(|(line_number, line)| GrepMatch {
+                    path: path.clone(),
+                    line_number,
+                    line,
+                }));
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// The numbers and text of the lines of the file at `path` containing `needle`.
+fn grep_file(
+    path: &Path,
+    needle: &[u8],
+    options: &GrepOptions,
+    resources: &Resources,
+) -> io::Result<Vec<(u64, String)>> {
+    let mut lines = Lines {
+        needle,
+        options,
+        partial: Vec::new(),
+        line_number: 0,
+        matches: Vec::new(),
+        probed: options.binary,
+        done: false,
+    };
+    read_contents(path, resources, |bytes| {
+        lines.feed(bytes);
+        Ok(())
+    })?;
+    lines.finish();
+    Ok(lines.matches)
+}
+
+/// Splits a file's contents into lines as they arrive and collects the matching ones.
+struct Lines<'a> {
+    needle: &'a [u8],
+    options: &'a GrepOptions,
+    /// The start of a line continuing in the next piece.
+    partial: Vec<u8>,
+    line_number: u64,
+    matches: Vec<(u64, String)>,
+    /// Whether the file was checked for being binary, or need not be.
+    probed: bool,
+    /// Set once the file was found to be binary or has enough matches.
+    done: bool,
+}
+
+impl Lines<'_> {
+    fn feed(&mut self, mut bytes: &[u8]) {
+        if !self.probed {
+            self.probed = true;
+            self.done = bytes[..bytes.len().min(BINARY_PROBE)].contains(&0);
+        }
+        while !self.done {
+            let Some(end) = bytes.iter().position(|&byte| byte == b'\n') else {
+                self.partial.extend_from_slice(bytes);
+                return;
+            };
+            match self.partial.is_empty() {
+                true => self.line(&bytes[..end]),
+                false => {
+                    let mut line = std::mem::take(&mut self.partial);
+                    line.extend_from_slice(&bytes[..end]);
+                    self.line(&line);
+                    line.clear();
+                    self.partial = line;
+                }
+            }
+            bytes = &bytes[end + 1..];
+        }
+    }
+
+    /// Handles a last line without a terminator.
+    fn finish(&mut self) {
+        if !self.done && !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.line(&line);
+        }
+    }
+
+    fn line(&mut self, line: &[u8]) {
+        self.line_number += 1;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if !contains(line, self.needle, self.options.ignore_case) {
+            return;
+        }
+        self.matches
+            .push((self.line_number, String::from_utf8_lossy(line).into_owned()));
+        if let Some(max) = self.options.max_matches_per_file {
+            self.done = self.matches.len() >= max;
+        }
+    }
+}
+
+/// Whether `needle` occurs in `haystack`; an empty needle occurs everywhere.
+fn contains(haystack: &[u8], needle: &[u8], ignore_case: bool) -> bool {
+    if needle.is_empty() {
+        return true;
+    }
+    haystack
+        .windows(needle.len())
+        .any(|window| match ignore_case {
+            true => window.eq_ignore_ascii_case(needle),
+            false => window == needle,
+        })
+}
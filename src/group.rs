@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::civil_date;
+use crate::mime::extension_type;
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// How `Tree::group_view` arranges files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// By broad kind derived from the extension, e.g. "Images" or "Documents".
+    Kind,
+    /// By lowercased extension, e.g. "rs"; files without one go in "(none)".
+    Extension,
+    /// By how long ago the file was modified: "Today", "Past week", and so on.
+    Modified,
+    /// By size bucket, from "Empty" to "Huge (over 1 GiB)".
+    Size,
+    /// By the (UTC) month the file was modified, e.g. "2023-07", newest first.
+    Month,
+}
+
+/// A virtual grouping of a tree's files, independent of where they live on disk.
+#[derive(Debug, Clone)]
+pub struct GroupView<'a> {
+    /// Non-empty groups in presentation order: by name for kinds and extensions, and
+    /// from newest or smallest for dates, months and sizes.
+    pub groups: Vec<Group<'a>>,
+}
+
+/// One group of a `GroupView`.
+#[derive(Debug, Clone)]
+pub struct Group<'a> {
+    /// Display name of the group.
+    pub name: String,
+    /// The files in the group, in tree order.
+    pub files: Vec<&'a Node>,
+    /// Combined size of the files.
+    pub size: u64,
+}
+
+impl Tree {
+    /// Arrange every resident file into virtual groups, like a file browser's
+    /// "arrange by" option. Directories are not grouped.
+    pub fn group_view(&self, by: GroupBy) -> GroupView<'_> {
+        let now = self.clock.now();
+        // Keyed by (presentation order, name) so the groups come out in order.
+        let mut groups: BTreeMap<(usize, String), Group<'_>> = BTreeMap::new();
+        for node in self.iter().filter(|node| node.is_file()) {
+            let key = group_key(node, by, now);
+            let group = groups.entry(key.clone()).or_insert_with(|| Group {
+                name: key.1,
+                files: Vec::new(),
+                size: 0,
+            });
+            group.files.push(node);
+            group.size += node.size;
+        }
+        GroupView {
+            groups: groups.into_values().collect(),
+        }
+    }
+}
+
+fn group_key(node: &Node, by: GroupBy, now: SystemTime) -> (usize, String) {
+    let extension = lowercase_extension(node);
+    match by {
+        GroupBy::Kind => (0, kind_of(extension.as_deref()).to_string()),
+        GroupBy::Extension => (0, extension_name(node)),
+        GroupBy::Modified => {
+            const DAY: u64 = 24 * 60 * 60;
+            let buckets = [
+                (DAY, "Today"),
+                (7 * DAY, "Past week"),
+                (30 * DAY, "Past month"),
+                (365 * DAY, "Past year"),
+            ];
+            let Some(modified) = node.metadata.modified else {
+                return (buckets.len() + 1, "Unknown".to_string());
+            };
+            // Timestamps in the future count as modified just now.
+            let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+            let index = buckets
+                .iter()
+                .position(|(limit, _)| age < Duration::from_secs(*limit));
+            match index {
+                Some(index) => (index, buckets[index].1.to_string()),
+                None => (buckets.len(), "Older".to_string()),
+            }
+        }
+        GroupBy::Size => {
+            const KIB: u64 = 1024;
+            let buckets = [
+                (1, "Empty"),
+                (16 * KIB, "Tiny (under 16 KiB)"),
+                (KIB * KIB, "Small (under 1 MiB)"),
+                (128 * KIB * KIB, "Medium (under 128 MiB)"),
+                (KIB * KIB * KIB, "Large (under 1 GiB)"),
+            ];
+            let index = buckets.iter().position(|(limit, _)| node.size < *limit);
+            match index {
+                Some(index) => (index, buckets[index].1.to_string()),
+                None => (buckets.len(), "Huge (over 1 GiB)".to_string()),
+            }
+        }
+        GroupBy::Month => {
+            let month = node.metadata.modified.and_then(civil_date);
+            let Some(((year, month, _), _)) = month else {
+                return (usize::MAX, "Unknown".to_string());
+            };
+            // Later months sort first.
+            let order = usize::MAX / 2 - (year as usize * 12 + month as usize);
+            (order, format!("{year:04}-{month:02}"))
+        }
+    }
+}
+
+/// The lowercased extension of `node`'s name, if it has one.
+fn lowercase_extension(node: &Node) -> Option<String> {
+    node.path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// The lowercased extension of `node`'s name, or "(none)" without one.
+pub(crate) fn extension_name(node: &Node) -> String {
+    lowercase_extension(node).unwrap_or_else(|| "(none)".to_string())
+}
+
+/// The broad kind of a file with the given lowercased extension.
+fn kind_of(extension: Option<&str>) -> &'static str {
+    extension
+        .and_then(extension_type)
+        .map_or("Other", |(_, kind)| kind)
+}
@@ -0,0 +1,212 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::node::Node;
+use crate::patterns::PathPatterns;
+use crate::shred::RemoveMode;
+use crate::tree::Tree;
+
+/// Checks that stand between a delete plan and the disk, so that a cleanup never removes
+/// what it must not. A path is refused if the entry or anything below it trips a guard.
+/// Without guards every path in the tree may be deleted.
+#[derive(Debug, Clone, Default)]
+pub struct DeletionGuards {
+    protected: PathPatterns,
+    newer_than: Option<Duration>,
+    #[cfg(all(feature = "procfs", target_os = "linux"))]
+    open: bool,
+}
+
+/// Why a deletion was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockReason {
+    /// The path is not in the tree.
+    NotInTree,
+    /// The path is the tree's root, which is never deleted.
+    Root,
+    /// `entry` matches a protected pattern.
+    Protected { entry: PathBuf },
+    /// `entry` was modified at `modified`, more recently than the threshold allows.
+    TooRecent {
+        entry: PathBuf,
+        modified: SystemTime,
+    },
+    /// `entry` is held open by process `pid`.
+    Open {
+        entry: PathBuf,
+        pid: u32,
+        command: String,
+    },
+    /// What is below the directory `entry` is not known (it was evicted, never listed,
+    /// or beyond the scan depth), so the guards cannot be checked for it.
+    Unlisted { entry: PathBuf },
+}
+
+/// A deletion refused by `DeletionGuards`, with every reason found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedDeletion {
+    /// The path whose deletion was requested.
+    pub path: PathBuf,
+    /// What refused it, for the path itself and the entries below it.
+    pub reasons: Vec<BlockReason>,
+}
+
+/// What `Tree::delete_guarded` did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeletionOutcome {
+    /// Paths removed from disk and from the tree.
+    pub deleted: Vec<PathBuf>,
+    /// Paths left alone.
+    pub blocked: Vec<BlockedDeletion>,
+}
+
+impl DeletionGuards {
+    /// Create guards that refuse nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse to delete entries matching `pattern`, relative to the tree's root, such as
+    /// `.git/**` or `**/*.key`; see `PathPatterns`.
+    pub fn protect(mut self, pattern: &str) -> Self {
+        self.protected.add(pattern);
+        self
+    }
+
+    /// Refuse to delete entries modified less than `age` ago, by the tree's clock.
+    pub fn refuse_newer_than(mut self, age: Duration) -> Self {
+        self.newer_than = Some(age);
+        self
+    }
+
+    /// Refuse to delete files some process holds open; see `Tree::open_files`.
+    #[cfg(all(feature = "procfs", target_os = "linux"))]
+    pub fn refuse_open(mut self, refuse: bool) -> Self {
+        self.open = refuse;
+        self
+    }
+
+    /// Returns `true` if no guard was set.
+    pub fn is_empty(&self) -> bool {
+        #[cfg(all(feature = "procfs", target_os = "linux"))]
+        if self.open {
+            return false;
+        }
+        self.protected.is_empty() && self.newer_than.is_none()
+    }
+}
+
+impl Tree {
+    /// Check `paths` against `guards` without deleting anything, returning the ones that
+    /// would be refused, in the order given. Only the open-files guard reads from disk
+    /// (`/proc`); the others go by what the tree recorded, so refresh it first.
+    pub fn check_deletions(
+        &self,
+        paths: &[PathBuf],
+        guards: &DeletionGuards,
+    ) -> io::Result<Vec<BlockedDeletion>> {
+        #[cfg(all(feature = "procfs", target_os = "linux"))]
+        let open = match guards.open {
+            true => self.open_files()?,
+            false => Vec::new(),
+        };
+        let now = self.clock.now();
+
+        let mut blocked = Vec::new();
+        for path in paths {
+            let mut reasons = Vec::new();
+            match self.get_node(path) {
+                Some(node) if node.path == self.head.path => reasons.push(BlockReason::Root),
+                Some(node) => self.walk_guarded(node, guards, now, &mut reasons),
+                None => reasons.push(BlockReason::NotInTree),
+            }
+            #[cfg(all(feature = "procfs", target_os = "linux"))]
+            reasons.extend(
+                open.iter()
+                    .filter(|file| file.path.starts_with(path))
+                    .map(|file| BlockReason::Open {
+                        entry: file.path.clone(),
+                        pid: file.pid,
+                        command: file.command.clone(),
+                    }),
+            );
+            if !reasons.is_empty() {
+                blocked.push(BlockedDeletion {
+                    path: path.clone(),
+                    reasons,
+                });
+            }
+        }
+        Ok(blocked)
+    }
+
+    /// Delete the `paths` that pass `guards` from disk, directories with everything in
+    /// them, and update the tree. All paths are checked before the first is deleted.
+    /// `mode` chooses whether file contents are overwritten first; see `RemoveMode`.
+    ///
+    /// The first failure stops the deletions and is returned; what was deleted before
+    /// it stays deleted and is reflected in the tree.
+    pub fn delete_guarded(
+        &mut self,
+        paths: &[PathBuf],
+        guards: &DeletionGuards,
+        mode: RemoveMode,
+    ) -> io::Result<DeletionOutcome> {
+        let blocked = self.check_deletions(paths, guards)?;
+        let mut deleted: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            let refused = blocked.iter().any(|blocked| blocked.path == *path);
+            let done = deleted.iter().any(|parent| path.starts_with(parent));
+            if refused || done {
+                continue;
+            }
+            mode.remove(&self.physical_path(path))?;
+            self.refresh_path(path)?;
+            deleted.push(path.clone());
+        }
+        Ok(DeletionOutcome { deleted, blocked })
+    }
+
+    /// Collects the reasons `node` and everything below it may not be deleted for.
+    fn walk_guarded(
+        &self,
+        node: &Node,
+        guards: &DeletionGuards,
+        now: SystemTime,
+        reasons: &mut Vec<BlockReason>,
+    ) {
+        let rel = node
+            .path
+            .strip_prefix(&self.head.path)
+            .unwrap_or(Path::new(""));
+        if !guards.protected.is_empty() && guards.protected.matches(rel) {
+            reasons.push(BlockReason::Protected {
+                entry: node.path.clone(),
+            });
+        }
+        if let (Some(age), Some(modified)) = (guards.newer_than, node.metadata.modified) {
+            // Directories change with their contents, which are checked themselves.
+            let recent = now
+                .duration_since(modified)
+                .map_or(true, |since| since < age);
+            if !node.is_dir() && recent {
+                reasons.push(BlockReason::TooRecent {
+                    entry: node.path.clone(),
+                    modified,
+                });
+            }
+        }
+        match &node.children {
+            Some(children) => {
+                for child in children {
+                    self.walk_guarded(child, guards, now, reasons);
+                }
+            }
+            None if node.is_dir() && !guards.is_empty() => reasons.push(BlockReason::Unlisted {
+                entry: node.path.clone(),
+            }),
+            None => {}
+        }
+    }
+}
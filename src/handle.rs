@@ -0,0 +1,173 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// A handle to a node that does not borrow the tree.
+///
+/// Handles identify a node by its path relative to the tree's root, so they can be
+/// sent across threads and kept across refreshes: looking one up again finds whatever
+/// is at that path now, or nothing if the entry has vanished. `Tree::resolve` also
+/// checks that the entry has not changed since the handle was obtained.
+///
+/// Two handles are equal if they refer to the same path, whichever generation they
+/// were obtained in, so they can serve as stable row identities.
+#[derive(Debug, Clone)]
+pub struct NodeId {
+    rel: PathBuf,
+    generation: u64,
+}
+
+impl PartialEq for NodeId {
+    fn eq(&self, other: &Self) -> bool {
+        self.rel == other.rel
+    }
+}
+
+impl Eq for NodeId {}
+
+impl PartialOrd for NodeId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NodeId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rel.cmp(&other.rel)
+    }
+}
+
+impl Hash for NodeId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rel.hash(state);
+    }
+}
+
+/// Why a `NodeId` no longer refers to the entry it was obtained for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stale {
+    /// Nothing is at the handle's path any more.
+    Vanished,
+    /// An entry is still at the path, but it changed after the handle was obtained.
+    /// Directories count as changed when anything below them changed. Entries that
+    /// were evicted and rescanned also count as changed.
+    Changed,
+}
+
+impl fmt::Display for Stale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stale::Vanished => write!(f, "entry no longer exists"),
+            Stale::Changed => write!(f, "entry changed since the handle was obtained"),
+        }
+    }
+}
+
+impl NodeId {
+    /// The node's path relative to the tree's root. Empty for the root itself.
+    pub fn relative_path(&self) -> &Path {
+        &self.rel
+    }
+
+    /// A handle for a path that is no longer in the tree.
+    pub(crate) fn detached(rel: PathBuf) -> Self {
+        Self { rel, generation: 0 }
+    }
+}
+
+impl Tree {
+    /// A handle to the node at `path`, if it is in the tree.
+    pub fn handle(&self, path: &Path) -> Option<NodeId> {
+        let node = self.get_node(path)?;
+        self.id_for(node)
+    }
+
+    /// Look up the node a handle refers to, failing if it vanished or changed since the
+    /// handle was obtained. Call `handle` again to re-acquire a changed entry.
+    pub fn resolve(&self, id: &NodeId) -> Result<&Node, Stale> {
+        let node = self.node(id).ok_or(Stale::Vanished)?;
+        if node.generation == id.generation {
+            Ok(node)
+        } else {
+            Err(Stale::Changed)
+        }
+    }
+
+    /// Look up whatever node is at a handle's path now, or `None` if nothing is.
+    pub fn node(&self, id: &NodeId) -> Option<&Node> {
+        self.get_node(&self.path_of(id))
+    }
+
+    /// The absolute path a handle refers to, whether or not it is still in the tree.
+    pub fn path_of(&self, id: &NodeId) -> PathBuf {
+        self.head.path.join(&id.rel)
+    }
+
+    /// Like `search`, but returns handles that outlive the borrow of the tree.
+    pub fn search_ids<F>(&self, predicate: F) -> Vec<NodeId>
+    where
+        F: Fn(&Node) -> bool,
+    {
+        self.iter()
+            .filter(|node| predicate(node))
+            .filter_map(|node| self.id_for(node))
+            .collect()
+    }
+
+    pub(crate) fn id_for(&self, node: &Node) -> Option<NodeId> {
+        let rel = node.path.strip_prefix(&self.head.path).ok()?;
+        Some(NodeId {
+            rel: rel.to_path_buf(),
+            generation: node.generation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+
+    use super::Stale;
+    use crate::builder::TreeBuilder;
+    use crate::testing::empty_dir;
+
+    /// Rewrites the file at `path` with contents of the same size and a new mtime.
+    fn rewrite_same_size(path: &std::path::Path) {
+        fs::write(path, "bbbb").unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+    }
+
+    #[test]
+    fn same_size_edit_makes_ancestors_stale() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir_all(dir.root.join("a/b")).unwrap();
+        fs::create_dir_all(dir.root.join("other")).unwrap();
+        fs::write(dir.root.join("a/b/file.txt"), "aaaa").unwrap();
+        fs::write(dir.root.join("other/file.txt"), "aaaa").unwrap();
+
+        for whole in [false, true] {
+            let mut tree = TreeBuilder::new(&dir.root).build().unwrap();
+            let handles = ["", "a", "a/b", "a/b/file.txt", "other"]
+                .map(|rel| tree.handle(&dir.root.join(rel)).unwrap());
+            rewrite_same_size(&dir.root.join("a/b/file.txt"));
+            match whole {
+                true => tree.refresh().unwrap(),
+                false => tree.refresh_path(&dir.root.join("a/b/file.txt")).unwrap(),
+            }
+            for id in &handles[..4] {
+                assert_eq!(tree.resolve(id).err(), Some(Stale::Changed), "{id:?}");
+            }
+            assert!(tree.resolve(&handles[4]).is_ok());
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::node::Node;
+use crate::options::SizeMetric;
+use crate::tree::Tree;
+
+impl Node {
+    /// Returns `true` if this is a file with other hard links to it, inside the tree or
+    /// not, by the link count recorded on Unix. Always `false` elsewhere.
+    pub fn is_hardlinked(&self) -> bool {
+        self.is_file() && self.metadata.nlink.is_some_and(|links| links > 1)
+    }
+
+    /// Sums directory sizes below and including this node, counting a hard-linked file
+    /// only for the first of its links found, in tree order; `seen` holds the files
+    /// counted so far. Directories add their own size under `metric`. Returns what this
+    /// node adds to its parent's size.
+    pub(crate) fn sum_sizes_once(
+        &mut self,
+        metric: SizeMetric,
+        seen: &mut HashSet<(u64, u64)>,
+    ) -> u64 {
+        if let Some(children) = &mut self.children {
+            let below: u64 = children
+                .iter_mut()
+                .map(|child| child.sum_sizes_once(metric, seen))
+                .sum();
+            self.size = self.own_size(metric) + below;
+        }
+        match counted(self, seen) {
+            true => self.size,
+            false => 0,
+        }
+    }
+}
+
+/// Whether `node`'s size counts towards its directory's: `false` for a hard-linked file
+/// in `seen` already, which is added to `seen` otherwise.
+pub(crate) fn counted(node: &Node, seen: &mut HashSet<(u64, u64)>) -> bool {
+    if !node.is_hardlinked() {
+        return true;
+    }
+    match (node.metadata.device, node.metadata.inode) {
+        (Some(device), Some(inode)) => seen.insert((device, inode)),
+        _ => true,
+    }
+}
+
+impl Tree {
+    /// The files with more than one hard link in the tree, grouped by the file they
+    /// link to: each group's links in path order, the groups in tree order of their
+    /// first link. Links outside the tree, or in evicted subtrees, are not listed.
+    pub fn hardlink_groups(&self) -> Vec<Vec<&Node>> {
+        let mut groups: Vec<Vec<&Node>> = Vec::new();
+        let mut by_id: HashMap<(u64, u64), usize> = HashMap::new();
+        for node in self.iter().filter(|node| node.is_hardlinked()) {
+            let (Some(device), Some(inode)) = (node.metadata.device, node.metadata.inode) else {
+                continue;
+            };
+            let index = *by_id.entry((device, inode)).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[index].push(node);
+        }
+        groups.retain(|group| group.len() > 1);
+        for group in &mut groups {
+            group.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        groups
+    }
+
+    /// Sums the directory sizes again, counting each hard-linked file once, if the tree
+    /// was built to; see `TreeBuilder::count_hardlinks_once`.
+    pub(crate) fn recount_hardlinks(&mut self) {
+        if self.options.hardlinks_once {
+            self.head
+                .sum_sizes_once(self.options.size_metric, &mut HashSet::new());
+        }
+    }
+}
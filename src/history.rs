@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::node::NodeType;
+use crate::snapshot::Snapshot;
+use crate::tree::Tree;
+
+/// A directory's size at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeSample {
+    /// When the size was recorded, by the tree's clock or the snapshot's time.
+    pub time: SystemTime,
+    /// The directory's size then.
+    pub size: u64,
+}
+
+/// How a directory's size changed over a window, from `Tree::growth` and
+/// `Tree::fastest_growing`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Growth {
+    /// The directory's path in the tree.
+    pub path: PathBuf,
+    /// The sample at the start of the window: the last one recorded before it began,
+    /// or the first one within it.
+    pub from: SizeSample,
+    /// The latest sample.
+    pub to: SizeSample,
+}
+
+impl Growth {
+    /// How many bytes the directory grew by; negative if it shrank.
+    pub fn change(&self) -> i64 {
+        self.to.size as i64 - self.from.size as i64
+    }
+
+    /// The average growth in bytes per day between the two samples; 0 if they were
+    /// taken at the same time.
+    pub fn per_day(&self) -> f64 {
+        let elapsed = self
+            .to
+            .time
+            .duration_since(self.from.time)
+            .unwrap_or_default();
+        match elapsed.is_zero() {
+            true => 0.0,
+            false => self.change() as f64 * 86_400.0 / elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// Directory sizes over time, recorded by `Tree::track_size_history`.
+#[derive(Debug, Clone)]
+pub(crate) struct SizeHistory {
+    interval: Duration,
+    retention: Duration,
+    /// When the tree's sizes were last recorded.
+    last: Option<SystemTime>,
+    /// Each directory's samples, oldest first.
+    series: BTreeMap<PathBuf, Vec<SizeSample>>,
+}
+
+impl SizeHistory {
+    fn insert(&mut self, path: PathBuf, sample: SizeSample) {
+        let series = self.series.entry(path).or_default();
+        let at = series.partition_point(|earlier| earlier.time <= sample.time);
+        series.insert(at, sample);
+    }
+
+    /// Drops the samples older than the retention allows, and directories left without.
+    fn expire(&mut self, now: SystemTime) {
+        let retention = self.retention;
+        let expired = |sample: &SizeSample| {
+            now.duration_since(sample.time)
+                .is_ok_and(|age| age > retention)
+        };
+        for series in self.series.values_mut() {
+            series.retain(|sample| !expired(sample));
+        }
+        self.series.retain(|_, series| !series.is_empty());
+    }
+
+    /// Estimated bytes held by the history.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let entry = size_of::<PathBuf>() + size_of::<Vec<SizeSample>>();
+        let paths: usize = self.series.keys().map(PathBuf::capacity).sum();
+        let samples: usize = self.series.values().map(Vec::capacity).sum();
+        self.series.len() * entry + paths + samples * size_of::<SizeSample>()
+    }
+}
+
+impl Tree {
+    /// Start recording the size of every directory over time, at most once per
+    /// `interval` by the tree's clock, keeping samples for `retention`. Sizes are
+    /// recorded now, and then as refreshes and applied events come in once the interval
+    /// has passed; call `record_sizes` to add a sample at a time of one's choosing, and
+    /// `record_snapshot` to fill in the past from snapshots.
+    ///
+    /// Every resident directory gets a sample each time, so the history grows with the
+    /// number of directories times the samples retained.
+    pub fn track_size_history(&mut self, interval: Duration, retention: Duration) {
+        match &mut self.history {
+            Some(history) => {
+                history.interval = interval;
+                history.retention = retention;
+            }
+            None => {
+                self.history = Some(SizeHistory {
+                    interval,
+                    retention,
+                    last: None,
+                    series: BTreeMap::new(),
+                });
+                self.record_sizes();
+            }
+        }
+    }
+
+    /// Record the current size of every resident directory, whether the interval has
+    /// passed or not. Does nothing unless `track_size_history` was called.
+    pub fn record_sizes(&mut self) {
+        let Some(mut history) = self.history.take() else {
+            return;
+        };
+        let now = self.clock.now();
+        for node in self.iter().filter(|node| node.is_dir()) {
+            let sample = SizeSample {
+                time: now,
+                size: node.size,
+            };
+            history.insert(node.path.clone(), sample);
+        }
+        history.last = Some(now);
+        history.expire(now);
+        self.history = Some(history);
+    }
+
+    /// Add the directory sizes recorded in `snapshot`, at the time it was taken, to the
+    /// history, with its entries placed below the tree's root. Samples older than the
+    /// retention are not kept. Does nothing unless `track_size_history` was called.
+    pub fn record_snapshot(&mut self, snapshot: &Snapshot) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        let directories = snapshot
+            .entries
+            .iter()
+            .filter(|entry| entry.node_type == NodeType::Directory);
+        for entry in directories {
+            let sample = SizeSample {
+                time: snapshot.taken,
+                size: entry.size,
+            };
+            history.insert(self.head.path.join(&entry.path), sample);
+        }
+        history.expire(self.clock.now());
+    }
+
+    /// The recorded sizes of the directory at `path`, oldest first; empty if none were
+    /// recorded. A directory that was removed keeps its samples until they expire.
+    pub fn size_history(&self, path: &Path) -> &[SizeSample] {
+        self.history
+            .as_ref()
+            .and_then(|history| history.series.get(path))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// How the directory at `path` grew over the last `window`, by its recorded sizes,
+    /// or `None` with fewer than two samples to compare.
+    pub fn growth(&self, path: &Path, window: Duration) -> Option<Growth> {
+        let start = self.clock.now().checked_sub(window)?;
+        growth_in(path, self.size_history(path), start)
+    }
+
+    /// The `n` directories that grew the most over the last `window`, e.g. 30 days for
+    /// "this month", by their recorded sizes, largest growth first. Directories that
+    /// shrank or stayed the same are left out.
+    pub fn fastest_growing(&self, window: Duration, n: usize) -> Vec<Growth> {
+        let (Some(history), Some(start)) = (&self.history, self.clock.now().checked_sub(window))
+        else {
+            return Vec::new();
+        };
+        let mut growing: Vec<Growth> = history
+            .series
+            .iter()
+            .filter_map(|(path, series)| growth_in(path, series, start))
+            .filter(|growth| growth.change() > 0)
+            .collect();
+        growing.sort_by(|a, b| {
+            b.change()
+                .cmp(&a.change())
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        growing.truncate(n);
+        growing
+    }
+
+    /// Records the directory sizes if tracked and the interval has passed, after a
+    /// rescan.
+    pub(crate) fn sample_sizes(&mut self) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let now = self.clock.now();
+        let due = history.last.is_none_or(|last| {
+            now.duration_since(last)
+                .is_ok_and(|since| since >= history.interval)
+        });
+        if due {
+            self.record_sizes();
+        }
+    }
+}
+
+/// The growth shown by `series`, samples of the directory at `path`, since `start`.
+fn growth_in(path: &Path, series: &[SizeSample], start: SystemTime) -> Option<Growth> {
+    let to = *series.last()?;
+    let before = series.partition_point(|sample| sample.time < start);
+    let from = *series.get(before.saturating_sub(1))?;
+    if from.time >= to.time {
+        return None;
+    }
+    Some(Growth {
+        path: path.to_path_buf(),
+        from,
+        to,
+    })
+}
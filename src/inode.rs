@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+use crate::node::Node;
+use crate::tree::{descendants, Tree};
+
+/// Paths by device and inode number, kept up to date as the tree is refreshed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InodeIndex {
+    by_id: HashMap<(u64, u64), BTreeSet<PathBuf>>,
+    by_path: BTreeMap<PathBuf, (u64, u64)>,
+}
+
+impl InodeIndex {
+    fn insert_subtree(&mut self, node: &Node) {
+        if let Some(id) = entry_id(node) {
+            self.by_id.entry(id).or_default().insert(node.path.clone());
+            self.by_path.insert(node.path.clone(), id);
+        }
+        for child in node.children.iter().flatten() {
+            self.insert_subtree(child);
+        }
+    }
+
+    fn remove_subtree(&mut self, path: &Path) {
+        let doomed: Vec<PathBuf> = descendants(&self.by_path, path).cloned().collect();
+        for entry in doomed {
+            let Some(id) = self.by_path.remove(&entry) else {
+                continue;
+            };
+            if let Some(paths) = self.by_id.get_mut(&id) {
+                paths.remove(&entry);
+                if paths.is_empty() {
+                    self.by_id.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Estimated bytes held by the index.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let entry = size_of::<PathBuf>() + size_of::<(u64, u64)>();
+        let paths: usize = self.by_path.keys().map(PathBuf::capacity).sum();
+        self.by_path.len() * entry * 2 + paths * 2
+    }
+}
+
+impl Tree {
+    /// Start maintaining an index of entries by device and inode number, so that
+    /// `find_by_inode` can map what `lsof`, audit logs or fanotify report back to paths.
+    /// The index is updated on every refresh and applied event; entries in evicted
+    /// subtrees are left out of the results.
+    ///
+    /// Inode numbers are recorded while scanning on Unix only; elsewhere, and for trees
+    /// read from manifests, the index stays empty.
+    pub fn track_inodes(&mut self) {
+        let mut index = InodeIndex::default();
+        index.insert_subtree(&self.head);
+        self.inodes = Some(index);
+    }
+
+    /// The entries with inode `inode` on device `device`: several for a file with hard
+    /// links, in path order. Without `track_inodes`, this walks the tree.
+    pub fn find_by_inode(&self, device: u64, inode: u64) -> Vec<&Node> {
+        if let Some(index) = &self.inodes {
+            return index
+                .by_id
+                .get(&(device, inode))
+                .into_iter()
+                .flatten()
+                .filter_map(|path| self.get_node(path))
+                .collect();
+        }
+
+        let mut found: Vec<&Node> = self
+            .iter()
+            .filter(|node| entry_id(node) == Some((device, inode)))
+            .collect();
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        found
+    }
+
+    /// Bring the inode index, if any, up to date after `path` was rescanned.
+    pub(crate) fn reindex_inodes(&mut self, path: &Path) {
+        if let Some(mut index) = self.inodes.take() {
+            index.remove_subtree(path);
+            if let Some(node) = self.get_node(path) {
+                index.insert_subtree(node);
+            }
+            self.inodes = Some(index);
+        }
+    }
+}
+
+/// The device and inode number recorded for `node`, if both were.
+fn entry_id(node: &Node) -> Option<(u64, u64)> {
+    Some((node.metadata.device?, node.metadata.inode?))
+}
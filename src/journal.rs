@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+
+use crate::event::FsEvent;
+
+/// A change together with its position in a `ChangeJournal`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Sequence number, starting at 1 and increasing by one for every recorded change.
+    pub seq: u64,
+    /// The change itself.
+    pub event: FsEvent,
+}
+
+/// The most recent changes, numbered so that a consumer can note how far it got and later
+/// pick up exactly where it left off.
+#[derive(Debug, Clone)]
+pub struct ChangeJournal {
+    entries: VecDeque<JournalEntry>,
+    capacity: usize,
+    /// Sequence number of the newest entry dropped to stay within `capacity`.
+    dropped: u64,
+    last: u64,
+}
+
+impl ChangeJournal {
+    /// Create a journal keeping at most `capacity` changes; older ones are dropped.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            dropped: 0,
+            last: 0,
+        }
+    }
+
+    /// Append `event`, returning the entry it was recorded as.
+    pub fn record(&mut self, event: FsEvent) -> &JournalEntry {
+        if self.entries.len() == self.capacity {
+            if let Some(oldest) = self.entries.pop_front() {
+                self.dropped = oldest.seq;
+            }
+        }
+        self.last += 1;
+        self.entries.push_back(JournalEntry {
+            seq: self.last,
+            event,
+        });
+        &self.entries[self.entries.len() - 1]
+    }
+
+    /// Sequence number of the latest change, or 0 if none was recorded yet.
+    pub fn last_seq(&self) -> u64 {
+        self.last
+    }
+
+    /// Every change after `seq`, oldest first. Returns `None` if some of them were already
+    /// dropped, or if `seq` lies beyond the latest change (e.g. it was handed out by another
+    /// journal), in which case the consumer has to resynchronise from scratch.
+    pub fn since(&self, seq: u64) -> Option<impl Iterator<Item = &JournalEntry>> {
+        if seq < self.dropped || seq > self.last {
+            return None;
+        }
+        let skip = (seq - self.dropped) as usize;
+        Some(self.entries.iter().skip(skip))
+    }
+}
@@ -0,0 +1,87 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::builder::TreeBuilder;
+use crate::node::Node;
+use crate::tree::Tree;
+
+impl Tree {
+    /// Create a tree rooted at `root` that only lists the root directory. Deeper
+    /// directories are listed the first time they are reached through `load` or `access`,
+    /// which makes opening `/` or a large network mount cheap.
+    ///
+    /// Until a directory is listed its size counts as 0, so directory sizes cover what was
+    /// loaded so far. Refreshes list again only the directories that were already listed,
+    /// and events below a directory that was never listed are ignored: it is read as it
+    /// is on disk once listed.
+    pub fn new_lazy(root: &Path) -> io::Result<Self> {
+        TreeBuilder::new(root).lazy(true).build()
+    }
+
+    /// A lazy tree rooted at `root` with only the root listed, for `TreeBuilder::lazy`.
+    pub(crate) fn build_lazy(root: &Path, include_hidden: bool) -> io::Result<Self> {
+        let mut head = Node::new_lazy(root.to_path_buf())?;
+        head.load_children_from(root, include_hidden)?;
+        Ok(Self::from_head(head))
+    }
+
+    /// Look up the node at `path`, listing every directory on the way to it, and the
+    /// node itself if it is a directory, that was not listed yet.
+    pub fn load(&mut self, path: &Path) -> io::Result<&Node> {
+        if !path.starts_with(&self.head.path) {
+            return Err(not_in_tree(path));
+        }
+        let mut chain: Vec<PathBuf> = path
+            .ancestors()
+            .filter(|dir| dir.starts_with(&self.head.path))
+            .map(Path::to_path_buf)
+            .collect();
+        chain.reverse();
+        for dir in &chain {
+            self.load_dir(dir)?;
+        }
+        self.get_node(path).ok_or_else(|| not_in_tree(path))
+    }
+
+    /// Read the complete subtree at `path` from disk, however much of it was listed.
+    pub fn expand(&mut self, path: &Path) -> io::Result<&Node> {
+        self.load(path)?;
+        let lazy = std::mem::replace(&mut self.options.lazy, false);
+        let refreshed = self.refresh_path(path);
+        self.options.lazy = lazy;
+        refreshed?;
+        self.get_node(path).ok_or_else(|| not_in_tree(path))
+    }
+
+    /// Lists the directory at `dir` if it was never listed, adding the sizes of what it
+    /// holds to its ancestors.
+    fn load_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let physical = self.physical_path(dir);
+        let include_hidden = self.options.include_hidden;
+        let Some(node) = self.get_node_mut(dir) else {
+            return Ok(());
+        };
+        if !node.needs_load() {
+            return Ok(());
+        }
+        node.load_children_from(&physical, include_hidden)?;
+        let added = node.size;
+        for ancestor in dir.ancestors().skip(1) {
+            if !ancestor.starts_with(&self.head.path) {
+                break;
+            }
+            if let Some(node) = self.get_node_mut(ancestor) {
+                node.size += added;
+            }
+        }
+        self.rescanned(dir, None);
+        Ok(())
+    }
+}
+
+fn not_in_tree(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not in the tree", path.display()),
+    )
+}
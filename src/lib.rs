@@ -1,5 +1,186 @@
+//! The core (scanning, search, diff, snapshots, manifests) only uses `std::fs`, so it
+//! builds on Linux, macOS and Windows. Where platforms differ (inodes and devices,
+//! permissions, hidden files), Unix semantics are used on Unix and the closest
+//! equivalent elsewhere.
+//! Watching needs a native notification backend and is behind the `watch` feature;
+//! the daemon additionally needs Unix domain sockets, `statvfs` (free space on volumes)
+//! needs Unix, and `procfs` (open-file correlation, access-time reliability), `numa`
+//! (worker pools pinned to NUMA nodes) and `fadvise` (page-cache hints) need Linux.
+
+#[cfg(all(feature = "daemon", not(unix)))]
+compile_error!("the `daemon` feature needs Unix domain sockets");
+#[cfg(all(feature = "procfs", not(target_os = "linux")))]
+compile_error!("the `procfs` feature needs Linux's /proc file system");
+#[cfg(all(feature = "numa", not(target_os = "linux")))]
+compile_error!("the `numa` feature needs Linux's CPU affinity and sysfs topology");
+#[cfg(all(feature = "fadvise", not(target_os = "linux")))]
+compile_error!("the `fadvise` feature needs Linux's page-cache hints");
+#[cfg(all(feature = "statvfs", not(unix)))]
+compile_error!("the `statvfs` feature needs Unix's statvfs");
+
+pub mod bench;
+#[cfg(all(feature = "daemon", unix))]
+pub mod daemon;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+#[cfg(feature = "python")]
+mod python;
+pub mod testing;
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+#[cfg(all(feature = "procfs", target_os = "linux"))]
+mod atime;
+mod batch;
+mod bloom;
+mod bookmark;
+mod builder;
+mod catalog;
+mod checksum;
+mod chroot;
+mod classify;
+mod clock;
+mod codec;
+mod completeness;
+mod config;
+mod content;
+mod dedup;
+mod delta;
+mod diff;
+#[cfg(all(feature = "dirfd", unix))]
+mod dirfd;
+mod duplicates;
+mod enrich;
+mod event;
+mod eviction;
+#[cfg(all(feature = "fadvise", target_os = "linux"))]
+mod fadvise;
+mod fanout;
+mod farm;
+mod federation;
+mod fingerprint;
+mod footprint;
+mod forecast;
+mod forest;
+mod grep;
+mod group;
+mod guard;
+mod handle;
+mod hardlink;
+mod history;
+mod inode;
+mod journal;
+mod lazy;
+mod manifest;
+mod mime;
+mod model;
+mod mtree;
+mod navigate;
 mod node;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+mod numa;
+mod oci;
+mod options;
+#[cfg(all(feature = "overlay", unix))]
+mod overlay;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod patterns;
+mod persist;
+mod platform;
+mod priority;
+#[cfg(all(feature = "dirfd", unix))]
+mod privilege;
+#[cfg(all(feature = "procfs", target_os = "linux"))]
+mod procfs;
+mod progress;
+mod query;
+mod recent;
+mod reconcile;
+mod reconfigure;
+mod report;
+#[cfg(feature = "regex")]
+mod regex_search;
+mod resources;
+mod selection;
+#[cfg(feature = "serde")]
+mod serialize;
+mod shred;
+mod sink;
+mod snapshot;
+mod source;
+mod stats;
+#[cfg(feature = "store")]
+mod store;
+mod tombstone;
+mod transform;
 mod tree;
+mod usage;
+mod validate;
+#[cfg(feature = "watch")]
+mod watcher;
 
+#[cfg(feature = "zip")]
+pub use archive::ZipCompression;
+#[cfg(all(feature = "procfs", target_os = "linux"))]
+pub use atime::{AtimePolicy, Confidence, MountAtime, UnusedEntry, UnusedReport};
+pub use batch::DeltaBatcher;
+pub use bloom::PathFilter;
+pub use bookmark::Bookmark;
+pub use builder::TreeBuilder;
+pub use catalog::{Catalog, PathState};
+pub use checksum::{Checksum, HashAlgorithm};
+pub use classify::ClassifiedDiff;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use completeness::QueryResult;
+pub use config::ScanConfig;
+pub use dedup::{ChunkingOptions, DedupStats};
+pub use delta::SnapshotDelta;
+pub use diff::{diff, ComparePolicy, DiffChange, Modification, TreeDiff};
+pub use duplicates::DuplicateGroup;
+pub use enrich::Enrichment;
+pub use event::{FsEvent, PriorityLanes, RescanPolicy, UpdateReport, UpdateStrategy};
+pub use eviction::EvictionPolicy;
+pub use fanout::{Fanout, Overflow, Subscriber};
+pub use farm::SymlinkFarm;
+pub use federation::{Federation, Location};
+pub use fingerprint::FingerprintFields;
+pub use footprint::MemoryFootprint;
+pub use forecast::{forecast, FitModel, Forecast};
+pub use forest::Forest;
+pub use grep::{GrepMatch, GrepOptions};
+pub use group::{Group, GroupBy, GroupView};
+pub use guard::{BlockReason, BlockedDeletion, DeletionGuards, DeletionOutcome};
+pub use handle::{NodeId, Stale};
+pub use history::{Growth, SizeSample};
+pub use journal::{ChangeJournal, JournalEntry};
+pub use manifest::ManifestFormat;
+pub use model::{ModelChange, TreeModel};
 pub use node::{Node, NodeType, ExtendedMetadata};
-pub use tree::Tree;
+pub use oci::{analyze_layers, analyze_layers_with, ImageAnalysis, LayerReport};
+pub use options::{ErrorPolicy, ScanOptions, SizeMetric, SortOrder};
+pub use patterns::PathPatterns;
+pub use priority::{ScanBudget, ScanPriority};
+#[cfg(all(feature = "dirfd", unix))]
+pub use privilege::{PrivilegedRoot, ReducedRoot};
+#[cfg(all(feature = "procfs", target_os = "linux"))]
+pub use procfs::OpenFile;
+pub use progress::{ProgressReporter, ScanProgress};
+pub use query::{LiveQuery, QueryChange};
+pub use report::{ReportKind, ReportScheduler};
+pub use resources::Resources;
+pub use selection::Selection;
+pub use shred::RemoveMode;
+pub use sink::{DirectorySink, HttpSink, ReportSink, StdoutSink};
+pub use snapshot::{Snapshot, SnapshotEntry};
+pub use source::{EventSource, Injector, SimulatedWatcher};
+pub use stats::{ExtensionStats, TreeStats};
+#[cfg(feature = "store")]
+pub use store::BlobStore;
+pub use tombstone::Tombstone;
+pub use transform::EventTransform;
+pub use tree::{BfsIterator, Tree, TreeIterator};
+pub use validate::Violation;
+#[cfg(feature = "watch")]
+pub use watcher::FsWatcher;
@@ -0,0 +1,183 @@
+use std::io::{self, BufRead};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::mtree::read_mtree;
+use crate::node::{ExtendedMetadata, NodeType};
+use crate::snapshot::SnapshotEntry;
+use crate::tree::Tree;
+
+/// The plain-text listing formats understood by `Tree::from_manifest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// Output of `find . -printf '%y %s %T@ %p\n'`: a type letter, the size in bytes,
+    /// the modification time in epoch seconds and the path, separated by spaces.
+    /// `d` marks directories; every other type letter is read as a file.
+    Find,
+    /// Comma-separated `path,size,mtime` rows with an optional header row.
+    /// The mtime column is optional and in epoch seconds; paths ending in `/` are directories.
+    Csv,
+    /// A BSD `mtree` specification, in either the full-path or the hierarchical form.
+    Mtree,
+}
+
+impl Tree {
+    /// Build a tree from a file listing instead of the filesystem, e.g. a vendor-provided
+    /// manifest, so a live directory can be diffed against it without the original files.
+    ///
+    /// Paths in the manifest are taken relative to `root`; absolute paths below `root`
+    /// are made relative, and a leading `./` is ignored. Manifests carry no file contents,
+    /// so compare the result with a metadata-based `ComparePolicy`.
+    pub fn from_manifest(
+        reader: impl BufRead,
+        format: ManifestFormat,
+        root: &Path,
+    ) -> io::Result<Tree> {
+        if format == ManifestFormat::Mtree {
+            return read_mtree(reader, root);
+        }
+
+        let mut entries = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parsed = match format {
+                ManifestFormat::Find => parse_find_line(&line),
+                ManifestFormat::Csv => parse_csv_line(&line, index == 0),
+                ManifestFormat::Mtree => unreachable!("mtree is read as a whole"),
+            };
+            match parsed {
+                Ok(Some(mut entry)) => {
+                    entry.path = relative_to_root(&entry.path, root)
+                        .ok_or_else(|| invalid_line(index, "path escapes the manifest root"))?;
+                    entries.push(entry);
+                }
+                Ok(None) => {}
+                Err(message) => return Err(invalid_line(index, message)),
+            }
+        }
+        Ok(Tree::from_entries(root.to_path_buf(), entries))
+    }
+}
+
+/// Parses `<type> <size> <mtime> <path>`, where the path may itself contain spaces.
+fn parse_find_line(line: &str) -> Result<Option<SnapshotEntry>, &'static str> {
+    let mut fields = line.splitn(4, ' ');
+    let kind = fields.next().ok_or("missing type")?;
+    let size = fields.next().ok_or("missing size")?;
+    let mtime = fields.next().ok_or("missing mtime")?;
+    let path = fields.next().ok_or("missing path")?;
+
+    let node_type = if kind == "d" {
+        NodeType::Directory
+    } else {
+        NodeType::File
+    };
+    Ok(Some(SnapshotEntry {
+        path: PathBuf::from(path),
+        node_type,
+        size: size.parse().map_err(|_| "invalid size")?,
+        metadata: ExtendedMetadata {
+            modified: Some(parse_epoch(mtime).ok_or("invalid mtime")?),
+            ..ExtendedMetadata::default()
+        },
+    }))
+}
+
+/// Parses `path,size[,mtime]`. A first row whose size column is not a number is
+/// treated as a header and skipped.
+fn parse_csv_line(line: &str, first: bool) -> Result<Option<SnapshotEntry>, &'static str> {
+    let fields = split_csv(line)?;
+    let path = fields.first().ok_or("missing path")?;
+    let size = fields.get(1).ok_or("missing size")?;
+    let size = match size.trim().parse() {
+        Ok(size) => size,
+        Err(_) if first => return Ok(None),
+        Err(_) => return Err("invalid size"),
+    };
+    let modified = match fields.get(2).map(|field| field.trim()) {
+        Some(mtime) if !mtime.is_empty() => Some(parse_epoch(mtime).ok_or("invalid mtime")?),
+        _ => None,
+    };
+
+    let node_type = if path.ends_with('/') {
+        NodeType::Directory
+    } else {
+        NodeType::File
+    };
+    Ok(Some(SnapshotEntry {
+        path: PathBuf::from(path),
+        node_type,
+        size,
+        metadata: ExtendedMetadata {
+            modified,
+            ..ExtendedMetadata::default()
+        },
+    }))
+}
+
+/// Splits one CSV row, honouring double-quoted fields and `""` escapes.
+fn split_csv(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote");
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Parses epoch seconds with an optional fractional part, keeping full nanosecond precision.
+pub(crate) fn parse_epoch(value: &str) -> Option<SystemTime> {
+    let (secs, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let secs: u64 = secs.parse().ok()?;
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut nanos = 0u32;
+    for i in 0..9 {
+        let digit = fraction
+            .as_bytes()
+            .get(i)
+            .map_or(0, |b| u32::from(b - b'0'));
+        nanos = nanos * 10 + digit;
+    }
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+/// Normalises a manifest path to one relative to `root`.
+/// Returns `None` for paths that step outside the root with `..`.
+pub(crate) fn relative_to_root(path: &Path, root: &Path) -> Option<PathBuf> {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::ParentDir => return None,
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    Some(relative)
+}
+
+fn invalid_line(index: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("manifest line {}: {}", index + 1, message),
+    )
+}
@@ -0,0 +1,289 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::node::Node;
+
+/// How many leading bytes of a file are read to recognize its format; enough for the
+/// `ustar` marker of tar archives at offset 257.
+const SNIFF_LEN: u64 = 512;
+
+impl Node {
+    /// The MIME type of a file, e.g. `image/png`: the one detected from its contents if
+    /// the tree was scanned with `TreeBuilder::mime_types`, and otherwise the one its
+    /// extension commonly stands for. `None` for directories, symbolic links, and files
+    /// of an extension not known here that were not read.
+    ///
+    /// Searches by kind need no external tool with it: every image over 10 MB is the
+    /// files whose type starts with `image/` and whose size is above 10,000,000.
+    pub fn mime_type(&self) -> Option<&str> {
+        if !self.is_file() {
+            return None;
+        }
+        if let Some(mime) = &self.metadata.mime_type {
+            return Some(mime);
+        }
+        by_extension(&self.path)
+    }
+}
+
+/// The MIME type of the file at `path` from its first bytes, falling back on its
+/// extension where they do not tell, e.g. for plain text or for the many formats that
+/// are ZIP archives inside, such as `.docx` and `.jar`. Files that still are not
+/// recognized are `inode/x-empty` if empty, as `file` has them, `text/plain` if they
+/// look like text, and `application/octet-stream` otherwise.
+pub(crate) fn sniff_file(path: &Path) -> io::Result<&'static str> {
+    let mut head = Vec::new();
+    File::open(path)?.take(SNIFF_LEN).read_to_end(&mut head)?;
+    Ok(match (by_content(&head), by_extension(path)) {
+        (Some("application/zip"), Some(mime)) => mime,
+        (Some(mime), _) | (None, Some(mime)) => mime,
+        (None, None) if head.is_empty() => "inode/x-empty",
+        (None, None) if looks_like_text(&head) => "text/plain",
+        (None, None) => "application/octet-stream",
+    })
+}
+
+/// The type commonly stored under the extension of `path`, compared case-insensitively.
+fn by_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    extension_type(&extension).map(|(mime, _)| mime)
+}
+
+/// The MIME type a lowercased extension commonly stands for, and the broad kind
+/// `GroupBy::Kind` files it under. The one table both look extensions up in.
+pub(crate) fn extension_type(extension: &str) -> Option<(&'static str, &'static str)> {
+    const IMAGES: &str = "Images";
+    const DOCUMENTS: &str = "Documents";
+    const AUDIO: &str = "Audio";
+    const VIDEO: &str = "Video";
+    const ARCHIVES: &str = "Archives";
+    const CODE: &str = "Code";
+    const OTHER: &str = "Other";
+    Some(match extension {
+        "png" => ("image/png", IMAGES),
+        "jpg" | "jpeg" => ("image/jpeg", IMAGES),
+        "gif" => ("image/gif", IMAGES),
+        "webp" => ("image/webp", IMAGES),
+        "bmp" => ("image/bmp", IMAGES),
+        "tif" | "tiff" => ("image/tiff", IMAGES),
+        "svg" => ("image/svg+xml", IMAGES),
+        "ico" => ("image/vnd.microsoft.icon", IMAGES),
+        "heic" => ("image/heic", IMAGES),
+        "avif" => ("image/avif", IMAGES),
+        "mp3" => ("audio/mpeg", AUDIO),
+        "flac" => ("audio/flac", AUDIO),
+        "ogg" | "oga" => ("audio/ogg", AUDIO),
+        "wav" => ("audio/wav", AUDIO),
+        "m4a" => ("audio/mp4", AUDIO),
+        "aac" => ("audio/aac", AUDIO),
+        "mp4" | "m4v" => ("video/mp4", VIDEO),
+        "mov" => ("video/quicktime", VIDEO),
+        "mkv" => ("video/x-matroska", VIDEO),
+        "webm" => ("video/webm", VIDEO),
+        "avi" => ("video/x-msvideo", VIDEO),
+        "pdf" => ("application/pdf", DOCUMENTS),
+        "doc" => ("application/msword", DOCUMENTS),
+        "docx" => (
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            DOCUMENTS,
+        ),
+        "xls" => ("application/vnd.ms-excel", DOCUMENTS),
+        "xlsx" => (
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            DOCUMENTS,
+        ),
+        "ppt" => ("application/vnd.ms-powerpoint", DOCUMENTS),
+        "pptx" => (
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            DOCUMENTS,
+        ),
+        "odt" => ("application/vnd.oasis.opendocument.text", DOCUMENTS),
+        "rtf" => ("application/rtf", DOCUMENTS),
+        "txt" => ("text/plain", DOCUMENTS),
+        "md" => ("text/markdown", DOCUMENTS),
+        "csv" => ("text/csv", DOCUMENTS),
+        "epub" => ("application/epub+zip", DOCUMENTS),
+        "zip" => ("application/zip", ARCHIVES),
+        "tar" => ("application/x-tar", ARCHIVES),
+        "gz" | "tgz" => ("application/gzip", ARCHIVES),
+        "bz2" => ("application/x-bzip2", ARCHIVES),
+        "xz" => ("application/x-xz", ARCHIVES),
+        "zst" => ("application/zstd", ARCHIVES),
+        "7z" => ("application/x-7z-compressed", ARCHIVES),
+        "rar" => ("application/vnd.rar", ARCHIVES),
+        "rs" => ("text/x-rust", CODE),
+        "c" | "h" => ("text/x-c", CODE),
+        "cpp" => ("text/x-c++", CODE),
+        "py" => ("text/x-python", CODE),
+        "js" => ("text/javascript", CODE),
+        "ts" => ("text/x-typescript", CODE),
+        "go" => ("text/x-go", CODE),
+        "java" => ("text/x-java", CODE),
+        "sh" => ("application/x-sh", CODE),
+        "toml" => ("application/toml", CODE),
+        "json" => ("application/json", CODE),
+        "yaml" | "yml" => ("application/yaml", CODE),
+        "mjs" => ("text/javascript", OTHER),
+        "html" | "htm" => ("text/html", OTHER),
+        "css" => ("text/css", OTHER),
+        "xml" => ("application/xml", OTHER),
+        "log" => ("text/plain", OTHER),
+        "jar" => ("application/java-archive", OTHER),
+        "apk" => ("application/vnd.android.package-archive", OTHER),
+        "iso" => ("application/x-iso9660-image", OTHER),
+        "wasm" => ("application/wasm", OTHER),
+        "exe" | "dll" => ("application/vnd.microsoft.portable-executable", OTHER),
+        "sqlite" | "db" => ("application/vnd.sqlite3", OTHER),
+        "woff" => ("font/woff", OTHER),
+        "woff2" => ("font/woff2", OTHER),
+        "ttf" => ("font/ttf", OTHER),
+        "otf" => ("font/otf", OTHER),
+        _ => return None,
+    })
+}
+
+/// The type the signature at the start of `head` marks, if it is one known here.
+fn by_content(head: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"II*\0", "image/tiff"),
+        (b"MM\0*", "image/tiff"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"PK\x05\x06", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"BZh", "application/x-bzip2"),
+        (b"\xfd7zXZ\0", "application/x-xz"),
+        (b"\x28\xb5\x2f\xfd", "application/zstd"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\x7fELF", "application/x-executable"),
+        (b"\0asm", "application/wasm"),
+        (b"SQLite format 3\0", "application/vnd.sqlite3"),
+        (b"ID3", "audio/mpeg"),
+        (b"fLaC", "audio/flac"),
+        (b"OggS", "audio/ogg"),
+        (b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+        (b"%!PS", "application/postscript"),
+    ];
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| head.starts_with(signature))
+    {
+        return Some(mime);
+    }
+    // Two-byte signatures that plenty of text starts with too ("BMW", "MZ-123"), taken
+    // only with the header fields that follow them.
+    if is_bmp(head) {
+        return Some("image/bmp");
+    }
+    if is_pe(head) {
+        return Some("application/vnd.microsoft.portable-executable");
+    }
+    // Containers naming their format a few bytes in.
+    match (head.get(..4), head.get(8..12)) {
+        (Some(b"RIFF"), Some(b"WEBP")) => return Some("image/webp"),
+        (Some(b"RIFF"), Some(b"WAVE")) => return Some("audio/wav"),
+        (Some(b"RIFF"), Some(b"AVI ")) => return Some("video/x-msvideo"),
+        _ => {}
+    }
+    if head.get(4..8) == Some(b"ftyp") {
+        return Some(match head.get(8..12)? {
+            b"qt  " => "video/quicktime",
+            b"heic" | b"heix" | b"mif1" => "image/heic",
+            b"avif" => "image/avif",
+            b"M4A " => "audio/mp4",
+            _ => "video/mp4",
+        });
+    }
+    if head.get(257..262) == Some(b"ustar") {
+        return Some("application/x-tar");
+    }
+    None
+}
+
+/// Whether `head` is a bitmap's: `BM`, reserved bytes that are zero, and the size of
+/// a known info header right after the 14-byte file header.
+fn is_bmp(head: &[u8]) -> bool {
+    head.starts_with(b"BM")
+        && head.get(6..10) == Some(&[0; 4])
+        && matches!(le_u32(head, 14), Some(12 | 40 | 52 | 56 | 64 | 108 | 124))
+}
+
+/// Whether `head` is a Windows executable's: `MZ`, and `PE\0\0` where the DOS header's
+/// `e_lfanew` at offset 0x3c points.
+fn is_pe(head: &[u8]) -> bool {
+    let Some(offset) = le_u32(head, 0x3c).and_then(|offset| usize::try_from(offset).ok()) else {
+        return false;
+    };
+    head.starts_with(b"MZ") && head.get(offset..offset.saturating_add(4)) == Some(b"PE\0\0")
+}
+
+/// The little-endian `u32` at `offset` in `head`, if it is that long.
+fn le_u32(head: &[u8], offset: usize) -> Option<u32> {
+    let bytes = head.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Whether `head` reads as text: valid UTF-8 without NUL bytes, allowing for a
+/// character cut off at the end.
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{by_content, sniff_file};
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn text_starting_with_weak_signatures_is_text() {
+        let dir = empty_dir().unwrap();
+        let cases = [
+            ("parts.csv", "MZ-123, widget, 4\n", "text/csv"),
+            ("cars.txt", "BMW 320d, 2019\n", "text/plain"),
+            ("notes.md", "BM: benchmark notes\n", "text/markdown"),
+            ("README", "MZ-123 is the part number\n", "text/plain"),
+        ];
+        for (name, contents, expected) in cases {
+            let path = dir.root.join(name);
+            fs::write(&path, contents).unwrap();
+            assert_eq!(sniff_file(&path).unwrap(), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn bitmaps_and_executables_are_recognized() {
+        let mut bmp = vec![0u8; 54];
+        bmp[..2].copy_from_slice(b"BM");
+        bmp[2..6].copy_from_slice(&54u32.to_le_bytes());
+        bmp[10..14].copy_from_slice(&54u32.to_le_bytes());
+        bmp[14..18].copy_from_slice(&40u32.to_le_bytes());
+        assert_eq!(by_content(&bmp), Some("image/bmp"));
+
+        let mut pe = vec![0u8; 0x100];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert_eq!(
+            by_content(&pe),
+            Some("application/vnd.microsoft.portable-executable")
+        );
+
+        assert_eq!(by_content(b"MZ"), None);
+        assert_eq!(by_content(b"BM"), None);
+    }
+}
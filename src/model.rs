@@ -0,0 +1,120 @@
+use crate::diff::TreeDiff;
+use crate::handle::NodeId;
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// The shape UI toolkits expect from a hierarchical item model: rows addressed by
+/// index under a parent, with identities that stay stable while rows move.
+///
+/// `None` as a parent stands for the invisible top level, whose rows are the entries
+/// directly below the model's root.
+pub trait TreeModel {
+    /// A stable identity for a row.
+    type Id: Clone + Eq;
+
+    /// The number of rows below `parent`.
+    fn row_count(&self, parent: Option<&Self::Id>) -> usize;
+
+    /// The row at `row` below `parent`.
+    fn child(&self, parent: Option<&Self::Id>, row: usize) -> Option<Self::Id>;
+
+    /// The parent of a row, or `None` for rows at the top level.
+    fn parent(&self, id: &Self::Id) -> Option<Self::Id>;
+
+    /// The index of a row below its parent.
+    fn row(&self, id: &Self::Id) -> Option<usize>;
+
+    /// The node shown by a row.
+    fn item(&self, id: &Self::Id) -> Option<&Node>;
+
+    /// Returns `true` if the row has rows below it.
+    fn has_children(&self, id: &Self::Id) -> bool {
+        self.row_count(Some(id)) > 0
+    }
+}
+
+/// A change to a `TreeModel`, in the terms list and tree views are notified with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelChange {
+    /// A row was inserted at `row` below `parent`.
+    Inserted {
+        parent: Option<NodeId>,
+        row: usize,
+        id: NodeId,
+    },
+    /// A row was removed. Views look up its former position by identity.
+    Removed { parent: Option<NodeId>, id: NodeId },
+    /// A row's data changed in place.
+    Changed { id: NodeId },
+}
+
+impl TreeModel for Tree {
+    type Id = NodeId;
+
+    fn row_count(&self, parent: Option<&NodeId>) -> usize {
+        self.model_children(parent).map_or(0, <[Node]>::len)
+    }
+
+    fn child(&self, parent: Option<&NodeId>, row: usize) -> Option<NodeId> {
+        self.id_for(self.model_children(parent)?.get(row)?)
+    }
+
+    fn parent(&self, id: &NodeId) -> Option<NodeId> {
+        let parent = id.relative_path().parent()?;
+        if parent.as_os_str().is_empty() {
+            return None;
+        }
+        self.handle(&self.head.path.join(parent))
+    }
+
+    fn row(&self, id: &NodeId) -> Option<usize> {
+        let path = self.path_of(id);
+        let siblings = self.get_node(path.parent()?)?.children.as_ref()?;
+        siblings.iter().position(|sibling| sibling.path == path)
+    }
+
+    fn item(&self, id: &NodeId) -> Option<&Node> {
+        self.node(id)
+    }
+}
+
+impl Tree {
+    /// Translate a delta, such as one delivered by `DeltaBatcher`, into model change
+    /// notifications against the tree's current state.
+    pub fn model_changes(&self, delta: &TreeDiff) -> Vec<ModelChange> {
+        let mut changes = Vec::new();
+        for rel in &delta.removed {
+            let id = NodeId::detached(rel.clone());
+            changes.push(ModelChange::Removed {
+                parent: self.parent(&id),
+                id,
+            });
+        }
+        for rel in &delta.added {
+            let Some(id) = self.handle(&self.head.path.join(rel)) else {
+                continue;
+            };
+            if let Some(row) = self.row(&id) {
+                changes.push(ModelChange::Inserted {
+                    parent: self.parent(&id),
+                    row,
+                    id,
+                });
+            }
+        }
+        for rel in &delta.modified {
+            if let Some(id) = self.handle(&self.head.path.join(rel)) {
+                changes.push(ModelChange::Changed { id });
+            }
+        }
+        changes
+    }
+
+    fn model_children(&self, parent: Option<&NodeId>) -> Option<&[Node]> {
+        let node = match parent {
+            Some(id) => self.node(id)?,
+            None => &self.head,
+        };
+        node.children.as_deref()
+    }
+}
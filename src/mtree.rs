@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::manifest::{parse_epoch, relative_to_root};
+use crate::node::{ExtendedMetadata, Node, NodeType};
+use crate::snapshot::SnapshotEntry;
+use crate::tree::Tree;
+
+impl Tree {
+    /// Write the tree as a BSD `mtree` specification, one full-path entry per line.
+    /// Records `type`, `size` (files only), `link` (symlinks only), `time` and, where
+    /// they were recorded, `mode`, `uid` and `gid` for every entry.
+    pub fn write_mtree(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "#mtree")?;
+        let mut stack = vec![&self.head];
+        let mut lines = BTreeMap::new();
+        while let Some(node) = stack.pop() {
+            let rel = node
+                .path
+                .strip_prefix(&self.head.path)
+                .unwrap_or(&node.path);
+            lines.insert(rel.to_path_buf(), spec_line(rel, node));
+            if let Some(children) = &node.children {
+                stack.extend(children);
+            }
+        }
+        for line in lines.values() {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a single `./path keyword=value ...` line.
+fn spec_line(rel: &Path, node: &Node) -> String {
+    let mut line = String::from(".");
+    for part in rel.iter() {
+        line.push('/');
+        line.push_str(&encode_name(&part.to_string_lossy()));
+    }
+    match &node.node_type {
+        NodeType::Directory => line.push_str(" type=dir"),
+        NodeType::File => line.push_str(&format!(" type=file size={}", node.size)),
+        NodeType::Symlink { target } => line.push_str(&format!(
+            " type=link link={}",
+            encode_name(&target.to_string_lossy())
+        )),
+    }
+    if let Some(modified) = node.metadata.modified {
+        if let Ok(since) = modified.duration_since(UNIX_EPOCH) {
+            line.push_str(&format!(
+                " time={}.{:09}",
+                since.as_secs(),
+                since.subsec_nanos()
+            ));
+        }
+    }
+    if let Some(mode) = node.metadata.mode {
+        line.push_str(&format!(" mode={:04o}", mode));
+    }
+    if let Some(uid) = node.metadata.uid {
+        line.push_str(&format!(" uid={}", uid));
+    }
+    if let Some(gid) = node.metadata.gid {
+        line.push_str(&format!(" gid={}", gid));
+    }
+    line
+}
+
+/// Reads an `mtree` specification, supporting both the full-path form (`./a/b`) and
+/// the classic hierarchical form where plain names are relative to the current
+/// directory and `..` moves back up. `/set` and `/unset` defaults are honoured.
+/// Keywords other than `type`, `size`, `time`, `link`, `mode`, `uid` and `gid` are
+/// ignored.
+pub(crate) fn read_mtree(reader: impl BufRead, root: &Path) -> io::Result<Tree> {
+    let mut entries = Vec::new();
+    let mut defaults: BTreeMap<String, String> = BTreeMap::new();
+    let mut cwd = PathBuf::new();
+    let mut pending = String::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        // A trailing backslash continues the entry on the next line.
+        if let Some(continued) = line.strip_suffix('\\') {
+            pending.push_str(continued);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(&line);
+        let line = std::mem::take(&mut pending);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let first = words.next().unwrap_or_default();
+        match first {
+            "/set" => {
+                for (key, value) in words.filter_map(keyword) {
+                    defaults.insert(key.to_string(), value.to_string());
+                }
+                continue;
+            }
+            "/unset" => {
+                for key in words {
+                    if key == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(key);
+                    }
+                }
+                continue;
+            }
+            ".." => {
+                cwd.pop();
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut keywords = defaults.clone();
+        for (key, value) in words.filter_map(keyword) {
+            keywords.insert(key.to_string(), value.to_string());
+        }
+
+        let name = decode_name(first);
+        let full_form = name.contains('/');
+        let path = if full_form {
+            PathBuf::from(&name)
+        } else {
+            cwd.join(&name)
+        };
+        let path = relative_to_root(&path, root)
+            .ok_or_else(|| invalid_line(index, "path escapes the mtree root"))?;
+
+        let node_type = match keywords.get("type").map(String::as_str) {
+            Some("dir") => NodeType::Directory,
+            Some("link") => NodeType::Symlink {
+                target: keywords
+                    .get("link")
+                    .map(|target| PathBuf::from(decode_name(target)))
+                    .ok_or_else(|| invalid_line(index, "link without a target"))?,
+            },
+            _ => NodeType::File,
+        };
+        // In the hierarchical form, a directory entry also descends into it.
+        if !full_form && node_type == NodeType::Directory && name != "." {
+            cwd = path.clone();
+        }
+
+        let size = match keywords.get("size") {
+            Some(size) => size
+                .parse()
+                .map_err(|_| invalid_line(index, "invalid size"))?,
+            None => 0,
+        };
+        let modified = match keywords.get("time") {
+            Some(time) => {
+                Some(parse_epoch(time).ok_or_else(|| invalid_line(index, "invalid time"))?)
+            }
+            None => None,
+        };
+        let number = |key: &str, radix: u32| {
+            keywords
+                .get(key)
+                .map(|value| u32::from_str_radix(value, radix))
+                .transpose()
+                .map_err(|_| invalid_line(index, &format!("invalid {}", key)))
+        };
+
+        entries.push(SnapshotEntry {
+            path,
+            node_type,
+            size,
+            metadata: ExtendedMetadata {
+                modified,
+                mode: number("mode", 8)?,
+                uid: number("uid", 10)?,
+                gid: number("gid", 10)?,
+                ..ExtendedMetadata::default()
+            },
+        });
+    }
+
+    Ok(Tree::from_entries(root.to_path_buf(), entries))
+}
+
+/// Splits a `key=value` word.
+fn keyword(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=')
+}
+
+/// Escapes a file name the way `vis(3)` does for mtree: whitespace, backslashes,
+/// `#` and non-printable bytes become backslash-octal sequences.
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_graphic() && byte != b'\\' && byte != b'#' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("\\{:03o}", byte));
+        }
+    }
+    encoded
+}
+
+/// Reverses `encode_name`, decoding backslash-octal sequences.
+fn decode_name(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|digits| bytes[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)));
+        match octal {
+            Some(digits) => {
+                let value = digits
+                    .iter()
+                    .fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0'));
+                decoded.push(value as u8);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn invalid_line(index: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("mtree line {}: {}", index + 1, message),
+    )
+}
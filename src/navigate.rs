@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use crate::handle::NodeId;
+use crate::tree::Tree;
+
+impl Tree {
+    /// Complete a partially typed path relative to the root, as for an address bar or
+    /// shell. `"src/li"` yields every entry in `src` whose name starts with `li`, as
+    /// paths relative to the root; directories get a trailing `/`. Results are sorted.
+    pub fn complete_path(&self, partial: &str) -> Vec<String> {
+        let (dir, prefix) = match partial.rfind('/') {
+            Some(split) => (&partial[..=split], &partial[split + 1..]),
+            None => ("", partial),
+        };
+        let Some(children) = self
+            .get_node(&self.head.path.join(dir))
+            .and_then(|node| node.children.as_ref())
+        else {
+            return Vec::new();
+        };
+
+        let mut completions: Vec<String> = children
+            .iter()
+            .filter_map(|child| {
+                let name = child.path.file_name()?.to_str()?;
+                if !name.starts_with(prefix) {
+                    return None;
+                }
+                let slash = if child.is_dir() { "/" } else { "" };
+                Some(format!("{dir}{name}{slash}"))
+            })
+            .collect();
+        completions.sort();
+        completions
+    }
+
+    /// The chain of (name, handle) pairs from the root down to the node a handle refers
+    /// to, for building breadcrumbs. The root is named by its full path. Empty if the
+    /// node is no longer in the tree.
+    pub fn breadcrumbs(&self, id: &NodeId) -> Vec<(String, NodeId)> {
+        let mut current = &self.head;
+        let mut crumbs = Vec::new();
+        crumbs.extend(
+            self.id_for(current)
+                .map(|root| (current.path.display().to_string(), root)),
+        );
+        for component in id.relative_path().components() {
+            let next = current.path.join(component);
+            let Some(child) = current
+                .children
+                .iter()
+                .flatten()
+                .find(|child| child.path == next)
+            else {
+                return Vec::new();
+            };
+            let name = component.as_os_str().to_string_lossy().into_owned();
+            crumbs.extend(self.id_for(child).map(|id| (name, id)));
+            current = child;
+        }
+        crumbs
+    }
+
+    /// The breadcrumbs for the node at `path`. See `breadcrumbs`.
+    pub fn breadcrumbs_for(&self, path: &Path) -> Vec<(String, NodeId)> {
+        self.handle(path)
+            .map(|id| self.breadcrumbs(&id))
+            .unwrap_or_default()
+    }
+}
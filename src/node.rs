@@ -1,43 +1,180 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io;
-use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-/// Represents whether a node is a file or a directory.
+use crate::builder::Scanner;
+use crate::checksum::Checksum;
+use crate::format::{self, Units};
+use crate::options::{ScanOptions, SizeMetric};
+use crate::platform::{
+    block_count, dir_id, file_id, group_name, is_hidden, is_hidden_path, link_count, ownership,
+    user_name, DirId,
+};
+
+/// Represents whether a node is a file, a directory or an unfollowed symbolic link.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeType {
     File,
     Directory,
+    /// A symbolic link recorded as such, either because links were not followed or
+    /// because its target does not exist. The size is that of the link itself.
+    Symlink {
+        /// Where the link points, as stored in it.
+        target: PathBuf,
+    },
+}
+
+/// Reads the metadata of the entry at `path` and decides its node type. Links are
+/// followed if `follow` is set, unless their target is missing.
+pub(crate) fn stat_entry(path: &Path, follow: bool) -> io::Result<(fs::Metadata, NodeType)> {
+    let link = fs::symlink_metadata(path)?;
+    if !link.file_type().is_symlink() {
+        let node_type = node_type_of(&link);
+        return Ok((link, node_type));
+    }
+    if follow {
+        match fs::metadata(path) {
+            Ok(metadata) => {
+                let node_type = node_type_of(&metadata);
+                return Ok((metadata, node_type));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let target = fs::read_link(path)?;
+    Ok((link, NodeType::Symlink { target }))
+}
+
+/// Follows the directory at `path` unless it is one of `ancestors`, the directories it
+/// was reached through. If it is, it is recorded as the link that leads back, or as an
+/// empty directory if it is not a link (e.g. a bind mount of an ancestor), and `None`
+/// is returned; otherwise its id to push while scanning below it.
+pub(crate) fn enter_dir(
+    node: &mut Node,
+    metadata: &fs::Metadata,
+    ancestors: &[DirId],
+) -> io::Result<Option<DirId>> {
+    let id = dir_id(&node.path, metadata)?;
+    if !ancestors.contains(&id) {
+        return Ok(Some(id));
+    }
+    match fs::read_link(&node.path) {
+        Ok(target) => {
+            let link = fs::symlink_metadata(&node.path)?;
+            node.node_type = NodeType::Symlink { target };
+            node.metadata = ExtendedMetadata::from_metadata(&link);
+            node.size = link.len();
+            node.children = None;
+        }
+        Err(_) => {
+            node.size = 0;
+            node.children = Some(Vec::new());
+        }
+    }
+    Ok(None)
+}
+
+fn node_type_of(metadata: &fs::Metadata) -> NodeType {
+    match metadata.is_dir() {
+        true => NodeType::Directory,
+        false => NodeType::File,
+    }
 }
 
 /// A struct to hold extended metadata about a file or directory.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExtendedMetadata {
     pub modified: Option<SystemTime>,
     pub accessed: Option<SystemTime>,
     pub created: Option<SystemTime>,
-    // Additional metadata such as permissions could be added here.
+    /// Permission bits, including setuid, setgid and sticky (`st_mode & 0o7777`).
+    /// `None` where the platform has no Unix permissions or they were not recorded.
+    pub mode: Option<u32>,
+    /// Owning user id, on Unix.
+    pub uid: Option<u32>,
+    /// Owning group id, on Unix.
+    pub gid: Option<u32>,
+    /// Inode number, on Unix.
+    pub inode: Option<u64>,
+    /// Device the entry is on, on Unix.
+    pub device: Option<u64>,
+    /// Number of hard links to the entry, on Unix; see `Node::is_hardlinked`.
+    pub nlink: Option<u64>,
+    /// Length in bytes as recorded by the file system, holes in sparse files included;
+    /// see `Node::apparent_size`.
+    pub len: Option<u64>,
+    /// Number of 512-byte blocks allocated on disk, on Unix; see `Node::disk_usage`.
+    pub blocks: Option<u64>,
+    /// Extended attributes by name, if they were read; see `TreeBuilder::xattrs`.
+    /// Names that are not valid UTF-8 are converted lossily.
+    pub xattrs: Option<BTreeMap<String, Vec<u8>>>,
+    /// The file's content checksum, if computed during the scan; see
+    /// `TreeBuilder::checksums` and `Node::hash`.
+    pub checksum: Option<Checksum>,
+    /// The file's MIME type as detected from its contents, if they were read during the
+    /// scan; see `TreeBuilder::mime_types` and `Node::mime_type`.
+    pub mime_type: Option<String>,
 }
 
 impl ExtendedMetadata {
     /// Create extended metadata for the given path.
     pub fn from_path(path: &Path) -> io::Result<Self> {
-        let metadata = fs::metadata(path)?;
-        Ok(Self {
+        Ok(Self::from_metadata(&fs::metadata(path)?))
+    }
+
+    /// Extended metadata from what was already read for an entry.
+    pub(crate) fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let (mode, uid, gid) = ownership(metadata);
+        let (device, inode) = file_id(metadata).unzip();
+        Self {
             modified: metadata.modified().ok(),
             accessed: metadata.accessed().ok(),
             created: metadata.created().ok(),
-        })
+            mode,
+            uid,
+            gid,
+            inode,
+            device,
+            nlink: link_count(metadata),
+            len: Some(metadata.len()),
+            blocks: block_count(metadata),
+            xattrs: None,
+            checksum: None,
+            mime_type: None,
+        }
+    }
+
+    /// The name of the owning user, looked up in the user database on first use.
+    /// `None` without a recorded uid or if no user has it.
+    pub fn user_name(&self) -> Option<&'static str> {
+        user_name(self.uid?)
+    }
+
+    /// The name of the owning group, looked up in the group database on first use.
+    /// `None` without a recorded gid or if no group has it.
+    pub fn group_name(&self) -> Option<&'static str> {
+        group_name(self.gid?)
+    }
+
+    /// Returns `true` if all permission bits in `bits` (e.g. `0o002` for world-writable)
+    /// are set. Always `false` without recorded permissions.
+    pub fn has_mode_bits(&self, bits: u32) -> bool {
+        self.mode.is_some_and(|mode| mode & bits == bits)
     }
 }
 
 /// A Node in the directory tree.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     /// Filesystem path of the node.
     pub path: PathBuf,
-    /// Whether the node is a file or a directory.
+    /// Whether the node is a file, a directory or a symbolic link.
     pub node_type: NodeType,
     /// Extended metadata for searching and reporting.
     pub metadata: ExtendedMetadata,
@@ -45,30 +182,86 @@ pub struct Node {
     pub children: Option<Vec<Node>>,
     /// Self Size if File, Cumulative size of all children if Directory.
     pub size: u64,
+    /// For a directory whose children were evicted to save memory, the number of
+    /// entries below it that were dropped. The size is kept.
+    pub evicted: Option<u64>,
+    /// The tree generation in which this entry was last seen to change. Used to tell
+    /// whether a `NodeId` still refers to the same entry.
+    pub generation: u64,
 }
 
 impl Node {
-    /// Create a new Node from a given path.
+    /// Create a new Node from a given path. Symbolic links are followed; dangling ones,
+    /// and those leading back to a directory they are inside of, are recorded as
+    /// `NodeType::Symlink`.
     pub fn new(path: PathBuf) -> io::Result<Self> {
-        let metadata = ExtendedMetadata::from_path(&path)?;
-        let node_type = if fs::metadata(&path)?.is_dir() {
-            NodeType::Directory
-        } else {
-            NodeType::File
-        };
+        Self::new_below(path, &mut Vec::new())
+    }
 
-        let mut node = Self {
+    /// Like `new`, for an entry reached through the directories `ancestors`.
+    fn new_below(path: PathBuf, ancestors: &mut Vec<DirId>) -> io::Result<Self> {
+        let (metadata, node_type) = stat_entry(&path, true)?;
+        let mut node = Self::from_parts(
             path,
             node_type,
-            metadata,
-            children: None,
-            size: 0,
-        };
+            ExtendedMetadata::from_metadata(&metadata),
+            metadata.len(),
+        );
+        if node.is_dir() {
+            node.children = None;
+            if let Some(id) = enter_dir(&mut node, &metadata, ancestors)? {
+                ancestors.push(id);
+                let populated = node.populate_children_below(ancestors);
+                ancestors.pop();
+                populated?;
+                node.size = node.children.iter().flatten().map(|child| child.size).sum();
+            }
+        }
+        Ok(node)
+    }
 
-        node.populate_children();
-        node.calc_size();
+    /// Create a node for `path` without reading below it: a directory's children are only
+    /// listed once requested through `children` (or `Tree::load`), so opening a huge tree
+    /// is cheap. Until then the directory's size is 0; afterwards it counts the files
+    /// listed so far.
+    pub fn new_lazy(path: PathBuf) -> io::Result<Self> {
+        let (metadata, node_type) = stat_entry(&path, true)?;
+        let size = match node_type {
+            NodeType::Directory => 0,
+            _ => metadata.len(),
+        };
+        Ok(Self {
+            metadata: ExtendedMetadata::from_metadata(&metadata),
+            path,
+            node_type,
+            children: None,
+            size,
+            evicted: None,
+            generation: 0,
+        })
+    }
 
-        Ok(node)
+    /// Create a node from already-known parts, without touching the filesystem.
+    /// Directories start out with an empty list of children.
+    pub(crate) fn from_parts(
+        path: PathBuf,
+        node_type: NodeType,
+        metadata: ExtendedMetadata,
+        size: u64,
+    ) -> Self {
+        let children = match node_type {
+            NodeType::Directory => Some(Vec::new()),
+            _ => None,
+        };
+        Self {
+            path,
+            node_type,
+            metadata,
+            children,
+            size,
+            evicted: None,
+            generation: 0,
+        }
     }
 
     /// Returns `true` if this node is a file.
@@ -81,72 +274,207 @@ impl Node {
         matches!(self.node_type, NodeType::Directory)
     }
 
+    /// The value of the extended attribute `name`, e.g. `user.checksum` or
+    /// `com.apple.quarantine`, if attributes were read and the entry has it.
+    pub fn xattr(&self, name: &str) -> Option<&[u8]> {
+        self.metadata.xattrs.as_ref()?.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns `true` if this node is a symbolic link that was not followed.
+    pub fn is_symlink(&self) -> bool {
+        matches!(self.node_type, NodeType::Symlink { .. })
+    }
+
+    /// The size for people to read, in binary units such as `1.4 GiB`; see the `format`
+    /// module for decimal units.
+    pub fn size_human(&self) -> String {
+        format::size(self.size, Units::Binary)
+    }
+
+    /// Returns `true` if this node is hidden: its name starts with a dot or, on Windows,
+    /// it has the hidden attribute, which is read from disk. Hidden ancestors do not
+    /// make a node hidden.
+    pub fn is_hidden(&self) -> bool {
+        is_hidden_path(&self.path)
+    }
+
+    /// Create a node for `path` scanned as `options` ask, e.g. without following
+    /// symbolic links.
+    pub fn with_options(path: PathBuf, options: &ScanOptions) -> io::Result<Self> {
+        Scanner::new(options, &path)?.scan(path, 0)
+    }
+
+    /// Returns `true` if this is a directory whose children were never listed, as left
+    /// by `new_lazy`. Evicted directories do not count.
+    pub fn needs_load(&self) -> bool {
+        self.is_dir() && self.children.is_none() && self.evicted.is_none()
+    }
+
+    /// This node's children, listing them first if the node was created lazily. Children
+    /// listed this way are lazy themselves. Files have no children.
+    pub fn children(&mut self) -> io::Result<&[Node]> {
+        self.load_children_from(&self.path.clone(), true)?;
+        Ok(self.children.as_deref().unwrap_or_default())
+    }
+
+    /// Lists the directory at `dir` as this node's lazy children, if they were never
+    /// listed, placing them below this node's own path. Hidden entries are left out
+    /// unless `include_hidden`. Updates the size to the sum of the children's.
+    pub(crate) fn load_children_from(
+        &mut self,
+        dir: &Path,
+        include_hidden: bool,
+    ) -> io::Result<()> {
+        if !self.needs_load() {
+            return Ok(());
+        }
+        let mut children = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !include_hidden && is_hidden(&entry) {
+                continue;
+            }
+            let mut child = Node::new_lazy(entry.path())?;
+            child.path = self.path.join(entry.file_name());
+            children.push(child);
+        }
+        self.size = children.iter().map(|child| child.size).sum();
+        self.children = Some(children);
+        Ok(())
+    }
+
+    /// Scans `path` lazily, listing again every directory that was listed in `like`, the
+    /// same entry as previously scanned. Hidden entries are left out unless
+    /// `include_hidden`.
+    pub(crate) fn rescan_like(
+        path: PathBuf,
+        like: Option<&Node>,
+        include_hidden: bool,
+    ) -> io::Result<Self> {
+        let mut node = Node::new_lazy(path.clone())?;
+        let Some(old_children) = like.and_then(|like| like.children.as_ref()) else {
+            return Ok(node);
+        };
+        if !node.is_dir() {
+            return Ok(node);
+        }
 
+        let mut children = Vec::new();
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            if !include_hidden && is_hidden(&entry) {
+                continue;
+            }
+            let old = old_children
+                .iter()
+                .find(|old| old.path.file_name() == Some(entry.file_name().as_os_str()));
+            children.push(Node::rescan_like(entry.path(), old, include_hidden)?);
+        }
+        node.size = children.iter().map(|child| child.size).sum();
+        node.children = Some(children);
+        Ok(node)
+    }
 
     /// Populate the node’s children from the file system.
     /// For a directory, reads its contents and creates child nodes.
     pub fn populate_children(&mut self) -> io::Result<()> {
-        if self.is_dir() {
-            let mut childs = Vec::new();
-            for entry in fs::read_dir(&self.path)? {
-                let entry = entry?;
-                let child_path = entry.path();
-                let child_node = Node::new(child_path)?;
-                childs.push(child_node);
-            }
-            self.children = Some(childs);
+        if !self.is_dir() {
+            return Ok(());
         }
+        let id = dir_id(&self.path, &fs::metadata(&self.path)?)?;
+        self.populate_children_below(&mut vec![id])
+    }
+
+    /// Like `populate_children`, for a directory reached through `ancestors` (itself
+    /// included).
+    fn populate_children_below(&mut self, ancestors: &mut Vec<DirId>) -> io::Result<()> {
+        let mut childs = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let child_path = entry.path();
+            let child_node = Node::new_below(child_path, ancestors)?;
+            childs.push(child_node);
+        }
+        self.children = Some(childs);
         Ok(())
     }
 
     /// Recursively updates the size of this node.
-    /// For directories, the size is the sum of sizes of all children.
-    fn calc_size(&mut self) -> io::Result<()> {
+    /// For directories, the size is the sum of sizes of all children, counting a file
+    /// with several hard links below the directory once, for the first link.
+    pub fn calc_size(&mut self) -> io::Result<()> {
+        self.read_sizes()?;
+        self.sum_sizes_once(SizeMetric::Apparent, &mut HashSet::new());
+        Ok(())
+    }
+
+    /// Re-reads the sizes and link counts of the files and links below this node.
+    fn read_sizes(&mut self) -> io::Result<()> {
         if self.is_file() {
             let metadata = fs::metadata(&self.path)?;
-            self.size = metadata.size();
+            self.size = metadata.len();
+            self.metadata.nlink = link_count(&metadata);
+            Ok(())
+        } else if self.is_symlink() {
+            self.size = fs::symlink_metadata(&self.path)?.len();
             Ok(())
         } else {
-            let mut total = 0;
             // Populate children if not already done.
             if self.children.is_none() {
                 self.populate_children()?;
             }
             if let Some(children) = &mut self.children {
                 for child in children {
-                    child.calc_size()?;
-                    total += child.size;
+                    child.read_sizes()?;
                 }
             }
-            self.size = total;
-
             Ok(())
         }
     }
+
+    /// Recomputes directory sizes from the sizes already recorded on their descendants,
+    /// without touching the filesystem. Returns the resulting size of this node.
+    pub(crate) fn sum_child_sizes(&mut self) -> u64 {
+        if let Some(children) = &mut self.children {
+            self.size = children.iter_mut().map(|child| child.sum_child_sizes()).sum();
+        }
+        self.size
+    }
 }
 
 use std::fmt;
 
 impl fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.node_type {
+        match &self.node_type {
             NodeType::File => {
                 write!(
                     f,
-                    "File: {} (size: {:?})",
+                    "File: {} (size: {})",
                     self.path.display(),
-                    self.size,
+                    self.size_human(),
                 )
             }
-            NodeType::Directory => {
+            NodeType::Symlink { target } => {
                 write!(
                     f,
-                    "Directory: {} (size: {:?})\n",
+                    "Symlink: {} -> {}",
                     self.path.display(),
-                    self.size
+                    target.display(),
+                )
+            }
+            NodeType::Directory => {
+                writeln!(
+                    f,
+                    "Directory: {} (size: {})",
+                    self.path.display(),
+                    self.size_human()
                 )?;
 
-                // If children are not populated, note that.
+                // If children were evicted or are not populated, note that.
+                if let Some(entries) = self.evicted {
+                    return write!(f, "  [Children evicted: {} entries]", entries);
+                }
                 if self.children.is_none() {
                     return write!(f, "  [Children not populated]");
                 }
@@ -158,8 +486,8 @@ impl fmt::Display for Node {
                 if let Some(children) = &self.children {
                     for child in children {
                         match child.node_type {
-                            NodeType::File => file_children.push(child),
                             NodeType::Directory => dir_children.push(child),
+                            _ => file_children.push(child),
                         }
                     }
                 }
@@ -170,9 +498,9 @@ impl fmt::Display for Node {
                     for child in file_children {
                         writeln!(
                             f,
-                            "    {} (size: {:?} bytes)",
+                            "    {} (size: {})",
                             child.path.display(),
-                            child.size
+                            child.size_human()
                         )?;
                     }
                 }
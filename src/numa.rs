@@ -0,0 +1,149 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::thread;
+
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+/// The CPUs of every NUMA node the process may run on, from sysfs. Without NUMA
+/// information, all CPUs the process may run on make up a single node.
+fn nodes() -> Vec<Vec<usize>> {
+    let allowed = allowed_cpus();
+    let mut nodes: Vec<(usize, Vec<usize>)> = fs::read_dir("/sys/devices/system/node")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()?;
+            let list = fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            let cpus = parse_cpu_list(&list)
+                .into_iter()
+                .filter(|cpu| allowed.contains(cpu))
+                .collect::<Vec<_>>();
+            Some((id, cpus))
+        })
+        .filter(|(_, cpus)| !cpus.is_empty())
+        .collect();
+    nodes.sort();
+    match nodes.is_empty() {
+        true => vec![allowed],
+        false => nodes.into_iter().map(|(_, cpus)| cpus).collect(),
+    }
+}
+
+/// Parses a CPU list such as `0-3,8,10-11`.
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+            cpus.extend(start..=end);
+        }
+    }
+    cpus
+}
+
+/// The CPUs the calling thread may run on.
+fn allowed_cpus() -> Vec<usize> {
+    // SAFETY: `set` is a plain bit set of the size passed.
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            let count = thread::available_parallelism().map_or(1, |count| count.get());
+            return (0..count).collect();
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect()
+    }
+}
+
+/// Restricts the calling thread to `cpus`. Failing to (e.g. in a restricted cpuset)
+/// leaves the thread where it is; pinning only helps performance.
+fn pin(cpus: &[usize]) {
+    // SAFETY: `set` is a plain bit set of the size passed, and only CPUs below its
+    // capacity are added.
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+/// A pool for each NUMA node, with `threads` threads dealt out between the nodes in
+/// turn and pinned to their node's CPUs. Nodes left without a thread get no pool.
+pub(crate) fn node_pools(threads: usize) -> io::Result<Vec<(ThreadPool, usize)>> {
+    let nodes = nodes();
+    let mut counts = vec![0; nodes.len()];
+    for thread in 0..threads {
+        counts[thread % nodes.len()] += 1;
+    }
+    nodes
+        .into_iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(cpus, count)| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(count)
+                .start_handler(move |_| pin(&cpus))
+                .build()
+                .map_err(io::Error::other)?;
+            Ok((pool, count))
+        })
+        .collect()
+}
+
+/// Applies `f` to every item, splitting the items into consecutive runs, one per pool
+/// and sized by its threads, that are processed on their pool. Results keep the order
+/// of the items.
+pub(crate) fn map_sharded<T: Send, R: Send>(
+    pools: &[(ThreadPool, usize)],
+    mut items: Vec<T>,
+    f: &(impl Fn(T) -> io::Result<R> + Sync),
+) -> io::Result<Vec<R>> {
+    let threads: usize = pools.iter().map(|(_, count)| count).sum();
+    let total = items.len();
+    let mut starts = Vec::with_capacity(pools.len());
+    let mut before = 0;
+    for (_, count) in pools {
+        starts.push(total * before / threads);
+        before += count;
+    }
+    let mut shards: Vec<Vec<T>> = starts
+        .iter()
+        .rev()
+        .map(|&start| items.split_off(start))
+        .collect();
+    shards.reverse();
+
+    let results: Vec<io::Result<Vec<R>>> = thread::scope(|scope| {
+        let handles: Vec<_> = pools
+            .iter()
+            .zip(shards)
+            .map(|((pool, _), shard)| {
+                scope.spawn(move || pool.install(|| shard.into_par_iter().map(f).collect()))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    let mut all = Vec::with_capacity(total);
+    for result in results {
+        all.extend(result?);
+    }
+    Ok(all)
+}
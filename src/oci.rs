@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::diff::TreeDiff;
+use crate::node::NodeType;
+use crate::resources::{Resources, Workers};
+use crate::tree::{descendants, Tree};
+
+/// Prefix of a whiteout entry, which deletes the same-named entry of lower layers.
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// Marker making a directory opaque: lower layers' contents of it are hidden.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// What one layer of a container image contributes.
+#[derive(Debug, Clone)]
+pub struct LayerReport {
+    /// The layer's unpacked directory.
+    pub root: PathBuf,
+    /// The layer's changes to the view of the layers below it, as in-image paths
+    /// relative to `/`: new entries are added, replaced files are modified, and entries
+    /// deleted by whiteouts (or hidden by opaque directories) are removed.
+    pub changes: TreeDiff,
+    /// Bytes of files in this layer.
+    pub size: u64,
+    /// Bytes of files in this layer that later layers override or delete. They are
+    /// still shipped with the image but never visible.
+    pub wasted: u64,
+}
+
+/// The result of `analyze_layers`.
+#[derive(Debug, Clone)]
+pub struct ImageAnalysis {
+    /// One report per layer, from the bottom of the stack to the top.
+    pub layers: Vec<LayerReport>,
+    /// Bytes of files visible in the final, merged filesystem.
+    pub visible: u64,
+}
+
+impl ImageAnalysis {
+    /// Bytes shipped in all layers but hidden by a later one.
+    pub fn wasted(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.wasted).sum()
+    }
+}
+
+/// A visible entry of the merged filesystem and the layer it comes from.
+struct Visible {
+    layer: usize,
+    is_dir: bool,
+    size: u64,
+}
+
+/// Analyze the unpacked layers of an OCI image, given bottom to top, by stacking them
+/// the way the image's union filesystem would. Each layer is scanned with `Tree::new_chroot`, so
+/// symbolic links count as links, and absolute ones are never resolved on the host.
+pub fn analyze_layers(layers: &[PathBuf]) -> io::Result<ImageAnalysis> {
+    analyze_layers_with(layers, Resources::default())
+}
+
+/// Like `analyze_layers`, scanning up to `Resources::analyze_threads` layers at once.
+pub fn analyze_layers_with(layers: &[PathBuf], resources: Resources) -> io::Result<ImageAnalysis> {
+    let threads = resources.analyze_threads.max(1);
+    let mut merged: BTreeMap<PathBuf, Visible> = BTreeMap::new();
+    let mut reports: Vec<LayerReport> = Vec::new();
+
+    let workers = Workers::new(threads, resources.numa)?;
+    for batch in layers.chunks(threads) {
+        let trees = workers.map(batch.to_vec(), |root| Tree::new_chroot(&root))?;
+        for (root, tree) in batch.iter().zip(trees) {
+            stack_layer(root, &tree, &mut merged, &mut reports);
+        }
+    }
+
+    Ok(ImageAnalysis {
+        layers: reports,
+        visible: merged.values().map(|entry| entry.size).sum(),
+    })
+}
+
+/// Puts the layer at `root`, scanned as `tree`, on top of the `merged` view of those
+/// below it, adding its report to `reports`.
+fn stack_layer(
+    root: &Path,
+    tree: &Tree,
+    merged: &mut BTreeMap<PathBuf, Visible>,
+    reports: &mut Vec<LayerReport>,
+) {
+    let index = reports.len();
+    let entries = tree.entries();
+    let mut changes = TreeDiff::default();
+    reports.push(LayerReport {
+        root: root.to_path_buf(),
+        changes: TreeDiff::default(),
+        size: tree.head.size,
+        wasted: 0,
+    });
+
+    // Opaque directories hide everything below them from earlier layers.
+    for rel in entries.keys() {
+        if rel.file_name().is_some_and(|name| name == OPAQUE_MARKER) {
+            let dir = rel.parent().unwrap_or(Path::new(""));
+            for hidden in hide_below(merged, dir, reports) {
+                changes.removed.push(hidden);
+            }
+        }
+    }
+
+    for (rel, entry) in &entries {
+        let Some(name) = rel.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name == OPAQUE_MARKER {
+            continue;
+        }
+        if let Some(target) = name.strip_prefix(WHITEOUT_PREFIX) {
+            let target = rel.with_file_name(target);
+            if merged.contains_key(&target) {
+                hide(merged, &target, reports);
+                hide_below(merged, &target, reports);
+                changes.removed.push(target);
+            }
+            continue;
+        }
+
+        let is_dir = *entry.node_type == NodeType::Directory;
+        match merged.get(rel) {
+            // Directories merge; only files replace what was below.
+            Some(lower) if lower.is_dir && is_dir => {}
+            Some(_) => {
+                hide(merged, rel, reports);
+                hide_below(merged, rel, reports);
+                changes.modified.push(rel.clone());
+            }
+            None => changes.added.push(rel.clone()),
+        }
+        merged.insert(
+            rel.clone(),
+            Visible {
+                layer: index,
+                is_dir,
+                size: if is_dir { 0 } else { entry.size },
+            },
+        );
+    }
+
+    changes.removed.sort();
+    changes.removed.dedup();
+    reports[index].changes = changes;
+}
+
+/// Removes `rel` from the merged view, charging its bytes to the layer it came from.
+fn hide(merged: &mut BTreeMap<PathBuf, Visible>, rel: &Path, reports: &mut [LayerReport]) {
+    if let Some(entry) = merged.remove(rel) {
+        reports[entry.layer].wasted += entry.size;
+    }
+}
+
+/// Removes everything below `dir` from the merged view, returning the removed paths.
+fn hide_below(
+    merged: &mut BTreeMap<PathBuf, Visible>,
+    dir: &Path,
+    reports: &mut [LayerReport],
+) -> Vec<PathBuf> {
+    let below: Vec<PathBuf> = descendants(merged, dir)
+        .filter(|rel| rel.as_path() != dir)
+        .cloned()
+        .collect();
+    for rel in &below {
+        hide(merged, rel, reports);
+    }
+    below
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::analyze_layers;
+    use crate::testing::empty_dir;
+
+    #[cfg(unix)]
+    #[test]
+    fn whiteouts_waste_lower_files_and_links_stay_links() {
+        let dir = empty_dir().unwrap();
+        let host = dir.root.join("host");
+        fs::create_dir(&host).unwrap();
+        fs::write(host.join("big"), vec![0; 4096]).unwrap();
+        let (lower, upper) = (dir.root.join("lower"), dir.root.join("upper"));
+        fs::create_dir_all(lower.join("etc/alternatives")).unwrap();
+        fs::write(lower.join("etc/passwd"), "root").unwrap();
+        std::os::unix::fs::symlink(&host, lower.join("etc/alternatives/editor")).unwrap();
+        fs::create_dir_all(upper.join("etc")).unwrap();
+        fs::write(upper.join("etc/.wh.passwd"), "").unwrap();
+        fs::write(upper.join("etc/motd"), "hi!").unwrap();
+
+        let analysis = analyze_layers(&[lower, upper]).unwrap();
+        let link = host.as_os_str().len() as u64;
+        assert_eq!(analysis.layers[0].size, 4 + link);
+        assert_eq!(analysis.layers[0].wasted, 4);
+        assert_eq!(
+            analysis.layers[1].changes.removed,
+            [PathBuf::from("etc/passwd")]
+        );
+        assert_eq!(
+            analysis.layers[1].changes.added,
+            [PathBuf::from("etc/motd")]
+        );
+        assert_eq!(analysis.visible, 3 + link);
+    }
+}
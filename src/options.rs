@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use crate::checksum::HashAlgorithm;
+use crate::patterns::PathPatterns;
+
+/// The settings a tree was scanned with.
+/// Recorded on every `Tree` so that snapshots can tell whether two scans are comparable,
+/// and so that refreshes rescan the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanOptions {
+    /// Whether symbolic links are followed while scanning. Links that are not followed,
+    /// and dangling ones, are recorded as `NodeType::Symlink` nodes.
+    pub follow_symlinks: bool,
+    /// Maximum depth below the root that was scanned, if limited. Directories at that
+    /// depth are recorded without their children.
+    pub max_depth: Option<usize>,
+    /// Whether directories are only listed when first accessed; see `Tree::new_lazy`.
+    pub lazy: bool,
+    /// Whether hidden entries were included: those whose name starts with a dot, and on
+    /// Windows those with the hidden attribute.
+    pub include_hidden: bool,
+    /// Whether the scan stayed on the root's file system, recording directories on other
+    /// file systems (mount points) without their children. Only honoured on Unix.
+    pub same_file_system: bool,
+    /// Patterns, as `PathPatterns` takes them, for the paths relative to the root that
+    /// were left out along with everything below them, e.g. `**/node_modules`. Not
+    /// honoured for lazy trees.
+    pub excludes: Vec<String>,
+    /// The order of each directory's children.
+    pub sort: SortOrder,
+    /// What happens when an entry below the root cannot be read.
+    pub errors: ErrorPolicy,
+    /// Whether extended attributes were read into `ExtendedMetadata::xattrs`. Only
+    /// honoured with the `xattr` feature, and not part of comparability.
+    pub xattrs: bool,
+    /// Whether directory sizes count a file with several hard links in the tree once,
+    /// for the first link in tree order, rather than once per link.
+    pub hardlinks_once: bool,
+    /// Which size of each entry directory sizes add up.
+    pub size_metric: SizeMetric,
+    /// The algorithm file checksums were computed with into
+    /// `ExtendedMetadata::checksum`, if any. Not part of comparability.
+    pub checksums: Option<HashAlgorithm>,
+    /// Whether the first bytes of each file were read to detect its MIME type into
+    /// `ExtendedMetadata::mime_type`. Not part of comparability.
+    pub mime_types: bool,
+}
+
+/// The order in which a directory's children are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SortOrder {
+    /// The order the operating system lists them in.
+    #[default]
+    Unsorted,
+    /// By file name.
+    Name,
+    /// By size, largest first, ties by name. Sorted as of the directory's last scan.
+    LargestFirst,
+}
+
+/// Which size of an entry is recorded in `Node::size` and added up into directories'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SizeMetric {
+    /// The length of each entry, as `ls -l` and `du --apparent-size` show it. Sparse
+    /// files count in full.
+    #[default]
+    Apparent,
+    /// The space allocated on disk to each entry: its blocks times 512, as `du` shows
+    /// it. Where blocks are not recorded, the length is used. Directories add their own
+    /// blocks to what is below them.
+    DiskUsage,
+}
+
+/// What a scan does with entries below the root that cannot be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorPolicy {
+    /// Fail the whole scan with the error.
+    #[default]
+    Abort,
+    /// Leave the entry out and carry on.
+    Skip,
+    /// Leave the entry out as `Skip` does, and record its path and the error, to be
+    /// returned by `TreeBuilder::build_collecting`. Elsewhere, e.g. when refreshing,
+    /// this acts as `Skip`.
+    Collect,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: true,
+            max_depth: None,
+            lazy: false,
+            include_hidden: true,
+            same_file_system: false,
+            excludes: Vec::new(),
+            sort: SortOrder::Unsorted,
+            errors: ErrorPolicy::Abort,
+            xattrs: false,
+            hardlinks_once: false,
+            size_metric: SizeMetric::Apparent,
+            checksums: None,
+            mime_types: false,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Describes each setting that differs between `self` and `other`.
+    /// An empty list means trees scanned with either set of options are comparable.
+    /// The sort order, and whether extended attributes were read, checksums computed or
+    /// MIME types detected, do not affect comparability.
+    pub fn differences(&self, other: &ScanOptions) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.follow_symlinks != other.follow_symlinks {
+            differences.push(format!(
+                "follow_symlinks: {} vs {}",
+                self.follow_symlinks, other.follow_symlinks
+            ));
+        }
+        if self.max_depth != other.max_depth {
+            differences.push(format!(
+                "max_depth: {:?} vs {:?}",
+                self.max_depth, other.max_depth
+            ));
+        }
+        if self.lazy != other.lazy {
+            differences.push(format!("lazy: {} vs {}", self.lazy, other.lazy));
+        }
+        if self.include_hidden != other.include_hidden {
+            differences.push(format!(
+                "include_hidden: {} vs {}",
+                self.include_hidden, other.include_hidden
+            ));
+        }
+        if self.same_file_system != other.same_file_system {
+            differences.push(format!(
+                "same_file_system: {} vs {}",
+                self.same_file_system, other.same_file_system
+            ));
+        }
+        if self.excludes != other.excludes {
+            differences.push(format!(
+                "excludes: {:?} vs {:?}",
+                self.excludes, other.excludes
+            ));
+        }
+        if self.hardlinks_once != other.hardlinks_once {
+            differences.push(format!(
+                "hardlinks_once: {} vs {}",
+                self.hardlinks_once, other.hardlinks_once
+            ));
+        }
+        if self.size_metric != other.size_metric {
+            differences.push(format!(
+                "size_metric: {:?} vs {:?}",
+                self.size_metric, other.size_metric
+            ));
+        }
+        if self.errors != other.errors {
+            differences.push(format!("errors: {:?} vs {:?}", self.errors, other.errors));
+        }
+        differences
+    }
+}
+
+/// The `ScanOptions::excludes` of a tree, ready to match its paths against.
+#[derive(Debug, Clone)]
+pub(crate) struct Excludes {
+    root: PathBuf,
+    patterns: PathPatterns,
+}
+
+impl Excludes {
+    /// The excludes in `options`, for a tree rooted at `root`.
+    pub(crate) fn new(options: &ScanOptions, root: &Path) -> Self {
+        let mut patterns = PathPatterns::new();
+        for pattern in &options.excludes {
+            patterns.add(pattern);
+        }
+        Self {
+            root: root.to_path_buf(),
+            patterns,
+        }
+    }
+
+    /// Returns `true` if the entry at `path`, below the root, is left out.
+    pub(crate) fn covers(&self, path: &Path) -> bool {
+        !self.patterns.is_empty()
+            && path
+                .strip_prefix(&self.root)
+                .is_ok_and(|rel| !rel.as_os_str().is_empty() && self.patterns.matches(rel))
+    }
+}
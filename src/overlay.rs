@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use crate::node::{ExtendedMetadata, NodeType};
+use crate::snapshot::SnapshotEntry;
+use crate::tree::{descendants, Tree};
+
+/// Marker file making a directory opaque, as written by aufs and OCI tooling.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+/// Prefix of an aufs/OCI-style whiteout file.
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// Extended attributes overlayfs uses to mark a directory opaque.
+const OPAQUE_XATTRS: [&str; 2] = ["trusted.overlay.opaque", "user.overlay.opaque"];
+
+impl Tree {
+    /// Build the merged view of an overlay filesystem from its layers, without mounting
+    /// it: what a container running on it would see.
+    ///
+    /// `lowers` are given top-most first, as in overlayfs' `lowerdir=` option, and
+    /// `upper` is stacked above them all. Whiteouts (0/0 character devices, or `.wh.`
+    /// files) delete entries of lower layers, and opaque directories hide their lower
+    /// contents. Symbolic links are recorded with their targets and never followed.
+    /// Entries are reported under `root`, typically the overlay's mount point;
+    /// refreshing or comparing contents reads from there.
+    pub fn overlay(lowers: &[PathBuf], upper: &Path, root: &Path) -> io::Result<Self> {
+        let mut merged = BTreeMap::new();
+        for layer in lowers.iter().rev().map(PathBuf::as_path).chain([upper]) {
+            merge_layer(&mut merged, layer, Path::new(""))?;
+        }
+        let mut tree = Tree::from_entries(root.to_path_buf(), merged.into_values());
+        if let Ok(metadata) = ExtendedMetadata::from_path(upper) {
+            tree.head.metadata = metadata;
+        }
+        Ok(tree)
+    }
+}
+
+/// Stacks the directory `rel` of `layer` onto the entries merged so far.
+fn merge_layer(
+    merged: &mut BTreeMap<PathBuf, SnapshotEntry>,
+    layer: &Path,
+    rel: &Path,
+) -> io::Result<()> {
+    let dir = layer.join(rel);
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let child = rel.join(&name);
+        let link_metadata = entry.metadata()?;
+
+        if name == OPAQUE_MARKER {
+            continue;
+        }
+        if let Some(target) = name.to_str().and_then(|n| n.strip_prefix(WHITEOUT_PREFIX)) {
+            remove_subtree(merged, &rel.join(target));
+            continue;
+        }
+        if link_metadata.file_type().is_char_device() && link_metadata.rdev() == 0 {
+            remove_subtree(merged, &child);
+            continue;
+        }
+
+        let path = entry.path();
+        // Links are not followed: an absolute target is a path inside the merged view,
+        // not on the host.
+        let metadata = link_metadata;
+        if metadata.is_dir() {
+            let lower_is_dir = merged
+                .get(&child)
+                .is_some_and(|lower| lower.node_type == NodeType::Directory);
+            if !lower_is_dir || is_opaque(&path) {
+                remove_subtree(merged, &child);
+            }
+            merged.insert(
+                child.clone(),
+                entry_for(&child, &path, NodeType::Directory, 0),
+            );
+            merge_layer(merged, layer, &child)?;
+        } else {
+            remove_subtree(merged, &child);
+            let node_type = match metadata.file_type().is_symlink() {
+                true => NodeType::Symlink {
+                    target: fs::read_link(&path)?,
+                },
+                false => NodeType::File,
+            };
+            let file = entry_for(&child, &path, node_type, metadata.len());
+            merged.insert(child, file);
+        }
+    }
+    Ok(())
+}
+
+fn entry_for(rel: &Path, path: &Path, node_type: NodeType, size: u64) -> SnapshotEntry {
+    SnapshotEntry {
+        path: rel.to_path_buf(),
+        node_type,
+        size,
+        metadata: ExtendedMetadata::from_path(path).unwrap_or_default(),
+    }
+}
+
+/// Returns `true` if the directory at `path` hides the contents of lower layers.
+/// Attributes that cannot be read (e.g. `trusted.*` without privileges) count as unset.
+fn is_opaque(path: &Path) -> bool {
+    path.join(OPAQUE_MARKER).exists()
+        || OPAQUE_XATTRS
+            .iter()
+            .any(|name| matches!(xattr::get(path, name), Ok(Some(value)) if value == b"y"))
+}
+
+/// Removes `rel` and everything below it.
+fn remove_subtree(merged: &mut BTreeMap<PathBuf, SnapshotEntry>, rel: &Path) {
+    let doomed: Vec<PathBuf> = descendants(merged, rel).cloned().collect();
+    for path in doomed {
+        merged.remove(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::node::NodeType;
+    use crate::testing::empty_dir;
+    use crate::tree::Tree;
+
+    #[test]
+    fn upper_layers_delete_hide_and_link() {
+        let dir = empty_dir().unwrap();
+        let (lower, upper) = (dir.root.join("lower"), dir.root.join("upper"));
+        let host = dir.root.join("host");
+        fs::create_dir(&host).unwrap();
+        fs::write(host.join("big"), vec![0; 4096]).unwrap();
+        for sub in ["a", "b"] {
+            fs::create_dir_all(lower.join(sub)).unwrap();
+            fs::create_dir_all(upper.join(sub)).unwrap();
+        }
+        fs::write(lower.join("a/kept"), "12").unwrap();
+        fs::write(lower.join("a/gone"), "123").unwrap();
+        fs::write(lower.join("b/hidden"), "1234").unwrap();
+        fs::write(upper.join("a/.wh.gone"), "").unwrap();
+        fs::write(upper.join("b/.wh..wh..opq"), "").unwrap();
+        fs::write(upper.join("b/new"), "12345").unwrap();
+        std::os::unix::fs::symlink(&host, upper.join("link")).unwrap();
+
+        let root = PathBuf::from("/merged");
+        let tree = Tree::overlay(&[lower], &upper, &root).unwrap();
+        let mut paths: Vec<PathBuf> = tree.iter().map(|node| node.path.clone()).collect();
+        paths.sort();
+        let expected = ["", "/a", "/a/kept", "/b", "/b/new", "/link"];
+        assert_eq!(
+            paths,
+            expected.map(|rel| PathBuf::from(format!("/merged{rel}")))
+        );
+        let link = tree.get_node(&root.join("link")).unwrap();
+        assert_eq!(link.node_type, NodeType::Symlink { target: host });
+        assert_eq!(tree.head.size, 2 + 5 + link.size);
+    }
+}
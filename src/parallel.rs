@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::builder::TreeBuilder;
+use crate::diff::{pair_children, walk_subtree, ComparePolicy, DiffChange, Sides};
+use crate::node::{enter_dir, stat_entry, ExtendedMetadata, Node, NodeType};
+use crate::options::{ScanOptions, SizeMetric};
+use crate::platform::{dir_id, link_count, DirId};
+use crate::resources::Resources;
+use crate::tree::Tree;
+
+impl Node {
+    /// Like `new`, but scans the directories below `path` in parallel on the current
+    /// rayon thread pool (the global one unless called from within `ThreadPool::install`).
+    pub fn new_parallel(path: PathBuf) -> io::Result<Self> {
+        Self::new_parallel_below(path, &[])
+    }
+
+    /// Like `new_parallel`, for an entry reached through the directories `ancestors`.
+    fn new_parallel_below(path: PathBuf, ancestors: &[DirId]) -> io::Result<Self> {
+        let (metadata, node_type) = stat_entry(&path, true)?;
+        let directory = node_type == NodeType::Directory;
+        let mut node = Self::from_parts(
+            path,
+            node_type,
+            ExtendedMetadata::from_metadata(&metadata),
+            metadata.len(),
+        );
+        if !directory {
+            return Ok(node);
+        }
+        node.children = None;
+        if let Some(id) = enter_dir(&mut node, &metadata, ancestors)? {
+            let mut below = ancestors.to_vec();
+            below.push(id);
+            node.populate_children_parallel_below(&below)?;
+        }
+        Ok(node)
+    }
+
+    /// Like `populate_children`, but builds the children in parallel and sums their
+    /// sizes into this node's, so no separate `calc_size` pass is needed.
+    pub fn populate_children_parallel(&mut self) -> io::Result<()> {
+        if !self.is_dir() {
+            return Ok(());
+        }
+        let id = dir_id(&self.path, &fs::metadata(&self.path)?)?;
+        self.populate_children_parallel_below(&[id])
+    }
+
+    /// Like `populate_children_parallel`, for a directory reached through `ancestors`
+    /// (itself included).
+    fn populate_children_parallel_below(&mut self, ancestors: &[DirId]) -> io::Result<()> {
+        let paths = fs::read_dir(&self.path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        let children = paths
+            .into_par_iter()
+            .map(|path| Node::new_parallel_below(path, ancestors))
+            .collect::<io::Result<Vec<_>>>()?;
+        self.size = children.iter().map(|child| child.size).sum();
+        self.children = Some(children);
+        Ok(())
+    }
+
+    /// Like `calc_size`, but re-reads file sizes in parallel.
+    pub fn calc_size_parallel(&mut self) -> io::Result<()> {
+        self.read_sizes_parallel()?;
+        self.sum_sizes_once(SizeMetric::Apparent, &mut HashSet::new());
+        Ok(())
+    }
+
+    /// Like `read_sizes`, reading the children of each directory in parallel.
+    fn read_sizes_parallel(&mut self) -> io::Result<()> {
+        if self.is_file() {
+            let metadata = fs::metadata(&self.path)?;
+            self.size = metadata.len();
+            self.metadata.nlink = link_count(&metadata);
+            return Ok(());
+        }
+        if self.is_symlink() {
+            self.size = fs::symlink_metadata(&self.path)?.len();
+            return Ok(());
+        }
+        let Some(children) = &mut self.children else {
+            return self.populate_children_parallel();
+        };
+        children
+            .par_iter_mut()
+            .try_for_each(|child| child.read_sizes_parallel())
+    }
+}
+
+impl Tree {
+    /// Create a tree from `root` scanned with `options`, fanning the scan out over
+    /// `resources.scan_threads` threads. A shorthand for building with `TreeBuilder`,
+    /// so it gives the same tree a serial scan with `options` does.
+    ///
+    /// Only the initial scan is parallel; later refreshes rescan serially.
+    pub fn new_parallel(
+        root: &Path,
+        options: ScanOptions,
+        resources: Resources,
+    ) -> io::Result<Self> {
+        TreeBuilder::new(root)
+            .with_options(options)
+            .resources(resources)
+            .build()
+    }
+}
+
+impl Tree {
+    /// Like `diff_each`, but compares subtrees concurrently on the current rayon thread
+    /// pool, which pays off when contents are compared or the trees are very large.
+    /// `emit` is called from several threads at once, in no particular order. The first
+    /// error stops the comparison.
+    pub fn par_diff_each<F>(&self, other: &Tree, policy: ComparePolicy, emit: F) -> io::Result<()>
+    where
+        F: Fn(DiffChange) + Sync,
+    {
+        let sides = Sides::new(self, other, policy);
+        par_diff_children(&sides, &self.head, &other.head, &emit)
+    }
+}
+
+/// Compares the children of two directories at the same place in their trees.
+fn par_diff_children<F>(sides: &Sides<'_>, old: &Node, new: &Node, emit: &F) -> io::Result<()>
+where
+    F: Fn(DiffChange) + Sync,
+{
+    pair_children(old, new)
+        .into_par_iter()
+        .try_for_each(|pair| match pair {
+            (Some(old), Some(new)) => {
+                if !sides.same(old, new)? {
+                    emit(DiffChange::Modified(sides.old_rel(old)));
+                }
+                par_diff_children(sides, old, new, emit)
+            }
+            (Some(old), None) => {
+                walk_subtree(old, &mut |node| {
+                    emit(DiffChange::Removed(sides.old_rel(node)))
+                });
+                Ok(())
+            }
+            (None, Some(new)) => {
+                walk_subtree(new, &mut |node| {
+                    emit(DiffChange::Added(sides.new_rel(node)))
+                });
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fake_tree, TreeSpec};
+
+    fn listing(tree: &Tree) -> Vec<(PathBuf, u64, bool)> {
+        let mut listing: Vec<_> = tree
+            .iter()
+            .map(|node| (node.path.clone(), node.size, node.is_dir()))
+            .collect();
+        listing.sort();
+        listing
+    }
+
+    #[test]
+    fn parallel_and_serial_scans_agree() {
+        let fake = fake_tree(&TreeSpec {
+            breadth: 3,
+            depth: 3,
+            files_per_dir: 4,
+            ..TreeSpec::default()
+        })
+        .unwrap();
+        let options = ScanOptions {
+            max_depth: Some(2),
+            ..ScanOptions::default()
+        };
+        let serial = TreeBuilder::new(&fake.root)
+            .with_options(options.clone())
+            .build()
+            .unwrap();
+        let resources = Resources {
+            scan_threads: 4,
+            ..Resources::default()
+        };
+        let parallel = Tree::new_parallel(&fake.root, options, resources).unwrap();
+        assert_eq!(listing(&parallel), listing(&serial));
+        assert!(parallel
+            .iter()
+            .all(|node| node.path.components().count() <= fake.root.components().count() + 2));
+    }
+}
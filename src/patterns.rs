@@ -0,0 +1,153 @@
+use std::io;
+use std::path::{Component, Path};
+
+use crate::diff::{diff_entries, ComparePolicy, Entries, TreeDiff};
+use crate::node::Node;
+use crate::selection::glob_match;
+use crate::tree::Tree;
+
+/// Path patterns a consumer is interested in, so that diffs only report, and only spend
+/// time comparing, the entries that matter, e.g. `etc/**` and `**/*.conf` for
+/// configuration-drift monitoring.
+///
+/// Patterns are matched against paths relative to the root, one component at a time:
+/// `**` stands for any number of components (including none), while `*` (any run of
+/// characters), `?` (a single one) and classes such as `[abc]`, `[a-z]` or `[!0-9]` (one
+/// character of the class, or not of it) stay within a component. Matching is
+/// case-sensitive unless `ignore_case` is set.
+#[derive(Debug, Clone, Default)]
+pub struct PathPatterns {
+    patterns: Vec<Vec<Segment>>,
+    ignore_case: bool,
+}
+
+/// One component of a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// `**`.
+    AnyDepth,
+    Glob(Vec<char>),
+}
+
+impl PathPatterns {
+    /// Create a set without patterns, which matches nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pattern.
+    pub fn with_pattern(mut self, pattern: &str) -> Self {
+        self.add(pattern);
+        self
+    }
+
+    /// Add a pattern.
+    pub fn add(&mut self, pattern: &str) {
+        let segments = pattern
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .map(|part| match part {
+                "**" => Segment::AnyDepth,
+                _ => Segment::Glob(part.chars().collect()),
+            })
+            .collect();
+        self.patterns.push(segments);
+    }
+
+    /// Match letters regardless of case, e.g. for `**/*.jpg` to match `IMG_001.JPG`.
+    pub fn ignore_case(mut self, ignore: bool) -> Self {
+        self.ignore_case = ignore;
+        self
+    }
+
+    /// Returns `true` if no pattern was added.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns `true` if the relative path `rel` matches any pattern.
+    pub fn matches(&self, rel: &Path) -> bool {
+        let components: Vec<Vec<char>> = rel
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().chars().collect()),
+                _ => None,
+            })
+            .collect();
+        self.patterns
+            .iter()
+            .any(|pattern| match_segments(pattern, &components, self.ignore_case))
+    }
+}
+
+fn match_segments(pattern: &[Segment], path: &[Vec<char>], ignore_case: bool) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((Segment::AnyDepth, rest)) => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..], ignore_case))
+        }
+        Some((Segment::Glob(glob), rest)) => path.split_first().is_some_and(|(name, tail)| {
+            glob_match(glob, name, ignore_case) && match_segments(rest, tail, ignore_case)
+        }),
+    }
+}
+
+impl TreeDiff {
+    /// Keep only the differences whose path matches `patterns`.
+    pub fn retain_matching(&mut self, patterns: &PathPatterns) {
+        self.added.retain(|path| patterns.matches(path));
+        self.removed.retain(|path| patterns.matches(path));
+        self.modified.retain(|path| patterns.matches(path));
+    }
+}
+
+impl Tree {
+    /// Every node below the root whose path relative to it matches the glob `pattern`,
+    /// e.g. `**/*.log`, in `iter` order. See `PathPatterns` for the syntax, and
+    /// `glob_with` to match regardless of case or against several patterns.
+    pub fn glob(&self, pattern: &str) -> Vec<&Node> {
+        self.glob_with(&PathPatterns::new().with_pattern(pattern))
+    }
+
+    /// Every node below the root whose path relative to it matches `patterns`.
+    pub fn glob_with(&self, patterns: &PathPatterns) -> Vec<&Node> {
+        self.iter()
+            .filter(|node| {
+                node.path
+                    .strip_prefix(&self.head.path)
+                    .is_ok_and(|rel| !rel.as_os_str().is_empty() && patterns.matches(rel))
+            })
+            .collect()
+    }
+
+    /// Compare this tree against `other` like `diff`, but only entries matching `patterns`;
+    /// the others are not even compared, so no contents are read for them.
+    pub fn diff_matching(
+        &self,
+        other: &Tree,
+        policy: ComparePolicy,
+        patterns: &PathPatterns,
+    ) -> io::Result<TreeDiff> {
+        diff_entries(
+            &matching(self.entries(), patterns),
+            &matching(other.entries(), patterns),
+            policy,
+        )
+    }
+
+    /// Verify the directory at `target` like `verify`, reporting only entries matching
+    /// `patterns`. The target is still scanned in full.
+    pub fn verify_matching(
+        &self,
+        target: &Path,
+        policy: ComparePolicy,
+        patterns: &PathPatterns,
+    ) -> io::Result<TreeDiff> {
+        self.diff_matching(&Tree::new(target)?, policy, patterns)
+    }
+}
+
+fn matching<'a>(mut entries: Entries<'a>, patterns: &PathPatterns) -> Entries<'a> {
+    entries.retain(|rel, _| patterns.matches(rel));
+    entries
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::codec::{
+    fnv1a, invalid, write_bookmarks, write_metadata, write_node_type, write_option_varint,
+    write_options, write_path, write_varint, Input,
+};
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// Identifies a saved tree, version 1.
+const MAGIC: &[u8; 4] = b"FFT1";
+
+impl Tree {
+    /// Save the whole tree to the file at `path`, so that it can be reloaded with
+    /// `load_snapshot` instead of scanned again. Every node is kept as it is, including
+    /// evicted and unlisted directories, together with the scan options, bookmarks and
+    /// host root needed to refresh it later.
+    ///
+    /// Entries are written depth first with only their names, their metadata as
+    /// variable-length integers, and a checksum at the end. The file is written next to
+    /// `path` and renamed over it, so an interrupted save keeps the previous one.
+    pub fn save_snapshot(&self, path: &Path) -> io::Result<()> {
+        let mut out = MAGIC.to_vec();
+        write_options(&mut out, &self.options);
+        match &self.host_root {
+            Some(host_root) => {
+                out.push(1);
+                write_path(&mut out, host_root);
+            }
+            None => out.push(0),
+        }
+        write_varint(&mut out, self.generation);
+        write_bookmarks(&mut out, &self.bookmarks);
+        write_path(&mut out, &self.head.path);
+        write_node(&mut out, &self.head);
+        let checksum = fnv1a(&out);
+        out.extend(checksum.to_le_bytes());
+
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        if let Err(e) = fs::write(&temp, &out).and_then(|()| fs::rename(&temp, path)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Load a tree saved by `save_snapshot`, without touching the entries it describes.
+    /// It starts without indexes or live queries, as if just scanned; refresh it to pick
+    /// up what changed on disk since. Malformed files are reported as `InvalidData`.
+    pub fn load_snapshot(path: &Path) -> io::Result<Tree> {
+        let bytes = fs::read(path)?;
+        let (body, checksum) = bytes
+            .split_last_chunk::<8>()
+            .ok_or_else(|| invalid("not a saved tree"))?;
+        let mut input = Input { bytes: body };
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a saved tree"));
+        }
+        if fnv1a(body) != u64::from_le_bytes(*checksum) {
+            return Err(invalid("saved tree is corrupted"));
+        }
+
+        let options = input.options()?;
+        let host_root = match input.flag()? {
+            true => Some(input.path()?),
+            false => None,
+        };
+        let generation = input.varint()?;
+        let bookmarks = input.bookmarks()?;
+        let root = input.path()?;
+        let head = read_node(&mut input, root)?;
+        if !input.bytes.is_empty() {
+            return Err(invalid("trailing bytes after the saved tree"));
+        }
+
+        let mut tree = Tree::from_head(head);
+        tree.options = options;
+        tree.host_root = host_root;
+        tree.bookmarks = bookmarks;
+        tree.generation = generation;
+        Ok(tree)
+    }
+}
+
+/// Writes `node` and everything below it, children by name only.
+fn write_node(out: &mut Vec<u8>, node: &Node) {
+    write_node_type(out, &node.node_type);
+    write_varint(out, node.size);
+    write_metadata(out, &node.metadata);
+    write_option_varint(out, node.evicted);
+    write_varint(out, node.generation);
+    match &node.children {
+        Some(children) => {
+            write_varint(out, children.len() as u64 + 1);
+            for child in children {
+                write_path(out, child.path.file_name().unwrap_or_default().as_ref());
+                write_node(out, child);
+            }
+        }
+        None => write_varint(out, 0),
+    }
+}
+
+fn read_node(input: &mut Input, path: PathBuf) -> io::Result<Node> {
+    let node_type = input.node_type()?;
+    let size = input.varint()?;
+    let metadata = input.metadata()?;
+    let mut node = Node::from_parts(path, node_type, metadata, size);
+    node.evicted = input.option_varint()?;
+    node.generation = input.varint()?;
+    node.children = match input.option_varint()? {
+        Some(count) => {
+            let mut children = Vec::new();
+            for _ in 0..count {
+                let name = input.path()?;
+                if name.file_name() != Some(name.as_os_str()) {
+                    return Err(invalid("invalid entry name"));
+                }
+                children.push(read_node(input, node.path.join(name))?);
+            }
+            Some(children)
+        }
+        None => None,
+    };
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::TreeBuilder;
+    use crate::node::{ExtendedMetadata, NodeType};
+    use crate::testing::{fake_tree, TreeSpec};
+
+    fn listing(tree: &Tree) -> Vec<(PathBuf, NodeType, u64, ExtendedMetadata, bool)> {
+        tree.iter()
+            .map(|node| {
+                (
+                    node.path.clone(),
+                    node.node_type.clone(),
+                    node.size,
+                    node.metadata.clone(),
+                    node.children.is_some(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saved_trees_load_as_they_were() {
+        let fake = fake_tree(&TreeSpec {
+            breadth: 2,
+            depth: 3,
+            files_per_dir: 3,
+            ..TreeSpec::default()
+        })
+        .unwrap();
+        let mut tree = TreeBuilder::new(&fake.root).max_depth(2).build().unwrap();
+        let sub = tree.head.children.as_ref().unwrap()[0].path.clone();
+        tree.bookmark("sub", &sub).unwrap();
+        let saved = fake.root.join("saved.fft");
+        tree.save_snapshot(&saved).unwrap();
+
+        let loaded = Tree::load_snapshot(&saved).unwrap();
+        assert_eq!(listing(&loaded), listing(&tree));
+        assert_eq!(loaded.options, tree.options);
+        assert_eq!(loaded.bookmark_path("sub"), Some(sub));
+        assert!(loaded.validate().is_ok());
+
+        let mut bytes = fs::read(&saved).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0xff;
+        fs::write(&saved, &bytes).unwrap();
+        let loaded = Tree::load_snapshot(&saved);
+        assert!(loaded.is_err_and(|e| e.kind() == io::ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,230 @@
+#[cfg(unix)]
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, DirEntry, Metadata};
+use std::io;
+use std::path::Path;
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+use crate::node::ExtendedMetadata;
+
+/// Identifies a directory on disk, so that a link back to one of its ancestors is not
+/// followed forever.
+#[cfg(unix)]
+pub(crate) type DirId = (u64, u64);
+#[cfg(not(unix))]
+pub(crate) type DirId = std::path::PathBuf;
+
+/// The device and inode of the directory at `path`, read from its `metadata`.
+#[cfg(unix)]
+pub(crate) fn dir_id(_path: &Path, metadata: &Metadata) -> io::Result<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+/// Without stable inode numbers, the canonical path of the directory at `path`.
+#[cfg(not(unix))]
+pub(crate) fn dir_id(path: &Path, _metadata: &Metadata) -> io::Result<DirId> {
+    std::fs::canonicalize(path)
+}
+
+/// The device and inode number of an entry, if the platform has them.
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// The number of hard links to an entry, if the platform tells.
+#[cfg(unix)]
+pub(crate) fn link_count(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.nlink())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn link_count(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// The number of 512-byte blocks allocated to an entry, if the platform tells.
+#[cfg(unix)]
+pub(crate) fn block_count(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.blocks())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn block_count(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// The device an entry is on, if the platform tells.
+#[cfg(unix)]
+pub(crate) fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn device(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether `entry` is hidden: its name starts with a dot or, on Windows, it has the
+/// hidden attribute.
+pub(crate) fn is_hidden(entry: &DirEntry) -> bool {
+    // On Windows the attributes come with the directory listing, at no extra cost.
+    let named = hidden_name(&entry.file_name());
+    named || cfg!(windows) && entry.metadata().is_ok_and(|m| hidden_attribute(&m))
+}
+
+/// Like `is_hidden`, for the entry at `path`. On Windows this reads its attributes.
+pub(crate) fn is_hidden_path(path: &Path) -> bool {
+    let named = path.file_name().is_some_and(hidden_name);
+    named || cfg!(windows) && fs::symlink_metadata(path).is_ok_and(|m| hidden_attribute(&m))
+}
+
+/// Whether `name` starts with a dot.
+fn hidden_name(name: &OsStr) -> bool {
+    name.as_encoded_bytes().starts_with(b".")
+}
+
+#[cfg(windows)]
+fn hidden_attribute(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn hidden_attribute(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// The permission bits, owner and group of an entry, where the platform has them.
+#[cfg(unix)]
+pub(crate) fn ownership(metadata: &Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    (
+        Some(metadata.mode() & 0o7777),
+        Some(metadata.uid()),
+        Some(metadata.gid()),
+    )
+}
+
+#[cfg(not(unix))]
+pub(crate) fn ownership(_metadata: &Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    (None, None, None)
+}
+
+/// The name of the user `uid`, from `/etc/passwd`, read once per process.
+#[cfg(unix)]
+pub(crate) fn user_name(uid: u32) -> Option<&'static str> {
+    static USERS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    USERS
+        .get_or_init(|| read_id_names(Path::new("/etc/passwd")))
+        .get(&uid)
+        .map(String::as_str)
+}
+
+/// The name of the group `gid`, from `/etc/group`, read once per process.
+#[cfg(unix)]
+pub(crate) fn group_name(gid: u32) -> Option<&'static str> {
+    static GROUPS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    GROUPS
+        .get_or_init(|| read_id_names(Path::new("/etc/group")))
+        .get(&gid)
+        .map(String::as_str)
+}
+
+/// Reads `name:password:id:...` lines, as in `/etc/passwd` and `/etc/group`. The first
+/// name listed for an id wins; a missing or unreadable file yields no names.
+#[cfg(unix)]
+fn read_id_names(path: &Path) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in std::fs::read_to_string(path).unwrap_or_default().lines() {
+        let mut fields = line.split(':');
+        let (Some(name), Some(_), Some(id)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if let Ok(id) = id.parse() {
+            names.entry(id).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+#[cfg(not(unix))]
+pub(crate) fn user_name(_uid: u32) -> Option<&'static str> {
+    None
+}
+
+#[cfg(not(unix))]
+pub(crate) fn group_name(_gid: u32) -> Option<&'static str> {
+    None
+}
+
+/// The permission bits, owner and group of an entry.
+#[cfg(unix)]
+pub(crate) type Permissions = (u32, u32, u32);
+/// Whether an entry is read-only, the only permission available here.
+#[cfg(not(unix))]
+pub(crate) type Permissions = bool;
+
+#[cfg(unix)]
+pub(crate) fn read_permissions(metadata: &Metadata) -> Permissions {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.mode() & 0o7777, metadata.uid(), metadata.gid())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn read_permissions(metadata: &Metadata) -> Permissions {
+    metadata.permissions().readonly()
+}
+
+/// The permissions recorded in `metadata`, if all of them were.
+#[cfg(unix)]
+pub(crate) fn recorded_permissions(metadata: &ExtendedMetadata) -> Option<Permissions> {
+    Some((metadata.mode?, metadata.uid?, metadata.gid?))
+}
+
+/// Read-only flags are not recorded; they are always read from disk.
+#[cfg(not(unix))]
+pub(crate) fn recorded_permissions(_metadata: &ExtendedMetadata) -> Option<Permissions> {
+    None
+}
+
+/// Creates a symbolic link at `path` pointing to `target`.
+#[cfg(unix)]
+pub(crate) fn create_symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+/// Creates a symbolic link at `path` pointing to `target`, as a directory link if the
+/// target (resolved next to the link) is a directory, and a file link otherwise.
+#[cfg(windows)]
+pub(crate) fn create_symlink(target: &Path, path: &Path) -> io::Result<()> {
+    let resolved = match path.parent() {
+        Some(parent) => parent.join(target),
+        None => target.to_path_buf(),
+    };
+    match std::fs::metadata(resolved).is_ok_and(|metadata| metadata.is_dir()) {
+        true => std::os::windows::fs::symlink_dir(target, path),
+        false => std::os::windows::fs::symlink_file(target, path),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn create_symlink(_target: &Path, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symbolic links are not supported on this platform",
+    ))
+}
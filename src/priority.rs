@@ -0,0 +1,109 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::DirEntry;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::snapshot::Snapshot;
+
+/// The order a scan descends into the entries of each directory in, so that a scan cut
+/// short by a `ScanBudget` has covered the parts that matter most. Set with
+/// `TreeBuilder::priority`; it only affects the initial scan, not refreshes.
+///
+/// With several scan threads the entries are handed out in this order but may finish in
+/// any. Children are kept in the order scanned unless a `SortOrder` is set.
+#[derive(Debug, Clone, Default)]
+pub enum ScanPriority {
+    /// The order the operating system lists entries in.
+    #[default]
+    Listing,
+    /// Most recently modified first, by each entry's own modification time, which costs
+    /// an extra `stat` per entry.
+    NewestFirst,
+    /// Largest first, by their sizes in an earlier scan keyed by path relative to the
+    /// root; entries without a size come last. See `largest_from`.
+    LargestFirst(HashMap<PathBuf, u64>),
+}
+
+impl ScanPriority {
+    /// Largest first, by the sizes recorded in `previous`, e.g. the last scan of the
+    /// same root.
+    pub fn largest_from(previous: &Snapshot) -> Self {
+        let sizes = previous
+            .entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.size))
+            .collect();
+        ScanPriority::LargestFirst(sizes)
+    }
+
+    /// Puts the `entries` of the directory at `rel`, relative to the root, in this
+    /// order. Entries that could not be listed go last.
+    pub(crate) fn order(&self, rel: &Path, entries: &mut [io::Result<DirEntry>]) {
+        match self {
+            ScanPriority::Listing => {}
+            ScanPriority::NewestFirst => entries.sort_by_cached_key(|entry| {
+                let entry = entry.as_ref().ok();
+                Reverse(entry.and_then(|entry| entry.metadata().ok()?.modified().ok()))
+            }),
+            ScanPriority::LargestFirst(sizes) => entries.sort_by_cached_key(|entry| {
+                let entry = entry.as_ref().ok();
+                Reverse(entry.and_then(|entry| sizes.get(&rel.join(entry.file_name())).copied()))
+            }),
+        }
+    }
+}
+
+/// When a scan stops descending, leaving the directories it has not entered yet
+/// unlisted (`Node::children` is `None`), as for directories beyond the maximum depth.
+/// Set with `TreeBuilder::budget`; without limits the whole tree is scanned.
+///
+/// The entries of the directory being listed when a limit is reached are still read, so
+/// the scan may overshoot `max_entries` by about a directory's worth.
+#[derive(Debug, Clone, Default)]
+pub struct ScanBudget {
+    /// Stop after about this many entries.
+    pub max_entries: Option<u64>,
+    /// Stop at this time.
+    pub deadline: Option<Instant>,
+    /// Stop once this is set, e.g. from another thread when the user cancels.
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+/// What a scan has used of its budget.
+pub(crate) struct Spent<'a> {
+    budget: &'a ScanBudget,
+    entries: AtomicU64,
+}
+
+impl<'a> Spent<'a> {
+    pub(crate) fn new(budget: &'a ScanBudget) -> Self {
+        Self {
+            budget,
+            entries: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a scanned entry.
+    pub(crate) fn entry(&self) {
+        self.entries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns `true` once any limit is reached.
+    pub(crate) fn exhausted(&self) -> bool {
+        let budget = self.budget;
+        budget
+            .max_entries
+            .is_some_and(|max| self.entries.load(Ordering::Relaxed) >= max)
+            || budget
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            || budget
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+}
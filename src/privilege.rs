@@ -0,0 +1,82 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::tree::Tree;
+
+/// The root of a scan, opened while the process still holds its privileges.
+///
+/// This is the first half of a privilege handoff for agents that start as root: open
+/// the root with `open`, give up privileges with `drop_privileges`, and only then scan
+/// the returned `ReducedRoot`. Nothing can be scanned before the drop has succeeded,
+/// and the scan reaches every entry relative to the opened root (see `Tree::scan_dir`),
+/// so no path is resolved with elevated rights.
+#[derive(Debug)]
+pub struct PrivilegedRoot {
+    dir: OwnedFd,
+    path: PathBuf,
+}
+
+/// A root whose process has given up its privileges, ready to be scanned.
+#[derive(Debug)]
+pub struct ReducedRoot {
+    dir: OwnedFd,
+    path: PathBuf,
+}
+
+impl PrivilegedRoot {
+    /// Open the directory at `root`.
+    pub fn open(root: &Path) -> io::Result<Self> {
+        let dir = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_CLOEXEC)
+            .open(root)?;
+        Ok(Self {
+            dir: dir.into(),
+            path: root.to_path_buf(),
+        })
+    }
+
+    /// Switch the whole process to `uid` and `gid`, clearing supplementary groups, and
+    /// verify that the old privileges cannot be regained. This affects every thread,
+    /// so do it before starting anything that relies on the old identity.
+    pub fn drop_privileges(self, uid: u32, gid: u32) -> io::Result<ReducedRoot> {
+        unsafe {
+            if libc::geteuid() == 0 && libc::setgroups(0, std::ptr::null()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setgid(gid) != 0 || libc::setuid(uid) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let dropped = libc::getuid() == uid
+                && libc::geteuid() == uid
+                && libc::getgid() == gid
+                && libc::getegid() == gid;
+            if !dropped || (uid != 0 && libc::setuid(0) == 0) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "privileges are still held after dropping them",
+                ));
+            }
+        }
+        Ok(ReducedRoot {
+            dir: self.dir,
+            path: self.path,
+        })
+    }
+}
+
+impl ReducedRoot {
+    /// The path the root was opened from, used for reporting.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Scan the root. Entries the reduced identity cannot read fail the scan.
+    pub fn scan(self) -> io::Result<Tree> {
+        Tree::scan_dir(self.dir, &self.path)
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::tree::Tree;
+
+/// A file in a tree held open by a running process, as found by `Tree::open_files`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFile {
+    /// The tree's path of the open entry.
+    pub path: PathBuf,
+    /// The process holding it open.
+    pub pid: u32,
+    /// The process's command name, as in `/proc/<pid>/comm`.
+    pub command: String,
+    /// The file descriptor it is open as.
+    pub fd: u32,
+}
+
+impl Tree {
+    /// The entries of the tree currently held open by running processes, found by
+    /// reading every `/proc/<pid>/fd` and matching the descriptors' targets with the
+    /// tree's paths, sorted by path, then process and descriptor. Useful before running
+    /// a cleanup or migration that would pull files from under a process.
+    ///
+    /// Only the descriptors of processes the caller may inspect (its own user's, or all
+    /// as root) are seen; others are skipped, as are processes that exit meanwhile.
+    /// Files that were deleted while open, memory mappings and working directories are
+    /// not reported.
+    pub fn open_files(&self) -> io::Result<Vec<OpenFile>> {
+        let root = fs::canonicalize(self.host_root())?;
+        let mut open = Vec::new();
+        for entry in fs::read_dir("/proc")? {
+            let entry = entry?;
+            let Some(pid) = entry.file_name().to_str().and_then(|pid| pid.parse().ok()) else {
+                continue;
+            };
+            let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+                continue;
+            };
+            let mut command = None;
+            for fd in fds.flatten() {
+                let Some(number) = fd.file_name().to_str().and_then(|fd| fd.parse().ok()) else {
+                    continue;
+                };
+                let Some(path) = fs::read_link(fd.path())
+                    .ok()
+                    .and_then(|target| self.tree_path(&root, &target))
+                else {
+                    continue;
+                };
+                let command = command.get_or_insert_with(|| command_name(&entry.path()));
+                open.push(OpenFile {
+                    path,
+                    pid,
+                    command: command.clone(),
+                    fd: number,
+                });
+            }
+        }
+        open.sort_by(|a, b| (&a.path, a.pid, a.fd).cmp(&(&b.path, b.pid, b.fd)));
+        Ok(open)
+    }
+
+    /// The tree's path for `target`, a location on disk, if it is in the tree. `root`
+    /// is the canonical location of the tree's root.
+    fn tree_path(&self, root: &Path, target: &Path) -> Option<PathBuf> {
+        let rel = target.strip_prefix(root).ok()?;
+        let path = self.head.path.join(rel);
+        self.get_node(&path)?;
+        Some(path)
+    }
+}
+
+/// The command name of the process at `/proc/<pid>`, or an empty one if it exited.
+fn command_name(process: &Path) -> String {
+    fs::read_to_string(process.join("comm"))
+        .map(|comm| comm.trim_end().to_string())
+        .unwrap_or_default()
+}
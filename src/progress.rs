@@ -0,0 +1,113 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How far a scan has got, handed to a `ProgressReporter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanProgress<'a> {
+    /// Entries scanned so far, the root included.
+    pub entries: u64,
+    /// Combined size of the files and links scanned so far.
+    pub bytes: u64,
+    /// The directory being entered; the root in the final report.
+    pub directory: &'a Path,
+    /// Time since the scan started.
+    pub elapsed: Duration,
+    /// Whether this is the final report, sent once the scan is done.
+    pub finished: bool,
+}
+
+/// Receives progress reports while a tree is scanned, e.g. to drive a progress bar or
+/// log heartbeat lines. Set with `TreeBuilder::progress`. Closures taking a
+/// `&ScanProgress` are reporters.
+///
+/// With several scan threads, reports may come from any of them.
+pub trait ProgressReporter: Send + Sync {
+    /// Called with the progress so far.
+    fn report(&self, progress: &ScanProgress<'_>);
+}
+
+impl<F> ProgressReporter for F
+where
+    F: Fn(&ScanProgress<'_>) + Send + Sync,
+{
+    fn report(&self, progress: &ScanProgress<'_>) {
+        self(progress)
+    }
+}
+
+/// A reporter and how often it wants to hear, as kept by `TreeBuilder`.
+#[derive(Clone)]
+pub(crate) struct Reporting {
+    pub(crate) reporter: Arc<dyn ProgressReporter>,
+    pub(crate) interval: Duration,
+}
+
+impl fmt::Debug for Reporting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reporting")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a scan has counted, reported as `reporting` asks.
+pub(crate) struct Tally<'a> {
+    reporting: &'a Reporting,
+    started: Instant,
+    /// When the last report was due, in nanoseconds since `started`.
+    last: AtomicU64,
+    entries: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl<'a> Tally<'a> {
+    pub(crate) fn new(reporting: &'a Reporting) -> Self {
+        Self {
+            reporting,
+            started: Instant::now(),
+            last: AtomicU64::new(0),
+            entries: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a scanned entry of `size` bytes; directories count 0.
+    pub(crate) fn entry(&self, size: u64) {
+        self.entries.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Reports entering `dir`, if a report is due. Of threads finding one due at the same
+    /// time, one reports.
+    pub(crate) fn directory(&self, dir: &Path) {
+        let now = self.started.elapsed().as_nanos() as u64;
+        let last = self.last.load(Ordering::Relaxed);
+        if now.saturating_sub(last) < self.reporting.interval.as_nanos() as u64 {
+            return;
+        }
+        let claimed = self
+            .last
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed);
+        if claimed.is_ok() {
+            self.report(dir, false);
+        }
+    }
+
+    /// Sends the final report, for the scan of `root`.
+    pub(crate) fn finish(&self, root: &Path) {
+        self.report(root, true);
+    }
+
+    fn report(&self, directory: &Path, finished: bool) {
+        self.reporting.reporter.report(&ScanProgress {
+            entries: self.entries.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            directory,
+            elapsed: self.started.elapsed(),
+            finished,
+        });
+    }
+}
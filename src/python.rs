@@ -0,0 +1,275 @@
+//! Python bindings, exposed as the `file_frontier._native` extension module and
+//! re-exported by the `file_frontier` package under `python/`. Build with maturin,
+//! which picks up the `python` feature from `pyproject.toml`.
+//!
+//! Scans, refreshes and waits for events release the GIL, so other Python threads keep
+//! running while a large tree is walked.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, UNIX_EPOCH};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::diff::ComparePolicy;
+use crate::event::{FsEvent, RescanPolicy};
+use crate::node::Node;
+use crate::selection::glob_match;
+use crate::tree::Tree;
+use crate::source::EventSource;
+use crate::watcher::FsWatcher;
+
+/// How long a blocking wait runs between checks for pending signals such as Ctrl-C.
+const SIGNAL_CHECK: Duration = Duration::from_millis(200);
+
+/// How long the watcher waits after a change for the rest of a burst.
+const BATCH_WINDOW: Duration = Duration::from_millis(100);
+
+/// A read-only copy of a node, detached from its tree.
+#[pyclass(name = "Node", module = "file_frontier", frozen, get_all)]
+struct PyNode {
+    path: String,
+    is_dir: bool,
+    size: u64,
+    /// Modification time in seconds since the epoch, if known.
+    modified: Option<f64>,
+}
+
+impl PyNode {
+    fn from_node(node: &Node) -> Self {
+        Self {
+            path: node.path.to_string_lossy().into_owned(),
+            is_dir: node.is_dir(),
+            size: node.size,
+            modified: node
+                .metadata
+                .modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs_f64()),
+        }
+    }
+}
+
+#[pymethods]
+impl PyNode {
+    fn __repr__(&self) -> String {
+        let kind = if self.is_dir { "dir" } else { "file" };
+        format!("Node({:?}, {}, size={})", self.path, kind, self.size)
+    }
+}
+
+/// Paths that differ between two trees, relative to their roots.
+#[pyclass(name = "Diff", module = "file_frontier", frozen, get_all)]
+struct PyDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+/// A scanned directory tree.
+#[pyclass(name = "Tree", module = "file_frontier", frozen)]
+struct PyTree {
+    tree: Arc<Mutex<Tree>>,
+}
+
+#[pymethods]
+impl PyTree {
+    /// Scan the directory at `root`.
+    #[new]
+    fn new(py: Python<'_>, root: PathBuf) -> PyResult<Self> {
+        let tree = py.detach(|| Tree::new(&root))?;
+        Ok(Self {
+            tree: Arc::new(Mutex::new(tree)),
+        })
+    }
+
+    /// The root directory.
+    #[getter]
+    fn root(&self) -> String {
+        lock(&self.tree).head.path.to_string_lossy().into_owned()
+    }
+
+    /// The total size of the tree in bytes.
+    #[getter]
+    fn size(&self) -> u64 {
+        lock(&self.tree).head.size
+    }
+
+    /// Rescan the whole tree from disk.
+    fn refresh(&self, py: Python<'_>) -> PyResult<()> {
+        py.detach(|| lock(&self.tree).refresh())?;
+        Ok(())
+    }
+
+    /// The node at `path`, or None.
+    fn get(&self, path: PathBuf) -> Option<PyNode> {
+        lock(&self.tree).get_node(&path).map(PyNode::from_node)
+    }
+
+    /// Every node whose file name matches `pattern` (`*`, `?` and `[...]` wildcards).
+    fn search(&self, pattern: &str) -> Vec<PyNode> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        lock(&self.tree)
+            .search(|node| {
+                node.path.file_name().is_some_and(|name| {
+                    let name: Vec<char> = name.to_string_lossy().chars().collect();
+                    glob_match(&pattern, &name, false)
+                })
+            })
+            .into_iter()
+            .map(PyNode::from_node)
+            .collect()
+    }
+
+    /// `(files, dirs, bytes)` totals.
+    fn stats(&self) -> (u64, u64, u64) {
+        let tree = lock(&self.tree);
+        let dirs = tree.iter().filter(|node| node.is_dir()).count() as u64;
+        let files = tree.iter().count() as u64 - dirs;
+        (files, dirs, tree.head.size)
+    }
+
+    /// Compare this (older) tree against `newer`. `policy` is one of "size_mtime"
+    /// (the default), "metadata", "content" or "full".
+    #[pyo3(signature = (newer, policy = "size_mtime"))]
+    fn diff(&self, py: Python<'_>, newer: &PyTree, policy: &str) -> PyResult<PyDiff> {
+        let policy = match policy {
+            "size_mtime" => ComparePolicy::SizeMtime,
+            "metadata" => ComparePolicy::MetadataOnly,
+            "content" => ComparePolicy::ContentOnly,
+            "full" => ComparePolicy::Full,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown compare policy {other:?}"
+                )))
+            }
+        };
+        if Arc::ptr_eq(&self.tree, &newer.tree) {
+            return Ok(PyDiff {
+                added: Vec::new(),
+                removed: Vec::new(),
+                modified: Vec::new(),
+            });
+        }
+        let diff = py.detach(|| lock(&self.tree).diff(&lock(&newer.tree), policy))?;
+        let strings = |paths: Vec<PathBuf>| {
+            paths
+                .into_iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        };
+        Ok(PyDiff {
+            added: strings(diff.added),
+            removed: strings(diff.removed),
+            modified: strings(diff.modified),
+        })
+    }
+
+    /// Start watching the tree's root. Changes are applied to this tree as they are
+    /// read from the returned watcher.
+    fn watch(&self) -> PyResult<PyWatcher> {
+        let root = lock(&self.tree).head.path.clone();
+        Ok(PyWatcher {
+            watcher: Mutex::new(FsWatcher::new(&root)?),
+            tree: Arc::clone(&self.tree),
+            pending: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    fn __len__(&self) -> usize {
+        lock(&self.tree).iter().count()
+    }
+
+    fn __repr__(&self) -> String {
+        let tree = lock(&self.tree);
+        format!("Tree({:?}, size={})", tree.head.path, tree.head.size)
+    }
+}
+
+/// An iterator of `(kind, path, to)` events, where `kind` is "created", "modified",
+/// "removed", "renamed" or "desynced" and `to` is None except for renames.
+#[pyclass(name = "Watcher", module = "file_frontier", frozen)]
+struct PyWatcher {
+    watcher: Mutex<FsWatcher>,
+    tree: Arc<Mutex<Tree>>,
+    pending: Mutex<VecDeque<FsEvent>>,
+}
+
+type PyEvent = (&'static str, String, Option<String>);
+
+#[pymethods]
+impl PyWatcher {
+    /// Wait up to `timeout` seconds for a burst of changes, apply it to the tree and
+    /// return its events. Returns an empty list on timeout.
+    fn poll(&self, py: Python<'_>, timeout: f64) -> PyResult<Vec<PyEvent>> {
+        let timeout = Duration::try_from_secs_f64(timeout)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let mut events: Vec<PyEvent> = lock(&self.pending).drain(..).map(to_py).collect();
+        events.extend(self.next_batch(py, timeout)?.into_iter().map(to_py));
+        Ok(events)
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<PyEvent> {
+        loop {
+            if let Some(event) = lock(&self.pending).pop_front() {
+                return Ok(to_py(event));
+            }
+            let batch = self.next_batch(py, SIGNAL_CHECK)?;
+            lock(&self.pending).extend(batch);
+            py.check_signals()?;
+        }
+    }
+}
+
+impl PyWatcher {
+    fn next_batch(&self, py: Python<'_>, timeout: Duration) -> PyResult<Vec<FsEvent>> {
+        let batch = py.detach(|| {
+            let watcher = lock(&self.watcher);
+            let Some(first) = watcher.recv_timeout(timeout) else {
+                return Vec::new();
+            };
+            let mut batch = vec![first];
+            while let Some(event) = watcher.recv_timeout(BATCH_WINDOW) {
+                batch.push(event);
+            }
+            batch
+        });
+        if !batch.is_empty() {
+            py.detach(|| lock(&self.tree).apply_events(&batch, &RescanPolicy::default()))?;
+        }
+        Ok(batch)
+    }
+}
+
+fn to_py(event: FsEvent) -> PyEvent {
+    let lossy = |path: PathBuf| path.to_string_lossy().into_owned();
+    match event {
+        FsEvent::Created(path) => ("created", lossy(path), None),
+        FsEvent::Modified(path) => ("modified", lossy(path), None),
+        FsEvent::Removed(path) => ("removed", lossy(path), None),
+        FsEvent::Renamed { from, to } => ("renamed", lossy(from), Some(lossy(to))),
+        FsEvent::Desynced(path) => ("desynced", lossy(path), None),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[pymodule]
+#[pyo3(name = "_native")]
+fn native(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTree>()?;
+    module.add_class::<PyNode>()?;
+    module.add_class::<PyDiff>()?;
+    module.add_class::<PyWatcher>()?;
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::node::Node;
+use crate::tree::{descendants_in, Tree};
+
+type Predicate = Box<dyn Fn(&Node) -> bool + Send>;
+
+/// A saved search whose results are kept up to date as the tree is refreshed.
+///
+/// Created with `Tree::live_query`. Every rescan of the tree, whether by `refresh`,
+/// `refresh_path` or `apply_events`, re-evaluates the query over the rescanned part
+/// only and reports what changed. Dropping the handle unregisters the query.
+pub struct LiveQuery {
+    name: String,
+    results: Arc<Mutex<BTreeSet<PathBuf>>>,
+    changes: Receiver<QueryChange>,
+}
+
+/// How the results of a `LiveQuery` changed after a rescan.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryChange {
+    /// Paths that started matching.
+    pub added: Vec<PathBuf>,
+    /// Paths that stopped matching or vanished.
+    pub removed: Vec<PathBuf>,
+}
+
+impl LiveQuery {
+    /// The name the query was registered under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The paths currently matching, sorted. Matches inside evicted subtrees are kept.
+    pub fn results(&self) -> Vec<PathBuf> {
+        lock(&self.results).iter().cloned().collect()
+    }
+
+    /// The number of paths currently matching.
+    pub fn len(&self) -> usize {
+        lock(&self.results).len()
+    }
+
+    /// Returns `true` if nothing matches.
+    pub fn is_empty(&self) -> bool {
+        lock(&self.results).is_empty()
+    }
+
+    /// Return the next change if one is already pending.
+    pub fn try_recv(&self) -> Option<QueryChange> {
+        self.changes.try_recv().ok()
+    }
+
+    /// Wait up to `timeout` for the next change.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<QueryChange> {
+        self.changes.recv_timeout(timeout).ok()
+    }
+}
+
+/// The tree's side of a `LiveQuery`.
+pub(crate) struct Registered {
+    predicate: Predicate,
+    results: Weak<Mutex<BTreeSet<PathBuf>>>,
+    sender: Sender<QueryChange>,
+}
+
+impl Tree {
+    /// Register a named query, evaluated now over the resident nodes and kept up to date
+    /// on every rescan from then on.
+    pub fn live_query<F>(&mut self, name: impl Into<String>, predicate: F) -> LiveQuery
+    where
+        F: Fn(&Node) -> bool + Send + 'static,
+    {
+        let matches = self
+            .iter()
+            .filter(|node| predicate(node))
+            .map(|node| node.path.clone())
+            .collect();
+        let results = Arc::new(Mutex::new(matches));
+        let (sender, changes) = mpsc::channel();
+        self.queries.push(Registered {
+            predicate: Box::new(predicate),
+            results: Arc::downgrade(&results),
+            sender,
+        });
+        LiveQuery {
+            name: name.into(),
+            results,
+            changes,
+        }
+    }
+
+    /// Re-evaluate every live query over the subtree at `path` after it was rescanned,
+    /// dropping queries whose handle is gone.
+    pub(crate) fn update_queries(&mut self, path: &Path) {
+        let mut queries = std::mem::take(&mut self.queries);
+        queries.retain(|query| match query.results.upgrade() {
+            Some(results) => {
+                let change = self.reevaluate(&query.predicate, &mut lock(&results), path);
+                if !change.added.is_empty() || !change.removed.is_empty() {
+                    let _ = query.sender.send(change);
+                }
+                true
+            }
+            None => false,
+        });
+        self.queries = queries;
+    }
+
+    fn reevaluate(
+        &self,
+        predicate: &Predicate,
+        results: &mut BTreeSet<PathBuf>,
+        path: &Path,
+    ) -> QueryChange {
+        let before: BTreeSet<PathBuf> = descendants_in(results, path).cloned().collect();
+        let mut after = BTreeSet::new();
+        if let Some(node) = self.get_node(path) {
+            let mut stack = vec![node];
+            while let Some(node) = stack.pop() {
+                if predicate(node) {
+                    after.insert(node.path.clone());
+                }
+                stack.extend(node.children.iter().flatten());
+            }
+        }
+
+        let change = QueryChange {
+            added: after.difference(&before).cloned().collect(),
+            removed: before.difference(&after).cloned().collect(),
+        };
+        for removed in &change.removed {
+            results.remove(removed);
+        }
+        results.extend(change.added.iter().cloned());
+        change
+    }
+}
+
+/// Locks the shared results; a panic elsewhere cannot leave a set of paths inconsistent.
+fn lock(results: &Mutex<BTreeSet<PathBuf>>) -> std::sync::MutexGuard<'_, BTreeSet<PathBuf>> {
+    results
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
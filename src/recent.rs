@@ -0,0 +1,118 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::node::Node;
+use crate::tree::{descendants, Tree};
+
+/// Files ordered by modification time, kept up to date as the tree is refreshed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RecentIndex {
+    by_path: BTreeMap<PathBuf, SystemTime>,
+    by_time: BTreeSet<(SystemTime, PathBuf)>,
+}
+
+impl RecentIndex {
+    fn insert_subtree(&mut self, node: &Node) {
+        if node.is_file() {
+            if let Some(modified) = node.metadata.modified {
+                self.by_path.insert(node.path.clone(), modified);
+                self.by_time.insert((modified, node.path.clone()));
+            }
+        }
+        for child in node.children.iter().flatten() {
+            self.insert_subtree(child);
+        }
+    }
+
+    fn remove_subtree(&mut self, path: &Path) {
+        let doomed: Vec<PathBuf> = descendants(&self.by_path, path).cloned().collect();
+        for entry in doomed {
+            if let Some(modified) = self.by_path.remove(&entry) {
+                self.by_time.remove(&(modified, entry));
+            }
+        }
+    }
+
+    /// Estimated bytes held by the index.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let entry = size_of::<PathBuf>() + size_of::<SystemTime>();
+        let paths: usize = self.by_path.keys().map(PathBuf::capacity).sum();
+        self.by_path.len() * entry * 2 + paths * 2
+    }
+}
+
+impl Tree {
+    /// Start maintaining an index of files by modification time, so `recent` and
+    /// `recent_since` no longer walk the tree. The index is updated on every refresh
+    /// and applied event; files in evicted subtrees are left out of the results.
+    pub fn track_recent(&mut self) {
+        let mut index = RecentIndex::default();
+        index.insert_subtree(&self.head);
+        self.recent = Some(index);
+    }
+
+    /// The `n` most recently modified files, newest first. Without `track_recent`, this
+    /// walks the tree but only sorts the `n` results.
+    pub fn recent(&self, n: usize) -> Vec<&Node> {
+        if let Some(index) = &self.recent {
+            return index
+                .by_time
+                .iter()
+                .rev()
+                .filter_map(|(_, path)| self.get_node(path))
+                .take(n)
+                .collect();
+        }
+
+        let mut files = modified_files(self);
+        if n < files.len() {
+            files.select_nth_unstable_by(n, newest_first);
+            files.truncate(n);
+        }
+        files.sort_by(newest_first);
+        files.into_iter().map(|(_, node)| node).collect()
+    }
+
+    /// The files modified at or after `since`, newest first.
+    pub fn recent_since(&self, since: SystemTime) -> Vec<&Node> {
+        if let Some(index) = &self.recent {
+            return index
+                .by_time
+                .range((since, PathBuf::new())..)
+                .rev()
+                .filter_map(|(_, path)| self.get_node(path))
+                .collect();
+        }
+
+        let mut files = modified_files(self);
+        files.retain(|(modified, _)| *modified >= since);
+        files.sort_by(newest_first);
+        files.into_iter().map(|(_, node)| node).collect()
+    }
+
+    /// Bring the recent-files index, if any, up to date after `path` was rescanned.
+    pub(crate) fn reindex_recent(&mut self, path: &Path) {
+        if let Some(mut index) = self.recent.take() {
+            index.remove_subtree(path);
+            if let Some(node) = self.get_node(path) {
+                index.insert_subtree(node);
+            }
+            self.recent = Some(index);
+        }
+    }
+}
+
+/// Every resident file with a known modification time.
+fn modified_files(tree: &Tree) -> Vec<(SystemTime, &Node)> {
+    tree.iter()
+        .filter(|node| node.is_file())
+        .filter_map(|node| Some((node.metadata.modified?, node)))
+        .collect()
+}
+
+fn newest_first(a: &(SystemTime, &Node), b: &(SystemTime, &Node)) -> Ordering {
+    (b.0, &b.1.path).cmp(&(a.0, &a.1.path))
+}
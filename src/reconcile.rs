@@ -0,0 +1,211 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::builder::TreeBuilder;
+use crate::diff::{diff, ComparePolicy, TreeDiff};
+use crate::options::ScanOptions;
+use crate::resources::Resources;
+use crate::tree::Tree;
+
+impl Tree {
+    /// Audit the incrementally maintained tree against a full rescan, repairing any entry
+    /// found to diverge. A non-empty result points at missed events (or bugs): `added`
+    /// lists entries on disk the tree did not know about, `removed` entries it still held
+    /// although they are gone, and `modified` entries whose size or modification time was
+    /// stale. Paths are relative to the root; evicted subtrees, and directories of lazy
+    /// trees that were never listed, are not audited.
+    pub fn reconcile(&mut self) -> io::Result<TreeDiff> {
+        let disk = self.scan_reference()?;
+        let divergence = self.divergence(&disk)?;
+        self.repair(&divergence)?;
+        Ok(divergence)
+    }
+
+    /// Run `reconcile` on a shared tree from a background thread. The full rescan happens
+    /// without holding the lock; it is only taken to compare and to rescan the entries
+    /// that diverged, so changes applied meanwhile are not overwritten with older state.
+    pub fn reconcile_in_background(tree: Arc<Mutex<Tree>>) -> JoinHandle<io::Result<TreeDiff>> {
+        thread::spawn(move || {
+            let reference = {
+                let tree = lock(&tree)?;
+                Reference::of(&tree)
+            };
+            let disk = reference.scan()?;
+            let mut tree = lock(&tree)?;
+            let divergence = tree.divergence(&disk)?;
+            tree.repair(&divergence)?;
+            Ok(divergence)
+        })
+    }
+
+    /// How this tree differs from `disk`, leaving out what lies below evicted stubs and
+    /// directories never listed.
+    fn divergence(&self, disk: &Tree) -> io::Result<TreeDiff> {
+        let mut divergence = diff(self, disk, ComparePolicy::SizeMtime)?;
+        let stubs: Vec<PathBuf> = self
+            .iter()
+            .filter(|node| node.evicted.is_some() || node.needs_load())
+            .filter_map(|node| node.path.strip_prefix(&self.head.path).ok())
+            .map(Path::to_path_buf)
+            .collect();
+        if !stubs.is_empty() {
+            let resident = |path: &PathBuf| {
+                !stubs
+                    .iter()
+                    .any(|stub| path.starts_with(stub) && path != stub)
+            };
+            divergence.added.retain(resident);
+            divergence.removed.retain(resident);
+            divergence.modified.retain(resident);
+        }
+        Ok(divergence)
+    }
+
+    /// Rescans the smallest set of paths covering `divergence`: the outermost entries
+    /// that appeared or vanished, and modified entries with nothing diverging below them
+    /// (their ancestors only changed in size, which the rescans adjust).
+    fn repair(&mut self, divergence: &TreeDiff) -> io::Result<()> {
+        let mut structural: Vec<&PathBuf> =
+            divergence.added.iter().chain(&divergence.removed).collect();
+        structural.sort();
+        let mut targets: Vec<&PathBuf> = Vec::new();
+        for path in structural {
+            if !targets.last().is_some_and(|outer| path.starts_with(outer)) {
+                targets.push(path);
+            }
+        }
+
+        let diverged: Vec<&PathBuf> = targets
+            .iter()
+            .copied()
+            .chain(&divergence.modified)
+            .collect();
+        for path in &divergence.modified {
+            let below = diverged
+                .iter()
+                .any(|other| other.starts_with(path) && other != &path);
+            let covered = targets.iter().any(|outer| path.starts_with(outer));
+            if !below && !covered {
+                targets.push(path);
+            }
+        }
+
+        for rel in targets {
+            let path = self.head.path.join(rel);
+            self.refresh_nearest(&path)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a full rescan to compare a tree against needs: where it lives, and how it was
+/// scanned.
+pub(crate) struct Reference {
+    root: PathBuf,
+    host: PathBuf,
+    options: ScanOptions,
+    resources: Resources,
+}
+
+impl Reference {
+    pub(crate) fn of(tree: &Tree) -> Self {
+        Self {
+            root: tree.head.path.clone(),
+            host: tree.host_root().to_path_buf(),
+            options: tree.options.clone(),
+            resources: tree.resources,
+        }
+    }
+
+    /// Scans the directory on disk as `scan_compared` does, into a tree with the tree's
+    /// paths.
+    pub(crate) fn scan(self) -> io::Result<Tree> {
+        let mut tree = scan_compared(&self.host, self.options, self.resources)?;
+        if self.host != self.root {
+            tree.present_at(&self.root);
+        }
+        Ok(tree)
+    }
+}
+
+impl Tree {
+    /// A full rescan of the tree from disk with its own options; see `Reference::scan`.
+    pub(crate) fn scan_reference(&self) -> io::Result<Tree> {
+        Reference::of(self).scan()
+    }
+}
+
+/// Scans `dir` with `options` to compare a tree scanned with them against, so that
+/// excluded, hidden and depth-cut entries stay out of the comparison and the error
+/// policy applies. The scan is never lazy, and computes neither checksums nor MIME
+/// types, which comparisons do not look at.
+pub(crate) fn scan_compared(
+    dir: &Path,
+    options: ScanOptions,
+    resources: Resources,
+) -> io::Result<Tree> {
+    let options = ScanOptions {
+        lazy: false,
+        checksums: None,
+        mime_types: false,
+        ..options
+    };
+    TreeBuilder::new(dir)
+        .with_options(options)
+        .resources(resources)
+        .build()
+}
+
+fn lock(tree: &Mutex<Tree>) -> io::Result<std::sync::MutexGuard<'_, Tree>> {
+    tree.lock()
+        .map_err(|_| io::Error::other("tree poisoned by a panicked thread"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::builder::TreeBuilder;
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn reconcile_honours_scan_options() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir_all(dir.root.join("a/b/c")).unwrap();
+        fs::create_dir_all(dir.root.join("node_modules/pkg")).unwrap();
+        fs::write(dir.root.join("a/b/c/deep.txt"), "deep").unwrap();
+        fs::write(dir.root.join(".hidden"), "hidden").unwrap();
+        fs::write(dir.root.join("node_modules/pkg/index.js"), "js").unwrap();
+        fs::write(dir.root.join("kept.txt"), "kept").unwrap();
+
+        let mut tree = TreeBuilder::new(&dir.root)
+            .include_hidden(false)
+            .max_depth(2)
+            .exclude("node_modules")
+            .build()
+            .unwrap();
+        let divergence = tree.reconcile().unwrap();
+        assert!(divergence.is_empty(), "{divergence:?}");
+        assert!(tree.get_node(&dir.root.join(".hidden")).is_none());
+        assert!(tree.get_node(&dir.root.join("node_modules")).is_none());
+    }
+
+    #[test]
+    fn reconcile_finds_missed_changes() {
+        let dir = empty_dir().unwrap();
+        fs::write(dir.root.join("old.txt"), "old").unwrap();
+        let mut tree = TreeBuilder::new(&dir.root).build().unwrap();
+        fs::write(dir.root.join("new.txt"), "new").unwrap();
+        fs::remove_file(dir.root.join("old.txt")).unwrap();
+
+        let divergence = tree.reconcile().unwrap();
+        assert_eq!(divergence.added, ["new.txt"].map(std::path::PathBuf::from));
+        assert_eq!(
+            divergence.removed,
+            ["old.txt"].map(std::path::PathBuf::from)
+        );
+        assert!(tree.reconcile().unwrap().is_empty());
+    }
+}
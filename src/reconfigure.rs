@@ -0,0 +1,105 @@
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+
+use crate::node::Node;
+use crate::options::{Excludes, ScanOptions};
+use crate::tree::Tree;
+
+impl Tree {
+    /// Switch the tree to scanning with `options`, bringing it in line with them without
+    /// a full rescan where the change allows: entries newly excluded are dropped, and the
+    /// areas an exclude no longer in place covered are rescanned. A change of any other
+    /// setting, or dropping an exclude that may match anywhere, such as
+    /// `**/node_modules`, rescans the whole tree as `refresh` does.
+    pub fn reconfigure(&mut self, options: ScanOptions) -> io::Result<()> {
+        let old = mem::replace(&mut self.options, options);
+        let unchanged = ScanOptions {
+            excludes: self.options.excludes.clone(),
+            ..old.clone()
+        };
+        if unchanged != self.options {
+            return self.refresh();
+        }
+        if self.options.lazy {
+            return Ok(());
+        }
+
+        if self
+            .options
+            .excludes
+            .iter()
+            .any(|pattern| !old.excludes.contains(pattern))
+        {
+            self.prune_excluded();
+        }
+        let mut areas = Vec::new();
+        for pattern in &old.excludes {
+            if self.options.excludes.contains(pattern) {
+                continue;
+            }
+            match fixed_prefix(pattern) {
+                Some(prefix) => areas.push(self.head.path.join(prefix)),
+                None => return self.refresh(),
+            }
+        }
+        areas.sort();
+        areas.dedup();
+        let mut rescanned: Vec<PathBuf> = Vec::new();
+        for area in areas {
+            let area = self.known_parent(area);
+            if rescanned.iter().any(|done| area.starts_with(done)) {
+                continue;
+            }
+            self.refresh_path(&area)?;
+            rescanned.push(area);
+        }
+        Ok(())
+    }
+
+    /// Drops the entries the excludes now cover, and sums the sizes again.
+    fn prune_excluded(&mut self) {
+        let excludes = Excludes::new(&self.options, &self.head.path);
+        prune(&mut self.head, &excludes);
+        self.head.sum_child_sizes();
+        let root = self.head.path.clone();
+        self.rescanned(&root, None);
+    }
+
+    /// `path` if its parent is in the tree, so that rescanning it adds it there, or
+    /// otherwise its nearest ancestor in the tree.
+    fn known_parent(&self, path: PathBuf) -> PathBuf {
+        let mut path = path;
+        while path != self.head.path {
+            match path.parent() {
+                Some(parent) if self.get_node(parent).is_none() => path = parent.to_path_buf(),
+                _ => break,
+            }
+        }
+        path
+    }
+}
+
+/// Removes the entries below `node` that `excludes` covers.
+fn prune(node: &mut Node, excludes: &Excludes) {
+    if let Some(children) = &mut node.children {
+        children.retain(|child| !excludes.covers(&child.path));
+        for child in children {
+            prune(child, excludes);
+        }
+    }
+}
+
+/// The leading components of `pattern` without wildcards, which every path it matches
+/// is at or below, or `None` if it starts with one.
+fn fixed_prefix(pattern: &str) -> Option<PathBuf> {
+    let prefix: PathBuf = pattern
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .take_while(|part| !part.contains(['*', '?', '[']))
+        .collect();
+    match prefix.as_os_str().is_empty() {
+        true => None,
+        false => Some(prefix),
+    }
+}
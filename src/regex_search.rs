@@ -0,0 +1,58 @@
+use std::io;
+use std::path::{Component, Path};
+
+use regex::Regex;
+
+use crate::node::Node;
+use crate::tree::Tree;
+
+impl Tree {
+    /// Every node below the root whose path relative to it, with components joined by
+    /// `/` on every platform, matches the regular expression `pattern`, in `iter` order.
+    /// The expression is unanchored; use `^` and `$` to match whole paths.
+    ///
+    /// Fails with `InvalidInput` if `pattern` is not a valid expression.
+    pub fn search_regex(&self, pattern: &str) -> io::Result<Vec<&Node>> {
+        let regex = compile(pattern)?;
+        Ok(self
+            .iter()
+            .filter(|node| {
+                let rel = node
+                    .path
+                    .strip_prefix(&self.head.path)
+                    .unwrap_or(&node.path);
+                !rel.as_os_str().is_empty() && regex.is_match(&joined(rel))
+            })
+            .collect())
+    }
+
+    /// Like `search_regex`, matching only the nodes' file names.
+    pub fn search_regex_names(&self, pattern: &str) -> io::Result<Vec<&Node>> {
+        let regex = compile(pattern)?;
+        Ok(self
+            .iter()
+            .filter(|node| node.path != self.head.path)
+            .filter(|node| {
+                node.path
+                    .file_name()
+                    .is_some_and(|name| regex.is_match(&name.to_string_lossy()))
+            })
+            .collect())
+    }
+}
+
+fn compile(pattern: &str) -> io::Result<Regex> {
+    Regex::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// The normal components of `rel` joined by `/`.
+fn joined(rel: &Path) -> String {
+    let names: Vec<_> = rel
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    names.join("/")
+}
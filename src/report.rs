@@ -0,0 +1,219 @@
+use std::fmt::{self, Write as _};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::clock::{utc_timestamp, Clock, SystemClock};
+use crate::event::RescanPolicy;
+use crate::node::Node;
+use crate::sink::ReportSink;
+use crate::source::EventSource;
+use crate::tree::Tree;
+
+/// Longest a `ReportScheduler::watch` loop waits before checking whether to stop.
+const STOP_CHECK: Duration = Duration::from_secs(1);
+
+/// A storage report over a tree, rendered as plain text with one entry per line and
+/// tab-separated fields, sizes in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportKind {
+    /// The size of each directory down to `depth` levels below the root, in tree
+    /// order, like `du --max-depth`: `size<TAB>path`.
+    DiskUsage { depth: usize },
+    /// The `n` largest files, largest first: `size<TAB>path`.
+    Largest { n: usize },
+    /// Files of at least `min_size` bytes with the same contents, as
+    /// `Tree::find_duplicates` finds them. Each group, those wasting the most space
+    /// first, is a `# count<TAB>size` line followed by its paths, and ends with an empty
+    /// line.
+    Duplicates { min_size: u64 },
+    /// Files not modified for at least `age`, by the tree's clock, the oldest first:
+    /// `modified<TAB>size<TAB>path`, with the time in UTC as `2023-07-04T12:00:00Z`.
+    Stale { age: Duration },
+}
+
+impl ReportKind {
+    /// Render this report for `tree` as it is now.
+    pub fn render(&self, tree: &Tree) -> io::Result<String> {
+        let mut out = String::new();
+        match self {
+            ReportKind::DiskUsage { depth } => {
+                let root = tree.head.path.components().count();
+                for node in tree.iter().filter(|node| node.is_dir()) {
+                    if node.path.components().count() - root <= *depth {
+                        let _ = writeln!(out, "{}\t{}", node.size, node.path.display());
+                    }
+                }
+            }
+            ReportKind::Largest { n } => {
+                let mut files: Vec<&Node> = tree.iter().filter(|node| node.is_file()).collect();
+                files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+                for node in files.into_iter().take(*n) {
+                    let _ = writeln!(out, "{}\t{}", node.size, node.path.display());
+                }
+            }
+            ReportKind::Duplicates { min_size } => {
+                let groups = tree.find_duplicates();
+                for group in groups.iter().filter(|group| group.size >= *min_size) {
+                    let _ = writeln!(out, "# {}\t{}", group.paths.len(), group.size);
+                    for path in &group.paths {
+                        let _ = writeln!(out, "{}", path.display());
+                    }
+                    out.push('\n');
+                }
+            }
+            ReportKind::Stale { age } => {
+                let now = tree.clock.now();
+                let mut files: Vec<(SystemTime, &Node)> = tree
+                    .iter()
+                    .filter(|node| node.is_file())
+                    .filter_map(|node| Some((node.metadata.modified?, node)))
+                    .filter(|(modified, _)| {
+                        now.duration_since(*modified)
+                            .is_ok_and(|since| since >= *age)
+                    })
+                    .collect();
+                files.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path)));
+                for (modified, node) in files {
+                    let _ = writeln!(
+                        out,
+                        "{}\t{}\t{}",
+                        utc_timestamp(modified, false),
+                        node.size,
+                        node.path.display()
+                    );
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Render this report for `tree` and deliver it to `sink` under `name`, timed by the
+    /// tree's clock.
+    pub fn send(&self, tree: &Tree, name: &str, sink: &mut dyn ReportSink) -> io::Result<()> {
+        sink.deliver(name, tree.clock.now(), &self.render(tree)?)
+    }
+}
+
+/// A report the scheduler runs.
+#[derive(Debug, Clone)]
+struct Scheduled {
+    name: String,
+    kind: ReportKind,
+    interval: Duration,
+    /// When the report is next due; `None` until it first ran.
+    due: Option<Instant>,
+}
+
+/// Runs reports over a tree at intervals and hands each run to a `ReportSink`, so that
+/// a watched tree turns into a storage-reporting agent. Reports are first due when
+/// first polled.
+pub struct ReportScheduler {
+    sink: Box<dyn ReportSink>,
+    reports: Vec<Scheduled>,
+    clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for ReportScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReportScheduler")
+            .field("reports", &self.reports)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReportScheduler {
+    /// Deliver reports to `sink`, e.g. a `DirectorySink`.
+    pub fn new(sink: impl ReportSink + 'static) -> Self {
+        Self {
+            sink: Box::new(sink),
+            reports: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Run the report `kind` every `interval`, delivering it under `name`; for a
+    /// `DirectorySink`, a plain file name without dots.
+    pub fn add(mut self, name: &str, kind: ReportKind, interval: Duration) -> Self {
+        self.reports.push(Scheduled {
+            name: name.to_string(),
+            kind,
+            interval,
+            due: None,
+        });
+        self
+    }
+
+    /// Measure the intervals and time the runs with `clock` instead of the system's
+    /// time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// How long until the next report is due, by the scheduler's clock: zero if one is
+    /// due already, `None` without reports.
+    pub fn due_in(&self) -> Option<Duration> {
+        let now = self.clock.instant();
+        self.reports
+            .iter()
+            .map(|report| match report.due {
+                Some(due) => due.saturating_duration_since(now),
+                None => Duration::ZERO,
+            })
+            .min()
+    }
+
+    /// Run the reports that are due over `tree`, returning the names of those
+    /// delivered. The first failure is returned; reports after it run at the next poll.
+    pub fn poll(&mut self, tree: &Tree) -> io::Result<Vec<String>> {
+        let now = self.clock.instant();
+        let mut delivered = Vec::new();
+        for report in &mut self.reports {
+            if report.due.is_some_and(|due| due > now) {
+                continue;
+            }
+            let contents = report.kind.render(tree)?;
+            self.sink
+                .deliver(&report.name, self.clock.now(), &contents)?;
+            report.due = Some(now + report.interval);
+            delivered.push(report.name.clone());
+        }
+        Ok(delivered)
+    }
+
+    /// Run every report over `tree` now, whether it is due or not, returning the names
+    /// of those delivered.
+    pub fn run_all(&mut self, tree: &Tree) -> io::Result<Vec<String>> {
+        for report in &mut self.reports {
+            report.due = None;
+        }
+        self.poll(tree)
+    }
+
+    /// Keep `tree` up to date with the events from `source`, applied with `policy`, and
+    /// run the reports as they fall due, until `stop` is set. A failure to apply events
+    /// or deliver a report ends the loop with the error.
+    pub fn watch(
+        &mut self,
+        source: &dyn EventSource,
+        tree: &mut Tree,
+        policy: &RescanPolicy,
+        stop: &AtomicBool,
+    ) -> io::Result<()> {
+        while !stop.load(Ordering::Relaxed) {
+            self.poll(tree)?;
+            let wait = self.due_in().unwrap_or(STOP_CHECK).min(STOP_CHECK);
+            let Some(first) = source.recv_timeout(wait) else {
+                continue;
+            };
+            let mut events = vec![first];
+            while let Some(event) = source.try_recv() {
+                events.push(event);
+            }
+            tree.apply_events(&events, policy)?;
+        }
+        Ok(())
+    }
+}
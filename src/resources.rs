@@ -0,0 +1,140 @@
+use std::io;
+
+use crate::tree::Tree;
+
+/// How many threads each kind of work may use, so that e.g. scanning can fan out over a
+/// fast file system while hashing stays gentle on a spinning disk. Set for a tree with
+/// `TreeBuilder::resources` or `Tree::set_resources`; the tree's operations then use
+/// these limits.
+///
+/// The default of one thread everywhere does all work on the calling thread. Larger
+/// limits need the `rayon` feature; without it they are accepted but ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resources {
+    /// Threads listing directories and reading metadata, for the initial scan and
+    /// refreshes.
+    pub scan_threads: usize,
+    /// Threads reading and hashing file contents, e.g. for `Tree::chunk_dedup_stats`.
+    pub hash_threads: usize,
+    /// Threads copying file contents, e.g. for `Tree::restore_from_with`.
+    pub copy_threads: usize,
+    /// Threads running analyses that build trees of their own, e.g. for
+    /// `analyze_layers_with`.
+    pub analyze_threads: usize,
+    /// Split each operation's threads between the machine's NUMA nodes, pinned to the
+    /// node's CPUs, and hand each node a share of the work of its own: whole subtrees of
+    /// the root when scanning, runs of neighbouring files when hashing or copying. The
+    /// nodes built and the memory they touch then stay close together, which pays off
+    /// on multi-socket machines with many threads. Needs the `numa` feature (Linux);
+    /// ignored without it.
+    pub numa: bool,
+    /// Read files of at least this many bytes by mapping them into memory instead of
+    /// copying them through a buffer, when hashing their contents or searching them.
+    /// Files that cannot be mapped are read as usual, and a mapped file that changes
+    /// while it is read fails the operation rather than give a torn result. A file
+    /// truncated while mapped can crash the process with `SIGBUS` on Unix, so leave
+    /// this unset for trees whose files may shrink meanwhile. Needs the `mmap` feature;
+    /// ignored without it. Defaults to `None`, never mapping.
+    pub mmap_threshold: Option<u64>,
+    /// Keep bulk reads and copies from displacing what the host has in its page cache:
+    /// the pages of files read to hash, search or copy them are dropped again once
+    /// done, unless some were cached before, and files written are flushed and dropped
+    /// too. Costs throughput on repeated passes over the same files. Needs the
+    /// `fadvise` feature (Linux), which also gives sequential-readahead hints
+    /// (`POSIX_FADV_SEQUENTIAL`) for files read; no `WILLNEED` prefetching is asked for.
+    /// Ignored without the feature.
+    pub spare_page_cache: bool,
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self {
+            scan_threads: 1,
+            hash_threads: 1,
+            copy_threads: 1,
+            analyze_threads: 1,
+            numa: false,
+            mmap_threshold: None,
+            spare_page_cache: false,
+        }
+    }
+}
+
+/// The threads one operation runs on: the calling thread, a pool, or a pool per NUMA
+/// node.
+pub(crate) enum Workers {
+    Inline,
+    #[cfg(feature = "rayon")]
+    Pool(rayon::ThreadPool),
+    /// Pools pinned to their node's CPUs, with the number of threads in each.
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    Nodes(Vec<(rayon::ThreadPool, usize)>),
+}
+
+impl Workers {
+    /// Up to `threads` threads, split between NUMA nodes with `numa`.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn new(threads: usize, numa: bool) -> io::Result<Self> {
+        if numa && threads > 1 {
+            #[cfg(all(feature = "numa", target_os = "linux"))]
+            return crate::numa::node_pools(threads).map(Workers::Nodes);
+        }
+        if threads <= 1 {
+            return Ok(Workers::Inline);
+        }
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map(Workers::Pool)
+            .map_err(io::Error::other)
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    pub(crate) fn new(_threads: usize, _numa: bool) -> io::Result<Self> {
+        Ok(Workers::Inline)
+    }
+
+    /// Returns `true` if work is spread over more than the calling thread.
+    pub(crate) fn is_parallel(&self) -> bool {
+        !matches!(self, Workers::Inline)
+    }
+
+    /// Applies `f` to every item, returning the results in the order of the items.
+    /// Parallel iterators inside `f` stay on the pool it runs on. The first error is
+    /// returned; later items may not be processed.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn map<T: Send, R: Send>(
+        &self,
+        items: Vec<T>,
+        f: impl Fn(T) -> io::Result<R> + Sync + Send,
+    ) -> io::Result<Vec<R>> {
+        use rayon::prelude::*;
+        match self {
+            Workers::Inline => items.into_iter().map(f).collect(),
+            Workers::Pool(pool) => pool.install(|| items.into_par_iter().map(f).collect()),
+            #[cfg(all(feature = "numa", target_os = "linux"))]
+            Workers::Nodes(pools) => crate::numa::map_sharded(pools, items, &f),
+        }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    pub(crate) fn map<T, R>(
+        &self,
+        items: Vec<T>,
+        f: impl Fn(T) -> io::Result<R>,
+    ) -> io::Result<Vec<R>> {
+        items.into_iter().map(f).collect()
+    }
+}
+
+impl Tree {
+    /// The thread limits the tree's operations use.
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// Limit how many threads the tree's operations use from now on, e.g. refreshes.
+    pub fn set_resources(&mut self, resources: Resources) {
+        self.resources = resources;
+    }
+}
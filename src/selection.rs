@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::handle::NodeId;
+use crate::node::Node;
+use crate::tree::Tree;
+
+/// A set of selected nodes, as kept by a file-manager frontend.
+///
+/// Entries are held as `NodeId`s keyed by path, so a selection can outlive refreshes
+/// of the tree it was made on; call `reconcile` after a refresh to drop entries that
+/// vanished.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    ids: BTreeMap<PathBuf, NodeId>,
+}
+
+impl Selection {
+    /// An empty selection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select a node. Returns `false` if it was already selected.
+    pub fn add(&mut self, id: NodeId) -> bool {
+        self.ids
+            .insert(id.relative_path().to_path_buf(), id)
+            .is_none()
+    }
+
+    /// Deselect a node. Returns `false` if it was not selected.
+    pub fn remove(&mut self, id: &NodeId) -> bool {
+        self.ids.remove(id.relative_path()).is_some()
+    }
+
+    /// Flip whether a node is selected, returning whether it is selected now.
+    pub fn toggle(&mut self, id: NodeId) -> bool {
+        if self.remove(&id) {
+            false
+        } else {
+            self.add(id)
+        }
+    }
+
+    /// Returns `true` if the node at the handle's path is selected.
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.ids.contains_key(id.relative_path())
+    }
+
+    /// The number of selected nodes.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns `true` if nothing is selected.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Deselect everything.
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+
+    /// The selected handles, ordered by path.
+    pub fn iter(&self) -> impl Iterator<Item = &NodeId> {
+        self.ids.values()
+    }
+
+    /// Select every child of `dir` whose name matches `pattern`, where `*` matches any
+    /// run of characters, `?` any single character and `[...]` any character of a class.
+    /// Returns the number newly selected.
+    pub fn select_glob(&mut self, tree: &Tree, dir: &Path, pattern: &str) -> usize {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let matches: Vec<NodeId> = resident_children(tree, dir)
+            .filter(|child| {
+                child.path.file_name().is_some_and(|name| {
+                    let name: Vec<char> = name.to_string_lossy().chars().collect();
+                    glob_match(&pattern, &name, false)
+                })
+            })
+            .filter_map(|child| tree.id_for(child))
+            .collect();
+        matches
+            .into_iter()
+            .filter(|id| self.add(id.clone()))
+            .count()
+    }
+
+    /// Toggle every child of `dir`, so that exactly the previously unselected ones are
+    /// selected.
+    pub fn invert(&mut self, tree: &Tree, dir: &Path) {
+        let children: Vec<NodeId> = resident_children(tree, dir)
+            .filter_map(|child| tree.id_for(child))
+            .collect();
+        for id in children {
+            self.toggle(id);
+        }
+    }
+
+    /// The total size of the selection. Nodes below another selected directory are not
+    /// counted twice, and nodes no longer in the tree count as zero.
+    pub fn total_size(&self, tree: &Tree) -> u64 {
+        let mut total = 0;
+        let mut counted: Option<&Path> = None;
+        // Paths are ordered, so any selected descendants directly follow their ancestor.
+        for (rel, id) in &self.ids {
+            if counted.is_some_and(|dir| rel.starts_with(dir)) {
+                continue;
+            }
+            if let Some(node) = tree.node(id) {
+                total += node.size;
+                counted = Some(rel);
+            }
+        }
+        total
+    }
+
+    /// Bring the selection up to date with `tree` after a refresh: entries that vanished
+    /// are dropped and entries that changed get fresh handles. Returns the number dropped.
+    pub fn reconcile(&mut self, tree: &Tree) -> usize {
+        let before = self.ids.len();
+        self.ids = std::mem::take(&mut self.ids)
+            .into_iter()
+            .filter_map(|(rel, id)| {
+                let fresh = tree.node(&id).and_then(|node| tree.id_for(node))?;
+                Some((rel, fresh))
+            })
+            .collect();
+        before - self.ids.len()
+    }
+}
+
+/// The resident children of the directory at `dir`, if it is in the tree.
+fn resident_children<'a>(tree: &'a Tree, dir: &Path) -> impl Iterator<Item = &'a Node> {
+    tree.get_node(dir)
+        .and_then(|node| node.children.as_ref())
+        .into_iter()
+        .flatten()
+}
+
+/// Matches `name` against a pattern of literal characters, `*`, `?` and character
+/// classes such as `[abc]`, `[a-z]` or `[!0-9]`. A `[` without a closing `]` is literal.
+pub(crate) fn glob_match(pattern: &[char], name: &[char], ignore_case: bool) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => {
+            (0..=name.len()).any(|skip| glob_match(rest, &name[skip..], ignore_case))
+        }
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..], ignore_case),
+        Some(('[', rest)) => match split_class(rest) {
+            Some((negated, class, after)) => name.split_first().is_some_and(|(&c, tail)| {
+                let matched = cases(c, ignore_case).any(|c| in_class(class, c));
+                matched != negated && glob_match(after, tail, ignore_case)
+            }),
+            None => match_literal('[', rest, name, ignore_case),
+        },
+        Some((&c, rest)) => match_literal(c, rest, name, ignore_case),
+    }
+}
+
+fn match_literal(c: char, rest: &[char], name: &[char], ignore_case: bool) -> bool {
+    name.split_first().is_some_and(|(&n, tail)| {
+        cases(c, ignore_case).any(|c| c == n) && glob_match(rest, tail, ignore_case)
+    })
+}
+
+/// Splits what follows a `[` into whether the class is negated, its contents and the
+/// rest of the pattern, or `None` if the class is not closed. A `]` right after the `[`
+/// (or the `!` or `^` negating it) belongs to the class.
+fn split_class(pattern: &[char]) -> Option<(bool, &[char], &[char])> {
+    let (negated, body) = match pattern.first() {
+        Some('!' | '^') => (true, &pattern[1..]),
+        _ => (false, pattern),
+    };
+    let end = 1 + body.iter().skip(1).position(|&c| c == ']')?;
+    Some((negated, &body[..end], &body[end + 1..]))
+}
+
+/// Returns `true` if `c` is one of the characters or `a-z` ranges in `class`.
+fn in_class(class: &[char], c: char) -> bool {
+    let mut rest = class;
+    while let Some(&first) = rest.first() {
+        if let [start, '-', end, ..] = rest {
+            if (*start..=*end).contains(&c) {
+                return true;
+            }
+            rest = &rest[3..];
+        } else {
+            if first == c {
+                return true;
+            }
+            rest = &rest[1..];
+        }
+    }
+    false
+}
+
+/// `c`, and with `ignore_case` also its lower and upper case forms, where those are
+/// single characters.
+fn cases(c: char, ignore_case: bool) -> impl Iterator<Item = char> {
+    fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+        let first = chars.next();
+        first.filter(|_| chars.next().is_none())
+    }
+    let folded = ignore_case.then(|| [single(c.to_lowercase()), single(c.to_uppercase())]);
+    std::iter::once(c).chain(folded.into_iter().flatten().flatten())
+}
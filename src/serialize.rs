@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::bookmark::Bookmarks;
+use crate::node::Node;
+use crate::options::ScanOptions;
+use crate::tree::Tree;
+
+/// What of a tree is serialized: its nodes and the settings needed to refresh it.
+/// Indexes, live queries, residency and the clock are runtime state; a deserialized
+/// tree starts without them, as if just scanned.
+#[derive(Serialize)]
+struct TreeRef<'a> {
+    head: &'a Node,
+    options: &'a ScanOptions,
+    host_root: Option<&'a Path>,
+    bookmarks: &'a Bookmarks,
+    generation: u64,
+}
+
+#[derive(Deserialize)]
+struct TreeData {
+    head: Node,
+    options: ScanOptions,
+    host_root: Option<PathBuf>,
+    bookmarks: Bookmarks,
+    generation: u64,
+}
+
+impl Serialize for Tree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TreeRef {
+            head: &self.head,
+            options: &self.options,
+            host_root: self.host_root.as_deref(),
+            bookmarks: &self.bookmarks,
+            generation: self.generation,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = TreeData::deserialize(deserializer)?;
+        let mut tree = Tree::from_head(data.head);
+        tree.options = data.options;
+        tree.host_root = data.host_root;
+        tree.bookmarks = data.bookmarks;
+        // Keeps `NodeId`s taken before serializing from matching changed entries.
+        tree.generation = data.generation;
+        Ok(tree)
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// How deleted files are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RemoveMode {
+    /// Unlink them; their contents stay on the device until overwritten.
+    #[default]
+    Unlink,
+    /// Overwrite every regular file's contents `passes` times with pseudo-random data,
+    /// flushing each pass to the device, then truncate and unlink it. Symbolic links are
+    /// removed without touching their targets.
+    ///
+    /// This only destroys the data where the file system writes in place. Journaling and
+    /// copy-on-write file systems (btrfs, ZFS, APFS), snapshots, and SSDs and other flash
+    /// storage (whose controllers remap writes and keep the old blocks until they are
+    /// erased) may all retain copies; use full-disk encryption or the device's secure
+    /// erase for those. Other hard links to a file see its contents overwritten too.
+    Shred {
+        /// Number of overwrites; 0 behaves like `Unlink`.
+        passes: u32,
+    },
+}
+
+impl RemoveMode {
+    /// Removes the entry at `path` on disk, a directory with everything in it.
+    pub(crate) fn remove(self, path: &Path) -> io::Result<()> {
+        let metadata = fs::symlink_metadata(path)?;
+        if let RemoveMode::Shred { passes } = self {
+            shred_all(path, &metadata, passes)?;
+        }
+        match metadata.is_dir() {
+            true => fs::remove_dir_all(path),
+            false => fs::remove_file(path),
+        }
+    }
+}
+
+/// Overwrites every regular file at or below `path`.
+fn shred_all(path: &Path, metadata: &fs::Metadata, passes: u32) -> io::Result<()> {
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            shred_all(&entry.path(), &fs::symlink_metadata(entry.path())?, passes)?;
+        }
+    } else if metadata.is_file() && passes > 0 {
+        shred_file(path, metadata.len(), passes)?;
+    }
+    Ok(())
+}
+
+fn shred_file(path: &Path, len: u64, passes: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut state = seed(path);
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+        let mut left = len;
+        while left > 0 {
+            let chunk = left.min(buf.len() as u64) as usize;
+            for word in buf[..chunk].chunks_mut(8) {
+                state = next(state);
+                word.copy_from_slice(&state.to_le_bytes()[..word.len()]);
+            }
+            file.write_all(&buf[..chunk])?;
+            left -= chunk as u64;
+        }
+        file.sync_data()?;
+    }
+    file.set_len(0)?;
+    file.sync_all()
+}
+
+/// A seed that differs between files and runs, so passes are not predictable.
+fn seed(path: &Path) -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64);
+    crate::codec::fnv1a(path.as_os_str().as_encoded_bytes()) ^ nanos
+}
+
+/// splitmix64.
+fn next(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
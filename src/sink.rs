@@ -0,0 +1,185 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::utc_timestamp;
+
+/// Where rendered reports go, such as a `ReportScheduler`'s runs or `ReportKind::send`,
+/// so that the routing is configured once for every report. Closures taking the name,
+/// the time of the run and the contents are sinks, for routing of one's own.
+pub trait ReportSink: Send {
+    /// Deliver the `contents` of the report `name`, run at `time`.
+    fn deliver(&mut self, name: &str, time: SystemTime, contents: &str) -> io::Result<()>;
+}
+
+impl<F> ReportSink for F
+where
+    F: FnMut(&str, SystemTime, &str) -> io::Result<()> + Send,
+{
+    fn deliver(&mut self, name: &str, time: SystemTime, contents: &str) -> io::Result<()> {
+        self(name, time, contents)
+    }
+}
+
+/// Writes each run to a file of its own in a directory, created when first needed, and
+/// removes old runs beyond a limit.
+///
+/// A report named `du` writes files such as `du.20230704T120000Z.txt`, named by the
+/// time of the run in UTC. Each file appears complete in one step, so readers never see
+/// a partial report.
+#[derive(Debug, Clone)]
+pub struct DirectorySink {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl DirectorySink {
+    /// Write to `dir`, keeping the last 10 runs of each report.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            keep: 10,
+        }
+    }
+
+    /// Keep the last `runs` outputs of each report, removing older ones after each run.
+    /// At least the latest is always kept.
+    pub fn keep(mut self, runs: usize) -> Self {
+        self.keep = runs.max(1);
+        self
+    }
+
+    /// Removes the oldest outputs of the report `name` beyond the number kept.
+    fn rotate(&self, name: &str) -> io::Result<()> {
+        let prefix = format!("{name}.");
+        let mut runs: Vec<PathBuf> = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if file_name.starts_with(&prefix) && file_name.ends_with(".txt") {
+                runs.push(entry.path());
+            }
+        }
+        // The timestamps sort in time order.
+        runs.sort();
+        let excess = runs.len().saturating_sub(self.keep);
+        for old in &runs[..excess] {
+            fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReportSink for DirectorySink {
+    fn deliver(&mut self, name: &str, time: SystemTime, contents: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self
+            .dir
+            .join(format!("{}.{}.txt", name, utc_timestamp(time, true)));
+        let temp = self.dir.join(format!(".{}.incoming", name));
+        fs::write(&temp, contents)?;
+        fs::rename(&temp, &path)?;
+        self.rotate(name)
+    }
+}
+
+/// Prints each run to standard output, after a `==> name at time <==` line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl ReportSink for StdoutSink {
+    fn deliver(&mut self, name: &str, time: SystemTime, contents: &str) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        writeln!(out, "==> {} at {} <==", name, utc_timestamp(time, false))?;
+        out.write_all(contents.as_bytes())?;
+        out.flush()
+    }
+}
+
+/// Posts each run to an HTTP endpoint as `text/plain`, with the report's name and the
+/// time of the run in `X-Report-Name` and `X-Report-Time` headers. Any status but 2xx
+/// fails the delivery.
+///
+/// Only plain `http://` URLs are supported; put a local proxy in front of endpoints
+/// that need TLS.
+#[derive(Debug, Clone)]
+pub struct HttpSink {
+    host: String,
+    port: u16,
+    path: String,
+    timeout: Duration,
+}
+
+impl HttpSink {
+    /// Post to `url`, such as `http://collector:8080/reports`, waiting up to 30
+    /// seconds for each request.
+    pub fn new(url: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{url} is not an http:// URL"),
+            )
+        };
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Wait up to `timeout` for connecting, sending and the response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl ReportSink for HttpSink {
+    fn deliver(&mut self, name: &str, time: SystemTime, contents: &str) -> io::Result<()> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\nX-Report-Name: {}\r\nX-Report-Time: {}\r\n\
+             Connection: close\r\n\r\n",
+            self.path,
+            self.host,
+            self.port,
+            contents.len(),
+            name,
+            utc_timestamp(time, false)
+        )
+        .into_bytes();
+        request.extend_from_slice(contents.as_bytes());
+        (&stream).write_all(&request)?;
+
+        let mut status = String::new();
+        BufReader::new(&stream).read_line(&mut status)?;
+        let code = status.split_whitespace().nth(1).unwrap_or_default();
+        match code.starts_with('2') && code.len() == 3 {
+            true => Ok(()),
+            false => Err(io::Error::other(format!(
+                "{} answered {:?}",
+                self.host,
+                status.trim_end()
+            ))),
+        }
+    }
+}
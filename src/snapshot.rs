@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::bookmark::Bookmark;
+use crate::diff::{
+    diff_entries, modifications, node_entries, ComparePolicy, Entries, Entry, Modification,
+    TreeDiff,
+};
+use crate::node::{ExtendedMetadata, NodeType};
+use crate::options::ScanOptions;
+use crate::platform::create_symlink;
+use crate::tree::Tree;
+
+/// A single entry recorded in a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    /// Path relative to the snapshot's root.
+    pub path: PathBuf,
+    /// Whether the entry was a file or a directory.
+    pub node_type: NodeType,
+    /// Self size if file, cumulative size if directory.
+    pub size: u64,
+    /// Extended metadata at the time of the snapshot.
+    pub metadata: ExtendedMetadata,
+}
+
+/// A point-in-time copy of a tree, together with a description of how it was produced.
+///
+/// Entries are stored relative to the root, with the absolute root recorded separately,
+/// so a snapshot taken in one place can be compared against a tree rooted somewhere else.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Optional human-readable name, e.g. "nightly" or "before-upgrade".
+    pub label: Option<String>,
+    /// Free-form tags for grouping and filtering snapshots.
+    pub tags: Vec<String>,
+    /// The scan settings of the tree the snapshot was taken from.
+    pub options: ScanOptions,
+    /// When the snapshot was taken.
+    pub taken: SystemTime,
+    /// The absolute path on disk of the tree's root when the snapshot was taken.
+    pub root: PathBuf,
+    /// Every entry below the root, sorted by relative path.
+    pub entries: Vec<SnapshotEntry>,
+    /// The tree's bookmarks, restored with `Tree::restore_bookmarks`.
+    pub bookmarks: BTreeMap<String, Bookmark>,
+}
+
+impl Snapshot {
+    /// Capture the current state of `tree`.
+    pub fn new(tree: &Tree) -> Self {
+        let entries = node_entries(&tree.head)
+            .into_iter()
+            .map(|(path, entry)| SnapshotEntry {
+                path,
+                node_type: entry.node_type.clone(),
+                size: entry.size,
+                metadata: entry.metadata.clone(),
+            })
+            .collect();
+
+        Self {
+            label: None,
+            tags: Vec::new(),
+            options: tree.options.clone(),
+            taken: tree.clock.now(),
+            root: tree.host_root().to_path_buf(),
+            entries,
+            bookmarks: tree.bookmarks.clone(),
+        }
+    }
+
+    /// Set the snapshot's label.
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Add a tag to the snapshot.
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Point the snapshot at a new root, e.g. where a backup of the original tree is mounted.
+    /// Relative entries are unchanged; only the location their contents are read from moves.
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Returns `true` if the snapshot carries the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Look up an entry by its path relative to the root.
+    pub fn get(&self, rel_path: &Path) -> Option<&SnapshotEntry> {
+        self.entries
+            .binary_search_by(|entry| entry.path.as_path().cmp(rel_path))
+            .ok()
+            .map(|index| &self.entries[index])
+    }
+
+    /// The absolute path of `entry` under the snapshot's current root.
+    pub fn absolute_path(&self, entry: &SnapshotEntry) -> PathBuf {
+        self.root.join(&entry.path)
+    }
+
+    /// Describes why this snapshot cannot be meaningfully compared with `other`.
+    /// An empty list means the two were scanned with compatible options.
+    pub fn incompatibilities(&self, other: &Snapshot) -> Vec<String> {
+        self.options.differences(&other.options)
+    }
+
+    /// Compare this snapshot against a newer one.
+    /// Fails with `InvalidInput` if the snapshots were scanned with incompatible options.
+    pub fn diff(&self, newer: &Snapshot, policy: ComparePolicy) -> io::Result<TreeDiff> {
+        refuse_incompatible(self.incompatibilities(newer))?;
+        diff_entries(&self.entry_views(), &newer.entry_views(), policy)
+    }
+
+    /// Compare this snapshot against a newer one even if their scan options differ,
+    /// returning the differences in options as warnings alongside the diff.
+    pub fn diff_with_warnings(
+        &self,
+        newer: &Snapshot,
+        policy: ComparePolicy,
+    ) -> io::Result<(TreeDiff, Vec<String>)> {
+        let warnings = self.incompatibilities(newer);
+        let diff = diff_entries(&self.entry_views(), &newer.entry_views(), policy)?;
+        Ok((diff, warnings))
+    }
+
+    /// Compare this snapshot against a live tree, which may be rooted anywhere.
+    /// Fails with `InvalidInput` if the tree was scanned with incompatible options.
+    pub fn diff_tree(&self, tree: &Tree, policy: ComparePolicy) -> io::Result<TreeDiff> {
+        refuse_incompatible(self.options.differences(&tree.options))?;
+        diff_entries(&self.entry_views(), &tree.entries(), policy)
+    }
+
+    /// Recreate the snapshot's directory skeleton below `target`.
+    ///
+    /// With `placeholders`, every file is also created empty and extended to its recorded
+    /// size (sparse where the filesystem supports it), with its recorded modification time,
+    /// and every symbolic link is recreated with its recorded target. Nothing already
+    /// there is overwritten: a file or link in the way of one fails with `AlreadyExists`.
+    pub fn materialize_structure(&self, target: &Path, placeholders: bool) -> io::Result<()> {
+        fs::create_dir_all(target)?;
+        for entry in &self.entries {
+            let path = target.join(&entry.path);
+            match &entry.node_type {
+                NodeType::Directory => fs::create_dir_all(&path)?,
+                NodeType::Symlink { target } if placeholders => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    create_symlink(target, &path)?;
+                }
+                NodeType::File if placeholders => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+                    file.set_len(entry.size)?;
+                    if let Some(modified) = entry.metadata.modified {
+                        file.set_modified(modified)?;
+                    }
+                }
+                NodeType::File | NodeType::Symlink { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds comparable views of the entries, resolved against the current root.
+    fn entry_views(&self) -> Entries<'_> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let view = Entry {
+                    node_type: &entry.node_type,
+                    size: entry.size,
+                    metadata: &entry.metadata,
+                    location: self.absolute_path(entry),
+                };
+                (entry.path.clone(), view)
+            })
+            .collect::<BTreeMap<_, _>>()
+    }
+}
+
+/// Turns a non-empty list of option differences into an `InvalidInput` error.
+fn refuse_incompatible(problems: Vec<String>) -> io::Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "snapshots were scanned with incompatible options: {}",
+            problems.join(", ")
+        ),
+    ))
+}
+
+impl Tree {
+    /// Take a snapshot of the tree in its current state.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self)
+    }
+
+    /// What changed in the tree since `snapshot` was taken, e.g. of yesterday's scan;
+    /// the same as `snapshot.diff_tree(self, policy)`.
+    pub fn diff_since(&self, snapshot: &Snapshot, policy: ComparePolicy) -> io::Result<TreeDiff> {
+        snapshot.diff_tree(self, policy)
+    }
+
+    /// The entries `diff_since` reports as modified, each with its size and modification
+    /// time then and now. Fails with `InvalidInput` if the tree was scanned with options
+    /// incompatible with the snapshot's.
+    pub fn modifications_since(
+        &self,
+        snapshot: &Snapshot,
+        policy: ComparePolicy,
+    ) -> io::Result<Vec<Modification>> {
+        refuse_incompatible(snapshot.options.differences(&self.options))?;
+        modifications(&snapshot.entry_views(), &self.entries(), policy)
+    }
+}
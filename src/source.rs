@@ -0,0 +1,285 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::batch::DeltaBatcher;
+use crate::diff::TreeDiff;
+use crate::event::{FsEvent, PriorityLanes};
+use crate::fanout::{Fanout, Overflow, Subscriber};
+use crate::transform::EventTransform;
+use crate::tree::Tree;
+
+/// A stream of `FsEvent`s, such as an `FsWatcher` or a `SimulatedWatcher`. Code written
+/// against this trait can be driven by injected events in tests.
+pub trait EventSource {
+    /// Block until the next event arrives, or return `None` once the source has ended.
+    fn recv(&self) -> Option<FsEvent>;
+
+    /// Return the next event if one is already pending.
+    fn try_recv(&self) -> Option<FsEvent>;
+
+    /// Wait up to `timeout` for the next event.
+    fn recv_timeout(&self, timeout: Duration) -> Option<FsEvent>;
+
+    /// Add an independent consumer of the full event stream, buffering up to `capacity`
+    /// events before `overflow` applies. It only sees events from now on, and lagging
+    /// behind affects neither this source's own `recv` methods nor other subscribers.
+    fn subscribe(&self, capacity: usize, overflow: Overflow) -> Subscriber;
+
+    /// Block until an event arrives, then keep collecting events for `window` so that
+    /// bursts of changes are returned together, ready for `Tree::apply_events`.
+    fn recv_batch(&self, window: Duration) -> Vec<FsEvent> {
+        self.recv_batch_prioritized(window, &PriorityLanes::default())
+    }
+
+    /// Like `recv_batch`, but return as soon as an event in one of `lanes` arrives, so that
+    /// urgent changes are not held back by the window.
+    fn recv_batch_prioritized(&self, window: Duration, lanes: &PriorityLanes) -> Vec<FsEvent> {
+        match self.recv() {
+            Some(first) => batch_after(self, first, window, lanes),
+            None => Vec::new(),
+        }
+    }
+
+    /// Block until an event arrives, then keep collecting events until `batcher` is due
+    /// to deliver, and return the resulting delta of `tree`.
+    fn recv_delta(&self, batcher: &mut DeltaBatcher, tree: &mut Tree) -> io::Result<TreeDiff> {
+        if !batcher.has_pending() {
+            match self.recv() {
+                Some(event) => batcher.push(event),
+                None => return Ok(TreeDiff::default()),
+            }
+        }
+        while let Some(left) = batcher.due_in() {
+            if left.is_zero() {
+                break;
+            }
+            match self.recv_timeout(left) {
+                Some(event) => batcher.push(event),
+                None => break,
+            }
+        }
+        batcher.flush(tree)
+    }
+}
+
+/// The batch starting with `first`, collected from `source` as `recv_batch_prioritized`
+/// does.
+pub(crate) fn batch_after<S: EventSource + ?Sized>(
+    source: &S,
+    first: FsEvent,
+    window: Duration,
+    lanes: &PriorityLanes,
+) -> Vec<FsEvent> {
+    let mut urgent = lanes.is_urgent(&first);
+    let mut batch = vec![first];
+    let deadline = Instant::now() + window;
+    while !urgent {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match source.recv_timeout(left) {
+            Some(event) => {
+                urgent = lanes.is_urgent(&event);
+                batch.push(event);
+            }
+            None => break,
+        }
+    }
+    batch
+}
+
+/// How many events an event source's own queue holds before further ones are lost.
+const QUEUE_CAPACITY: usize = 65_536;
+
+/// Hands events to an event source's own queue and to its subscribers, after running
+/// them through the source's `EventTransform`.
+#[derive(Clone)]
+pub struct Injector {
+    sender: SyncSender<FsEvent>,
+    lost: Arc<Mutex<Option<PathBuf>>>,
+    fanout: Fanout,
+    transform: EventTransform,
+}
+
+impl Injector {
+    /// Create an injector and the receiving end of its queue.
+    pub(crate) fn channel(transform: EventTransform) -> (Self, EventQueue) {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let lost = Arc::new(Mutex::new(None));
+        let injector = Self {
+            sender,
+            lost: Arc::clone(&lost),
+            fanout: Fanout::new(),
+            transform,
+        };
+        (injector, EventQueue { receiver, lost })
+    }
+
+    /// Deliver `event` as if the filesystem had reported it.
+    pub fn inject(&self, event: FsEvent) {
+        let Some(event) = self.transform.apply(event) else {
+            return;
+        };
+        self.fanout.publish(&event);
+        if let Err(TrySendError::Full(event)) = self.sender.try_send(event) {
+            let mut lost = self.lost.lock().unwrap_or_else(|p| p.into_inner());
+            for path in event.paths() {
+                *lost = Some(match lost.take() {
+                    Some(lost) => common_ancestor(&lost, path),
+                    None => path.to_path_buf(),
+                });
+            }
+        }
+    }
+
+    #[cfg(feature = "watch")]
+    pub(crate) fn subscribe(&self, capacity: usize, overflow: Overflow) -> Subscriber {
+        self.fanout.subscribe(capacity, overflow)
+    }
+}
+
+/// The receiving end of an event source's own queue. Once events were lost to a full
+/// queue, the next one received is `FsEvent::Desynced` for the closest directory
+/// containing everything lost.
+pub(crate) struct EventQueue {
+    receiver: Receiver<FsEvent>,
+    lost: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl EventQueue {
+    pub(crate) fn recv(&self) -> Option<FsEvent> {
+        self.take_lost().or_else(|| self.receiver.recv().ok())
+    }
+
+    pub(crate) fn try_recv(&self) -> Option<FsEvent> {
+        self.take_lost().or_else(|| self.receiver.try_recv().ok())
+    }
+
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Option<FsEvent> {
+        self.take_lost()
+            .or_else(|| self.receiver.recv_timeout(timeout).ok())
+    }
+
+    fn take_lost(&self) -> Option<FsEvent> {
+        let mut lost = self.lost.lock().unwrap_or_else(|p| p.into_inner());
+        lost.take().map(FsEvent::Desynced)
+    }
+}
+
+/// The longest path both `a` and `b` lie at or below.
+fn common_ancestor(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect()
+}
+
+/// An event source driven by programmatic injection instead of a real filesystem, for
+/// unit-testing code that reacts to changes (alerts, mirrors, policies).
+///
+/// Events injected with `inject`, or through an `Injector` from another thread, come out
+/// of the `EventSource` methods in order. Like a watcher's, the queue holds up to
+/// 65,536 events; what does not fit is reported as one `FsEvent::Desynced`. Once `close` was called and every `Injector`
+/// dropped, `recv` returns `None` after the remaining events, like a watcher that died.
+pub struct SimulatedWatcher {
+    injector: Option<Injector>,
+    // Kept apart from the injector so subscribing still works once closed.
+    fanout: Fanout,
+    receiver: EventQueue,
+}
+
+impl SimulatedWatcher {
+    /// Create a source with no pending events.
+    pub fn new() -> Self {
+        Self::with_transform(EventTransform::default())
+    }
+
+    /// Create a source that rewrites injected events with `transform`.
+    pub fn with_transform(transform: EventTransform) -> Self {
+        let (injector, receiver) = Injector::channel(transform);
+        Self {
+            fanout: injector.fanout.clone(),
+            injector: Some(injector),
+            receiver,
+        }
+    }
+
+    /// Deliver `event` as if the filesystem had reported it. Does nothing once closed.
+    pub fn inject(&self, event: FsEvent) {
+        if let Some(injector) = &self.injector {
+            injector.inject(event);
+        }
+    }
+
+    /// Deliver each of `events` in order.
+    pub fn inject_all(&self, events: impl IntoIterator<Item = FsEvent>) {
+        for event in events {
+            self.inject(event);
+        }
+    }
+
+    /// A handle for injecting events from elsewhere, e.g. another thread.
+    /// Returns `None` once closed.
+    pub fn injector(&self) -> Option<Injector> {
+        self.injector.clone()
+    }
+
+    /// Stop accepting events through this source's own `inject`, so that `recv` ends
+    /// once the queue is drained and every `Injector` handed out is dropped.
+    pub fn close(&mut self) {
+        self.injector = None;
+    }
+}
+
+impl Default for SimulatedWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventSource for SimulatedWatcher {
+    fn recv(&self) -> Option<FsEvent> {
+        self.receiver.recv()
+    }
+
+    fn try_recv(&self) -> Option<FsEvent> {
+        self.receiver.try_recv()
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Option<FsEvent> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    fn subscribe(&self, capacity: usize, overflow: Overflow) -> Subscriber {
+        self.fanout.subscribe(capacity, overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{EventSource, SimulatedWatcher, QUEUE_CAPACITY};
+    use crate::event::FsEvent;
+    use crate::fanout::Overflow;
+
+    #[test]
+    fn events_past_a_full_queue_become_one_desynced() {
+        let source = SimulatedWatcher::new();
+        let subscriber = source.subscribe(4, Overflow::DropOldest);
+        let event = |path: &str| FsEvent::Modified(PathBuf::from(path));
+        for _ in 0..QUEUE_CAPACITY {
+            source.inject(event("/root/a/queued"));
+        }
+        source.inject(event("/root/a/lost"));
+        source.inject(event("/root/b/lost"));
+
+        let desynced = FsEvent::Desynced(PathBuf::from("/root"));
+        assert_eq!(source.try_recv(), Some(desynced));
+        assert_eq!(source.try_recv(), Some(event("/root/a/queued")));
+        assert_eq!(subscriber.try_recv(), Some(event("/root/a/queued")));
+        assert_eq!(subscriber.missed(), QUEUE_CAPACITY as u64 - 2);
+    }
+}
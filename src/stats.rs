@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::group::extension_name;
+use crate::node::NodeType;
+use crate::tree::Tree;
+
+/// Totals over a tree's resident entries, as found by `Tree::stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    /// Number of files.
+    pub files: u64,
+    /// Number of directories, including the root.
+    pub dirs: u64,
+    /// Number of symbolic links recorded as such.
+    pub symlinks: u64,
+    /// Size of the whole tree, as the root reports it.
+    pub size: u64,
+    /// Combined size of the files, which `average_file_size` is taken over.
+    pub file_bytes: u64,
+    /// Files and their combined size by lowercased extension, as `GroupBy::Extension`
+    /// names them; files without one go in "(none)".
+    pub extensions: BTreeMap<String, ExtensionStats>,
+    /// The entry furthest below the root, the first in breadth-first order among those
+    /// as deep, or the root itself in a tree without entries.
+    pub deepest: PathBuf,
+    /// How far below the root `deepest` is; 0 for the root itself.
+    pub depth: usize,
+}
+
+/// The files of one extension in `TreeStats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtensionStats {
+    /// Number of files.
+    pub files: u64,
+    /// Their combined size.
+    pub size: u64,
+}
+
+impl TreeStats {
+    /// The mean size of the files, rounded down; 0 without any.
+    pub fn average_file_size(&self) -> u64 {
+        self.file_bytes.checked_div(self.files).unwrap_or(0)
+    }
+}
+
+impl Tree {
+    /// Count the tree's resident entries by type and extension, and find its deepest
+    /// entry, in one pass over the tree. Entries of lazy directories not yet loaded are
+    /// not counted.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            size: self.head.size,
+            deepest: self.head.path.clone(),
+            ..TreeStats::default()
+        };
+        for (depth, node) in self.iter_bfs() {
+            match node.node_type {
+                NodeType::Directory => stats.dirs += 1,
+                NodeType::Symlink { .. } => stats.symlinks += 1,
+                NodeType::File => {
+                    stats.files += 1;
+                    stats.file_bytes += node.size;
+                    let extension = stats.extensions.entry(extension_name(node)).or_default();
+                    extension.files += 1;
+                    extension.size += node.size;
+                }
+            }
+            if depth > stats.depth {
+                stats.depth = depth;
+                stats.deepest = node.path.clone();
+            }
+        }
+        stats
+    }
+}
@@ -0,0 +1,364 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use sha2::{Digest, Sha256};
+
+use crate::builder::TreeBuilder;
+use crate::codec::{invalid, write_path, Input};
+use crate::content::{read_all, read_contents};
+use crate::node::NodeType;
+use crate::platform::create_symlink;
+use crate::resources::{Resources, Workers};
+use crate::snapshot::Snapshot;
+use crate::tree::Tree;
+
+/// A directory holding file contents under the SHA-256 of their bytes, so that identical
+/// contents are stored once however many files and backups share them.
+///
+/// Each blob keeps a count of the backed-up files referring to it; `release` drops a
+/// reference and deletes the blob once none remain. Backups made with `Tree::backup_to`
+/// are recorded in the store by their snapshot's `Snapshot::digest`, which is all
+/// `Tree::restore_from` needs besides the snapshot itself.
+///
+/// The store is safe to use from one process at a time.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+/// Distinguishes temporary files written concurrently by the same process.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl BlobStore {
+    /// Open the store at `root`, creating it if needed.
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(root.join("blobs"))?;
+        fs::create_dir_all(root.join("backups"))?;
+        Ok(Self { root })
+    }
+
+    /// Where the store lives.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Store everything `reader` yields and take a reference on it, returning its hash
+    /// as lowercase hex.
+    pub fn put(&self, reader: impl Read) -> io::Result<String> {
+        self.put_with(|consume| read_all(reader, consume))
+    }
+
+    /// Store the contents of the file at `path`.
+    pub fn put_file(&self, path: &Path) -> io::Result<String> {
+        self.put_with(|consume| read_contents(path, &Resources::default(), consume))
+    }
+
+    /// Store the bytes `feed` hands to the function it is given.
+    fn put_with(
+        &self,
+        feed: impl FnOnce(&mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()>,
+    ) -> io::Result<String> {
+        let temp = self.root.join(format!(
+            "blobs/.incoming-{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = File::create(&temp)?;
+        let mut hasher = Sha256::new();
+        let written = feed(&mut |bytes| {
+            hasher.update(bytes);
+            file.write_all(bytes)
+        });
+        drop(file);
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+
+        let hash = to_hex(&hasher.finalize());
+        let path = self.blob_path(&hash);
+        if path.exists() {
+            fs::remove_file(&temp)?;
+        } else {
+            fs::create_dir_all(path.parent().expect("blobs have a parent"))?;
+            fs::rename(&temp, &path)?;
+        }
+        self.set_refcount(&hash, self.refcount(&hash)? + 1)?;
+        Ok(hash)
+    }
+
+    /// Open the blob with the given hash for reading.
+    pub fn get(&self, hash: &str) -> io::Result<File> {
+        File::open(self.blob_path(&checked(hash)?))
+    }
+
+    /// Returns `true` if the store holds a blob with the given hash.
+    pub fn contains(&self, hash: &str) -> bool {
+        checked(hash).is_ok_and(|hash| self.blob_path(&hash).exists())
+    }
+
+    /// How many references the blob with the given hash has; 0 if it is not stored.
+    pub fn refcount(&self, hash: &str) -> io::Result<u64> {
+        match fs::read_to_string(self.refs_path(&checked(hash)?)) {
+            Ok(count) => count
+                .trim()
+                .parse()
+                .map_err(|_| invalid("corrupt reference count")),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Drop one reference to the blob, deleting it once none remain. Returns `true` if
+    /// the blob was deleted.
+    pub fn release(&self, hash: &str) -> io::Result<bool> {
+        let hash = checked(hash)?;
+        let count = self.refcount(&hash)?;
+        if count > 1 {
+            self.set_refcount(&hash, count - 1)?;
+            return Ok(false);
+        }
+        remove_if_present(&self.blob_path(&hash))?;
+        remove_if_present(&self.refs_path(&hash))?;
+        Ok(count == 1)
+    }
+
+    /// Drop the backup of `snapshot`, releasing its references. Returns `false` if the
+    /// store holds no such backup.
+    pub fn forget(&self, snapshot: &Snapshot) -> io::Result<bool> {
+        let path = self.backup_path(snapshot.digest());
+        let blobs = match self.read_backup(&path) {
+            Ok(blobs) => blobs,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        for (_, hash) in &blobs {
+            self.release(hash)?;
+        }
+        fs::remove_file(path)?;
+        Ok(true)
+    }
+
+    /// Returns `true` if the store holds a backup of `snapshot`.
+    pub fn has_backup(&self, snapshot: &Snapshot) -> bool {
+        self.backup_path(snapshot.digest()).exists()
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.root.join("blobs").join(&hash[..2]).join(hash)
+    }
+
+    fn refs_path(&self, hash: &str) -> PathBuf {
+        self.root
+            .join("blobs")
+            .join(&hash[..2])
+            .join(format!("{}.refs", hash))
+    }
+
+    fn backup_path(&self, digest: u64) -> PathBuf {
+        self.root.join("backups").join(format!("{:016x}", digest))
+    }
+
+    fn set_refcount(&self, hash: &str, count: u64) -> io::Result<()> {
+        fs::write(self.refs_path(hash), count.to_string())
+    }
+
+    /// Records which blob holds each file of a backup: the hash, then the path relative
+    /// to the backup's root.
+    fn write_backup(&self, path: &Path, blobs: &[(PathBuf, String)]) -> io::Result<()> {
+        let mut out = Vec::new();
+        for (rel, hash) in blobs {
+            out.extend_from_slice(hash.as_bytes());
+            write_path(&mut out, rel);
+        }
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, out)?;
+        fs::rename(temp, path)
+    }
+
+    fn read_backup(&self, path: &Path) -> io::Result<Vec<(PathBuf, String)>> {
+        let bytes = fs::read(path)?;
+        let mut input = Input { bytes: &bytes };
+        let mut blobs = Vec::new();
+        while !input.bytes.is_empty() {
+            let hash = String::from_utf8(input.take(64)?.to_vec())
+                .map_err(|_| invalid("corrupt backup record"))?;
+            blobs.push((input.path()?, checked(&hash)?));
+        }
+        Ok(blobs)
+    }
+}
+
+impl Tree {
+    /// Save the contents of every file in the tree to `store` and return the snapshot
+    /// describing the backup. Contents already in the store are not written again.
+    /// Files are read as the tree's `Resources` ask for, e.g. mapped into memory.
+    /// Backing up an unchanged tree twice replaces the earlier backup of it.
+    pub fn backup_to(&self, store: &BlobStore) -> io::Result<Snapshot> {
+        let snapshot = self.snapshot();
+        let mut blobs = Vec::new();
+        for entry in &snapshot.entries {
+            if entry.node_type == NodeType::File {
+                let path = snapshot.absolute_path(entry);
+                let resources = &self.resources;
+                let hash = store.put_with(|consume| read_contents(&path, resources, consume))?;
+                blobs.push((entry.path.clone(), hash));
+            }
+        }
+        // Take the new references before dropping the old ones, so shared blobs survive.
+        let previous = store.backup_path(snapshot.digest());
+        let replaced = match store.read_backup(&previous) {
+            Ok(replaced) => replaced,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        store.write_backup(&previous, &blobs)?;
+        for (_, hash) in &replaced {
+            store.release(hash)?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Recreate the backup of `snapshot` from `store` below `target` and return the
+    /// restored tree. Files get their recorded modification times, and symbolic links
+    /// their recorded targets. Existing files are
+    /// never overwritten; finding one is reported as `AlreadyExists`. Fails with
+    /// `NotFound` if the store has no backup of the snapshot.
+    pub fn restore_from(store: &BlobStore, snapshot: &Snapshot, target: &Path) -> io::Result<Tree> {
+        Self::restore_from_with(store, snapshot, target, Resources::default())
+    }
+
+    /// Like `restore_from`, copying files on up to `Resources::copy_threads` threads at
+    /// once and scanning the result with `Resources::scan_threads`. The returned tree
+    /// keeps `resources`.
+    pub fn restore_from_with(
+        store: &BlobStore,
+        snapshot: &Snapshot,
+        target: &Path,
+        resources: Resources,
+    ) -> io::Result<Tree> {
+        let blobs = store
+            .read_backup(&store.backup_path(snapshot.digest()))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the store holds no backup of this snapshot",
+                ),
+                _ => e,
+            })?;
+        let mut blobs = blobs.into_iter().peekable();
+
+        // Directories and links first, so every file's parent exists when it is copied.
+        fs::create_dir_all(target)?;
+        let mut files = Vec::new();
+        for entry in &snapshot.entries {
+            let path = target.join(&entry.path);
+            match &entry.node_type {
+                NodeType::Directory => fs::create_dir_all(&path)?,
+                NodeType::Symlink { target } => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    create_symlink(target, &path)?;
+                }
+                NodeType::File => {
+                    // Both lists are sorted by relative path.
+                    while blobs.peek().is_some_and(|(rel, _)| *rel < entry.path) {
+                        blobs.next();
+                    }
+                    let hash = match blobs.peek() {
+                        Some((rel, hash)) if *rel == entry.path => hash.clone(),
+                        _ => return Err(invalid("backup record does not match the snapshot")),
+                    };
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    files.push((path, hash, entry.metadata.modified));
+                }
+            }
+        }
+
+        let workers = Workers::new(resources.copy_threads, resources.numa)?;
+        workers.map(files, |(path, hash, modified)| {
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            let blob = store.get(&hash)?;
+            #[cfg(all(feature = "fadvise", target_os = "linux"))]
+            let _hints = (
+                crate::fadvise::Streaming::read(&blob, resources.spare_page_cache),
+                crate::fadvise::Streaming::write(&file, resources.spare_page_cache),
+            );
+            io::copy(&mut &blob, &mut &file)?;
+            if let Some(modified) = modified {
+                file.set_modified(modified)?;
+            }
+            Ok(())
+        })?;
+        TreeBuilder::new(target).resources(resources).build()
+    }
+}
+
+/// Rejects anything but a lowercase hex SHA-256, so hashes can safely become paths.
+fn checked(hash: &str) -> io::Result<String> {
+    let valid = hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    match valid {
+        true => Ok(hash.to_string()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a blob hash", hash),
+        )),
+    }
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn backups_share_blobs_and_restore() {
+        let dir = empty_dir().unwrap();
+        let source = dir.root.join("source");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("one"), b"same").unwrap();
+        fs::write(source.join("sub/two"), b"same").unwrap();
+        fs::write(source.join("sub/other"), b"different").unwrap();
+        let store = BlobStore::open(dir.root.join("store")).unwrap();
+
+        let tree = TreeBuilder::new(&source).build().unwrap();
+        let snapshot = tree.backup_to(&store).unwrap();
+        let same = store.put(&b"same"[..]).unwrap();
+        assert_eq!(store.refcount(&same).unwrap(), 3);
+        assert!(store.release(&same).is_ok_and(|deleted| !deleted));
+        assert!(store.has_backup(&snapshot));
+
+        let target = dir.root.join("restored");
+        let restored = Tree::restore_from(&store, &snapshot, &target).unwrap();
+        assert_eq!(restored.head.size, tree.head.size);
+        assert_eq!(fs::read(target.join("sub/two")).unwrap(), b"same");
+        assert_eq!(fs::read(target.join("sub/other")).unwrap(), b"different");
+        let again = Tree::restore_from(&store, &snapshot, &target);
+        assert!(again.is_err_and(|e| e.kind() == io::ErrorKind::AlreadyExists));
+
+        assert!(store.forget(&snapshot).unwrap());
+        assert!(!store.contains(&same));
+        assert!(!store.has_backup(&snapshot));
+        assert!(!store.forget(&snapshot).unwrap());
+    }
+}
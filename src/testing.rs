@@ -0,0 +1,176 @@
+//! Utilities for building reproducible synthetic trees in tests.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How the sizes of generated files are chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeDistribution {
+    /// Every file has the same size.
+    Fixed(u64),
+    /// Sizes are spread evenly between `min` and `max` (inclusive).
+    Uniform { min: u64, max: u64 },
+}
+
+/// The shape of a synthetic tree.
+/// The same spec, including its seed, always produces the same tree.
+#[derive(Debug, Clone)]
+pub struct TreeSpec {
+    /// Number of subdirectories created in each directory above the deepest level.
+    pub breadth: usize,
+    /// Number of directory levels below the root.
+    pub depth: usize,
+    /// Number of files created in every directory, including the root.
+    pub files_per_dir: usize,
+    /// Sizes of the generated files.
+    pub sizes: SizeDistribution,
+    /// Seed for the pseudo-random choices made while generating.
+    pub seed: u64,
+}
+
+impl Default for TreeSpec {
+    fn default() -> Self {
+        Self {
+            breadth: 4,
+            depth: 3,
+            files_per_dir: 16,
+            sizes: SizeDistribution::Uniform {
+                min: 0,
+                max: 64 * 1024,
+            },
+            seed: 0x5eed,
+        }
+    }
+}
+
+/// Counts of what was generated on disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GeneratedTree {
+    /// Number of files created.
+    pub files: u64,
+    /// Number of directories created, not counting the root.
+    pub dirs: u64,
+    /// Total logical size of the created files.
+    pub bytes: u64,
+}
+
+/// A synthetic tree in a temporary directory, removed again when dropped.
+#[derive(Debug)]
+pub struct FakeTree {
+    /// The root of the generated tree.
+    pub root: PathBuf,
+    /// What was generated below the root.
+    pub generated: GeneratedTree,
+}
+
+impl Drop for FakeTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Generate a synthetic tree in a fresh temporary directory.
+pub fn fake_tree(spec: &TreeSpec) -> io::Result<FakeTree> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let root = std::env::temp_dir().join(format!(
+        "file-frontier-{}-{}-{:x}",
+        process_tag(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        spec.seed
+    ));
+    if root.exists() {
+        fs::remove_dir_all(&root)?;
+    }
+    let generated = fake_tree_at(&root, spec)?;
+    Ok(FakeTree { root, generated })
+}
+
+/// A fresh, empty temporary directory, removed again when dropped.
+pub fn empty_dir() -> io::Result<FakeTree> {
+    fake_tree(&TreeSpec {
+        breadth: 0,
+        depth: 0,
+        files_per_dir: 0,
+        ..TreeSpec::default()
+    })
+}
+
+/// Distinguishes temporary trees of concurrently running processes.
+#[cfg(not(target_os = "wasi"))]
+fn process_tag() -> u32 {
+    std::process::id()
+}
+
+/// WASI has no process ids, and a sandboxed module rarely shares its temp directory.
+#[cfg(target_os = "wasi")]
+fn process_tag() -> u32 {
+    0
+}
+
+/// Generate a synthetic tree below `root`, creating it if needed.
+/// Files are created sparse, so large sizes cost little disk space.
+pub fn fake_tree_at(root: &Path, spec: &TreeSpec) -> io::Result<GeneratedTree> {
+    let mut rng = SplitMix64::new(spec.seed);
+    let mut generated = GeneratedTree::default();
+    fs::create_dir_all(root)?;
+    generate_level(root, spec, spec.depth, &mut rng, &mut generated)?;
+    Ok(generated)
+}
+
+fn generate_level(
+    dir: &Path,
+    spec: &TreeSpec,
+    remaining: usize,
+    rng: &mut SplitMix64,
+    generated: &mut GeneratedTree,
+) -> io::Result<()> {
+    for i in 0..spec.files_per_dir {
+        let size = match spec.sizes {
+            SizeDistribution::Fixed(size) => size,
+            SizeDistribution::Uniform { min, max } => rng.between(min, max),
+        };
+        File::create(dir.join(format!("file_{i}.dat")))?.set_len(size)?;
+        generated.files += 1;
+        generated.bytes += size;
+    }
+    if remaining == 0 {
+        return Ok(());
+    }
+    for i in 0..spec.breadth {
+        let sub = dir.join(format!("dir_{i}"));
+        fs::create_dir(&sub)?;
+        generated.dirs += 1;
+        generate_level(&sub, spec, remaining - 1, rng, generated)?;
+    }
+    Ok(())
+}
+
+/// A small, fast pseudo-random generator; plenty for picking synthetic sizes.
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value between `min` and `max`, inclusive.
+    pub(crate) fn between(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        match (max - min).checked_add(1) {
+            Some(span) => min + self.next_u64() % span,
+            None => self.next_u64(),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::node::{ExtendedMetadata, Node, NodeType};
+use crate::tree::{descendants, Tree};
+
+/// An entry that disappeared from the tree, with what was last known about it.
+#[derive(Debug, Clone)]
+pub struct Tombstone {
+    /// Where the entry was in the tree.
+    pub path: PathBuf,
+    /// Whether it was a file or a directory.
+    pub node_type: NodeType,
+    /// Its last known size.
+    pub size: u64,
+    /// Its last known metadata.
+    pub metadata: ExtendedMetadata,
+    /// When the rescan noticed it was gone.
+    pub deleted: SystemTime,
+}
+
+/// Tombstones kept by `Tree::keep_tombstones`, keyed by path.
+#[derive(Debug, Clone)]
+pub(crate) struct Tombstones {
+    retention: Duration,
+    entries: BTreeMap<PathBuf, Tombstone>,
+}
+
+impl Tombstones {
+    /// Records `old` and everything below it that has no counterpart in `fresh`.
+    fn bury(&mut self, old: &Node, fresh: Option<&Node>, now: SystemTime) {
+        if fresh.is_none() {
+            self.entries.insert(
+                old.path.clone(),
+                Tombstone {
+                    path: old.path.clone(),
+                    node_type: old.node_type.clone(),
+                    size: old.size,
+                    metadata: old.metadata.clone(),
+                    deleted: now,
+                },
+            );
+        }
+
+        let fresh_children: HashMap<&Path, &Node> = fresh
+            .and_then(|fresh| fresh.children.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|child| (child.path.as_path(), child))
+            .collect();
+        for child in old.children.iter().flatten() {
+            let counterpart = fresh_children.get(child.path.as_path()).copied();
+            self.bury(child, counterpart, now);
+        }
+    }
+
+    /// Estimated bytes held by the tombstones.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let entry = size_of::<PathBuf>() + size_of::<Tombstone>();
+        let paths: usize = self.entries.keys().map(PathBuf::capacity).sum();
+        self.entries.len() * entry + paths * 2
+    }
+}
+
+impl Tree {
+    /// Start keeping a tombstone for every entry that a refresh or applied event finds
+    /// gone, for at least `retention`, so they can be listed with `recently_deleted`.
+    /// Entries only known as part of an evicted subtree leave no tombstone of their own.
+    pub fn keep_tombstones(&mut self, retention: Duration) {
+        match &mut self.tombstones {
+            Some(tombstones) => tombstones.retention = retention,
+            None => {
+                self.tombstones = Some(Tombstones {
+                    retention,
+                    entries: BTreeMap::new(),
+                })
+            }
+        }
+    }
+
+    /// Entries deleted within the retention window, most recently deleted first.
+    /// A path that has reappeared since is no longer listed.
+    pub fn recently_deleted(&self) -> Vec<&Tombstone> {
+        let Some(tombstones) = &self.tombstones else {
+            return Vec::new();
+        };
+        let now = self.clock.now();
+        let mut deleted: Vec<&Tombstone> = tombstones
+            .entries
+            .values()
+            .filter(|tombstone| !is_expired(tombstone, tombstones.retention, now))
+            .collect();
+        deleted.sort_by_key(|tombstone| std::cmp::Reverse(tombstone.deleted));
+        deleted
+    }
+
+    /// Updates the tombstones after the subtree at `path` was rescanned, given what the
+    /// subtree held before, and drops those that have expired.
+    pub(crate) fn reindex_tombstones(&mut self, path: &Path, displaced: Option<&Node>) {
+        let Some(mut tombstones) = self.tombstones.take() else {
+            return;
+        };
+        let now = self.clock.now();
+
+        let revived: Vec<PathBuf> = descendants(&tombstones.entries, path)
+            .filter(|entry| self.get_node(entry).is_some())
+            .cloned()
+            .collect();
+        for entry in revived {
+            tombstones.entries.remove(&entry);
+        }
+
+        if let Some(old) = displaced {
+            let fresh = self.get_node(path);
+            tombstones.bury(old, fresh, now);
+        }
+
+        let retention = tombstones.retention;
+        tombstones
+            .entries
+            .retain(|_, tombstone| !is_expired(tombstone, retention, now));
+        self.tombstones = Some(tombstones);
+    }
+}
+
+fn is_expired(tombstone: &Tombstone, retention: Duration, now: SystemTime) -> bool {
+    now.duration_since(tombstone.deleted)
+        .is_ok_and(|age| age > retention)
+}
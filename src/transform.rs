@@ -0,0 +1,91 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::event::FsEvent;
+
+type Rule = Arc<dyn Fn(&Path) -> Option<PathBuf> + Send + Sync>;
+
+/// Rewrites the paths of events before they reach a tree or any subscriber, e.g. to
+/// strip a mount prefix or map container paths to host paths. Rules run in the order
+/// they were added, each seeing the previous one's output; a rule returning `None`
+/// drops the path.
+#[derive(Clone, Default)]
+pub struct EventTransform {
+    rules: Vec<Rule>,
+}
+
+impl EventTransform {
+    /// Create a transform that leaves paths unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move paths under `from` to the same place under `to`. Other paths pass unchanged.
+    pub fn map_prefix(self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Self {
+        let (from, to) = (from.into(), to.into());
+        self.rewrite(move |path| {
+            Some(match path.strip_prefix(&from) {
+                Ok(rel) => to.join(rel),
+                Err(_) => path.to_path_buf(),
+            })
+        })
+    }
+
+    /// Drop paths outside `prefix`.
+    pub fn only_below(self, prefix: impl Into<PathBuf>) -> Self {
+        let prefix = prefix.into();
+        self.rewrite(move |path| path.starts_with(&prefix).then(|| path.to_path_buf()))
+    }
+
+    /// Add an arbitrary rule, returning the new path or `None` to drop it.
+    pub fn rewrite<F>(mut self, rule: F) -> Self
+    where
+        F: Fn(&Path) -> Option<PathBuf> + Send + Sync + 'static,
+    {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+
+    /// Returns `true` if no rule was added.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Run every rule over `path`.
+    pub fn apply_path(&self, path: &Path) -> Option<PathBuf> {
+        let mut path = path.to_path_buf();
+        for rule in &self.rules {
+            path = rule(&path)?;
+        }
+        Some(path)
+    }
+
+    /// Rewrite the paths of `event`. An event losing its only path is dropped; a rename
+    /// losing one side becomes a creation or removal of the other.
+    pub fn apply(&self, event: FsEvent) -> Option<FsEvent> {
+        if self.rules.is_empty() {
+            return Some(event);
+        }
+        Some(match event {
+            FsEvent::Created(path) => FsEvent::Created(self.apply_path(&path)?),
+            FsEvent::Modified(path) => FsEvent::Modified(self.apply_path(&path)?),
+            FsEvent::Removed(path) => FsEvent::Removed(self.apply_path(&path)?),
+            FsEvent::Desynced(path) => FsEvent::Desynced(self.apply_path(&path)?),
+            FsEvent::Renamed { from, to } => match (self.apply_path(&from), self.apply_path(&to)) {
+                (Some(from), Some(to)) => FsEvent::Renamed { from, to },
+                (Some(from), None) => FsEvent::Removed(from),
+                (None, Some(to)) => FsEvent::Created(to),
+                (None, None) => return None,
+            },
+        })
+    }
+}
+
+impl fmt::Debug for EventTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventTransform")
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
@@ -1,12 +1,55 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::io;
+use std::mem;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::node::Node;
+use crate::bookmark::Bookmarks;
+use crate::builder::Scanner;
+use crate::chroot::rebase;
+use crate::clock::{Clock, SystemClock};
+use crate::eviction::Residency;
+use crate::history::SizeHistory;
+use crate::inode::InodeIndex;
+use crate::node::{ExtendedMetadata, Node, NodeType};
+use crate::options::ScanOptions;
+use crate::query::Registered;
+use crate::recent::RecentIndex;
+use crate::resources::Resources;
+use crate::snapshot::SnapshotEntry;
+use crate::tombstone::Tombstones;
 
 /// An in-memory representation of a directory tree.
 pub struct Tree {
     /// The root node of the tree.
     pub head: Node,
+    /// The settings the tree was scanned with.
+    pub options: ScanOptions,
+    /// Access times and pins deciding which subtrees stay resident.
+    pub(crate) residency: Residency,
+    /// Bumped on every refresh; see `Node::generation`.
+    pub(crate) generation: u64,
+    /// Files by modification time, once enabled with `track_recent`.
+    pub(crate) recent: Option<RecentIndex>,
+    /// Named pointers to paths, kept across refreshes.
+    pub(crate) bookmarks: Bookmarks,
+    /// Live queries to re-evaluate on every rescan.
+    pub(crate) queries: Vec<Registered>,
+    /// The directory on disk standing in for `head.path`, for trees built with
+    /// `new_chroot`. `None` when paths are used as they are.
+    pub(crate) host_root: Option<PathBuf>,
+    /// Entries recently found deleted, once enabled with `keep_tombstones`.
+    pub(crate) tombstones: Option<Tombstones>,
+    /// Paths by device and inode number, once enabled with `track_inodes`.
+    pub(crate) inodes: Option<InodeIndex>,
+    /// Directory sizes over time, once enabled with `track_size_history`.
+    pub(crate) history: Option<SizeHistory>,
+    /// Where timestamps and ages are read from; see `set_clock`.
+    pub(crate) clock: Arc<dyn Clock>,
+    /// How many threads each kind of operation may use; see `set_resources`.
+    pub(crate) resources: Resources,
+    /// Whether sizes and metadata are yet to be read; see `details_pending`.
+    pub(crate) details_pending: bool,
     // In lieu of a mutable “focus” pointer, we provide iterator and search methods.
 }
 
@@ -14,23 +57,165 @@ impl Tree {
     /// Create a new tree from a given root path.
     pub fn new(root: &Path) -> io::Result<Self> {
         let head = Node::new(root.to_path_buf())?;
-        Ok(Self { head })
+        Ok(Self::from_head(head))
+    }
+
+    /// Wrap an already built root node.
+    pub(crate) fn from_head(head: Node) -> Self {
+        Self {
+            head,
+            options: ScanOptions::default(),
+            residency: Residency::default(),
+            generation: 0,
+            recent: None,
+            bookmarks: Bookmarks::default(),
+            queries: Vec::new(),
+            host_root: None,
+            tombstones: None,
+            inodes: None,
+            history: None,
+            clock: Arc::new(SystemClock),
+            resources: Resources::default(),
+            details_pending: false,
+        }
+    }
+
+    /// Read the current time from `clock` instead of the system, e.g. a `ManualClock`
+    /// in tests.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Build a tree in memory from entries relative to `root`, without touching the filesystem.
+    /// Missing parent directories are created implicitly, and directory sizes are summed
+    /// from their contents rather than taken from the entries.
+    pub(crate) fn from_entries(
+        root: PathBuf,
+        entries: impl IntoIterator<Item = SnapshotEntry>,
+    ) -> Self {
+        let mut head = Node::from_parts(root, NodeType::Directory, ExtendedMetadata::default(), 0);
+        for entry in entries {
+            insert_entry(&mut head, entry);
+        }
+        head.sum_child_sizes();
+        Self::from_head(head)
     }
 
     /// Returns an iterator over all nodes in the tree using depth-first search.
-    pub fn iter(&self) -> TreeIterator {
+    pub fn iter(&self) -> TreeIterator<'_> {
         TreeIterator {
             stack: vec![&self.head],
         }
     }
 
+    /// Call `visit` on every node with mutable access, each directory before its
+    /// children, in the same order as `iter`. Children added or removed by `visit` are
+    /// the ones then walked.
+    ///
+    /// Nothing is kept consistent for the caller: ancestor sizes, indexes and live
+    /// queries do not follow changes made here, and changed paths stop matching lookups.
+    pub fn visit_mut<F>(&mut self, mut visit: F)
+    where
+        F: FnMut(&mut Node),
+    {
+        let mut stack = vec![&mut self.head];
+        while let Some(node) = stack.pop() {
+            visit(node);
+            if let Some(children) = &mut node.children {
+                stack.extend(children.iter_mut());
+            }
+        }
+    }
+
+    /// Like `visit_mut`, but visits each directory after all of its children, so that
+    /// values computed from the children (sizes, hashes) can be stored on the way up.
+    pub fn visit_mut_post<F>(&mut self, mut visit: F)
+    where
+        F: FnMut(&mut Node),
+    {
+        fn walk<F: FnMut(&mut Node)>(node: &mut Node, visit: &mut F) {
+            if let Some(children) = &mut node.children {
+                for child in children.iter_mut().rev() {
+                    walk(child, visit);
+                }
+            }
+            visit(node);
+        }
+        walk(&mut self.head, &mut visit);
+    }
+
+    /// Returns an iterator over all nodes in the tree in breadth-first order, each with
+    /// its depth below the root (0 for the root itself), so that every entry of one level
+    /// comes before the next level's.
+    pub fn iter_bfs(&self) -> BfsIterator<'_> {
+        BfsIterator {
+            queue: VecDeque::from([(0, &self.head)]),
+        }
+    }
+
     /// Refreshes the tree structure by re-populating children and updating sizes.
     pub fn refresh(&mut self) -> io::Result<()> {
-        self.head.populate_children()?;
-        self.head.update_size()?;
+        self.generation += 1;
+        let host = self.host_root().to_path_buf();
+        let mut fresh = match self.options.lazy {
+            true => Node::rescan_like(host, Some(&self.head), self.options.include_hidden)?,
+            false => Scanner::new(&self.options, &host)?
+                .resources(&self.resources)
+                .scan(host, 0)?,
+        };
+        rebase(&mut fresh, self.host_root(), &self.head.path);
+        carry_generations(Some(&self.head), &mut fresh, self.generation);
+        let old = mem::replace(&mut self.head, fresh);
+        self.rescanned(&old.path, Some(&old));
+        Ok(())
+    }
+
+    /// Rescans only the subtree at `path` and splices it into place, adjusting the
+    /// sizes of its ancestors by the difference. A path that has appeared since the
+    /// last scan is added below its parent; one that has vanished is removed.
+    pub fn refresh_path(&mut self, path: &Path) -> io::Result<()> {
+        if path == self.head.path {
+            return self.refresh();
+        }
+        if !path.starts_with(&self.head.path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside the tree", path.display()),
+            ));
+        }
+        self.generation += 1;
+        let mount = self.mount();
+        let root = self.head.path.clone();
+        let scan = Rescan {
+            generation: self.generation,
+            mount: mount.as_ref(),
+            root: &root,
+            scanner: Scanner::new(&self.options, self.host_root())?.resources(&self.resources),
+            lazy: self.options.lazy,
+            include_hidden: self.options.include_hidden,
+        };
+        let splice = refresh_subtree(&mut self.head, path, &scan)?;
+        self.rescanned(&splice.path, splice.displaced.as_ref());
         Ok(())
     }
 
+    /// The tree's root and the directory on disk standing in for it, if they differ.
+    fn mount(&self) -> Option<Mount> {
+        let host = self.host_root.clone()?;
+        Some((self.head.path.clone(), host))
+    }
+
+    /// Bring indexes and live queries up to date after the subtree at `path` was rescanned,
+    /// given the entry it replaced, if there was one.
+    pub(crate) fn rescanned(&mut self, path: &Path, displaced: Option<&Node>) {
+        self.recount_sizes();
+        self.reindex_recent(path);
+        self.reindex_tombstones(path, displaced);
+        self.reindex_inodes(path);
+        self.update_queries(path);
+        self.sample_sizes();
+    }
+
     /// Search for nodes matching a given predicate.
     pub fn search<F>(&self, predicate: F) -> Vec<&Node>
     where
@@ -54,10 +239,279 @@ impl Tree {
             //.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Node not found"))
     //}
 
-    // Retrieve a node by its path, if it exists in the tree.
-    //pub fn get_node(&self, path: &Path) -> Option<&Node> {
-        //self.iter().find(|node| node.path == path)
-    //}
+    /// Retrieve a node by its path, if it exists in the tree.
+    pub fn get_node(&self, path: &Path) -> Option<&Node> {
+        let rel = path.strip_prefix(&self.head.path).ok()?;
+        let mut current = &self.head;
+        for component in rel.components() {
+            let next = current.path.join(component);
+            current = current
+                .children
+                .as_ref()?
+                .iter()
+                .find(|child| child.path == next)?;
+        }
+        Some(current)
+    }
+
+    /// Retrieve a node by its path for modification. Callers are responsible for
+    /// keeping ancestor sizes consistent.
+    pub(crate) fn get_node_mut(&mut self, path: &Path) -> Option<&mut Node> {
+        let rel = path.strip_prefix(&self.head.path).ok()?.to_path_buf();
+        let mut current = &mut self.head;
+        for component in rel.components() {
+            let next = current.path.join(component);
+            current = current
+                .children
+                .as_mut()?
+                .iter_mut()
+                .find(|child| child.path == next)?;
+        }
+        Some(current)
+    }
+}
+
+/// The outcome of rescanning one entry below the root.
+struct Splice {
+    /// The entry that was rescanned: the one asked for, or the evicted stub above it.
+    path: PathBuf,
+    /// The old and new size of the entry, so that every ancestor can be adjusted.
+    sizes: (u64, u64),
+    /// The entry as it was before the rescan, if it was in the tree.
+    displaced: Option<Node>,
+    /// Whether anything at or below the entry changed, which every ancestor takes on.
+    changed: bool,
+}
+
+/// How to rescan entries below the root.
+struct Rescan<'a> {
+    /// The generation to stamp on entries found changed.
+    generation: u64,
+    /// The tree's root and the directory standing in for it, for chroot trees.
+    mount: Option<&'a Mount>,
+    /// The tree's root, which depths are counted from.
+    root: &'a Path,
+    /// Scans with the tree's options.
+    scanner: Scanner<'a>,
+    /// Whether to list only the directories that were listed before.
+    lazy: bool,
+    /// Whether lazy listings include hidden entries.
+    include_hidden: bool,
+}
+
+/// The keys of `map` at or below `path`. Paths order component by component, so
+/// everything below a path sorts directly after it, before any sibling.
+pub(crate) fn descendants<'a, V>(
+    map: &'a BTreeMap<PathBuf, V>,
+    path: &'a Path,
+) -> impl Iterator<Item = &'a PathBuf> {
+    map.range(path.to_path_buf()..)
+        .map(|(entry, _)| entry)
+        .take_while(move |entry| entry.starts_with(path))
+}
+
+/// The paths of `set` at or below `path`, found as `descendants` does.
+pub(crate) fn descendants_in<'a>(
+    set: &'a BTreeSet<PathBuf>,
+    path: &'a Path,
+) -> impl Iterator<Item = &'a PathBuf> {
+    set.range(path.to_path_buf()..)
+        .take_while(move |entry| entry.starts_with(path))
+}
+
+/// Rescans `path` somewhere below `node` and splices the result into place.
+fn refresh_subtree(node: &mut Node, path: &Path, scan: &Rescan<'_>) -> io::Result<Splice> {
+    if !node.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not below a directory in the tree", path.display()),
+        ));
+    }
+    if node.needs_load() {
+        // Nothing below a directory that was never listed is known yet; it is read
+        // as it is on disk once listed.
+        return Ok(Splice {
+            path: path.to_path_buf(),
+            sizes: (0, 0),
+            displaced: None,
+            changed: false,
+        });
+    }
+    let generation = scan.generation;
+    let children = node.children.get_or_insert_with(Vec::new);
+
+    let position = children
+        .iter()
+        .position(|child| path.starts_with(&child.path));
+    let splice = match position {
+        // Nothing below an evicted stub is known to splice changes into, so the stub is
+        // rescanned as a whole, which makes it resident again.
+        Some(index) if children[index].path == path || children[index].evicted.is_some() => {
+            let old = children[index].size;
+            let target = children[index].path.clone();
+            match rescan(&target, Some(&children[index]), scan)? {
+                Some(mut fresh) => {
+                    let new = fresh.size;
+                    carry_generations(Some(&children[index]), &mut fresh, generation);
+                    Splice {
+                        path: target,
+                        sizes: (old, new),
+                        changed: fresh.generation == generation,
+                        displaced: Some(mem::replace(&mut children[index], fresh)),
+                    }
+                }
+                None => Splice {
+                    path: target,
+                    sizes: (old, 0),
+                    displaced: Some(children.remove(index)),
+                    changed: true,
+                },
+            }
+        }
+        Some(index) => refresh_subtree(&mut children[index], path, scan)?,
+        None if path.parent() == Some(node.path.as_path()) => {
+            let (sizes, changed) = match rescan(path, None, scan)? {
+                Some(mut fresh) => {
+                    let new = fresh.size;
+                    carry_generations(None, &mut fresh, generation);
+                    children.push(fresh);
+                    scan.scanner.sort(children);
+                    ((0, new), true)
+                }
+                None => ((0, 0), false),
+            };
+            Splice {
+                path: path.to_path_buf(),
+                sizes,
+                displaced: None,
+                changed,
+            }
+        }
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("parent of {} is not in the tree", path.display()),
+            ))
+        }
+    };
+
+    let (old, new) = splice.sizes;
+    node.size = node.size.saturating_sub(old) + new;
+    if old != new || splice.changed {
+        node.generation = generation;
+    }
+    Ok(splice)
+}
+
+/// Gives every entry in `fresh` the generation of its counterpart in `old` if it looks
+/// unchanged (same type, size and modification time), and `generation` otherwise. A
+/// directory with anything changed below it, or with entries added or removed, takes
+/// `generation` too.
+fn carry_generations(old: Option<&Node>, fresh: &mut Node, generation: u64) {
+    fresh.generation = match old {
+        Some(old)
+            if old.node_type == fresh.node_type
+                && old.size == fresh.size
+                && old.metadata.modified == fresh.metadata.modified =>
+        {
+            old.generation
+        }
+        _ => generation,
+    };
+
+    let old_children: HashMap<&Path, &Node> = old
+        .and_then(|old| old.children.as_ref())
+        .into_iter()
+        .flatten()
+        .map(|child| (child.path.as_path(), child))
+        .collect();
+    let mut carried = 0;
+    for child in fresh.children.iter_mut().flatten() {
+        let previous = old_children.get(child.path.as_path()).copied();
+        carried += usize::from(previous.is_some());
+        carry_generations(previous, child, generation);
+        if child.generation == generation {
+            fresh.generation = generation;
+        }
+    }
+    if carried != old_children.len() {
+        fresh.generation = generation;
+    }
+}
+
+/// A tree's root and the directory on disk standing in for it.
+type Mount = (PathBuf, PathBuf);
+
+/// Scans `path` from disk, returning `None` if it no longer exists. Lazy scans list the
+/// directories listed in `like`, the entry being replaced.
+fn rescan(path: &Path, like: Option<&Node>, scan: &Rescan<'_>) -> io::Result<Option<Node>> {
+    let depth = path.strip_prefix(scan.root).map_or(0, |rel| rel.components().count());
+    let scan_node = |physical: PathBuf| match scan.lazy {
+        true => Node::rescan_like(physical, like, scan.include_hidden),
+        false => scan.scanner.scan(physical, depth),
+    };
+    let Some((root, host)) = scan.mount else {
+        if !scan.lazy && scan.scanner.excluded(path) {
+            return Ok(None);
+        }
+        return match scan_node(path.to_path_buf()) {
+            Ok(node) => Ok(Some(node)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        };
+    };
+    let physical = host.join(path.strip_prefix(root).unwrap_or(path));
+    if !scan.lazy && scan.scanner.excluded(&physical) {
+        return Ok(None);
+    }
+    match scan_node(physical.clone()) {
+        Ok(mut node) => {
+            rebase(&mut node, &physical, path);
+            Ok(Some(node))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Places `entry` below `head`, creating any missing intermediate directories.
+pub(crate) fn insert_entry(head: &mut Node, entry: SnapshotEntry) {
+    if entry.path.as_os_str().is_empty() {
+        head.metadata = entry.metadata;
+        return;
+    }
+
+    let mut current = head;
+    let mut components = entry.path.components().peekable();
+    while let Some(component) = components.next() {
+        let path = current.path.join(component);
+        let children = current.children.get_or_insert_with(Vec::new);
+        // Entries usually arrive sorted, so the matching child is most likely the last one.
+        let index = match children.iter().rposition(|child| child.path == path) {
+            Some(index) => index,
+            None => {
+                children.push(Node::from_parts(
+                    path,
+                    NodeType::Directory,
+                    ExtendedMetadata::default(),
+                    0,
+                ));
+                children.len() - 1
+            }
+        };
+
+        if components.peek().is_none() {
+            let node = &mut children[index];
+            if entry.node_type != NodeType::Directory {
+                node.children = None;
+            }
+            node.node_type = entry.node_type;
+            node.metadata = entry.metadata;
+            node.size = entry.size;
+            return;
+        }
+        current = &mut children[index];
+    }
 }
 
 /// An iterator that traverses the tree in a depth-first manner.
@@ -77,4 +531,55 @@ impl<'a> Iterator for TreeIterator<'a> {
         }
         Some(current)
     }
-}
\ No newline at end of file
+}
+/// An iterator that traverses the tree level by level, yielding each node with its depth.
+pub struct BfsIterator<'a> {
+    queue: VecDeque<(usize, &'a Node)>,
+}
+
+impl<'a> Iterator for BfsIterator<'a> {
+    type Item = (usize, &'a Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, current) = self.queue.pop_front()?;
+        if let Some(children) = &current.children {
+            self.queue.extend(children.iter().map(|child| (depth + 1, child)));
+        }
+        Some((depth, current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_other_than_directories_have_no_children() {
+        let entry = |path: &str, node_type| SnapshotEntry {
+            path: PathBuf::from(path),
+            node_type,
+            size: 1,
+            metadata: ExtendedMetadata::default(),
+        };
+        let link = NodeType::Symlink {
+            target: PathBuf::from("elsewhere"),
+        };
+        let tree = Tree::from_entries(
+            PathBuf::from("/root"),
+            [
+                entry("dir/file", NodeType::File),
+                entry("link", link.clone()),
+                entry("dir", NodeType::Directory),
+            ],
+        );
+        let find = |path: &str| {
+            tree.iter()
+                .find(|node| node.path == Path::new(path))
+                .unwrap()
+        };
+        assert_eq!(find("/root/link").node_type, link);
+        assert!(find("/root/link").children.is_none());
+        assert!(find("/root/dir/file").children.is_none());
+        assert_eq!(find("/root/dir").children.as_ref().map(Vec::len), Some(1));
+    }
+}
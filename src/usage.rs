@@ -0,0 +1,149 @@
+use crate::node::Node;
+use crate::options::SizeMetric;
+use crate::tree::Tree;
+
+impl Node {
+    /// The entry's length in bytes, as `ls -l` shows it, holes in sparse files
+    /// included; for a directory, the sum over what is below it, as
+    /// `du --apparent-size` has it. A directory whose children are not resident gives
+    /// its recorded size.
+    pub fn apparent_size(&self) -> u64 {
+        match &self.children {
+            Some(children) => children.iter().map(Node::apparent_size).sum(),
+            None if self.is_dir() => self.size,
+            None => self.metadata.len.unwrap_or(self.size),
+        }
+    }
+
+    /// The space allocated on disk to the entry, its blocks times 512, which is less than
+    /// the length for sparse files and more for most others; for a directory, its own
+    /// blocks and the sum over what is below it, as `du` has it. Without recorded
+    /// blocks, e.g. off Unix, this is the apparent size. A directory whose children are
+    /// not resident gives its recorded size.
+    pub fn disk_usage(&self) -> u64 {
+        match &self.children {
+            Some(children) => {
+                self.own_size(SizeMetric::DiskUsage)
+                    + children.iter().map(Node::disk_usage).sum::<u64>()
+            }
+            None if self.is_dir() => self.size,
+            None => match self.metadata.blocks {
+                Some(blocks) => blocks * 512,
+                None => self.apparent_size(),
+            },
+        }
+    }
+
+    /// What a directory adds to its size besides its children's under `metric`: its own
+    /// blocks times 512 for disk usage, nothing for the apparent size. Nothing for other
+    /// entries, or without recorded blocks.
+    pub(crate) fn own_size(&self, metric: SizeMetric) -> u64 {
+        match (metric, self.is_dir(), self.metadata.blocks) {
+            (SizeMetric::DiskUsage, true, Some(blocks)) => blocks * 512,
+            _ => 0,
+        }
+    }
+
+    /// Records the disk usage as the size of every entry below and including this one,
+    /// and sums directory sizes from it.
+    fn use_disk_usage(&mut self) -> u64 {
+        if let Some(children) = &mut self.children {
+            let below: u64 = children.iter_mut().map(Node::use_disk_usage).sum();
+            self.size = self.own_size(SizeMetric::DiskUsage) + below;
+        } else if !self.is_dir() {
+            self.size = self.disk_usage();
+        }
+        self.size
+    }
+}
+
+impl Tree {
+    /// Sums the directory sizes again after a scan, if the tree was built to record disk
+    /// usage or to count hard links once; see `TreeBuilder::size_metric` and
+    /// `TreeBuilder::count_hardlinks_once`.
+    pub(crate) fn recount_sizes(&mut self) {
+        if self.options.size_metric == SizeMetric::DiskUsage {
+            self.head.use_disk_usage();
+        }
+        self.recount_hardlinks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::builder::TreeBuilder;
+    use crate::options::SizeMetric;
+    use crate::testing::empty_dir;
+
+    #[test]
+    fn directories_add_up_their_own_and_their_files_blocks() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir(dir.root.join("sub")).unwrap();
+        fs::write(dir.root.join("sub/data"), vec![1; 5000]).unwrap();
+        let tree = TreeBuilder::new(&dir.root)
+            .size_metric(SizeMetric::DiskUsage)
+            .build()
+            .unwrap();
+
+        let file = tree.get_node(&dir.root.join("sub/data")).unwrap();
+        let sub = tree.get_node(&dir.root.join("sub")).unwrap();
+        assert_eq!(file.size, file.disk_usage());
+        assert_eq!(sub.size, sub.own_size(SizeMetric::DiskUsage) + file.size);
+        assert_eq!(
+            tree.head.size,
+            tree.head.own_size(SizeMetric::DiskUsage) + sub.size
+        );
+        assert_eq!(tree.head.disk_usage(), tree.head.size);
+        assert!(tree.validate().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn totals_match_du() {
+        let dir = empty_dir().unwrap();
+        fs::create_dir_all(dir.root.join("a/b")).unwrap();
+        fs::write(dir.root.join("a/small"), b"x").unwrap();
+        fs::write(dir.root.join("a/b/data"), vec![7; 20_000]).unwrap();
+        fs::hard_link(dir.root.join("a/b/data"), dir.root.join("linked")).unwrap();
+        fs::File::create(dir.root.join("sparse"))
+            .unwrap()
+            .set_len(1 << 20)
+            .unwrap();
+        let Some(expected) = du(&dir.root) else {
+            return;
+        };
+
+        let mut tree = TreeBuilder::new(&dir.root)
+            .size_metric(SizeMetric::DiskUsage)
+            .count_hardlinks_once(true)
+            .build()
+            .unwrap();
+        assert_eq!(tree.head.size, expected);
+        assert!(tree.validate().is_ok());
+
+        fs::write(dir.root.join("a/small"), vec![1; 9000]).unwrap();
+        tree.refresh_path(&dir.root.join("a/small")).unwrap();
+        assert_eq!(Some(tree.head.size), du(&dir.root));
+        assert!(tree.validate().is_ok());
+    }
+
+    /// The bytes `du` counts below `path`, or `None` where no GNU `du` runs.
+    #[cfg(unix)]
+    fn du(path: &std::path::Path) -> Option<u64> {
+        let output = std::process::Command::new("du")
+            .arg("-B1")
+            .arg("-s")
+            .arg(path)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        String::from_utf8(output.stdout)
+            .ok()?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::hardlink::counted;
+use crate::node::Node;
+use crate::options::SizeMetric;
+use crate::tree::Tree;
+
+/// A broken internal invariant found by `Tree::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A directory's size is not the sum of its children's sizes.
+    SizeMismatch {
+        path: PathBuf,
+        recorded: u64,
+        expected: u64,
+    },
+    /// The same path appears more than once in the tree.
+    DuplicatePath { path: PathBuf },
+    /// A child whose path is not directly below its parent's path.
+    MisplacedChild { parent: PathBuf, child: PathBuf },
+    /// A file or symlink node that has children.
+    FileWithChildren { path: PathBuf },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::SizeMismatch {
+                path,
+                recorded,
+                expected,
+            } => write!(
+                f,
+                "{}: recorded size {} but children sum to {}",
+                path.display(),
+                recorded,
+                expected
+            ),
+            Violation::DuplicatePath { path } => {
+                write!(f, "{}: appears more than once", path.display())
+            }
+            Violation::MisplacedChild { parent, child } => write!(
+                f,
+                "{}: listed as a child of {}",
+                child.display(),
+                parent.display()
+            ),
+            Violation::FileWithChildren { path } => {
+                write!(f, "{}: file has children", path.display())
+            }
+        }
+    }
+}
+
+impl Tree {
+    /// Check the tree's internal invariants, returning every violation found.
+    ///
+    /// Checks that directory sizes equal the sum of their children (counting hard links
+    /// once if the tree was built to, and a directory's own blocks under
+    /// `SizeMetric::DiskUsage`), that no path appears twice, that every child's
+    /// path sits directly below its parent's, and that files have no children.
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        let mut seen = HashSet::new();
+        let mut links = self.options.hardlinks_once.then(HashSet::new);
+        let metric = self.options.size_metric;
+        validate_node(
+            &self.head,
+            metric,
+            &mut seen,
+            links.as_mut(),
+            &mut violations,
+        );
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+/// Checks `node` and everything below it, returning what it adds to its parent's size.
+/// With `links`, hard-linked files already in it add nothing. Directories add their own
+/// size under `metric`.
+fn validate_node<'a>(
+    node: &'a Node,
+    metric: SizeMetric,
+    seen: &mut HashSet<&'a PathBuf>,
+    mut links: Option<&mut HashSet<(u64, u64)>>,
+    violations: &mut Vec<Violation>,
+) -> u64 {
+    if !seen.insert(&node.path) {
+        violations.push(Violation::DuplicatePath {
+            path: node.path.clone(),
+        });
+    }
+
+    let own = match links
+        .as_deref_mut()
+        .is_some_and(|links| !counted(node, links))
+    {
+        true => 0,
+        false => node.size,
+    };
+    let Some(children) = &node.children else {
+        return own;
+    };
+    if !node.is_dir() && !children.is_empty() {
+        violations.push(Violation::FileWithChildren {
+            path: node.path.clone(),
+        });
+    }
+
+    // The size is checked against what the children add up to, reported before them.
+    let at = violations.len();
+    let mut expected = node.own_size(metric);
+    for child in children {
+        if child.path.parent() != Some(node.path.as_path()) {
+            violations.push(Violation::MisplacedChild {
+                parent: node.path.clone(),
+                child: child.path.clone(),
+            });
+        }
+        expected += validate_node(child, metric, seen, links.as_deref_mut(), violations);
+    }
+    if node.is_dir() && node.size != expected {
+        violations.insert(
+            at,
+            Violation::SizeMismatch {
+                path: node.path.clone(),
+                recorded: node.size,
+                expected,
+            },
+        );
+    }
+    own
+}
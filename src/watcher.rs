@@ -0,0 +1,208 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::event::FsEvent;
+use crate::fanout::{Overflow, Subscriber};
+use crate::source::{EventQueue, EventSource, Injector};
+use crate::transform::EventTransform;
+
+/// How often the backend's health is checked in the background.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Watches a directory recursively and reports changes as `FsEvent`s, both through its
+/// own `EventSource` methods and to any number of additional subscribers.
+///
+/// When events may have been lost (the backend's queue overflowed, it reported an error,
+/// the root was replaced so the watch went stale, or the watcher's own queue of 65,536
+/// events filled up because nothing received them), an `FsEvent::Desynced` is reported
+/// instead, which makes `Tree::apply_events` rescan the affected path. A background check
+/// re-establishes a failed watch; see `check_health`.
+pub struct FsWatcher {
+    backend: Arc<Backend>,
+    receiver: EventQueue,
+}
+
+/// The part of a watcher shared with its health check.
+struct Backend {
+    root: PathBuf,
+    injector: Injector,
+    // Kept alive for as long as events should be delivered.
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    identity: Mutex<Option<Identity>>,
+    failed: Arc<AtomicBool>,
+}
+
+impl FsWatcher {
+    /// Start watching `root` and everything below it.
+    pub fn new(root: &Path) -> io::Result<Self> {
+        Self::with_transform(root, EventTransform::default())
+    }
+
+    /// Start watching `root`, rewriting the paths of its events with `transform` before
+    /// anything receives them.
+    pub fn with_transform(root: &Path, transform: EventTransform) -> io::Result<Self> {
+        let (injector, receiver) = Injector::channel(transform);
+        let failed = Arc::new(AtomicBool::new(false));
+        let watcher = start(root, injector.clone(), Arc::clone(&failed))?;
+        let backend = Arc::new(Backend {
+            root: root.to_path_buf(),
+            injector,
+            watcher: Mutex::new(Some(watcher)),
+            identity: Mutex::new(identity(root)),
+            failed,
+        });
+
+        let health = Arc::downgrade(&backend);
+        thread::spawn(move || loop {
+            thread::sleep(HEALTH_INTERVAL);
+            let Some(backend) = health.upgrade() else {
+                return;
+            };
+            // A watch that cannot be re-established now is retried on the next round.
+            let _ = backend.heal();
+        });
+
+        Ok(Self { backend, receiver })
+    }
+
+    /// Check that the watch is still live, as the background check does every few
+    /// seconds. If the backend reported an error or the root was replaced since, the
+    /// watch is re-established, `FsEvent::Desynced` is reported for the root, and
+    /// `Ok(false)` is returned.
+    pub fn check_health(&self) -> io::Result<bool> {
+        self.backend.heal()
+    }
+}
+
+impl EventSource for FsWatcher {
+    fn recv(&self) -> Option<FsEvent> {
+        self.receiver.recv()
+    }
+
+    fn try_recv(&self) -> Option<FsEvent> {
+        self.receiver.try_recv()
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Option<FsEvent> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    fn subscribe(&self, capacity: usize, overflow: Overflow) -> Subscriber {
+        self.backend.injector.subscribe(capacity, overflow)
+    }
+}
+
+impl Backend {
+    fn heal(&self) -> io::Result<bool> {
+        let current = identity(&self.root);
+        let mut known = self.identity.lock().unwrap_or_else(|p| p.into_inner());
+        let replaced = current != *known;
+        if !replaced && !self.failed.swap(false, Ordering::Relaxed) {
+            return Ok(true);
+        }
+
+        *known = current;
+        let mut watcher = self.watcher.lock().unwrap_or_else(|p| p.into_inner());
+        *watcher = None;
+        if known.is_some() {
+            *watcher = Some(start(
+                &self.root,
+                self.injector.clone(),
+                Arc::clone(&self.failed),
+            )?);
+        }
+        self.injector.inject(FsEvent::Desynced(self.root.clone()));
+        Ok(false)
+    }
+}
+
+/// Watch `root` with the platform's backend, sending what it reports to `injector`.
+/// Backend errors and the loss of the root are recorded in `failed` for the health check
+/// to repair.
+fn start(
+    root: &Path,
+    injector: Injector,
+    failed: Arc<AtomicBool>,
+) -> io::Result<RecommendedWatcher> {
+    let root_path = root.to_path_buf();
+    let mut watcher =
+        notify::recommended_watcher(move |result: notify::Result<notify::Event>| match result {
+            Ok(event) => {
+                for fs_event in translate(event, &root_path) {
+                    // Removing the root also ends the watch on it, even if it comes back.
+                    let gone = match &fs_event {
+                        FsEvent::Removed(path) => *path == root_path,
+                        FsEvent::Renamed { from, .. } => *from == root_path,
+                        _ => false,
+                    };
+                    if gone {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    injector.inject(fs_event);
+                }
+            }
+            Err(_) => failed.store(true, Ordering::Relaxed),
+        })
+        .map_err(io::Error::other)?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)
+        .map_err(io::Error::other)?;
+    Ok(watcher)
+}
+
+/// What identifies the directory at a path, to notice it being replaced.
+#[cfg(unix)]
+type Identity = (u64, u64);
+#[cfg(not(unix))]
+type Identity = Option<std::time::SystemTime>;
+
+#[cfg(unix)]
+fn identity(path: &Path) -> Option<Identity> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(path: &Path) -> Option<Identity> {
+    Some(fs::metadata(path).ok()?.created().ok())
+}
+
+/// Maps a backend event onto zero or more `FsEvent`s. An event flagged as needing a
+/// rescan (e.g. after a queue overflow) becomes `Desynced`, for `root` if it names no path.
+fn translate(event: notify::Event, root: &Path) -> Vec<FsEvent> {
+    if event.need_rescan() {
+        if event.paths.is_empty() {
+            return vec![FsEvent::Desynced(root.to_path_buf())];
+        }
+        return event.paths.into_iter().map(FsEvent::Desynced).collect();
+    }
+    let mut paths = event.paths.into_iter();
+    match event.kind {
+        EventKind::Access(_) => Vec::new(),
+        EventKind::Create(_) => paths.map(FsEvent::Created).collect(),
+        EventKind::Remove(_) => paths.map(FsEvent::Removed).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            match (paths.next(), paths.next()) {
+                (Some(from), Some(to)) => vec![FsEvent::Renamed { from, to }],
+                (Some(path), None) => vec![FsEvent::Modified(path)],
+                _ => Vec::new(),
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(FsEvent::Removed).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.map(FsEvent::Created).collect()
+        }
+        _ => paths.map(FsEvent::Modified).collect(),
+    }
+}